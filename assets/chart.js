@@ -0,0 +1,338 @@
+// An interactive chart of a profile's weights and their trend, drawn on a
+// canvas from the series at `url`. Scroll or pinch to zoom, drag to pan, and
+// double-click to show every entry again. Hovering over the chart shows the
+// nearest entry in `tooltip`, along with the day's note if it has one. Entries
+// with notes are ringed so they can be found.
+"use strict";
+
+function weightChart(canvas, tooltip, url, accent) {
+	const DAY = 86400000;
+	const MIN_SPAN = 7 * DAY;
+	const pad = { left: 52, right: 12, top: 12, bottom: 28 };
+	const ctx = canvas.getContext("2d");
+	let points = [];
+	let unit = "";
+	let extent = null;
+	let view = null;
+	let hover = -1;
+	let y = () => 0;
+	let message = "Loading…";
+	// pointers down on the canvas, by id, for dragging and pinching
+	const pointers = new Map();
+
+	const plotWidth = () => canvas.clientWidth - pad.left - pad.right;
+	const plotHeight = () => canvas.clientHeight - pad.top - pad.bottom;
+	const x = t => pad.left + (t - view.from) / (view.to - view.from) * plotWidth();
+	const time = px => view.from + (px - pad.left) / plotWidth() * (view.to - view.from);
+	const utc = { timeZone: "UTC" };
+
+	// Keep the view at least a week wide and within the dates of the entries.
+	function clamp(from, to) {
+		const all = extent.to - extent.from;
+		const span = Math.min(Math.max(to - from, MIN_SPAN), Math.max(all, MIN_SPAN));
+		if (span >= all) {
+			from = (extent.from + extent.to - span) / 2;
+		} else {
+			from = Math.min(Math.max(from, extent.from), extent.to - span);
+		}
+		view = { from: from, to: from + span };
+		draw();
+	}
+
+	function zoom(factor, px) {
+		const t = time(px);
+		clamp(t - (t - view.from) * factor, t + (view.to - t) * factor);
+	}
+
+	function pan(dx) {
+		const dt = dx / plotWidth() * (view.to - view.from);
+		clamp(view.from - dt, view.to - dt);
+	}
+
+	// The step between the ticks on the weight axis, leaving room for about
+	// one label every 40 pixels.
+	function weightStep(min, max) {
+		for (const step of [0.1, 0.2, 0.5, 1, 2, 5, 10, 20, 50, 100]) {
+			if ((max - min) / step <= plotHeight() / 40) {
+				return step;
+			}
+		}
+		return 200;
+	}
+
+	// The times of the ticks on the date axis and their labels, leaving room
+	// for about one label every 90 pixels.
+	function dateTicks() {
+		const days = (view.to - view.from) / DAY;
+		const count = Math.max(plotWidth() / 90, 1);
+		const ticks = [];
+		for (const step of [1, 2, 7, 14]) {
+			if (days / step <= count) {
+				const first = Math.ceil(view.from / (step * DAY)) * step * DAY;
+				for (let t = first; t <= view.to; t += step * DAY) {
+					ticks.push([t, new Date(t).toLocaleDateString(undefined,
+						{ month: "short", day: "numeric", ...utc })]);
+				}
+				return ticks;
+			}
+		}
+		let months = [1, 2, 3, 6, 12, 24, 60].find(m => days / (m * 30.4) <= count);
+		months = months || 120;
+		const start = new Date(view.from);
+		let year = start.getUTCFullYear();
+		let month = Math.ceil(start.getUTCMonth() / months) * months;
+		for (;;) {
+			const t = Date.UTC(year, month);
+			if (t > view.to) {
+				return ticks;
+			}
+			if (t >= view.from) {
+				const format = months >= 12 ? { year: "numeric" }
+					: { month: "short", year: "numeric" };
+				ticks.push([t, new Date(t).toLocaleDateString(undefined,
+					{ ...format, ...utc })]);
+			}
+			month += months;
+		}
+	}
+
+	function line(get, color, width) {
+		ctx.strokeStyle = color;
+		ctx.lineWidth = width;
+		ctx.beginPath();
+		points.forEach((p, i) => {
+			const px = x(p.t);
+			const py = y(get(p));
+			i === 0 ? ctx.moveTo(px, py) : ctx.lineTo(px, py);
+		});
+		ctx.stroke();
+	}
+
+	function draw() {
+		const ratio = window.devicePixelRatio || 1;
+		const width = canvas.clientWidth;
+		const height = canvas.clientHeight;
+		canvas.width = width * ratio;
+		canvas.height = height * ratio;
+		ctx.setTransform(ratio, 0, 0, ratio, 0, 0);
+		ctx.clearRect(0, 0, width, height);
+		ctx.font = "12px sans-serif";
+		ctx.fillStyle = "#555";
+		if (!view) {
+			ctx.textAlign = "center";
+			ctx.fillText(message, width / 2, height / 2);
+			return;
+		}
+
+		const shown = points.filter(p => p.t >= view.from && p.t <= view.to);
+		const values = shown.flatMap(p => [p.weight, p.trend]);
+		let min = Math.min(...values);
+		let max = Math.max(...values);
+		if (!values.length) {
+			min = 0;
+			max = 1;
+		}
+		const margin = Math.max((max - min) * 0.05, 0.5);
+		min -= margin;
+		max += margin;
+		y = w => pad.top + (max - w) / (max - min) * plotHeight();
+
+		ctx.strokeStyle = "#ddd";
+		ctx.lineWidth = 1;
+		ctx.textAlign = "right";
+		ctx.textBaseline = "middle";
+		const step = weightStep(min, max);
+		for (let w = Math.ceil(min / step) * step; w <= max; w += step) {
+			ctx.beginPath();
+			ctx.moveTo(pad.left, y(w));
+			ctx.lineTo(width - pad.right, y(w));
+			ctx.stroke();
+			ctx.fillText(w.toFixed(step < 1 ? 1 : 0), pad.left - 6, y(w));
+		}
+		ctx.textAlign = "center";
+		ctx.textBaseline = "top";
+		for (const [t, label] of dateTicks()) {
+			ctx.beginPath();
+			ctx.moveTo(x(t), pad.top);
+			ctx.lineTo(x(t), height - pad.bottom);
+			ctx.stroke();
+			ctx.fillText(label, x(t), height - pad.bottom + 6);
+		}
+
+		ctx.save();
+		ctx.beginPath();
+		ctx.rect(pad.left, pad.top, plotWidth(), plotHeight());
+		ctx.clip();
+		line(p => p.weight, "#999", 1);
+		line(p => p.trend, accent, 2.5);
+		ctx.fillStyle = "#333";
+		ctx.strokeStyle = "#333";
+		ctx.lineWidth = 1;
+		for (const p of shown) {
+			ctx.beginPath();
+			ctx.arc(x(p.t), y(p.weight), 2.5, 0, 2 * Math.PI);
+			ctx.fill();
+			if (p.note) {
+				ctx.beginPath();
+				ctx.arc(x(p.t), y(p.weight), 6, 0, 2 * Math.PI);
+				ctx.stroke();
+			}
+		}
+		if (hover >= 0) {
+			const p = points[hover];
+			ctx.strokeStyle = accent;
+			ctx.lineWidth = 2;
+			ctx.beginPath();
+			ctx.arc(x(p.t), y(p.weight), 5, 0, 2 * Math.PI);
+			ctx.stroke();
+		}
+		ctx.restore();
+		showTooltip();
+	}
+
+	function showTooltip() {
+		if (hover < 0) {
+			tooltip.hidden = true;
+			return;
+		}
+		const p = points[hover];
+		tooltip.replaceChildren();
+		const add = text => {
+			const div = document.createElement("div");
+			div.textContent = text;
+			tooltip.append(div);
+		};
+		add(p.date);
+		add(`${p.weight.toFixed(1)} ${unit}`);
+		add(`Trend: ${p.trend.toFixed(1)} ${unit}`);
+		if (p.tags.length) {
+			add(p.tags.join(", "));
+		}
+		if (p.note) {
+			add(p.note);
+		}
+		tooltip.hidden = false;
+		const left = Math.min(x(p.t) + 10, canvas.clientWidth - tooltip.offsetWidth);
+		tooltip.style.left = `${Math.max(left, 0)}px`;
+		tooltip.style.top = `${Math.max(y(p.weight) - tooltip.offsetHeight - 10, 0)}px`;
+	}
+
+	// Point out the entry nearest to `px` if it is close enough.
+	function hoverAt(px) {
+		let nearest = -1;
+		let distance = 30;
+		points.forEach((p, i) => {
+			const d = Math.abs(x(p.t) - px);
+			if (p.t >= view.from && p.t <= view.to && d < distance) {
+				nearest = i;
+				distance = d;
+			}
+		});
+		if (nearest !== hover) {
+			hover = nearest;
+			draw();
+		}
+	}
+
+	const offset = e => e.clientX - canvas.getBoundingClientRect().left;
+
+	canvas.addEventListener("wheel", e => {
+		if (!view) {
+			return;
+		}
+		e.preventDefault();
+		zoom(Math.exp(e.deltaY * 0.002), offset(e));
+	}, { passive: false });
+
+	canvas.addEventListener("pointerdown", e => {
+		canvas.setPointerCapture(e.pointerId);
+		pointers.set(e.pointerId, offset(e));
+		if (view) {
+			hoverAt(offset(e));
+		}
+	});
+
+	canvas.addEventListener("pointermove", e => {
+		if (!view) {
+			return;
+		}
+		const px = offset(e);
+		const last = pointers.get(e.pointerId);
+		if (last === undefined) {
+			hoverAt(px);
+			return;
+		}
+		if (pointers.size === 2) {
+			const [, other] = [...pointers].find(([id]) => id !== e.pointerId);
+			const before = Math.abs(last - other);
+			const after = Math.abs(px - other);
+			pointers.set(e.pointerId, px);
+			if (before > 0 && after > 0) {
+				zoom(before / after, (px + other) / 2);
+			}
+			return;
+		}
+		pointers.set(e.pointerId, px);
+		pan(px - last);
+		hoverAt(px);
+	});
+
+	for (const type of ["pointerup", "pointercancel"]) {
+		canvas.addEventListener(type, e => pointers.delete(e.pointerId));
+	}
+
+	canvas.addEventListener("pointerleave", e => {
+		if (!pointers.size && e.pointerType === "mouse" && hover >= 0) {
+			hover = -1;
+			draw();
+		}
+	});
+
+	canvas.addEventListener("dblclick", () => chart.show());
+	window.addEventListener("resize", draw);
+
+	const chart = {
+		// Show the last `days` days, or every entry without them.
+		show(days) {
+			if (extent) {
+				clamp(days ? extent.to - days * DAY : extent.from, extent.to);
+			}
+		},
+	};
+
+	fetch(url)
+		.then(response => {
+			if (!response.ok) {
+				throw new Error(response.statusText);
+			}
+			return response.json();
+		})
+		.then(series => {
+			unit = series.unit;
+			points = series.dates.map((date, i) => ({
+				t: Date.parse(date),
+				date: date,
+				weight: series.weight[i],
+				trend: series.trend[i],
+				tags: series.tags[i],
+				note: series.notes[i],
+			}));
+			if (!points.length) {
+				message = "No entries yet.";
+				draw();
+				return;
+			}
+			extent = {
+				from: points[0].t - DAY / 2,
+				to: points[points.length - 1].t + DAY / 2,
+			};
+			chart.show();
+		})
+		.catch(e => {
+			message = `Failed to load the entries: ${e.message}`;
+			draw();
+		});
+
+	draw();
+	return chart;
+}
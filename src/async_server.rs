@@ -0,0 +1,99 @@
+//! An alternative to the blocking accept loop in the binary, built on tokio.
+//! Connections are read and written asynchronously, while the handlers in
+//! [`crate::web`] run on tokio's blocking thread pool since they touch the
+//! data file and wait on gnuplot.
+
+use std::{net::SocketAddr, sync::Arc};
+
+use tokio::{
+    io::AsyncWriteExt,
+    net::{TcpListener, TcpStream},
+};
+use tracing::{error, info, info_span, Instrument};
+
+use crate::{
+    http::{Request, Response},
+    web::{self, State},
+    Result,
+};
+
+/// Serve requests on `port` until the listener fails.
+pub async fn serve(state: Arc<State>, port: u16) -> Result<()> {
+    let listener = TcpListener::bind(("0.0.0.0", port)).await?;
+    let addr = listener.local_addr()?;
+    info!("listening on {addr}");
+    #[cfg(unix)]
+    crate::systemd::ready(addr.port());
+
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                error!("failed to accept connection: {e}");
+                continue;
+            }
+        };
+        let state = Arc::clone(&state);
+        let span = info_span!("connection", %peer);
+        tokio::spawn(dispatch(stream, peer, state).instrument(span));
+    }
+}
+
+async fn dispatch(mut stream: TcpStream, peer: SocketAddr, state: Arc<State>) {
+    let max_body = state.config().max_body_size;
+    // refused clients are answered before anything they send is read
+    let mut response = match web::forbidden(Some(peer.ip()), &state) {
+        Some(response) => response,
+        None => match Request::read_async(&mut stream, max_body).await {
+            Ok(request) => match handle(request, state).await {
+                Some(response) => response,
+                None => return,
+            },
+            Err(e) => match web::read_error(e, &state) {
+                Some(response) => response,
+                None => return,
+            },
+        },
+    };
+    let bytes = response.as_bytes();
+    let rest = response.take_stream();
+    if let Err(e) = stream.write_all(&bytes).await {
+        error!("failed to write response: {e}");
+        return;
+    }
+    if let Some(rest) = rest {
+        // streams are long-lived and blocking, so they get their own thread
+        // rather than tying up one of tokio's
+        let stream = match stream
+            .into_std()
+            .and_then(|s| s.set_nonblocking(false).map(|()| s))
+        {
+            Ok(stream) => stream,
+            Err(e) => {
+                error!("failed to hand off connection: {e}");
+                return;
+            }
+        };
+        let span = tracing::Span::current();
+        std::thread::spawn(move || {
+            let _span = span.entered();
+            rest(stream);
+        });
+    }
+}
+
+async fn handle(request: Request, state: Arc<State>) -> Option<Response> {
+    let span = tracing::Span::current();
+    let handled = tokio::task::spawn_blocking(move || {
+        let _span = span.entered();
+        web::handle(&request, &state)
+    })
+    .await;
+    match handled {
+        Ok(response) => Some(response),
+        Err(e) => {
+            error!("request handler failed: {e}");
+            None
+        }
+    }
+}
@@ -0,0 +1,259 @@
+//! An append-only log of every change to the weight entries, recording when
+//! each change was made, where it came from, and the entry before and after.
+//! The log is kept in an `.audit` file next to the default profile's data
+//! file, with one change per line in tab-separated fields: the time in UTC
+//! like `2026-10-15T07:30:00Z`, the source, the action, the profile (`-` for
+//! the default), and the old and new entries in the data file format (`-`
+//! for none).
+
+use std::{
+    fmt::Display,
+    fs::File,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use time::{OffsetDateTime, Time, UtcOffset};
+use tracing::{debug, instrument};
+
+use crate::{
+    crypto::Cipher,
+    store::{self, format_date, parse_date},
+    Entry,
+};
+
+/// Where a change came from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Source {
+    /// A form in the browser
+    Web,
+
+    /// A request authorized with an API token
+    Api,
+
+    /// The command line
+    Cli,
+
+    /// An imported file
+    Import,
+}
+
+impl FromStr for Source {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "web" => Ok(Self::Web),
+            "api" => Ok(Self::Api),
+            "cli" => Ok(Self::Cli),
+            "import" => Ok(Self::Import),
+            _ => Err(format!("unknown source `{s}`")),
+        }
+    }
+}
+
+impl Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Source::Web => write!(f, "web"),
+            Source::Api => write!(f, "api"),
+            Source::Cli => write!(f, "cli"),
+            Source::Import => write!(f, "import"),
+        }
+    }
+}
+
+/// What happened to an entry.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    Create,
+    Edit,
+    Delete,
+
+    /// Added from an import or a restored backup
+    Import,
+
+    /// Taken back out of the trash
+    Restore,
+}
+
+impl FromStr for Action {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "create" => Ok(Self::Create),
+            "edit" => Ok(Self::Edit),
+            "delete" => Ok(Self::Delete),
+            "import" => Ok(Self::Import),
+            "restore" => Ok(Self::Restore),
+            _ => Err(format!("unknown action `{s}`")),
+        }
+    }
+}
+
+impl Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Action::Create => write!(f, "create"),
+            Action::Edit => write!(f, "edit"),
+            Action::Delete => write!(f, "delete"),
+            Action::Import => write!(f, "import"),
+            Action::Restore => write!(f, "restore"),
+        }
+    }
+}
+
+/// One change to one entry.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Change {
+    pub time: OffsetDateTime,
+    pub source: Source,
+    pub action: Action,
+
+    /// The profile whose entry changed, `None` for the default profile
+    pub profile: Option<String>,
+    pub old: Option<Entry>,
+    pub new: Option<Entry>,
+}
+
+impl Change {
+    /// Parse a line written by the `Display` implementation.
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.split('\t');
+        let time = parse_time(fields.next()?)?;
+        let source = fields.next()?.parse().ok()?;
+        let action = fields.next()?.parse().ok()?;
+        let profile = match fields.next()? {
+            "-" => None,
+            name => Some(name.to_owned()),
+        };
+        let mut entry = || match fields.next()? {
+            "-" => Some(None),
+            entry => Entry::parse(entry).map(Some),
+        };
+        let old = entry()?;
+        let new = entry()?;
+        Some(Self {
+            time,
+            source,
+            action,
+            profile,
+            old,
+            new,
+        })
+    }
+}
+
+/// Formats the change as a line of the audit file.
+impl Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let time = self.time.to_offset(UtcOffset::UTC);
+        write!(
+            f,
+            "{}T{:02}:{:02}:{:02}Z\t{}\t{}\t{}",
+            format_date(time.date()),
+            time.hour(),
+            time.minute(),
+            time.second(),
+            self.source,
+            self.action,
+            self.profile.as_deref().unwrap_or("-"),
+        )?;
+        for entry in [&self.old, &self.new] {
+            match entry {
+                Some(entry) => write!(f, "\t{entry}")?,
+                None => write!(f, "\t-")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parse a UTC time like `2026-10-15T07:30:00Z`.
+fn parse_time(s: &str) -> Option<OffsetDateTime> {
+    let (date, time) = s.strip_suffix('Z')?.split_once('T')?;
+    let mut fields = time.split(':').map(|f| f.parse().ok());
+    let time =
+        Time::from_hms(fields.next()??, fields.next()??, fields.next()??)
+            .ok()?;
+    Some(parse_date(date)?.with_time(time).assume_utc())
+}
+
+/// The audit file, which is only ever appended to.
+pub struct Audit {
+    path: PathBuf,
+
+    /// Held while the file is being written
+    file: Mutex<()>,
+
+    /// Set if the data files are encrypted, in which case this file is too
+    /// and has to be rewritten in full to add to it
+    cipher: Option<Cipher>,
+}
+
+impl Audit {
+    /// Set up the audit file at `path`, encrypting it with a key derived
+    /// from `passphrase` if given. The file is created once there is
+    /// something to put in it.
+    pub fn open(path: &Path, passphrase: Option<Arc<str>>) -> io::Result<Self> {
+        let contents = read(path)?;
+        let cipher =
+            passphrase.map(|p| Cipher::new(p, &contents)).transpose()?;
+        Ok(Self {
+            path: path.to_owned(),
+            file: Mutex::default(),
+            cipher,
+        })
+    }
+
+    /// Every change recorded so far, oldest first.
+    pub fn changes(&self) -> io::Result<Vec<Change>> {
+        let _file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+        Ok(store::text(&read(&self.path)?, self.cipher.as_ref())?
+            .lines()
+            .flat_map(Change::parse)
+            .collect())
+    }
+
+    /// Add `changes` to the end of the file.
+    #[instrument(skip_all, fields(path = %self.path.display()))]
+    pub fn record(&self, changes: &[Change]) -> io::Result<()> {
+        if changes.is_empty() {
+            return Ok(());
+        }
+        let lines: String = changes.iter().map(|c| format!("{c}\n")).collect();
+        let _file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+        match &self.cipher {
+            Some(cipher) => {
+                let contents = read(&self.path)?;
+                let mut text = store::text(&contents, Some(cipher))?;
+                text.push_str(&lines);
+                store::replace_file(
+                    &self.path,
+                    &cipher.encrypt(text.as_bytes())?,
+                )?;
+            }
+            None => {
+                let mut file = File::options()
+                    .create(true)
+                    .append(true)
+                    .open(&self.path)?;
+                file.write_all(lines.as_bytes())?;
+                file.sync_data()?;
+            }
+        }
+        debug!(changes = changes.len(), "recorded changes");
+        Ok(())
+    }
+}
+
+/// The contents of the file at `path`, which may not exist yet.
+fn read(path: &Path) -> io::Result<Vec<u8>> {
+    match std::fs::read(path) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        result => result,
+    }
+}
@@ -0,0 +1,236 @@
+//! Backups of every profile's data. A snapshot is a text file starting with
+//! a header line, followed by a `profile` line for each profile with the
+//! profile's entries below it, in the same format as the data files.
+
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use tracing::{error, info, instrument};
+
+use crate::{
+    config::Config,
+    crypto,
+    profile::{Profile, Profiles},
+    store::Entry,
+    Error, Result, State,
+};
+
+const HEADER: &str = "weight-watcher backup v1";
+
+/// Render the entries of every profile in `profiles` as a snapshot.
+pub fn snapshot(profiles: &Profiles) -> String {
+    let mut out = Vec::new();
+    write_snapshot(&mut out, profiles.iter().map(|p| &**p))
+        .expect("writing to a Vec can't fail");
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Write a snapshot of the entries of every profile in `profiles` to `out`.
+pub fn write_snapshot<'a>(
+    out: &mut dyn Write,
+    profiles: impl IntoIterator<Item = &'a Profile>,
+) -> std::io::Result<()> {
+    writeln!(out, "{HEADER}")?;
+    for profile in profiles {
+        match profile.name() {
+            Some(name) => writeln!(out, "profile {name}")?,
+            None => writeln!(out, "profile")?,
+        }
+        for entry in profile.store().iter_batched() {
+            writeln!(out, "{entry}")?;
+        }
+    }
+    Ok(())
+}
+
+/// The entries of one profile in a backup, with `None` for the default
+/// profile.
+pub type ProfileEntries = (Option<String>, Vec<Entry>);
+
+/// Parse a snapshot made by [`snapshot`], decrypting it first if it is an
+/// uploaded backup. Nothing is restored unless every line is valid, so the
+/// whole file is checked up front.
+pub fn parse(contents: &[u8], config: &Config) -> Result<Vec<ProfileEntries>> {
+    let decrypted;
+    let contents = if crypto::is_encrypted(contents) {
+        let key_file = config
+            .backup_key_file
+            .as_ref()
+            .or(config.encryption_key_file.as_ref())
+            .ok_or_else(|| {
+                Error::Restore(
+                    "the backup is encrypted, but neither backup_key_file nor \
+                     encryption_key_file is set"
+                        .into(),
+                )
+            })?;
+        let passphrase = crypto::read_passphrase(key_file)?;
+        let cipher = crypto::Cipher::new(passphrase.into(), contents)?;
+        decrypted = cipher
+            .decrypt(contents)
+            .map_err(|e| Error::Restore(e.to_string()))?;
+        &decrypted
+    } else {
+        contents
+    };
+    let contents = std::str::from_utf8(contents)
+        .map_err(|_| Error::Restore("the backup isn't valid UTF-8".into()))?;
+
+    let mut lines = contents.lines().enumerate();
+    if lines.next().map(|(_, line)| line.trim_end()) != Some(HEADER) {
+        return Err(Error::Restore(
+            "the file isn't a weight-watcher backup".into(),
+        ));
+    }
+    let mut profiles: Vec<ProfileEntries> = Vec::new();
+    for (i, line) in lines {
+        let line = line.trim_end();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(rest) = line.strip_prefix("profile") {
+            let name = match rest.trim() {
+                "" => None,
+                name => Some(name.to_owned()),
+            };
+            if profiles.iter().any(|(n, _)| *n == name) {
+                return Err(Error::Restore(format!(
+                    "line {}: profile listed twice",
+                    i + 1
+                )));
+            }
+            profiles.push((name, Vec::new()));
+            continue;
+        }
+        let Some((_, entries)) = profiles.last_mut() else {
+            return Err(Error::Restore(format!(
+                "line {}: entry before the first profile line",
+                i + 1
+            )));
+        };
+        let entry = Entry::parse(line).ok_or_else(|| {
+            Error::Restore(format!("line {}: invalid entry `{line}`", i + 1))
+        })?;
+        entries.push(entry);
+    }
+    Ok(profiles)
+}
+
+/// How a backup is restored into the existing data.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum RestoreMode {
+    /// Replace each profile's entries with the ones in the backup
+    #[default]
+    Replace,
+
+    /// Only add the entries on dates that don't already have one
+    Merge,
+}
+
+impl FromStr for RestoreMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "replace" => Ok(Self::Replace),
+            "merge" => Ok(Self::Merge),
+            _ => Err(format!(
+                "unknown restore mode `{s}`, expected replace or merge"
+            )),
+        }
+    }
+}
+
+/// Encrypt a snapshot of the data in `state` and upload it to the configured
+/// backup URL with curl. Backups rotate through `backup_keep` file names, so
+/// each upload replaces the one from `backup_keep` intervals ago.
+#[instrument(skip_all)]
+pub fn upload(state: &State) -> Result<()> {
+    let config = state.config();
+    let Some(url) = &config.backup_url else {
+        return Ok(());
+    };
+    let key_file = config
+        .backup_key_file
+        .as_ref()
+        .or(config.encryption_key_file.as_ref())
+        .ok_or_else(|| {
+            Error::Backup(
+                "backups need backup_key_file or encryption_key_file to be \
+                 set so they can be encrypted"
+                    .into(),
+            )
+        })?;
+    let passphrase = crypto::read_passphrase(key_file)?;
+    let cipher = crypto::Cipher::new(passphrase.into(), &[])?;
+    let data = cipher.encrypt(state.snapshot().as_bytes())?;
+
+    // curl needs a file rather than stdin to send a Content-Length, which S3
+    // requires
+    let tmp = std::env::temp_dir().join("weight-watcher-backup.tmp");
+    std::fs::write(&tmp, data)?;
+    let interval = config.backup_interval.as_secs().max(1);
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let slot = now / interval % config.backup_keep.max(1) as u64;
+    let url =
+        format!("{}/weight-watcher-{slot}.bak", url.trim_end_matches('/'));
+
+    let mut curl = Command::new("curl");
+    curl.args(["--silent", "--show-error", "--fail", "--config", "-"])
+        .arg("--upload-file")
+        .arg(&tmp)
+        .arg(&url)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped());
+    if let Some(region) = &config.backup_s3_region {
+        curl.arg("--aws-sigv4").arg(format!("aws:amz:{region}:s3"));
+    }
+    // credentials go through stdin rather than the command line so other
+    // users can't see them in the process list
+    let credentials = match &config.backup_credentials_file {
+        Some(path) => {
+            let user = crypto::read_passphrase(path)?
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"");
+            format!("user = \"{user}\"\n")
+        }
+        None => String::new(),
+    };
+    drop(config);
+
+    let mut child = curl.spawn()?;
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    stdin.write_all(credentials.as_bytes())?;
+    drop(stdin);
+    let output = child.wait_with_output();
+    let _ = std::fs::remove_file(&tmp);
+    let output = output?;
+    if !output.status.success() {
+        return Err(Error::Backup(format!(
+            "uploading to {url} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    info!("uploaded backup to {url}");
+    Ok(())
+}
+
+/// Spawn a thread that uploads a backup of the data in `state` every
+/// `interval`, starting right away.
+pub fn upload_periodically(state: Arc<State>, interval: Duration) {
+    std::thread::spawn(move || loop {
+        if let Err(e) = upload(&state) {
+            error!("failed to upload backup: {e}");
+        }
+        std::thread::sleep(interval);
+    });
+}
@@ -0,0 +1,247 @@
+//! A compact binary format for data files with long histories, chosen with
+//! the `data_format` setting.
+//!
+//! The file starts with [`MAGIC`], followed by an index of the runs of
+//! entries from the same year and then the entries themselves. All numbers
+//! are little-endian. The index is a `u32` count of runs, followed by the
+//! year (`i32`), the offset of its first entry from the start of the entries
+//! (`u32`), and the number of entries (`u32`) of each run. Each entry is its
+//! date as a Julian day number (`i32`), its weight in tenths (`i32`), and a
+//! `u8` count of its tags, each of which is a `u8` length followed by the
+//! tag.
+
+use std::io;
+
+use time::Date;
+
+use crate::{
+    store::{valid_tag, valid_weight},
+    Entry,
+};
+
+/// The first bytes of a binary data file.
+pub const MAGIC: &[u8] = b"weight-watcher binary v1\n";
+
+/// Whether `contents` are in the binary format rather than the text one.
+pub fn is_binary(contents: &[u8]) -> bool {
+    contents.starts_with(MAGIC)
+}
+
+/// Write `entries` in the binary format, in the order given.
+pub fn encode(entries: &[Entry]) -> io::Result<Vec<u8>> {
+    let mut records = Vec::new();
+    let mut runs: Vec<(i32, u32, u32)> = Vec::new();
+    for entry in entries {
+        let year = entry.date.year();
+        match runs.last_mut() {
+            Some((y, _, count)) if *y == year => *count += 1,
+            _ => runs.push((year, records.len() as u32, 1)),
+        }
+        records.extend(entry.date.to_julian_day().to_le_bytes());
+        records.extend(((entry.weight * 10.0).round() as i32).to_le_bytes());
+        records.push(byte(entry.tags.len())?);
+        for tag in &entry.tags {
+            records.push(byte(tag.len())?);
+            records.extend(tag.as_bytes());
+        }
+    }
+    let mut contents = MAGIC.to_vec();
+    contents.extend((runs.len() as u32).to_le_bytes());
+    for (year, offset, count) in runs {
+        contents.extend(year.to_le_bytes());
+        contents.extend(offset.to_le_bytes());
+        contents.extend(count.to_le_bytes());
+    }
+    contents.extend(records);
+    Ok(contents)
+}
+
+/// Read the entries from the `contents` of a binary data file.
+pub fn decode(contents: &[u8]) -> io::Result<Vec<Entry>> {
+    let mut reader = Reader(contents.strip_prefix(MAGIC).ok_or_else(corrupt)?);
+    let runs = reader.u32()?;
+    let mut index = Vec::new();
+    for _ in 0..runs {
+        index.push((reader.i32()?, reader.u32()?, reader.u32()?));
+    }
+    // the runs follow each other without gaps, and anything after the last
+    // one means the index lost track of some entries
+    let records = reader.0.len();
+    let mut entries = Vec::new();
+    for (year, offset, count) in index {
+        if offset as usize != records - reader.0.len() {
+            return Err(corrupt());
+        }
+        for _ in 0..count {
+            let entry = reader.entry()?;
+            if entry.date.year() != year {
+                return Err(corrupt());
+            }
+            entries.push(entry);
+        }
+    }
+    if !reader.0.is_empty() {
+        return Err(corrupt());
+    }
+    Ok(entries)
+}
+
+/// `n` as a length byte, which limits how many tags an entry can have and
+/// how long they can be.
+fn byte(n: usize) -> io::Result<u8> {
+    u8::try_from(n).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "an entry has too many tags or too long a tag for the binary \
+             format",
+        )
+    })
+}
+
+fn corrupt() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        "the binary data file is corrupt",
+    )
+}
+
+/// The unread part of a binary data file.
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn take<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        let (bytes, rest) = self.0.split_first_chunk().ok_or_else(corrupt)?;
+        self.0 = rest;
+        Ok(*bytes)
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        self.take().map(u32::from_le_bytes)
+    }
+
+    fn i32(&mut self) -> io::Result<i32> {
+        self.take().map(i32::from_le_bytes)
+    }
+
+    fn entry(&mut self) -> io::Result<Entry> {
+        let date = Date::from_julian_day(self.i32()?).map_err(|_| corrupt())?;
+        let weight = f64::from(self.i32()?) / 10.0;
+        if !valid_weight(weight) {
+            return Err(corrupt());
+        }
+        let [tags] = self.take()?;
+        let tags = (0..tags)
+            .map(|_| {
+                let [len] = self.take()?;
+                let (tag, rest) =
+                    self.0.split_at_checked(len.into()).ok_or_else(corrupt)?;
+                self.0 = rest;
+                std::str::from_utf8(tag)
+                    .ok()
+                    .filter(|tag| valid_tag(tag))
+                    .map(str::to_owned)
+                    .ok_or_else(corrupt)
+            })
+            .collect::<io::Result<_>>()?;
+        Ok(Entry { date, weight, tags })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::Month;
+
+    use super::*;
+
+    fn date(year: i32, month: Month, day: u8) -> Date {
+        Date::from_calendar_date(year, month, day).unwrap()
+    }
+
+    fn entries() -> Vec<Entry> {
+        vec![
+            Entry {
+                date: date(2023, Month::December, 31),
+                weight: 81.2,
+                tags: vec!["travel".to_owned()],
+            },
+            Entry {
+                date: date(2024, Month::January, 1),
+                weight: 80.9,
+                tags: Vec::new(),
+            },
+            Entry {
+                date: date(2024, Month::January, 2),
+                weight: 80.5,
+                tags: vec!["sick".to_owned(), "cold-2".to_owned()],
+            },
+        ]
+    }
+
+    #[test]
+    fn round_trip() {
+        let contents = encode(&entries()).unwrap();
+        assert!(is_binary(&contents));
+        assert_eq!(decode(&contents).unwrap(), entries());
+    }
+
+    #[test]
+    fn empty() {
+        let contents = encode(&[]).unwrap();
+        assert_eq!(decode(&contents).unwrap(), Vec::new());
+    }
+
+    #[test]
+    fn too_many_tags() {
+        let entry = Entry {
+            tags: vec!["a".to_owned(); 256],
+            ..entries()[0].clone()
+        };
+        assert!(encode(&[entry]).is_err());
+    }
+
+    #[test]
+    fn truncated() {
+        let contents = encode(&entries()).unwrap();
+        for len in 0..contents.len() {
+            assert!(decode(&contents[..len]).is_err(), "{len} bytes");
+        }
+    }
+
+    #[test]
+    fn trailing_bytes() {
+        let mut contents = encode(&entries()).unwrap();
+        contents.push(0);
+        assert!(decode(&contents).is_err());
+    }
+
+    #[test]
+    fn short_index() {
+        // claiming the second year has one entry leaves the last one out
+        let mut contents = encode(&entries()).unwrap();
+        let count = MAGIC.len() + 4 + 12 + 8;
+        contents[count..count + 4].copy_from_slice(&1u32.to_le_bytes());
+        assert!(decode(&contents).is_err());
+    }
+
+    #[test]
+    fn corrupt_bytes() {
+        let contents = encode(&entries()).unwrap();
+        for i in MAGIC.len()..contents.len() {
+            let mut contents = contents.clone();
+            contents[i] ^= 0xff;
+            // flipping a byte can't be caught everywhere, but it must never
+            // panic or produce a different number of entries
+            if let Ok(decoded) = decode(&contents) {
+                assert_eq!(decoded.len(), entries().len(), "byte {i}");
+            }
+        }
+    }
+
+    #[test]
+    fn bad_weight() {
+        let mut contents = encode(&entries()[..1]).unwrap();
+        let weight = MAGIC.len() + 4 + 12 + 4;
+        contents[weight..weight + 4].copy_from_slice(&0i32.to_le_bytes());
+        assert!(decode(&contents).is_err());
+    }
+}
@@ -0,0 +1,119 @@
+//! Ranges of IP addresses in CIDR notation, like `192.168.1.0/24`, for the
+//! `allow` setting that limits which clients can connect.
+
+use std::{net::IpAddr, str::FromStr};
+
+/// A block of IP addresses sharing their first `prefix` bits with `addr`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Cidr {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    /// Whether `ip` is in this block. IPv4 addresses mapped into IPv6, as
+    /// dual-stack sockets report them, count as the IPv4 address.
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32);
+                let mask = mask.unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32);
+                let mask = mask.unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = String;
+
+    /// Parse a block like `10.8.0.0/24` or `fd00::/8`, or a single address,
+    /// which is a block of one.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix) = match s.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s, None),
+        };
+        let addr: IpAddr = addr
+            .parse()
+            .map_err(|_| format!("invalid address `{addr}`"))?;
+        let bits = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            None => bits,
+            Some(prefix) => match prefix.parse() {
+                Ok(prefix) if prefix <= bits => prefix,
+                _ => return Err(format!("invalid prefix length `{prefix}`")),
+            },
+        };
+        Ok(Self { addr, prefix })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn block() {
+        let cidr: Cidr = "192.168.1.0/24".parse().unwrap();
+        assert!(cidr.contains(ip("192.168.1.0")));
+        assert!(cidr.contains(ip("192.168.1.255")));
+        assert!(!cidr.contains(ip("192.168.2.1")));
+        assert!(!cidr.contains(ip("::1")));
+    }
+
+    #[test]
+    fn everywhere() {
+        let cidr: Cidr = "0.0.0.0/0".parse().unwrap();
+        assert!(cidr.contains(ip("10.0.0.1")));
+        assert!(cidr.contains(ip("255.255.255.255")));
+        let cidr: Cidr = "::/0".parse().unwrap();
+        assert!(cidr.contains(ip("fd00::1")));
+    }
+
+    #[test]
+    fn single_address() {
+        let cidr: Cidr = "10.8.0.1/32".parse().unwrap();
+        assert!(cidr.contains(ip("10.8.0.1")));
+        assert!(!cidr.contains(ip("10.8.0.2")));
+        assert_eq!("10.8.0.1".parse::<Cidr>().unwrap(), cidr);
+    }
+
+    #[test]
+    fn ipv6() {
+        let cidr: Cidr = "fd00::/8".parse().unwrap();
+        assert!(cidr.contains(ip("fd12:3456::1")));
+        assert!(!cidr.contains(ip("fe80::1")));
+        assert!(!cidr.contains(ip("10.0.0.1")));
+        let cidr: Cidr = "::1/128".parse().unwrap();
+        assert!(cidr.contains(ip("::1")));
+        assert!(!cidr.contains(ip("::2")));
+    }
+
+    #[test]
+    fn ipv4_mapped() {
+        let cidr: Cidr = "192.168.1.0/24".parse().unwrap();
+        assert!(cidr.contains(ip("::ffff:192.168.1.7")));
+        assert!(!cidr.contains(ip("::ffff:192.168.2.7")));
+    }
+
+    #[test]
+    fn bad_prefix() {
+        assert!("10.0.0.0/33".parse::<Cidr>().is_err());
+        assert!("fd00::/129".parse::<Cidr>().is_err());
+        assert!("10.0.0.0/".parse::<Cidr>().is_err());
+        assert!("10.0.0.0/-1".parse::<Cidr>().is_err());
+        assert!("10.0.0.0/x".parse::<Cidr>().is_err());
+        assert!("10.0.0/24".parse::<Cidr>().is_err());
+    }
+}
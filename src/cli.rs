@@ -0,0 +1,233 @@
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand};
+
+use crate::{export, import::Format, merge::Policy, plot::Span, units::Unit};
+
+/// Simple web app to record body weights over time
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// Port to listen on
+    #[arg(short, long, default_value_t = 9999)]
+    pub port: u16,
+
+    /// Path to the weight data file [default: weights.dat in the platform
+    /// data directory, such as ~/.local/share/weight-watcher on Linux]
+    #[arg(short, long)]
+    pub data_file: Option<PathBuf>,
+
+    /// Path to the config file [default: config in the platform config
+    /// directory, such as ~/.config/weight-watcher on Linux]
+    #[arg(short, long)]
+    pub config: Option<PathBuf>,
+
+    /// Log more details. Pass twice to log everything
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+
+    /// Refuse every change to the data over the web and hide the form for
+    /// entering weights, as with `read_only = true` in the config file
+    #[arg(long)]
+    pub read_only: bool,
+
+    /// Serve requests with the tokio runtime instead of the blocking
+    /// accept loop
+    #[cfg(feature = "tokio")]
+    #[arg(long = "async")]
+    pub use_async: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run the web server. This is the default when no command is given
+    Serve,
+
+    /// Restore a backup made with /backup or uploaded to the backup URL,
+    /// replacing the current entries. The current data is saved to the
+    /// snapshots directory next to the data file first
+    Restore {
+        /// The backup file to restore
+        file: PathBuf,
+
+        /// Only add entries on dates that don't already have one
+        #[arg(long)]
+        merge: bool,
+    },
+
+    /// Merge another data file, like one from a different server, into the
+    /// current entries and report what changed
+    Merge {
+        /// The data file to merge in
+        file: PathBuf,
+
+        /// What to do on dates that have different entries in each file:
+        /// keep the existing ones, replace them, or keep both
+        #[arg(long, default_value = "keep")]
+        conflicts: Policy,
+
+        /// The profile to merge into instead of the default profile
+        #[arg(long)]
+        profile: Option<String>,
+    },
+
+    /// Import the history exported from another app, or a weight-watcher
+    /// data file, into the current entries without starting the server and
+    /// report what changed
+    Import {
+        /// The export to import
+        file: PathBuf,
+
+        /// The format of the file: myfitnesspal, libra, withings, renpho,
+        /// zepp, weight-watcher, or json [default: guessed from the file]
+        #[arg(long)]
+        format: Option<Format>,
+
+        /// The unit the file's weights are in, if it doesn't say [default:
+        /// the profile's unit]
+        #[arg(long)]
+        unit: Option<Unit>,
+
+        /// What to do on dates that already have a different entry: keep
+        /// the existing one, replace it, or keep both
+        #[arg(long, default_value = "keep")]
+        conflicts: Policy,
+
+        /// The profile to import into instead of the default profile
+        #[arg(long)]
+        profile: Option<String>,
+    },
+
+    /// Write the full history to a file without starting the server, like
+    /// for a backup from cron
+    Export {
+        /// Where to write the history. The format is chosen by the
+        /// extension: `.dat` for the data file format, `.csv`, `.json`, or
+        /// `.txt` for a backup of every profile like /backup makes
+        file: PathBuf,
+
+        /// The format to write in, regardless of the extension: dat, csv,
+        /// json, or txt
+        #[arg(long)]
+        format: Option<export::Format>,
+
+        /// The profile to export instead of the default profile. Backups
+        /// always include every profile
+        #[arg(long)]
+        profile: Option<String>,
+    },
+
+    /// Render the graph from the index page to a PNG file without starting
+    /// the server, like for showing it on another dashboard
+    Graph {
+        /// Where to write the graph
+        output: PathBuf,
+
+        /// How many days before today to show, or `all`
+        #[arg(long, default_value = "28")]
+        days: Span,
+
+        /// Only show entries with this tag
+        #[arg(long)]
+        tag: Option<String>,
+
+        /// The profile to graph instead of the default profile
+        #[arg(long)]
+        profile: Option<String>,
+    },
+
+    /// Check every profile's data file for unreadable lines, impossible
+    /// weights, entries out of date order, repeated entries, and sudden
+    /// jumps in weight, and print each problem with its line number. Exits
+    /// unsuccessfully if any are found
+    Check {
+        /// Save a snapshot of the data to the snapshots directory and then
+        /// rewrite the data files in date order without unreadable lines or
+        /// repeated entries. Jumps in weight are left to be fixed by hand
+        #[arg(long)]
+        fix: bool,
+    },
+
+    /// Print a completion script for `shell` to add to its startup files
+    #[command(hide = true)]
+    Completions {
+        /// The shell to complete for: bash, elvish, fish, powershell, or zsh
+        shell: clap_complete::Shell,
+    },
+
+    /// Print the man page in roff, for `man -l -`
+    #[command(hide = true)]
+    Man {
+        /// Write the pages for the program and each command to this
+        /// directory instead, like /usr/local/share/man/man1
+        #[arg(long)]
+        dir: Option<PathBuf>,
+    },
+
+    /// Manage the API tokens accepted in place of the admin password
+    Token {
+        #[command(subcommand)]
+        action: TokenCommand,
+    },
+
+    /// Manage the links for recording a weight with one request, like
+    /// /log/<token>?w=81.4
+    LogToken {
+        #[command(subcommand)]
+        action: LogTokenCommand,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum TokenCommand {
+    /// Create a token and print it. It can't be shown again later
+    Create {
+        /// What the token is for, like "phone shortcut"
+        label: String,
+    },
+
+    /// List the tokens by ID, creation date, and label
+    List,
+
+    /// Revoke the token with the given ID
+    Revoke {
+        /// The ID shown by `token list`
+        id: String,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum LogTokenCommand {
+    /// Create a token and print its link. It can't be shown again later
+    Create {
+        /// What the token is for, like "bathroom button"
+        label: String,
+
+        /// The profile it records weights for, instead of the default one
+        #[arg(long)]
+        profile: Option<String>,
+    },
+
+    /// List the tokens by ID, creation date, profile, and label
+    List,
+
+    /// Revoke the token with the given ID
+    Revoke {
+        /// The ID shown by `log-token list`
+        id: String,
+    },
+}
+
+impl Cli {
+    /// The log level requested with `--verbose`, if any.
+    pub fn log_level(&self) -> Option<&'static str> {
+        match self.verbose {
+            0 => None,
+            1 => Some("debug"),
+            _ => Some("trace"),
+        }
+    }
+}
@@ -0,0 +1,743 @@
+use std::{
+    fmt::Display,
+    net::IpAddr,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+use time::Weekday;
+
+use crate::{
+    cidr::Cidr,
+    crypto,
+    date_format::DateFormat,
+    growth::Child,
+    logging::LogFormat,
+    profile,
+    stats::Direction,
+    store::{self, DataFormat},
+    templates::Density,
+    timezone::Timezone,
+    units::Unit,
+};
+
+/// User settings read from the `config` file in the config directory. Each
+/// non-empty line has the form `key = value`, and lines starting with `#` are
+/// ignored.
+pub struct Config {
+    /// Page title shown in the browser tab and the main heading
+    pub title: String,
+
+    /// CSS color used for headings and buttons
+    pub accent: String,
+
+    /// CSS font size of the pages' text, like `18px` or `110%`
+    pub font_size: String,
+
+    /// How tightly the pages' tables are laid out
+    pub density: Density,
+
+    /// Minimum level of log messages to print, or any other `RUST_LOG`-style
+    /// filter directive
+    pub log_level: String,
+
+    /// Whether to print logs as text or JSON
+    pub log_format: LogFormat,
+
+    /// The largest request body accepted, in bytes
+    pub max_body_size: usize,
+
+    /// Names of the profiles to serve in addition to the default profile
+    pub profiles: Vec<String>,
+
+    /// Profiles for pets, with the unit each one's weight is recorded in if
+    /// it differs from `unit`. These are served like the named `profiles`.
+    pub pets: Vec<(String, Option<Unit>)>,
+
+    /// Profiles tracking a child's growth, by profile name with `None` for
+    /// the default profile
+    pub children: Vec<(Option<String>, Child)>,
+
+    /// The weight each profile is aiming for, by profile name with `None`
+    /// for the default profile
+    pub goals: Vec<(Option<String>, f64)>,
+
+    /// The unit weights are recorded in
+    pub unit: Unit,
+
+    /// Whether people's weights are shown and typed in as stones and pounds,
+    /// while still being recorded in `unit`
+    pub stones: bool,
+
+    /// Whether a new weight replaces one already recorded on the same day
+    /// instead of being added alongside it
+    pub overwrite_same_day: bool,
+
+    /// A new weight that differs from the previous entry by more than this
+    /// has to be confirmed before it is saved, to catch typos
+    pub confirm_change: Option<f64>,
+
+    /// How many of the latest entries to list on the index page
+    pub recent_entries: usize,
+
+    /// How many days deleted entries are kept in the trash
+    pub trash_days: u32,
+
+    /// How dates are shown in tables and on the graph
+    pub date_format: DateFormat,
+
+    /// Whether to embed the graph in the index page as a `data:` URI instead
+    /// of linking to `graph.png`
+    pub inline_graph: bool,
+
+    /// Whether to commit the data files to a git repository after each change
+    pub git: bool,
+
+    /// The git remote to pull from and push to, if any
+    pub git_remote: Option<String>,
+
+    /// How often to sync with `git_remote`
+    pub git_sync_interval: Duration,
+
+    /// The host name to advertise the server under with mDNS, without the
+    /// `.local` suffix. The server isn't advertised if this is unset.
+    pub mdns_name: Option<String>,
+
+    /// The password for administrative pages like `/backup`, read from the
+    /// `admin_password_file` setting. These pages are disabled without one.
+    pub admin_password: Option<String>,
+
+    /// The addresses clients may connect from, or anywhere if this is empty
+    pub allow: Vec<Cidr>,
+
+    /// Whether an entry in `allow` couldn't be parsed. No client is let in
+    /// then, rather than more of them than the setting meant to.
+    pub allow_invalid: bool,
+
+    /// Whether to refuse every change to the data over the web and hide the
+    /// form for entering weights
+    pub read_only: bool,
+
+    /// WebDAV or S3 URL of the directory to upload backups to
+    pub backup_url: Option<String>,
+
+    /// The region of an S3-compatible `backup_url`, which is required to
+    /// sign requests to it
+    pub backup_s3_region: Option<String>,
+
+    /// File holding the `user:password` or S3 `access_key:secret_key` for
+    /// `backup_url`
+    pub backup_credentials_file: Option<PathBuf>,
+
+    /// File holding the passphrase backups are encrypted with, if it
+    /// differs from `encryption_key_file`
+    pub backup_key_file: Option<PathBuf>,
+
+    /// How often to upload a backup
+    pub backup_interval: Duration,
+
+    /// How many backups to keep at `backup_url`
+    pub backup_keep: usize,
+
+    /// InfluxDB or VictoriaMetrics write URL to push the entries to in line
+    /// protocol
+    pub influx_url: Option<String>,
+
+    /// File holding the token for `influx_url`
+    pub influx_token_file: Option<PathBuf>,
+
+    /// How often to push the entries to `influx_url`
+    pub influx_interval: Duration,
+
+    /// The client ID of the Withings developer app to fetch weigh-ins with
+    pub withings_client_id: Option<String>,
+
+    /// File holding the Withings app's client secret
+    pub withings_client_secret_file: Option<PathBuf>,
+
+    /// This server's `/withings/callback` URL as the browser reaches it,
+    /// which has to be registered with the Withings app
+    pub withings_redirect_url: Option<String>,
+
+    /// The profile Withings weigh-ins are added to, `None` for the default
+    pub withings_profile: Option<String>,
+
+    /// How often to fetch new weigh-ins from Withings
+    pub withings_interval: Duration,
+
+    /// File holding the passphrase the data files are encrypted with, if
+    /// they should be encrypted
+    pub encryption_key_file: Option<PathBuf>,
+
+    /// The format to keep the weight data files in
+    pub data_format: DataFormat,
+
+    /// The timezone for deciding which day it is, or `None` to use the
+    /// system's local offset
+    pub timezone: Option<Timezone>,
+
+    /// Entries with any of these tags are still shown but left out of
+    /// statistics like the graph's y-axis range
+    pub exclude_tags: Vec<String>,
+
+    /// The day of the week of the official weigh-in, if only that day's
+    /// entries should make up the graph and statistics
+    pub weigh_in_day: Option<Weekday>,
+
+    /// Which way a change in weight is colored as progress in tables
+    pub good_change: Direction,
+
+    /// Problems encountered while reading the config file. These are
+    /// collected rather than logged directly because the config has to be
+    /// loaded before logging is set up.
+    pub warnings: Vec<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            title: "weight-watcher".to_owned(),
+            accent: "black".to_owned(),
+            font_size: "100%".to_owned(),
+            density: Density::default(),
+            log_level: "info".to_owned(),
+            log_format: LogFormat::default(),
+            max_body_size: 1024 * 1024,
+            profiles: Vec::new(),
+            pets: Vec::new(),
+            children: Vec::new(),
+            goals: Vec::new(),
+            unit: Unit::default(),
+            stones: false,
+            timezone: None,
+            encryption_key_file: None,
+            data_format: DataFormat::default(),
+            admin_password: None,
+            allow: Vec::new(),
+            allow_invalid: false,
+            read_only: false,
+            backup_url: None,
+            backup_s3_region: None,
+            backup_credentials_file: None,
+            backup_key_file: None,
+            backup_interval: Duration::from_secs(24 * 60 * 60),
+            backup_keep: 7,
+            influx_url: None,
+            influx_token_file: None,
+            influx_interval: Duration::from_secs(60 * 60),
+            withings_client_id: None,
+            withings_client_secret_file: None,
+            withings_redirect_url: None,
+            withings_profile: None,
+            withings_interval: Duration::from_secs(60 * 60),
+            git: false,
+            git_remote: None,
+            git_sync_interval: Duration::from_secs(60 * 60),
+            mdns_name: None,
+            recent_entries: 7,
+            trash_days: 30,
+            date_format: DateFormat::default(),
+            inline_graph: false,
+            overwrite_same_day: false,
+            confirm_change: None,
+            exclude_tags: Vec::new(),
+            weigh_in_day: None,
+            good_change: Direction::default(),
+            warnings: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    /// The names of every named profile, both people's and pets'.
+    pub fn profile_names(&self) -> Vec<String> {
+        let mut names = self.profiles.clone();
+        for (pet, _) in &self.pets {
+            if !names.contains(pet) {
+                names.push(pet.clone());
+            }
+        }
+        names
+    }
+
+    /// Whether `profile` is one of the `pets`.
+    pub fn is_pet(&self, profile: Option<&str>) -> bool {
+        self.pets
+            .iter()
+            .any(|(name, _)| Some(name.as_str()) == profile)
+    }
+
+    /// The unit `profile`'s weights are recorded in.
+    pub fn unit_for(&self, profile: Option<&str>) -> Unit {
+        self.pets
+            .iter()
+            .find(|(name, _)| Some(name.as_str()) == profile)
+            .and_then(|(_, unit)| *unit)
+            .unwrap_or(self.unit)
+    }
+
+    /// The unit `profile`'s weights are recorded in, if they are shown in
+    /// stones and pounds. Only adults' are, since pets and children are too
+    /// light for stones to be useful.
+    pub fn stones_for(&self, profile: Option<&str>) -> Option<Unit> {
+        let adult = !self.is_pet(profile) && self.child(profile).is_none();
+        (self.stones && adult).then(|| self.unit_for(profile))
+    }
+
+    /// Which way a change in `profile`'s weight counts as progress. Children
+    /// are always meant to be growing.
+    pub fn good_change_for(&self, profile: Option<&str>) -> Direction {
+        match self.child(profile) {
+            Some(_) => Direction::Gain,
+            None => self.good_change,
+        }
+    }
+
+    /// Whether a client connecting from `ip` is let in. Clients whose
+    /// address can't be determined are only let in without an allowlist, and
+    /// none are if the allowlist has an invalid entry.
+    pub fn allows(&self, ip: Option<IpAddr>) -> bool {
+        if self.allow_invalid {
+            return false;
+        }
+        self.allow.is_empty()
+            || ip.is_some_and(|ip| self.allow.iter().any(|n| n.contains(ip)))
+    }
+
+    /// The child tracked by `profile`, if it tracks one.
+    pub fn child(&self, profile: Option<&str>) -> Option<&Child> {
+        self.children.iter().find_map(|(name, child)| {
+            (name.as_deref() == profile).then_some(child)
+        })
+    }
+
+    /// The weight `profile` is aiming for, if it has a goal.
+    pub fn goal_for(&self, profile: Option<&str>) -> Option<f64> {
+        self.goals.iter().find_map(|(name, goal)| {
+            (name.as_deref() == profile).then_some(*goal)
+        })
+    }
+
+    pub fn load(path: &Path) -> Self {
+        let mut config = Self::default();
+        let Ok(contents) = std::fs::read_to_string(path) else {
+            return config;
+        };
+        // relative paths in the config are relative to its directory
+        let dir = path.parent().unwrap_or(Path::new(""));
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                config
+                    .warnings
+                    .push(format!("ignoring malformed config line: {line}"));
+                continue;
+            };
+            let key = key.trim();
+            let value = value.trim().to_owned();
+            let warnings = &mut config.warnings;
+            match key {
+                "title" => config.title = value,
+                "accent" => config.accent = value,
+                "font_size" => config.font_size = value,
+                "density" => set(&mut config.density, key, &value, warnings),
+                "log_level" => config.log_level = value,
+                "log_format" => {
+                    set(&mut config.log_format, key, &value, warnings)
+                }
+                "data_format" => {
+                    set(&mut config.data_format, key, &value, warnings)
+                }
+                "max_body_size" => match parse_size(&value) {
+                    Some(size) => config.max_body_size = size,
+                    None => warnings
+                        .push(format!("invalid size for {key}: {value}")),
+                },
+                "profiles" => {
+                    for name in value.split(',').map(str::trim) {
+                        if name.is_empty() {
+                            continue;
+                        }
+                        if profile::valid_name(name) {
+                            config.profiles.push(name.to_owned());
+                        } else {
+                            warnings.push(format!(
+                                "invalid profile name `{name}`: names may \
+                                 only contain letters, digits, - and _"
+                            ));
+                        }
+                    }
+                }
+                "pets" => {
+                    for pet in value.split(',').map(str::trim) {
+                        if pet.is_empty() {
+                            continue;
+                        }
+                        match parse_pet(pet) {
+                            Ok(pet) => config.pets.push(pet),
+                            Err(e) => warnings
+                                .push(format!("invalid pet `{pet}`: {e}")),
+                        }
+                    }
+                }
+                "children" => {
+                    for child in value.split(',').map(str::trim) {
+                        if child.is_empty() {
+                            continue;
+                        }
+                        match parse_child(child) {
+                            Ok(child) => config.children.push(child),
+                            Err(e) => warnings
+                                .push(format!("invalid child `{child}`: {e}")),
+                        }
+                    }
+                }
+                "allow" => {
+                    for network in value.split(',').map(str::trim) {
+                        if network.is_empty() {
+                            continue;
+                        }
+                        match network.parse() {
+                            Ok(network) => config.allow.push(network),
+                            Err(e) => {
+                                config.allow_invalid = true;
+                                warnings.push(format!(
+                                    "invalid network `{network}` in {key}: \
+                                     {e}; refusing every client"
+                                ))
+                            }
+                        }
+                    }
+                }
+                "goals" => {
+                    for goal in value.split(',').map(str::trim) {
+                        if goal.is_empty() {
+                            continue;
+                        }
+                        match parse_goal(goal) {
+                            Ok(goal) => config.goals.push(goal),
+                            Err(e) => warnings
+                                .push(format!("invalid goal `{goal}`: {e}")),
+                        }
+                    }
+                }
+                "unit" => set(&mut config.unit, key, &value, warnings),
+                "stones" => set(&mut config.stones, key, &value, warnings),
+                "recent_entries" => match value.parse() {
+                    Ok(n) if n > 0 => config.recent_entries = n,
+                    _ => warnings.push(format!(
+                        "invalid value for {key}: expected a positive number"
+                    )),
+                },
+                "trash_days" => {
+                    set(&mut config.trash_days, key, &value, warnings)
+                }
+                "overwrite_same_day" => {
+                    set(&mut config.overwrite_same_day, key, &value, warnings)
+                }
+                "confirm_change" => match value.parse::<f64>() {
+                    Ok(change) if change > 0.0 => {
+                        config.confirm_change = Some(change)
+                    }
+                    // zero turns the confirmation off
+                    Ok(0.0) => config.confirm_change = None,
+                    _ => warnings.push(format!(
+                        "invalid value for {key}: expected a positive number"
+                    )),
+                },
+                "date_format" => {
+                    set(&mut config.date_format, key, &value, warnings)
+                }
+                "inline_graph" => {
+                    set(&mut config.inline_graph, key, &value, warnings)
+                }
+                "git" => set(&mut config.git, key, &value, warnings),
+                "read_only" => {
+                    set(&mut config.read_only, key, &value, warnings)
+                }
+                "git_remote" => {
+                    config.git_remote = (!value.is_empty()).then_some(value)
+                }
+                "mdns_name" => {
+                    let value = value.trim_end_matches(".local");
+                    if value.is_empty() {
+                        config.mdns_name = None;
+                    } else if value
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '-')
+                    {
+                        config.mdns_name = Some(value.to_owned());
+                    } else {
+                        warnings.push(format!(
+                            "invalid mdns_name `{value}`: names may only \
+                             contain letters, digits, and -"
+                        ));
+                    }
+                }
+                "git_sync_interval" => match parse_duration(&value) {
+                    Some(interval) => config.git_sync_interval = interval,
+                    None => warnings
+                        .push(format!("invalid duration for {key}: {value}")),
+                },
+                "encryption_key_file" => {
+                    config.encryption_key_file = Some(dir.join(value))
+                }
+                "admin_password_file" => {
+                    let path = dir.join(value);
+                    match crypto::read_passphrase(&path) {
+                        Ok(password) if !password.is_empty() => {
+                            config.admin_password = Some(password)
+                        }
+                        Ok(_) => warnings.push(format!(
+                            "ignoring empty {key} {}",
+                            path.display()
+                        )),
+                        Err(e) => warnings.push(format!(
+                            "failed to read {key} {}: {e}",
+                            path.display()
+                        )),
+                    }
+                }
+                "backup_url" => {
+                    config.backup_url = (!value.is_empty()).then_some(value)
+                }
+                "backup_s3_region" => {
+                    config.backup_s3_region =
+                        (!value.is_empty()).then_some(value)
+                }
+                "backup_credentials_file" => {
+                    config.backup_credentials_file = Some(dir.join(value))
+                }
+                "backup_key_file" => {
+                    config.backup_key_file = Some(dir.join(value))
+                }
+                "backup_interval" => match parse_duration(&value) {
+                    Some(interval) => config.backup_interval = interval,
+                    None => warnings
+                        .push(format!("invalid duration for {key}: {value}")),
+                },
+                "backup_keep" => {
+                    set(&mut config.backup_keep, key, &value, warnings)
+                }
+                "influx_url" => {
+                    config.influx_url = (!value.is_empty()).then_some(value)
+                }
+                "influx_token_file" => {
+                    config.influx_token_file = Some(dir.join(value))
+                }
+                "influx_interval" => match parse_duration(&value) {
+                    Some(interval) => config.influx_interval = interval,
+                    None => warnings
+                        .push(format!("invalid duration for {key}: {value}")),
+                },
+                "withings_client_id" => {
+                    config.withings_client_id =
+                        (!value.is_empty()).then_some(value)
+                }
+                "withings_client_secret_file" => {
+                    config.withings_client_secret_file = Some(dir.join(value))
+                }
+                "withings_redirect_url" => {
+                    config.withings_redirect_url =
+                        (!value.is_empty()).then_some(value)
+                }
+                "withings_profile" => {
+                    config.withings_profile =
+                        (!value.is_empty()).then_some(value)
+                }
+                "withings_interval" => match parse_duration(&value) {
+                    Some(interval) => config.withings_interval = interval,
+                    None => warnings
+                        .push(format!("invalid duration for {key}: {value}")),
+                },
+                "timezone" => match value.parse() {
+                    Ok(tz) => config.timezone = Some(tz),
+                    Err(e) => {
+                        warnings.push(format!("invalid value for {key}: {e}"))
+                    }
+                },
+                "exclude_tags" => match store::parse_tags(&value) {
+                    Some(tags) => config.exclude_tags = tags,
+                    None => warnings.push(format!(
+                        "invalid tags for {key}: tags may only contain \
+                         letters, digits, - and _"
+                    )),
+                },
+                "good_change" => {
+                    set(&mut config.good_change, key, &value, warnings)
+                }
+                "weigh_in_day" if value.is_empty() => {
+                    config.weigh_in_day = None
+                }
+                "weigh_in_day" => match parse_weekday(&value) {
+                    Some(day) => config.weigh_in_day = Some(day),
+                    None => warnings.push(format!(
+                        "invalid value for {key}: expected a day of the week \
+                         like monday"
+                    )),
+                },
+                key => {
+                    warnings.push(format!("ignoring unknown config key: {key}"))
+                }
+            }
+        }
+        config
+    }
+}
+
+/// Parse a day of the week like `monday` or `Mon`.
+fn parse_weekday(value: &str) -> Option<Weekday> {
+    let value = value.to_ascii_lowercase();
+    [
+        Weekday::Monday,
+        Weekday::Tuesday,
+        Weekday::Wednesday,
+        Weekday::Thursday,
+        Weekday::Friday,
+        Weekday::Saturday,
+        Weekday::Sunday,
+    ]
+    .into_iter()
+    .find(|day| {
+        let name = day.to_string().to_ascii_lowercase();
+        name == value || name[..3] == value
+    })
+}
+
+/// Parse a pet like `rex` or `whiskers g` into its profile name and unit.
+fn parse_pet(
+    value: &str,
+) -> std::result::Result<(String, Option<Unit>), String> {
+    let mut fields = value.split_ascii_whitespace();
+    let name = fields.next().unwrap_or_default();
+    if !profile::valid_name(name) || name == "default" {
+        return Err(format!("invalid profile name `{name}`"));
+    }
+    let unit = fields.next().map(str::parse).transpose()?;
+    if fields.next().is_some() {
+        return Err("expected a profile name and optionally a unit".to_owned());
+    }
+    Ok((name.to_owned(), unit))
+}
+
+/// Parse a child like `anna 2024-03-01 girl` into the profile name, with
+/// `default` for the default profile, and the child's details.
+fn parse_child(
+    value: &str,
+) -> std::result::Result<(Option<String>, Child), String> {
+    let fields: Vec<_> = value.split_ascii_whitespace().collect();
+    let [name, birth, sex] = fields[..] else {
+        return Err(
+            "expected a profile name, birth date, and boy or girl".to_owned()
+        );
+    };
+    let name = match name {
+        "default" => None,
+        name if profile::valid_name(name) => Some(name.to_owned()),
+        _ => return Err(format!("invalid profile name `{name}`")),
+    };
+    let birth = store::parse_date(birth)
+        .ok_or_else(|| format!("invalid birth date `{birth}`"))?;
+    Ok((
+        name,
+        Child {
+            birth,
+            sex: sex.parse()?,
+        },
+    ))
+}
+
+/// Parse a goal like `anna 60` into the profile name, with `default` for the
+/// default profile, and the weight.
+fn parse_goal(
+    value: &str,
+) -> std::result::Result<(Option<String>, f64), String> {
+    let fields: Vec<_> = value.split_ascii_whitespace().collect();
+    let [name, weight] = fields[..] else {
+        return Err("expected a profile name and a weight".to_owned());
+    };
+    let name = match name {
+        "default" => None,
+        name if profile::valid_name(name) => Some(name.to_owned()),
+        _ => return Err(format!("invalid profile name `{name}`")),
+    };
+    match store::parse_weight(weight) {
+        Some(weight) if store::valid_weight(weight) => Ok((name, weight)),
+        _ => Err(format!("invalid weight `{weight}`")),
+    }
+}
+
+/// Parse `value` into `field`, or record a warning if `value` is invalid.
+fn set<T>(field: &mut T, key: &str, value: &str, warnings: &mut Vec<String>)
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    match value.parse() {
+        Ok(v) => *field = v,
+        Err(e) => warnings.push(format!("invalid value for {key}: {e}")),
+    }
+}
+
+/// Parse a number of bytes with an optional `K`, `M`, or `G` suffix for
+/// multiples of 1024, like `512K` or `10M`.
+fn parse_size(value: &str) -> Option<usize> {
+    let (digits, scale) = match value.char_indices().last()? {
+        (i, 'K' | 'k') => (&value[..i], 1 << 10),
+        (i, 'M' | 'm') => (&value[..i], 1 << 20),
+        (i, 'G' | 'g') => (&value[..i], 1 << 30),
+        _ => (value, 1),
+    };
+    digits.trim().parse::<usize>().ok()?.checked_mul(scale)
+}
+
+/// Parse a number of seconds with an optional `s`, `m`, or `h` suffix, like
+/// `90s` or `15m`. Zero isn't a useful interval, so it is rejected.
+fn parse_duration(value: &str) -> Option<Duration> {
+    let (digits, scale) = match value.char_indices().last()? {
+        (i, 's') => (&value[..i], 1),
+        (i, 'm') => (&value[..i], 60),
+        (i, 'h') => (&value[..i], 60 * 60),
+        _ => (value, 1),
+    };
+    let secs = digits.trim().parse::<u64>().ok()?.checked_mul(scale)?;
+    (secs > 0).then(|| Duration::from_secs(secs))
+}
+
+/// The directory of template overrides next to `config_file`.
+pub fn templates_dir(config_file: &Path) -> PathBuf {
+    config_file
+        .parent()
+        .unwrap_or(Path::new(""))
+        .join("templates")
+}
+
+/// Load the favicon from `logo.png` next to `config_file`, falling back on
+/// the embedded logo.
+pub fn load_logo(config_file: &Path) -> Vec<u8> {
+    config_file
+        .parent()
+        .and_then(|dir| std::fs::read(dir.join("logo.png")).ok())
+        .unwrap_or_else(|| include_bytes!("../logo.png").to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_allow_refuses_everyone() {
+        let path = std::env::temp_dir()
+            .join(format!("weight-watcher-allow-{}", std::process::id()));
+        std::fs::write(&path, "allow = 127.0.0.1, 10.0.0.0/33\n").unwrap();
+        let config = Config::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(config.allow_invalid);
+        assert!(!config.allows(Some("127.0.0.1".parse().unwrap())));
+        assert!(!config.allows(None));
+    }
+}
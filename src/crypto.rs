@@ -0,0 +1,184 @@
+//! Encryption of the data files at rest. An encrypted file starts with a
+//! short header identifying it and the salt used to derive its key from the
+//! passphrase, followed by the nonce and the XChaCha20-Poly1305 ciphertext of
+//! the plaintext data file.
+
+use std::{io, path::Path, sync::Arc};
+
+use argon2::Argon2;
+use chacha20poly1305::{
+    aead::{Aead, Generate, KeyInit, Payload},
+    XChaCha20Poly1305, XNonce,
+};
+
+const MAGIC: &[u8] = b"weight-watcher encrypted v1\n";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+const HEADER_LEN: usize = MAGIC.len() + SALT_LEN;
+
+/// A key derived from a passphrase, for reading and writing one data file.
+pub struct Cipher {
+    passphrase: Arc<str>,
+    salt: [u8; SALT_LEN],
+    aead: XChaCha20Poly1305,
+}
+
+impl Cipher {
+    /// Derive the key for the file with `contents` from `passphrase`, reusing
+    /// the file's salt if it is already encrypted and picking a new one
+    /// otherwise. Key derivation is deliberately slow, so the cipher should
+    /// be kept around rather than recreated for each read.
+    pub fn new(passphrase: Arc<str>, contents: &[u8]) -> io::Result<Self> {
+        let salt = match salt(contents) {
+            Some(salt) => salt,
+            None => <[u8; SALT_LEN]>::generate(),
+        };
+        let aead = derive(&passphrase, &salt)?;
+        Ok(Self {
+            passphrase,
+            salt,
+            aead,
+        })
+    }
+
+    pub fn passphrase(&self) -> &Arc<str> {
+        &self.passphrase
+    }
+
+    /// Encrypt `plaintext` into the contents of an encrypted file.
+    pub fn encrypt(&self, plaintext: &[u8]) -> io::Result<Vec<u8>> {
+        let mut out = Vec::with_capacity(HEADER_LEN + NONCE_LEN);
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&self.salt);
+        let nonce = XNonce::generate();
+        let ciphertext = self
+            .aead
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: plaintext,
+                    aad: &out,
+                },
+            )
+            .map_err(|_| io::Error::other("encryption failed"))?;
+        out.extend_from_slice(&nonce);
+        out.extend_from_slice(&ciphertext);
+        Ok(out)
+    }
+
+    /// Decrypt the contents of an encrypted file. Files whose salt differs
+    /// from this cipher's, such as ones replaced from a backup, need their
+    /// own key to be derived first.
+    pub fn decrypt(&self, contents: &[u8]) -> io::Result<Vec<u8>> {
+        let salt = salt(contents).ok_or_else(|| invalid("not encrypted"))?;
+        let derived;
+        let aead = if salt == self.salt {
+            &self.aead
+        } else {
+            derived = derive(&self.passphrase, &salt)?;
+            &derived
+        };
+        let (header, rest) = contents.split_at(HEADER_LEN);
+        if rest.len() < NONCE_LEN {
+            return Err(invalid("encrypted data file is truncated"));
+        }
+        let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+        let nonce = XNonce::try_from(nonce).expect("nonce has the right size");
+        aead.decrypt(
+            &nonce,
+            Payload {
+                msg: ciphertext,
+                aad: header,
+            },
+        )
+        .map_err(|_| {
+            invalid("failed to decrypt data file, is the key file correct?")
+        })
+    }
+}
+
+/// Read a passphrase from the file at `path`, ignoring trailing whitespace
+/// like the newline most editors add.
+pub fn read_passphrase(path: &Path) -> io::Result<String> {
+    let mut passphrase = std::fs::read_to_string(path)?;
+    passphrase.truncate(passphrase.trim_end().len());
+    Ok(passphrase)
+}
+
+/// Whether `contents` are those of an encrypted file.
+pub fn is_encrypted(contents: &[u8]) -> bool {
+    contents.starts_with(MAGIC)
+}
+
+/// The salt from the header of an encrypted file.
+fn salt(contents: &[u8]) -> Option<[u8; SALT_LEN]> {
+    if !is_encrypted(contents) || contents.len() < HEADER_LEN {
+        return None;
+    }
+    contents[MAGIC.len()..HEADER_LEN].try_into().ok()
+}
+
+fn derive(passphrase: &str, salt: &[u8]) -> io::Result<XChaCha20Poly1305> {
+    let mut key = [0; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| io::Error::other(format!("key derivation failed: {e}")))?;
+    Ok(XChaCha20Poly1305::new(&key.into()))
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DATA: &[u8] = b"2024-05-01 80.3\n2024-05-02 80.1 travel\n";
+
+    fn cipher(passphrase: &str, contents: &[u8]) -> Cipher {
+        Cipher::new(passphrase.into(), contents).unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        let contents = cipher("hunter2", b"").encrypt(DATA).unwrap();
+        assert!(is_encrypted(&contents));
+        assert!(!contents.windows(DATA.len()).any(|w| w == DATA));
+        // a new cipher picks up the file's salt
+        let cipher = cipher("hunter2", &contents);
+        assert_eq!(cipher.decrypt(&contents).unwrap(), DATA);
+    }
+
+    #[test]
+    fn wrong_passphrase() {
+        let contents = cipher("hunter2", b"").encrypt(DATA).unwrap();
+        let err = cipher("hunter3", &contents).decrypt(&contents).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn tampered() {
+        let cipher = cipher("hunter2", b"");
+        let contents = cipher.encrypt(DATA).unwrap();
+        for i in [
+            MAGIC.len(),
+            HEADER_LEN,
+            HEADER_LEN + NONCE_LEN,
+            contents.len() - 1,
+        ] {
+            let mut contents = contents.clone();
+            contents[i] ^= 1;
+            assert!(cipher.decrypt(&contents).is_err(), "byte {i}");
+        }
+    }
+
+    #[test]
+    fn truncated() {
+        let cipher = cipher("hunter2", b"");
+        let contents = cipher.encrypt(DATA).unwrap();
+        for len in [0, MAGIC.len(), HEADER_LEN, contents.len() - 1] {
+            assert!(cipher.decrypt(&contents[..len]).is_err(), "{len} bytes");
+        }
+    }
+}
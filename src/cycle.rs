@@ -0,0 +1,116 @@
+//! Menstrual cycle tracking, since water retention around a period can move
+//! weight by a kilogram or two and look like a change in the trend. Each
+//! profile keeps the dates its periods started in a `.cycle` file next to
+//! its data file, one `YYYY-MM-DD` per line.
+
+use std::fmt::Display;
+
+use time::{Date, Duration};
+
+use crate::{
+    journal::{Journal, Record},
+    store::{format_date, parse_date},
+};
+
+/// The period start dates of one profile.
+pub type Cycles = Journal<PeriodStart>;
+
+/// The first day of a period, which starts a cycle.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PeriodStart {
+    pub date: Date,
+}
+
+impl Record for PeriodStart {
+    fn parse(line: &str) -> Option<Self> {
+        Some(Self {
+            date: parse_date(line.trim())?,
+        })
+    }
+
+    fn date(&self) -> Date {
+        self.date
+    }
+
+    fn replaces(&self, other: &Self) -> bool {
+        self.date == other.date
+    }
+}
+
+/// Formats the start as a line of a cycle file.
+impl Display for PeriodStart {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", format_date(self.date))
+    }
+}
+
+/// The cycle length assumed until there are enough cycles to average.
+const DEFAULT_LENGTH: i64 = 28;
+
+/// Cycles shorter or longer than this are probably a missed entry rather
+/// than a real cycle, so they don't count towards the average.
+const LENGTHS: std::ops::RangeInclusive<i64> = 18..=45;
+
+/// How many days a period is assumed to last.
+const PERIOD_DAYS: i64 = 5;
+
+/// How many days before a period water retention is usually highest.
+const PREMENSTRUAL_DAYS: i64 = 7;
+
+/// The average length in days of the cycles between `starts`, which are in
+/// date order, or the usual 28 days if there are none to go by.
+pub fn average_length(starts: &[Date]) -> i64 {
+    let lengths: Vec<_> = starts
+        .windows(2)
+        .map(|w| (w[1] - w[0]).whole_days())
+        .filter(|l| LENGTHS.contains(l))
+        .collect();
+    if lengths.is_empty() {
+        return DEFAULT_LENGTH;
+    }
+    (lengths.iter().sum::<i64>() as f64 / lengths.len() as f64).round() as i64
+}
+
+/// A stretch of days in one phase of a cycle.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Phase {
+    Period,
+
+    /// The week before a period, when weight tends to be highest
+    Premenstrual,
+}
+
+/// The periods and premenstrual weeks around `starts`, which are in date
+/// order, as the phase with its first and last days. The next period is
+/// predicted from the average cycle length, so that the current cycle's
+/// premenstrual week is shown too.
+pub fn phases(starts: &[Date]) -> Vec<(Phase, Date, Date)> {
+    let Some(last) = starts.last() else {
+        return Vec::new();
+    };
+    let next = *last + Duration::days(average_length(starts));
+    let mut phases = Vec::new();
+    for (i, start) in starts.iter().enumerate() {
+        // the premenstrual week can't begin before the previous period ends
+        let earliest = i
+            .checked_sub(1)
+            .map(|j| starts[j] + Duration::days(PERIOD_DAYS));
+        let before = *start - Duration::days(PREMENSTRUAL_DAYS);
+        let before = earliest.map_or(before, |e| before.max(e));
+        if before < *start {
+            phases.push((Phase::Premenstrual, before, *start - Duration::DAY));
+        }
+        let end = *start + Duration::days(PERIOD_DAYS - 1);
+        let end = starts
+            .get(i + 1)
+            .map_or(end, |n| end.min(*n - Duration::DAY));
+        phases.push((Phase::Period, *start, end));
+    }
+    phases.push((
+        Phase::Premenstrual,
+        (next - Duration::days(PREMENSTRUAL_DAYS))
+            .max(*last + Duration::days(PERIOD_DAYS)),
+        next - Duration::DAY,
+    ));
+    phases
+}
@@ -0,0 +1,90 @@
+use std::str::FromStr;
+
+use time::Date;
+
+/// How dates are shown on the pages and the graph. Dates are always stored
+/// as `YYYY-MM-DD` regardless of this.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DateFormat {
+    parts: Vec<Part>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Part {
+    Year,
+    Month,
+    Day,
+    Literal(char),
+}
+
+impl Default for DateFormat {
+    fn default() -> Self {
+        "YYYY-MM-DD"
+            .parse()
+            .expect("the default date format is valid")
+    }
+}
+
+impl DateFormat {
+    pub fn format(&self, date: Date) -> String {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                Part::Year => date.year().to_string(),
+                Part::Month => format!("{:02}", date.month() as u8),
+                Part::Day => format!("{:02}", date.day()),
+                Part::Literal(c) => c.to_string(),
+            })
+            .collect()
+    }
+
+    /// The equivalent `strftime` format, as used by gnuplot.
+    pub fn strftime(&self) -> String {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                Part::Year => "%Y".to_owned(),
+                Part::Month => "%m".to_owned(),
+                Part::Day => "%d".to_owned(),
+                Part::Literal(c) => c.to_string(),
+            })
+            .collect()
+    }
+}
+
+impl FromStr for DateFormat {
+    type Err = String;
+
+    /// Parse a pattern like `DD.MM.YYYY` or `MM/DD`. The month and day are
+    /// required, and everything other than `YYYY`, `MM`, and `DD` must be
+    /// punctuation or spaces. Characters that are special to gnuplot or in
+    /// HTML are left out.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = Vec::new();
+        let mut rest = s;
+        while let Some(c) = rest.chars().next() {
+            let (part, len) = if rest.starts_with("YYYY") {
+                (Part::Year, 4)
+            } else if rest.starts_with("MM") {
+                (Part::Month, 2)
+            } else if rest.starts_with("DD") {
+                (Part::Day, 2)
+            } else if (c.is_ascii_punctuation() && !"%\"\\<>&'".contains(c))
+                || c == ' '
+            {
+                (Part::Literal(c), 1)
+            } else {
+                return Err(format!(
+                    "unexpected `{c}` in date format `{s}`, expected YYYY, \
+                     MM, DD, or punctuation"
+                ));
+            };
+            parts.push(part);
+            rest = &rest[len..];
+        }
+        if !parts.contains(&Part::Month) || !parts.contains(&Part::Day) {
+            return Err(format!("date format `{s}` needs both MM and DD"));
+        }
+        Ok(Self { parts })
+    }
+}
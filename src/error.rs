@@ -0,0 +1,87 @@
+use std::fmt::Display;
+
+/// Everything that can go wrong while serving a request or starting up.
+#[derive(Debug)]
+pub enum Error {
+    /// Reading or writing the data file, socket, or graph failed
+    Io(std::io::Error),
+
+    /// gnuplot exited unsuccessfully
+    Plot(Option<i32>),
+
+    /// The client sent something that isn't a valid HTTP request
+    BadRequest(String),
+
+    /// The client sent too many or too large header fields
+    HeaderTooLarge(String),
+
+    /// The client sent a request body of this many bytes, more than the
+    /// configured maximum
+    BodyTooLarge(usize),
+
+    /// One of the platform's standard directories could not be determined
+    NoDirectory(&'static str),
+
+    /// A file being imported couldn't be understood
+    Import(String),
+
+    /// A git command failed
+    Git(String),
+
+    /// A backup couldn't be made or uploaded
+    Backup(String),
+
+    /// A backup couldn't be restored
+    Restore(String),
+
+    /// The entries couldn't be pushed to InfluxDB
+    Influx(String),
+
+    /// Weigh-ins couldn't be fetched from Withings
+    Withings(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "I/O error: {e}"),
+            Error::Plot(Some(code)) => {
+                write!(f, "gnuplot exited with status {code}")
+            }
+            Error::Plot(None) => write!(f, "gnuplot was killed by a signal"),
+            Error::BadRequest(msg) => write!(f, "bad request: {msg}"),
+            Error::HeaderTooLarge(msg) => {
+                write!(f, "request header too large: {msg}")
+            }
+            Error::BodyTooLarge(len) => {
+                write!(f, "request body too large: {len} bytes")
+            }
+            Error::NoDirectory(kind) => {
+                write!(f, "failed to determine the {kind} directory")
+            }
+            Error::Import(msg) => write!(f, "import failed: {msg}"),
+            Error::Git(msg) => write!(f, "{msg}"),
+            Error::Backup(msg) => write!(f, "backup failed: {msg}"),
+            Error::Restore(msg) => write!(f, "restore failed: {msg}"),
+            Error::Influx(msg) => write!(f, "InfluxDB push failed: {msg}"),
+            Error::Withings(msg) => write!(f, "Withings sync failed: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
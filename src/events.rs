@@ -0,0 +1,79 @@
+//! Notifications about changes to the data, for pages that update live.
+
+use std::{
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc, Mutex, PoisonError,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::Entry;
+
+/// Something that happened to a profile's data.
+#[derive(Debug)]
+pub enum Event {
+    /// A new weight was recorded
+    Added(Entry),
+
+    /// The entries changed in some other way, like an import or reload
+    Changed,
+
+    /// A new graph of the entries finished rendering in the background
+    Graphed,
+}
+
+#[derive(Debug)]
+pub struct Message {
+    /// The profile that changed, `None` for the default profile
+    pub profile: Option<String>,
+    pub event: Event,
+}
+
+/// Delivers each published message to every current subscriber.
+#[derive(Default)]
+pub struct Events {
+    subscribers: Mutex<Vec<Sender<Arc<Message>>>>,
+}
+
+impl Events {
+    /// Start receiving the messages published from now on. Dropping the
+    /// receiver unsubscribes.
+    pub fn subscribe(&self) -> Receiver<Arc<Message>> {
+        let (tx, rx) = mpsc::channel();
+        self.subscribers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(tx);
+        rx
+    }
+
+    /// Send `event` for `profile` to the subscribers.
+    pub fn publish(&self, profile: Option<&str>, event: Event) {
+        let message = Arc::new(Message {
+            profile: profile.map(str::to_owned),
+            event,
+        });
+        self.subscribers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .retain(|tx| tx.send(Arc::clone(&message)).is_ok());
+    }
+}
+
+/// Wait up to `timeout` for the next message on `events` about `profile`,
+/// skipping ones about other profiles.
+pub fn next_for(
+    events: &Receiver<Arc<Message>>,
+    profile: Option<&str>,
+    timeout: Duration,
+) -> Result<Arc<Message>, RecvTimeoutError> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        let message = events.recv_timeout(timeout)?;
+        if message.profile.as_deref() == profile {
+            return Ok(message);
+        }
+    }
+}
@@ -0,0 +1,73 @@
+//! A log of exercise, so that activity can be seen next to the weights. Each
+//! profile keeps its workouts in an `.exercise` file next to its data file,
+//! with lines of the form `YYYY-MM-DD minutes activity`.
+
+use std::fmt::Display;
+
+use time::Date;
+
+use crate::{
+    journal::{Journal, Record},
+    store::{format_date, parse_date},
+};
+
+/// The workouts of one profile.
+pub type Exercise = Journal<Workout>;
+
+/// `minutes` spent on `activity`, like `running` or `weights`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Workout {
+    pub date: Date,
+    pub minutes: u32,
+    pub activity: String,
+}
+
+impl Record for Workout {
+    fn parse(line: &str) -> Option<Self> {
+        let (date, rest) = line.trim().split_once(' ')?;
+        let (minutes, activity) = rest.trim_start().split_once(' ')?;
+        Some(Self {
+            date: parse_date(date)?,
+            minutes: minutes.parse().ok().filter(|m| *m > 0)?,
+            activity: valid_activity(activity)?,
+        })
+    }
+
+    fn date(&self) -> Date {
+        self.date
+    }
+}
+
+/// Formats the workout as a line of an exercise file.
+impl Display for Workout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {}",
+            format_date(self.date),
+            self.minutes,
+            self.activity
+        )
+    }
+}
+
+/// `activity` in lowercase with surrounding whitespace trimmed and inner
+/// whitespace collapsed, or `None` if it is empty or too long.
+pub fn valid_activity(activity: &str) -> Option<String> {
+    const MAX_LEN: usize = 50;
+    let activity = activity
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase();
+    (!activity.is_empty() && activity.len() <= MAX_LEN).then_some(activity)
+}
+
+/// Describe a number of minutes like `45 min` or `2 h 15 min`.
+pub fn format_minutes(minutes: u32) -> String {
+    match (minutes / 60, minutes % 60) {
+        (0, m) => format!("{m} min"),
+        (h, 0) => format!("{h} h"),
+        (h, m) => format!("{h} h {m} min"),
+    }
+}
@@ -0,0 +1,170 @@
+//! Writing the entries in formats other apps can read.
+
+use std::{
+    io::{self, Write},
+    path::Path,
+    str::FromStr,
+};
+
+use time::Time;
+
+use crate::{store::format_date, timezone::Timezone, units::Unit, Entry};
+
+/// The formats the history can be exported in from the command line.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    /// The data file format, one entry per line
+    DataFile,
+
+    /// CSV with a `Date,Weight,Tags` header, tags separated by spaces
+    Csv,
+
+    /// An array of objects with a `date`, a `weight`, and `tags`, which can
+    /// be imported again
+    Json,
+
+    /// A snapshot of every profile, as made by /backup
+    Backup,
+}
+
+impl Format {
+    /// The format for a file named like `path`: `.dat`, `.csv`, `.json`, or
+    /// `.txt` for a backup.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        path.extension()?
+            .to_str()?
+            .to_ascii_lowercase()
+            .parse()
+            .ok()
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dat" => Ok(Self::DataFile),
+            "csv" => Ok(Self::Csv),
+            "json" => Ok(Self::Json),
+            "txt" => Ok(Self::Backup),
+            _ => Err(format!(
+                "unknown export format `{s}`, expected dat, csv, json, or txt"
+            )),
+        }
+    }
+}
+
+/// Write `entries` to `out` in the data file format.
+pub fn data_file(
+    out: &mut dyn Write,
+    entries: impl IntoIterator<Item = Entry>,
+) -> io::Result<()> {
+    for entry in entries {
+        writeln!(out, "{entry}")?;
+    }
+    Ok(())
+}
+
+/// Write `entries` to `out` as CSV with a `Date,Weight,Tags` header.
+pub fn csv(
+    out: &mut dyn Write,
+    entries: impl IntoIterator<Item = Entry>,
+) -> io::Result<()> {
+    writeln!(out, "Date,Weight,Tags")?;
+    for entry in entries {
+        writeln!(
+            out,
+            "{},{:.1},{}",
+            format_date(entry.date),
+            entry.weight,
+            entry.tags.join(" ")
+        )?;
+    }
+    Ok(())
+}
+
+/// Write `entries` to `out` as a JSON array with one entry per line. Tags
+/// are limited to characters that don't need escaping.
+pub fn json(
+    out: &mut dyn Write,
+    entries: impl IntoIterator<Item = Entry>,
+) -> io::Result<()> {
+    write!(out, "[")?;
+    for (i, entry) in entries.into_iter().enumerate() {
+        let tags: Vec<_> =
+            entry.tags.iter().map(|t| format!("\"{t}\"")).collect();
+        write!(
+            out,
+            "{}\n{{\"date\":\"{}\",\"weight\":{:.1},\"tags\":[{}]}}",
+            if i == 0 { "" } else { "," },
+            format_date(entry.date),
+            entry.weight,
+            tags.join(",")
+        )?;
+    }
+    writeln!(out, "\n]")
+}
+
+/// Write `entries` to `out` in InfluxDB line protocol, as points of the
+/// `weight` measurement with the profile's name (`default` for the default
+/// profile) and `unit` as tags, the weight as the `value` field, and any
+/// tags of the entry joined by commas as the `tags` field. Entries only have
+/// dates, so every point is timestamped at midnight in `timezone`, in
+/// nanoseconds.
+pub fn influx(
+    out: &mut dyn Write,
+    entries: impl IntoIterator<Item = Entry>,
+    profile: Option<&str>,
+    unit: Unit,
+    timezone: Timezone,
+) -> io::Result<()> {
+    let profile = profile.unwrap_or("default");
+    for entry in entries {
+        write!(
+            out,
+            "weight,profile={profile},unit={unit} value={:.1}",
+            entry.weight
+        )?;
+        if !entry.tags.is_empty() {
+            write!(out, ",tags=\"{}\"", entry.tags.join(","))?;
+        }
+        let time = timezone.at(entry.date, Time::MIDNIGHT);
+        writeln!(out, " {}", time.unix_timestamp_nanos())?;
+    }
+    Ok(())
+}
+
+/// The HealthKit identifier for body weight samples.
+const BODY_MASS: &str = "HKQuantityTypeIdentifierBodyMass";
+
+/// Write `entries` to `out` as CSV for adding to Apple Health, one sample per
+/// row with an ISO 8601 timestamp, the HealthKit sample type, the weight,
+/// and `unit`. Entries only have dates, so every sample is timestamped at
+/// 8:00 in `timezone`.
+pub fn apple_health(
+    out: &mut dyn Write,
+    entries: impl IntoIterator<Item = Entry>,
+    unit: Unit,
+    timezone: Timezone,
+) -> io::Result<()> {
+    writeln!(out, "startDate,type,value,unit")?;
+    for entry in entries {
+        let time = timezone.at(entry.date, Time::from_hms(8, 0, 0).unwrap());
+        let (hours, minutes, _) = time.offset().as_hms();
+        let sign = if time.offset().is_negative() {
+            '-'
+        } else {
+            '+'
+        };
+        writeln!(
+            out,
+            "{}T08:00:00{sign}{:02}:{:02},{BODY_MASS},{:.1},{unit}",
+            format_date(entry.date),
+            hours.abs(),
+            minutes.abs(),
+            entry.weight
+        )?;
+    }
+    Ok(())
+}
@@ -0,0 +1,124 @@
+//! Keeping the data directory in a git repository, for history and syncing
+//! between machines. This shells out to the `git` command, so git has to be
+//! installed for these options to work.
+
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{Arc, Mutex, PoisonError},
+    time::Duration,
+};
+
+use tracing::{debug, error, info};
+
+use crate::{Error, Result, State};
+
+/// A git repository holding the data files.
+pub struct Repo {
+    dir: PathBuf,
+
+    /// The remote to pull from and push to, if any
+    remote: Option<String>,
+
+    /// Held while running git, which doesn't like concurrent commands in the
+    /// same repository.
+    lock: Mutex<()>,
+}
+
+impl Repo {
+    /// Use the repository at `dir`, creating it if `dir` isn't already one.
+    pub fn open(dir: &Path, remote: Option<String>) -> Result<Self> {
+        let repo = Self {
+            dir: dir.to_owned(),
+            remote,
+            lock: Mutex::new(()),
+        };
+        if !dir.join(".git").exists() {
+            info!("initializing git repository in {}", dir.display());
+            repo.git(&["init", "--quiet"])?;
+            std::fs::write(dir.join(".gitignore"), "*.tmp\n")?;
+        }
+        // commits fail without an identity, so fall back on one for the app
+        if repo.git(&["config", "user.email"]).is_err() {
+            repo.git(&["config", "user.name", "weight-watcher"])?;
+            repo.git(&["config", "user.email", "weight-watcher@localhost"])?;
+        }
+        repo.commit("Add existing data")?;
+        Ok(repo)
+    }
+
+    /// Commit any changes to the data files with `message`.
+    pub fn commit(&self, message: &str) -> Result<()> {
+        let _guard = self.lock.lock().unwrap_or_else(PoisonError::into_inner);
+        self.git(&["add", "--all"])?;
+        // exits unsuccessfully if there are staged changes
+        if self.git(&["diff", "--cached", "--quiet"]).is_ok() {
+            return Ok(());
+        }
+        self.git(&["commit", "--quiet", "--message", message])?;
+        debug!("committed {message:?}");
+        Ok(())
+    }
+
+    /// Pull changes from the remote and push local commits to it. Does
+    /// nothing if there is no remote.
+    pub fn sync(&self) -> Result<()> {
+        let Some(remote) = &self.remote else {
+            return Ok(());
+        };
+        let _guard = self.lock.lock().unwrap_or_else(PoisonError::into_inner);
+        let branch = self.git(&["rev-parse", "--abbrev-ref", "HEAD"])?;
+        // there's nothing to pull before the first push
+        let missing = self
+            .git(&["ls-remote", "--heads", remote, &branch])?
+            .is_empty();
+        if missing {
+            self.git(&["push", "--quiet", remote, &branch])?;
+            debug!("pushed to {remote}");
+            return Ok(());
+        }
+        if let Err(e) = self.git(&[
+            "pull",
+            "--quiet",
+            "--rebase",
+            "--autostash",
+            remote,
+            &branch,
+        ]) {
+            // leave the repository usable if the rebase stopped on a conflict
+            let _ = self.git(&["rebase", "--abort"]);
+            return Err(e);
+        }
+        self.git(&["push", "--quiet", remote, &branch])?;
+        debug!("synced with {remote}");
+        Ok(())
+    }
+
+    /// Run git in the repository with `args`, returning its trimmed output.
+    fn git(&self, args: &[&str]) -> Result<String> {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(&self.dir)
+            .args(args)
+            .output()?;
+        if !output.status.success() {
+            return Err(Error::Git(format!(
+                "git {} failed: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+    }
+}
+
+/// Spawn a thread that syncs the repository in `state` with its remote every
+/// `interval` and reloads the data files afterwards.
+pub fn sync_periodically(state: Arc<State>, interval: Duration) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(interval);
+        if let Err(e) = state.sync() {
+            error!("failed to sync data: {e}");
+        }
+    });
+}
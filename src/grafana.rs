@@ -0,0 +1,150 @@
+//! The endpoints of Grafana's Simple JSON datasource, so that dashboards can
+//! plot the weights and their trend. Grafana sends a JSON request body to
+//! each one and expects timestamps in milliseconds since the Unix epoch.
+//! Entries only have dates, so each is placed at midnight in the configured
+//! timezone.
+
+use time::{Date, Time};
+
+use crate::{
+    json::{self, Json},
+    medication::{Change, MedicationChange},
+    stats,
+    store::parse_date,
+    timezone::Timezone,
+    Entry,
+};
+
+/// The series that can be queried: every entry, and the 30-day rolling
+/// average shown in the history.
+pub const SERIES: [&str; 2] = ["weight", "trend"];
+
+/// The days of the average in the `trend` series.
+const TREND_DAYS: i64 = 30;
+
+/// The names of the series, for `/search`.
+pub fn search() -> String {
+    let names: Vec<_> = SERIES.iter().map(|s| json::string(s)).collect();
+    format!("[{}]", names.join(","))
+}
+
+/// The datapoints of each series in the `targets` of `request`, a `/query`
+/// body, between the dates of its `range`.
+pub fn query(
+    request: &Json,
+    entries: &[Entry],
+    timezone: Timezone,
+) -> Result<String, String> {
+    let (from, to) = range(request)?;
+    let Json::Array(targets) = request.get("targets") else {
+        return Err("expected a list of targets".into());
+    };
+    let trend = stats::rolling_average(entries, TREND_DAYS);
+    let mut series = Vec::new();
+    for target in targets {
+        let name = target.get("target").as_str().unwrap_or_default();
+        let mut points = Vec::new();
+        let shown = entries.iter().filter(|e| (from..=to).contains(&e.date));
+        match name {
+            "weight" => {
+                for entry in shown {
+                    points.push((entry.weight, millis(entry.date, timezone)));
+                }
+            }
+            "trend" => {
+                let mut dates: Vec<_> = shown.map(|e| e.date).collect();
+                dates.sort();
+                dates.dedup();
+                for date in dates {
+                    points.push((trend[&date], millis(date, timezone)));
+                }
+            }
+            _ => return Err(format!("unknown target {}", json::string(name))),
+        }
+        let points: Vec<_> = points
+            .iter()
+            .map(|(value, time)| format!("[{value:.1},{time}]"))
+            .collect();
+        series.push(format!(
+            "{{\"target\":{},\"datapoints\":[{}]}}",
+            json::string(name),
+            points.join(",")
+        ));
+    }
+    Ok(format!("[{}]", series.join(",")))
+}
+
+/// Annotations for the dates in the `range` of `request`, an
+/// `/annotations` body. If the annotation's query names a tag, the entries
+/// with that tag are annotated. Otherwise every tagged entry is, along with
+/// each medication started or stopped.
+pub fn annotations(
+    request: &Json,
+    entries: &[Entry],
+    medications: &[MedicationChange],
+    timezone: Timezone,
+) -> Result<String, String> {
+    let (from, to) = range(request)?;
+    let annotation = request.get("annotation");
+    let tag = annotation.get("query").as_str().unwrap_or_default().trim();
+    let echo = format!(
+        "{{\"name\":{}}}",
+        json::string(annotation.get("name").as_str().unwrap_or_default())
+    );
+    let mut out = Vec::new();
+    let mut add = |date: Date, title: String, tags: &[String]| {
+        let tags: Vec<_> = tags.iter().map(|t| json::string(t)).collect();
+        out.push(format!(
+            "{{\"annotation\":{echo},\"time\":{},\"title\":{},\"text\":{},\
+             \"tags\":[{}]}}",
+            millis(date, timezone),
+            json::string(&title),
+            json::string(&title),
+            tags.join(",")
+        ));
+    };
+    for entry in entries.iter().filter(|e| (from..=to).contains(&e.date)) {
+        let tagged = match tag {
+            "" => !entry.tags.is_empty(),
+            tag => entry.has_tag(tag),
+        };
+        if tagged {
+            add(entry.date, entry.to_string(), &entry.tags);
+        }
+    }
+    if tag.is_empty() {
+        for change in
+            medications.iter().filter(|m| (from..=to).contains(&m.date))
+        {
+            let verb = match change.change {
+                Change::Start => "Started",
+                Change::Stop => "Stopped",
+            };
+            add(
+                change.date,
+                format!("{verb} {}", change.name),
+                &["medication".to_owned()],
+            );
+        }
+    }
+    Ok(format!("[{}]", out.join(",")))
+}
+
+/// The first and last dates of the `range` in `request`, which are
+/// timestamps like `2026-10-15T07:30:00.000Z`.
+fn range(request: &Json) -> Result<(Date, Date), String> {
+    let range = request.get("range");
+    let date = |key: &str| {
+        let s = range.get(key).as_str()?;
+        parse_date(s.split('T').next()?)
+    };
+    match (date("from"), date("to")) {
+        (Some(from), Some(to)) => Ok((from, to)),
+        _ => Err("expected a range with from and to timestamps".into()),
+    }
+}
+
+/// The milliseconds since the Unix epoch at the start of `date`.
+fn millis(date: Date, timezone: Timezone) -> i128 {
+    timezone.at(date, Time::MIDNIGHT).unix_timestamp_nanos() / 1_000_000
+}
@@ -0,0 +1,16 @@
+$data << EOD
+{{data}}EOD
+
+$curves << EOD
+{{curves}}EOD
+
+set terminal pngcairo
+set output '{{output}}'
+
+set xrange [0:{{max_age}}]
+set ylabel "{{ylabel}}"
+set xlabel "Age (months)"
+{{labels}}
+unset key
+plot for [i=0:{{curve_count}}] $curves index i u 1:2 w lines dt 2 lc "gray", \
+	$data u 1:2 w linespoints pointtype 7 lc "black"
@@ -0,0 +1,270 @@
+//! Growth charts for tracking a child's weight and height against the WHO
+//! child growth standards, which cover birth to five years.
+//!
+//! The standards describe each age with the LMS method: the value `x` at a
+//! z-score `z` is `M * (1 + L * S * z)^(1 / L)`. The tables below are a
+//! coarse sample of the published monthly tables, interpolated linearly
+//! between ages, which is plenty for seeing which curve a child is following
+//! but isn't meant for clinical use.
+
+use std::{fmt::Display, str::FromStr};
+
+use time::Date;
+
+/// Average days per month, for converting ages to the months the tables
+/// are indexed by.
+const DAYS_PER_MONTH: f64 = 30.4375;
+
+/// The percentile curves drawn on the charts, with their z-scores.
+pub const CURVES: [(&str, f64); 5] = [
+    ("3rd", -1.881),
+    ("15th", -1.036),
+    ("50th", 0.0),
+    ("85th", 1.036),
+    ("97th", 1.881),
+];
+
+/// The oldest age covered by the tables, in months.
+pub const MAX_MONTHS: f64 = 60.0;
+
+/// Age in months, L, M, and S of weight-for-age in kg for boys.
+const WEIGHT_BOYS: &[[f64; 4]] = &[
+    [0.0, 0.3487, 3.3464, 0.14602],
+    [1.0, 0.2297, 4.4709, 0.13395],
+    [2.0, 0.1970, 5.5675, 0.12385],
+    [3.0, 0.1738, 6.3762, 0.11727],
+    [4.0, 0.1553, 7.0023, 0.11316],
+    [6.0, 0.1257, 7.9340, 0.10958],
+    [9.0, 0.0917, 8.9014, 0.10881],
+    [12.0, 0.0644, 9.6479, 0.10925],
+    [18.0, 0.0079, 10.9385, 0.11070],
+    [24.0, -0.0137, 12.1515, 0.11426],
+    [36.0, -0.0340, 14.3429, 0.11975],
+    [48.0, -0.0850, 16.3489, 0.12450],
+    [60.0, -0.1600, 18.3366, 0.12955],
+];
+
+/// Age in months, L, M, and S of weight-for-age in kg for girls.
+const WEIGHT_GIRLS: &[[f64; 4]] = &[
+    [0.0, 0.3809, 3.2322, 0.14171],
+    [1.0, 0.1714, 4.1873, 0.13724],
+    [2.0, 0.0962, 5.1282, 0.13000],
+    [3.0, 0.0402, 5.8458, 0.12619],
+    [4.0, -0.0050, 6.4237, 0.12402],
+    [6.0, -0.0756, 7.2970, 0.12204],
+    [9.0, -0.1507, 8.2254, 0.12222],
+    [12.0, -0.2024, 8.9481, 0.12268],
+    [18.0, -0.2637, 10.2315, 0.12498],
+    [24.0, -0.2970, 11.4775, 0.12842],
+    [36.0, -0.3500, 13.8503, 0.13690],
+    [48.0, -0.3900, 16.0697, 0.14300],
+    [60.0, -0.4200, 18.2193, 0.14800],
+];
+
+/// Age in months, L, M, and S of length (under two) or height-for-age in cm
+/// for boys.
+const HEIGHT_BOYS: &[[f64; 4]] = &[
+    [0.0, 1.0, 49.8842, 0.03795],
+    [1.0, 1.0, 54.7244, 0.03557],
+    [2.0, 1.0, 58.4249, 0.03424],
+    [3.0, 1.0, 61.4292, 0.03328],
+    [4.0, 1.0, 63.8860, 0.03257],
+    [6.0, 1.0, 67.6236, 0.03165],
+    [9.0, 1.0, 72.0023, 0.03130],
+    [12.0, 1.0, 75.7488, 0.03137],
+    [18.0, 1.0, 82.2587, 0.03253],
+    [24.0, 1.0, 87.1161, 0.03507],
+    [36.0, 1.0, 96.0835, 0.03780],
+    [48.0, 1.0, 103.3273, 0.04000],
+    [60.0, 1.0, 110.0, 0.04100],
+];
+
+/// Age in months, L, M, and S of length (under two) or height-for-age in cm
+/// for girls.
+const HEIGHT_GIRLS: &[[f64; 4]] = &[
+    [0.0, 1.0, 49.1477, 0.03790],
+    [1.0, 1.0, 53.6872, 0.03640],
+    [2.0, 1.0, 57.0673, 0.03568],
+    [3.0, 1.0, 59.8029, 0.03520],
+    [4.0, 1.0, 62.0899, 0.03486],
+    [6.0, 1.0, 65.7311, 0.03448],
+    [9.0, 1.0, 70.1435, 0.03479],
+    [12.0, 1.0, 74.0150, 0.03574],
+    [18.0, 1.0, 80.7079, 0.03768],
+    [24.0, 1.0, 85.7153, 0.03860],
+    [36.0, 1.0, 95.0515, 0.04040],
+    [48.0, 1.0, 102.7312, 0.04160],
+    [60.0, 1.0, 109.4233, 0.04240],
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Sex {
+    Boy,
+    Girl,
+}
+
+impl FromStr for Sex {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "boy" | "male" | "m" => Ok(Self::Boy),
+            "girl" | "female" | "f" => Ok(Self::Girl),
+            _ => Err(format!("unknown sex `{s}`, expected boy or girl")),
+        }
+    }
+}
+
+impl Display for Sex {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Sex::Boy => write!(f, "boys"),
+            Sex::Girl => write!(f, "girls"),
+        }
+    }
+}
+
+/// A child whose growth is tracked by a profile.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Child {
+    pub birth: Date,
+    pub sex: Sex,
+}
+
+impl Child {
+    /// The child's age in months on `date`.
+    pub fn months(&self, date: Date) -> f64 {
+        (date - self.birth).whole_days() as f64 / DAYS_PER_MONTH
+    }
+}
+
+/// What a growth chart measures.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Chart {
+    /// Weight in kg
+    Weight,
+
+    /// Length or height in cm
+    Height,
+}
+
+impl Chart {
+    /// The name of the chart in URLs and file names.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Chart::Weight => "weight",
+            Chart::Height => "height",
+        }
+    }
+
+    fn table(&self, sex: Sex) -> &'static [[f64; 4]] {
+        match (self, sex) {
+            (Chart::Weight, Sex::Boy) => WEIGHT_BOYS,
+            (Chart::Weight, Sex::Girl) => WEIGHT_GIRLS,
+            (Chart::Height, Sex::Boy) => HEIGHT_BOYS,
+            (Chart::Height, Sex::Girl) => HEIGHT_GIRLS,
+        }
+    }
+
+    /// The L, M, and S values for `sex` at `months`, or `None` if `months` is
+    /// outside of the tables.
+    fn lms(&self, sex: Sex, months: f64) -> Option<(f64, f64, f64)> {
+        let table = self.table(sex);
+        let i = table.windows(2).position(|w| months <= w[1][0])?;
+        let [a, b] = [table[i], table[i + 1]];
+        if months < a[0] {
+            return None;
+        }
+        let t = (months - a[0]) / (b[0] - a[0]);
+        let lerp = |j: usize| a[j] + t * (b[j] - a[j]);
+        Some((lerp(1), lerp(2), lerp(3)))
+    }
+
+    /// The value at z-score `z` for a child of `sex` aged `months`.
+    pub fn value(&self, sex: Sex, months: f64, z: f64) -> Option<f64> {
+        let (l, m, s) = self.lms(sex, months)?;
+        Some(if l.abs() < 1e-9 {
+            m * (s * z).exp()
+        } else {
+            m * (1.0 + l * s * z).powf(1.0 / l)
+        })
+    }
+
+    /// The percentile of `value` for a child of `sex` aged `months`, from 0
+    /// to 100.
+    pub fn percentile(&self, sex: Sex, months: f64, value: f64) -> Option<f64> {
+        let (l, m, s) = self.lms(sex, months)?;
+        if value <= 0.0 {
+            return None;
+        }
+        let z = if l.abs() < 1e-9 {
+            (value / m).ln() / s
+        } else {
+            ((value / m).powf(l) - 1.0) / (l * s)
+        };
+        Some(100.0 * normal_cdf(z))
+    }
+}
+
+/// The [`CURVES`] of `chart` for `sex` from birth to `months`, sampled every
+/// half month, as pairs of age and value.
+pub fn curves(chart: Chart, sex: Sex, months: f64) -> Vec<Vec<(f64, f64)>> {
+    let months = months.min(MAX_MONTHS);
+    CURVES
+        .iter()
+        .map(|(_, z)| {
+            (0..=(months * 2.0).ceil() as u32)
+                .map(|i| (f64::from(i) / 2.0).min(months))
+                .filter_map(|m| Some((m, chart.value(sex, m, *z)?)))
+                .collect()
+        })
+        .collect()
+}
+
+/// The standard normal cumulative distribution function, using the
+/// approximation of the error function from Abramowitz and Stegun 7.1.26.
+fn normal_cdf(z: f64) -> f64 {
+    let x = z.abs() / std::f64::consts::SQRT_2;
+    let t = 1.0 / (1.0 + 0.327_591_1 * x);
+    let poly = t
+        * (0.254_829_592
+            + t * (-0.284_496_736
+                + t * (1.421_413_741
+                    + t * (-1.453_152_027 + t * 1.061_405_429))));
+    let erf = 1.0 - poly * (-x * x).exp();
+    if z < 0.0 {
+        0.5 * (1.0 - erf)
+    } else {
+        0.5 * (1.0 + erf)
+    }
+}
+
+/// Describe an age in months like `14 months` or `3 years 2 months`.
+pub fn format_age(months: f64) -> String {
+    let months = months.floor() as i64;
+    if months < 24 {
+        return match months {
+            1 => "1 month".to_owned(),
+            n => format!("{n} months"),
+        };
+    }
+    match (months / 12, months % 12) {
+        (y, 0) => format!("{y} years"),
+        (y, 1) => format!("{y} years 1 month"),
+        (y, m) => format!("{y} years {m} months"),
+    }
+}
+
+/// Describe a percentile like `45th`. Percentiles are rounded to between
+/// the 1st and the 99th, since the tails of the curves aren't precise.
+pub fn ordinal(percentile: f64) -> String {
+    let n = percentile.round().clamp(1.0, 99.0) as u32;
+    let suffix = match (n % 10, n % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    };
+    format!("{n}{suffix}")
+}
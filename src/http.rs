@@ -0,0 +1,1098 @@
+use std::{
+    borrow::Cow,
+    fmt::Display,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::TcpStream,
+};
+
+use tracing::warn;
+
+use crate::{Error, Result};
+
+pub enum ContentType {
+    Html,
+    Png,
+    Jpeg,
+    Webp,
+    Csv,
+    Text,
+    Json,
+    EventStream,
+    JavaScript,
+    Pdf,
+}
+
+impl Display for ContentType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ContentType::Html => write!(f, "text/html"),
+            ContentType::Png => write!(f, "image/png"),
+            ContentType::Jpeg => write!(f, "image/jpeg"),
+            ContentType::Webp => write!(f, "image/webp"),
+            ContentType::Csv => write!(f, "text/csv"),
+            ContentType::Text => write!(f, "text/plain; charset=utf-8"),
+            ContentType::Json => write!(f, "application/json"),
+            ContentType::EventStream => write!(f, "text/event-stream"),
+            ContentType::JavaScript => write!(f, "text/javascript"),
+            ContentType::Pdf => write!(f, "application/pdf"),
+        }
+    }
+}
+
+/// Takes over the connection once a response's header has been sent, for
+/// responses that stay open like WebSockets.
+pub type Stream = Box<dyn FnOnce(TcpStream) + Send>;
+
+/// How much of a streamed body is collected before it is sent as a chunk.
+const CHUNK_SIZE: usize = 8 * 1024;
+
+/// Writes a body with chunked transfer encoding, collecting small writes
+/// into chunks of up to [`CHUNK_SIZE`] bytes.
+struct Chunked<W: Write> {
+    inner: W,
+    buf: Vec<u8>,
+}
+
+impl<W: Write> Chunked<W> {
+    fn new(inner: W) -> Self {
+        Self {
+            inner,
+            buf: Vec::with_capacity(CHUNK_SIZE),
+        }
+    }
+
+    fn send_chunk(&mut self) -> io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        write!(self.inner, "{:x}\r\n", self.buf.len())?;
+        self.inner.write_all(&self.buf)?;
+        self.inner.write_all(b"\r\n")?;
+        self.buf.clear();
+        Ok(())
+    }
+
+    /// Send the rest of the body and the empty chunk that ends it.
+    fn finish(mut self) -> io::Result<()> {
+        self.send_chunk()?;
+        self.inner.write_all(b"0\r\n\r\n")?;
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Write for Chunked<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        if self.buf.len() >= CHUNK_SIZE {
+            self.send_chunk()?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.send_chunk()?;
+        self.inner.flush()
+    }
+}
+
+pub enum Body {
+    String(String),
+    Bytes(Vec<u8>),
+    Stream(Stream),
+}
+
+impl From<&str> for Body {
+    fn from(value: &str) -> Self {
+        Self::String(value.into())
+    }
+}
+
+impl From<String> for Body {
+    fn from(value: String) -> Self {
+        Self::String(value)
+    }
+}
+
+pub struct Response {
+    status: usize,
+    location: Option<Cow<'static, str>>,
+    content_type: ContentType,
+
+    /// Any other header fields to send
+    headers: Vec<(&'static str, String)>,
+
+    body: Body,
+}
+
+impl Response {
+    pub fn ok() -> Self {
+        Self {
+            status: 200,
+            body: Body::String(String::new()),
+            content_type: ContentType::Html,
+            headers: Vec::new(),
+            location: None,
+        }
+    }
+
+    pub fn redirect(to: impl Into<Cow<'static, str>>) -> Self {
+        Self {
+            status: 303,
+            location: Some(to.into()),
+            body: Body::String(String::new()),
+            content_type: ContentType::Html,
+            headers: Vec::new(),
+        }
+    }
+
+    /// A 101 response switching the connection to `protocol`, which `stream`
+    /// then speaks.
+    pub fn switching_protocols(protocol: &str, stream: Stream) -> Self {
+        Self {
+            status: 101,
+            body: Body::Stream(stream),
+            content_type: ContentType::Html,
+            headers: vec![
+                ("Upgrade", protocol.to_owned()),
+                ("Connection", "Upgrade".to_owned()),
+            ],
+            location: None,
+        }
+    }
+
+    pub fn err() -> Self {
+        Self {
+            status: 404,
+            body: Body::String(String::new()),
+            content_type: ContentType::Html,
+            headers: Vec::new(),
+            location: None,
+        }
+    }
+
+    pub fn bad_request() -> Self {
+        Self {
+            status: 400,
+            body: Body::String(String::new()),
+            content_type: ContentType::Html,
+            headers: Vec::new(),
+            location: None,
+        }
+    }
+
+    /// A 401 response asking the client for HTTP Basic credentials for
+    /// `realm`.
+    pub fn unauthorized(realm: &str) -> Self {
+        Self {
+            status: 401,
+            body: Body::String(String::new()),
+            content_type: ContentType::Html,
+            headers: vec![(
+                "WWW-Authenticate",
+                format!("Basic realm=\"{realm}\", charset=\"UTF-8\""),
+            )],
+            location: None,
+        }
+    }
+
+    pub fn forbidden() -> Self {
+        Self {
+            status: 403,
+            body: Body::String(String::new()),
+            content_type: ContentType::Html,
+            headers: Vec::new(),
+            location: None,
+        }
+    }
+
+    pub fn header_fields_too_large() -> Self {
+        Self {
+            status: 431,
+            body: Body::String(String::new()),
+            content_type: ContentType::Html,
+            headers: Vec::new(),
+            location: None,
+        }
+    }
+
+    pub fn payload_too_large() -> Self {
+        Self {
+            status: 413,
+            body: Body::String(String::new()),
+            content_type: ContentType::Html,
+            headers: Vec::new(),
+            location: None,
+        }
+    }
+
+    pub fn service_unavailable() -> Self {
+        Self {
+            status: 503,
+            body: Body::String(String::new()),
+            content_type: ContentType::Html,
+            headers: Vec::new(),
+            location: None,
+        }
+    }
+
+    pub fn internal_error() -> Self {
+        Self {
+            status: 500,
+            body: Body::String(String::new()),
+            content_type: ContentType::Html,
+            headers: Vec::new(),
+            location: None,
+        }
+    }
+
+    pub fn body(mut self, body: Body) -> Self {
+        self.body = body;
+        self
+    }
+
+    pub fn content_type(mut self, content_type: ContentType) -> Self {
+        self.content_type = content_type;
+        self
+    }
+
+    /// Add a header field called `name` with `value`.
+    pub fn header(mut self, name: &'static str, value: String) -> Self {
+        self.headers.push((name, value));
+        self
+    }
+
+    /// Send what `write` writes as the body, streaming it to the client as
+    /// it is written rather than building it in memory first. A request for
+    /// part of the body, as when resuming a download, is answered from a
+    /// buffered copy instead, since the range depends on the whole length.
+    pub fn streamed<F>(self, request: &Request, write: F) -> Self
+    where
+        F: FnOnce(&mut dyn Write) -> io::Result<()> + Send + 'static,
+    {
+        if request.header("range").is_some() {
+            let mut body = Vec::new();
+            return match write(&mut body) {
+                Ok(()) => self.body(Body::Bytes(body)).ranged(request),
+                Err(e) => {
+                    warn!("failed to write response: {e}");
+                    Response::internal_error()
+                }
+            };
+        }
+        self.header("Transfer-Encoding", "chunked".into())
+            .body(Body::Stream(Box::new(move |stream| {
+                let mut out = Chunked::new(stream);
+                if let Err(e) = write(&mut out).and_then(|()| out.finish()) {
+                    warn!("failed to stream response: {e}");
+                }
+            })))
+    }
+
+    /// Honor a `Range` header field in `request` by sending only the
+    /// requested bytes of the body, so that interrupted downloads can be
+    /// resumed. Only single byte ranges are supported, and anything else is
+    /// answered with the whole body.
+    pub fn ranged(mut self, request: &Request) -> Self {
+        self.headers.push(("Accept-Ranges", "bytes".into()));
+        // without a validator to compare against, a conditional range can't
+        // be trusted to refer to the same body
+        if self.status != 200 || request.header("if-range").is_some() {
+            return self;
+        }
+        let Some(range) = request.header("range") else {
+            return self;
+        };
+        let body =
+            match std::mem::replace(&mut self.body, Body::Bytes(Vec::new())) {
+                Body::String(s) => s.into_bytes(),
+                Body::Bytes(bytes) => bytes,
+                stream @ Body::Stream(_) => {
+                    self.body = stream;
+                    return self;
+                }
+            };
+        let len = body.len();
+        match byte_range(range, len) {
+            Some(Ok((start, end))) => {
+                self.status = 206;
+                self.headers.push((
+                    "Content-Range",
+                    format!("bytes {start}-{end}/{len}"),
+                ));
+                self.body = Body::Bytes(body[start..=end].to_vec());
+            }
+            Some(Err(())) => {
+                self.status = 416;
+                self.headers
+                    .push(("Content-Range", format!("bytes */{len}")));
+            }
+            None => self.body = Body::Bytes(body),
+        }
+        self
+    }
+
+    pub fn status(&self) -> usize {
+        self.status
+    }
+
+    /// Take the function that continues a streaming response, leaving an
+    /// empty body. This has to come after [`Response::as_bytes`], which only
+    /// leaves out the `Content-Length` of a streamed body.
+    pub fn take_stream(&mut self) -> Option<Stream> {
+        match std::mem::replace(&mut self.body, Body::Bytes(Vec::new())) {
+            Body::Stream(stream) => Some(stream),
+            body => {
+                self.body = body;
+                None
+            }
+        }
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            101 => "Switching Protocols",
+            200 => "OK",
+            206 => "Partial Content",
+            303 => "See Other",
+            400 => "Bad Request",
+            401 => "Unauthorized",
+            403 => "Forbidden",
+            404 => "Not Found",
+            413 => "Content Too Large",
+            416 => "Range Not Satisfiable",
+            431 => "Request Header Fields Too Large",
+            500 => "Internal Server Error",
+            503 => "Service Unavailable",
+            _ => "",
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut header = self.to_string().into_bytes();
+
+        match &self.body {
+            Body::String(s) => header.extend(s.as_bytes()),
+            Body::Bytes(bytes) => header.extend(bytes),
+            // the rest is written by the stream itself
+            Body::Stream(_) => {}
+        }
+
+        header
+    }
+}
+
+impl Display for Response {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "HTTP/1.1 {} {}\r\n", self.status, self.reason())?;
+        for (name, value) in &self.headers {
+            write!(f, "{name}: {value}\r\n")?;
+        }
+        // streamed bodies are either chunked or end when the connection
+        // closes
+        let len = match &self.body {
+            Body::String(s) => Some(s.len()),
+            Body::Bytes(bytes) => Some(bytes.len()),
+            Body::Stream(_) => None,
+        };
+        if let Some(len) = len.filter(|_| self.status != 101) {
+            write!(f, "Content-Length: {len}\r\n")?;
+        }
+        if let Some(location) = &self.location {
+            write!(f, "Location: {location}\r\n")?;
+        } else if self.status != 101 {
+            write!(f, "Content-Type: {}\r\n", self.content_type)?;
+        }
+        write!(f, "\r\n")?;
+
+        Ok(())
+    }
+}
+
+/// Parse the value of a `Range` header field for a body of `len` bytes into
+/// the first and last byte to send. Returns `None` for ranges that should be
+/// ignored, like multiple ranges or other units, and `Some(Err(()))` for a
+/// range that doesn't overlap the body.
+fn byte_range(
+    range: &str,
+    len: usize,
+) -> Option<std::result::Result<(usize, usize), ()>> {
+    let spec = range.trim().strip_prefix("bytes=")?.trim();
+    if spec.contains(',') {
+        return None;
+    }
+    let (start, end) = spec.split_once('-')?;
+    let (start, end) = match (start.trim(), end.trim()) {
+        // the last `end` bytes
+        ("", end) => {
+            let suffix: usize = end.parse().ok()?;
+            if suffix == 0 {
+                return Some(Err(()));
+            }
+            (len.saturating_sub(suffix), len.checked_sub(1))
+        }
+        (start, "") => (start.parse().ok()?, len.checked_sub(1)),
+        (start, end) => {
+            let (start, end): (usize, usize) =
+                (start.parse().ok()?, end.parse().ok()?);
+            if end < start {
+                return None;
+            }
+            (start, Some(end.min(len.saturating_sub(1))))
+        }
+    };
+    match end {
+        Some(end) if start < len => Some(Ok((start, end))),
+        _ => Some(Err(())),
+    }
+}
+
+/// The longest request line accepted, in bytes.
+pub const MAX_REQUEST_LINE: usize = 8 * 1024;
+
+/// The most header fields accepted in a request.
+pub const MAX_HEADERS: usize = 100;
+
+/// The most bytes of header fields accepted in a request, not counting the
+/// request line.
+pub const MAX_HEADER_BYTES: usize = 16 * 1024;
+
+/// The parts of an HTTP request used by the handlers in [`crate::web`].
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub query: Option<String>,
+
+    /// Header fields as `(name, value)` pairs. Names are lowercased since
+    /// they are case-insensitive.
+    pub headers: Vec<(String, String)>,
+
+    pub body: Vec<u8>,
+}
+
+/// Accumulates the lines of a request head while enforcing the size limits.
+#[derive(Default)]
+struct Head {
+    lines: Vec<String>,
+    header_bytes: usize,
+}
+
+impl Head {
+    /// The most bytes to read for the next line, including the line ending.
+    /// Reading one byte more than this means the limit was exceeded.
+    fn line_limit(&self) -> usize {
+        if self.lines.is_empty() {
+            MAX_REQUEST_LINE + 2
+        } else {
+            MAX_HEADER_BYTES.saturating_sub(self.header_bytes) + 2
+        }
+    }
+
+    /// Add a line read with at most [`Head::line_limit`] + 1 bytes. Returns
+    /// `true` once the end of the head has been reached.
+    fn push(&mut self, mut line: Vec<u8>) -> Result<bool> {
+        let complete = line.ends_with(b"\n");
+        if !complete && line.len() >= self.line_limit() {
+            return Err(if self.lines.is_empty() {
+                Error::BadRequest("request line too long".into())
+            } else {
+                Error::HeaderTooLarge("header fields too large".into())
+            });
+        }
+        while line.last().is_some_and(|&b| b == b'\n' || b == b'\r') {
+            line.pop();
+        }
+        // an incomplete line means the client hung up
+        if line.is_empty() || !complete {
+            return Ok(true);
+        }
+        if !self.lines.is_empty() {
+            if self.lines.len() > MAX_HEADERS {
+                return Err(Error::HeaderTooLarge(format!(
+                    "more than {MAX_HEADERS} header fields"
+                )));
+            }
+            // the line limit leaves room for either line ending, so a line
+            // ending in a bare `\n` can still take one byte too many
+            self.header_bytes += line.len();
+            if self.header_bytes > MAX_HEADER_BYTES {
+                return Err(Error::HeaderTooLarge(
+                    "header fields too large".into(),
+                ));
+            }
+        }
+        let line = String::from_utf8(line).map_err(|_| {
+            Error::BadRequest("request head is not valid UTF-8".into())
+        })?;
+        self.lines.push(line);
+        Ok(false)
+    }
+}
+
+impl Request {
+    /// Read a request from `stream`, rejecting bodies longer than `max_body`
+    /// bytes.
+    pub fn read(stream: impl Read, max_body: usize) -> Result<Self> {
+        let mut reader = BufReader::new(stream);
+        let mut head = Head::default();
+        loop {
+            let mut line = Vec::new();
+            let limit = head.line_limit() as u64 + 1;
+            (&mut reader).take(limit).read_until(b'\n', &mut line)?;
+            if head.push(line)? {
+                break;
+            }
+        }
+        let mut request = Self::parse(&head.lines)?;
+        request.body = vec![0; request.content_length(max_body)?];
+        reader.read_exact(&mut request.body)?;
+        Ok(request)
+    }
+
+    /// Asynchronous version of [`Request::read`].
+    #[cfg(feature = "tokio")]
+    pub async fn read_async(
+        stream: impl tokio::io::AsyncRead + Unpin,
+        max_body: usize,
+    ) -> Result<Self> {
+        use tokio::io::{AsyncBufReadExt, AsyncReadExt};
+
+        let mut reader = tokio::io::BufReader::new(stream);
+        let mut head = Head::default();
+        loop {
+            let mut line = Vec::new();
+            let limit = head.line_limit() as u64 + 1;
+            (&mut reader)
+                .take(limit)
+                .read_until(b'\n', &mut line)
+                .await?;
+            if head.push(line)? {
+                break;
+            }
+        }
+        let mut request = Self::parse(&head.lines)?;
+        request.body = vec![0; request.content_length(max_body)?];
+        reader.read_exact(&mut request.body).await?;
+        Ok(request)
+    }
+
+    /// The length of the body declared in the request's `Content-Length`
+    /// header, or an error if it's invalid or longer than `max_body`.
+    fn content_length(&self, max_body: usize) -> Result<usize> {
+        if self.header("transfer-encoding").is_some() {
+            return Err(Error::BadRequest(
+                "chunked request bodies are not supported".into(),
+            ));
+        }
+        let Some(len) = self.header("content-length") else {
+            return Ok(0);
+        };
+        let len = len.parse::<usize>().map_err(|_| {
+            Error::BadRequest(format!("invalid Content-Length: {len}"))
+        })?;
+        if len > max_body {
+            return Err(Error::BodyTooLarge(len));
+        }
+        Ok(len)
+    }
+
+    /// Parse the request line and headers in `head`, which should not include
+    /// the blank line ending the header section.
+    fn parse(head: &[String]) -> Result<Self> {
+        let Some(request_line) = head.first() else {
+            return Err(Error::BadRequest("empty request".into()));
+        };
+        let malformed = || {
+            Error::BadRequest(format!("malformed request line: {request_line}"))
+        };
+        let mut fields = request_line.split_ascii_whitespace();
+        let (Some(method), Some(target), Some(version), None) =
+            (fields.next(), fields.next(), fields.next(), fields.next())
+        else {
+            return Err(malformed());
+        };
+        if !method.bytes().all(|b| b.is_ascii_alphabetic())
+            || !version.starts_with("HTTP/")
+        {
+            return Err(malformed());
+        }
+        let (path, query) = parse_target(target)?;
+        let mut headers = Vec::new();
+        for line in &head[1..] {
+            let field = line.split_once(':').filter(|(name, _)| {
+                // a name can't be empty or contain whitespace, which also
+                // rules out obsolete folded lines
+                !name.is_empty() && !name.contains(char::is_whitespace)
+            });
+            let Some((name, value)) = field else {
+                return Err(Error::BadRequest(format!(
+                    "malformed header field: {line}"
+                )));
+            };
+            headers.push((name.to_lowercase(), value.trim().to_owned()));
+        }
+        Ok(Self {
+            method: method.to_owned(),
+            path,
+            query,
+            headers,
+            body: Vec::new(),
+        })
+    }
+
+    /// The value of the first header field called `name`, which should be
+    /// lowercase.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find_map(|(n, v)| (n == name).then_some(v.as_str()))
+    }
+
+    /// The user name and password from an HTTP Basic `Authorization` header.
+    pub fn basic_auth(&self) -> Option<(String, String)> {
+        let (scheme, credentials) =
+            self.header("authorization")?.trim().split_once(' ')?;
+        if !scheme.eq_ignore_ascii_case("basic") {
+            return None;
+        }
+        let decoded =
+            String::from_utf8(base64_decode(credentials.trim())?).ok()?;
+        let (user, password) = decoded.split_once(':')?;
+        Some((user.to_owned(), password.to_owned()))
+    }
+
+    /// The decoded key-value pairs from the query string, followed by those
+    /// from the body if it was submitted from an HTML form.
+    pub fn params(&self) -> Vec<(String, String)> {
+        let mut params = parse_query(self.query.as_deref().unwrap_or(""));
+        let is_form = self.header("content-type").is_some_and(|t| {
+            t.starts_with("application/x-www-form-urlencoded")
+        });
+        if is_form {
+            params.extend(parse_query(&String::from_utf8_lossy(&self.body)));
+        }
+        params
+    }
+
+    /// The parts of a `multipart/form-data` body, as sent by HTML forms with
+    /// file inputs. Returns `None` if the body isn't multipart or is
+    /// malformed.
+    pub fn multipart(&self) -> Option<Vec<Part>> {
+        let (mime, params) = self.header("content-type")?.split_once(';')?;
+        if !mime.trim().eq_ignore_ascii_case("multipart/form-data") {
+            return None;
+        }
+        let boundary = params
+            .split(';')
+            .find_map(|p| p.trim().strip_prefix("boundary="))?
+            .trim_matches('"');
+        let delim = format!("--{boundary}").into_bytes();
+        let end_delim = [b"\r\n", delim.as_slice()].concat();
+
+        let mut rest = &self.body[find(&self.body, &delim)? + delim.len()..];
+        let mut parts = Vec::new();
+        // each part is preceded by a delimiter, and the last delimiter is
+        // followed by `--`
+        while !rest.starts_with(b"--") {
+            rest = rest.strip_prefix(b"\r\n")?;
+            let head_end = find(rest, b"\r\n\r\n")?;
+            let head = std::str::from_utf8(&rest[..head_end]).ok()?;
+            rest = &rest[head_end + 4..];
+            let data_end = find(rest, &end_delim)?;
+            let data = rest[..data_end].to_vec();
+            rest = &rest[data_end + end_delim.len()..];
+
+            let disposition = head.split("\r\n").find_map(|line| {
+                let (name, value) = line.split_once(':')?;
+                name.trim()
+                    .eq_ignore_ascii_case("content-disposition")
+                    .then_some(value)
+            })?;
+            let mut name = None;
+            let mut filename = None;
+            for param in disposition.split(';').skip(1) {
+                let Some((key, value)) = param.split_once('=') else {
+                    continue;
+                };
+                let value = value.trim().trim_matches('"').to_owned();
+                match key.trim() {
+                    "name" => name = Some(value),
+                    "filename" => filename = Some(value),
+                    _ => {}
+                }
+            }
+            parts.push(Part {
+                name: name?,
+                filename,
+                data,
+            });
+        }
+        Some(parts)
+    }
+}
+
+/// Split the target of a request line into its path and query. Besides the
+/// usual `/path?query`, the absolute form `http://host/path?query` that
+/// proxies send is accepted, a query may itself contain `?`, and a fragment
+/// sent by a careless client is dropped.
+fn parse_target(target: &str) -> Result<(String, Option<String>)> {
+    let target = target.split_once('#').map_or(target, |(t, _)| t);
+    let target: Cow<str> = match target.split_once("://") {
+        Some((scheme, rest))
+            if scheme.eq_ignore_ascii_case("http")
+                || scheme.eq_ignore_ascii_case("https") =>
+        {
+            // the path starts after the host, and may be empty
+            let start = rest.find(['/', '?']).unwrap_or(rest.len());
+            match &rest[start..] {
+                path if path.starts_with('/') => path.into(),
+                rest => format!("/{rest}").into(),
+            }
+        }
+        _ => target.into(),
+    };
+    let (path, query) = match target.split_once('?') {
+        Some((path, query)) => (path, Some(query.to_owned())),
+        None => (&*target, None),
+    };
+    if !path.starts_with('/') {
+        return Err(Error::BadRequest(format!(
+            "malformed request target: {target}"
+        )));
+    }
+    Ok((path.to_owned(), query))
+}
+
+/// One field of a `multipart/form-data` request body.
+pub struct Part {
+    pub name: String,
+
+    /// The name of the uploaded file, for file inputs
+    pub filename: Option<String>,
+
+    pub data: Vec<u8>,
+}
+
+impl Part {
+    /// The data of the part as text, replacing invalid UTF-8.
+    pub fn text(&self) -> std::borrow::Cow<'_, str> {
+        String::from_utf8_lossy(&self.data)
+    }
+}
+
+/// The index of the first occurrence of `needle` in `haystack`.
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+/// Split a query string like `a=1&b=2` into its decoded key-value pairs.
+pub fn parse_query(query: &str) -> Vec<(String, String)> {
+    query
+        .split('&')
+        .filter(|param| !param.is_empty())
+        .map(|param| {
+            let (key, value) = param.split_once('=').unwrap_or((param, ""));
+            (percent_decode(key), percent_decode(value))
+        })
+        .collect()
+}
+
+/// Encode `data` as standard, padded base64.
+pub fn base64_encode(data: &[u8]) -> String {
+    const ALPHABET: &[u8] =
+        b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let mut bytes = [0; 4];
+        bytes[1..=chunk.len()].copy_from_slice(chunk);
+        let n = u32::from_be_bytes(bytes);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Decode standard, padded base64, or return `None` if `s` isn't valid
+/// base64.
+pub fn base64_decode(s: &str) -> Option<Vec<u8>> {
+    fn value(c: u8) -> Option<u32> {
+        match c {
+            b'A'..=b'Z' => Some((c - b'A') as u32),
+            b'a'..=b'z' => Some((c - b'a') as u32 + 26),
+            b'0'..=b'9' => Some((c - b'0') as u32 + 52),
+            b'+' => Some(62),
+            b'/' => Some(63),
+            _ => None,
+        }
+    }
+    let bytes = s.as_bytes();
+    if !bytes.len().is_multiple_of(4) {
+        return None;
+    }
+    let mut out = Vec::with_capacity(bytes.len() / 4 * 3);
+    for chunk in bytes.chunks(4) {
+        let padding = chunk.iter().rev().take_while(|&&c| c == b'=').count();
+        if padding > 2 {
+            return None;
+        }
+        let mut n = 0;
+        for &c in &chunk[..4 - padding] {
+            n = n << 6 | value(c)?;
+        }
+        n <<= 6 * padding;
+        out.extend(&n.to_be_bytes()[1..4 - padding]);
+    }
+    Some(out)
+}
+
+/// Decode `+` and `%XX` escapes from a form-encoded string. Invalid escapes
+/// are passed through unchanged.
+pub fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+                match hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                    Some(b) => {
+                        out.push(b);
+                        i += 2;
+                    }
+                    None => out.push(b'%'),
+                }
+            }
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Escape every byte of `s` other than letters, digits, and `-._~` as
+/// `%XX`, for a query string.
+pub fn percent_encode(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z'
+            | b'a'..=b'z'
+            | b'0'..=b'9'
+            | b'-'
+            | b'.'
+            | b'_'
+            | b'~' => out.push(b.into()),
+            b => out.push_str(&format!("%{b:02X}")),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(request: &str) -> Result<Request> {
+        Request::read(request.as_bytes(), 1024)
+    }
+
+    fn is_bad_request(request: &str) -> bool {
+        matches!(read(request), Err(Error::BadRequest(_)))
+    }
+
+    #[test]
+    fn simple() {
+        let request =
+            read("GET /p/rex/history?tag=sick HTTP/1.1\r\nHost: x\r\n\r\n")
+                .unwrap();
+        assert_eq!(request.method, "GET");
+        assert_eq!(request.path, "/p/rex/history");
+        assert_eq!(request.query.as_deref(), Some("tag=sick"));
+        assert_eq!(request.header("host"), Some("x"));
+    }
+
+    #[test]
+    fn empty() {
+        assert!(is_bad_request(""));
+        assert!(is_bad_request("\r\n"));
+        assert!(is_bad_request("\r\n\r\n"));
+    }
+
+    #[test]
+    fn extra_whitespace() {
+        let request = read("GET  \t/graph.png   HTTP/1.1 \r\n\r\n").unwrap();
+        assert_eq!(request.path, "/graph.png");
+        assert_eq!(request.query, None);
+    }
+
+    #[test]
+    fn malformed_request_line() {
+        assert!(is_bad_request("GET\r\n\r\n"));
+        assert!(is_bad_request("GET /\r\n\r\n"));
+        assert!(is_bad_request("GET / HTTP/1.1 extra\r\n\r\n"));
+        assert!(is_bad_request("GET / FTP/1.0\r\n\r\n"));
+        assert!(is_bad_request("G<T / HTTP/1.1\r\n\r\n"));
+        assert!(is_bad_request("GET relative HTTP/1.1\r\n\r\n"));
+        assert!(is_bad_request("GET ftp://host/ HTTP/1.1\r\n\r\n"));
+    }
+
+    #[test]
+    fn absolute_form() {
+        let request =
+            read("GET http://localhost:3000/history?n=5 HTTP/1.1\r\n\r\n")
+                .unwrap();
+        assert_eq!(request.path, "/history");
+        assert_eq!(request.query.as_deref(), Some("n=5"));
+
+        let request = read("GET HTTPS://localhost HTTP/1.1\r\n\r\n").unwrap();
+        assert_eq!(request.path, "/");
+        assert_eq!(request.query, None);
+
+        let request =
+            read("GET http://localhost?n=5 HTTP/1.1\r\n\r\n").unwrap();
+        assert_eq!(request.path, "/");
+        assert_eq!(request.query.as_deref(), Some("n=5"));
+    }
+
+    #[test]
+    fn multiple_question_marks() {
+        let request = read("GET /?a=1?b=2?c HTTP/1.1\r\n\r\n").unwrap();
+        assert_eq!(request.path, "/");
+        assert_eq!(request.query.as_deref(), Some("a=1?b=2?c"));
+        assert_eq!(request.params(), [("a".into(), "1?b=2?c".into())]);
+    }
+
+    #[test]
+    fn fragment() {
+        let request = read("GET /history?n=5#top HTTP/1.1\r\n\r\n").unwrap();
+        assert_eq!(request.path, "/history");
+        assert_eq!(request.query.as_deref(), Some("n=5"));
+    }
+
+    #[test]
+    fn malformed_headers() {
+        assert!(is_bad_request("GET / HTTP/1.1\r\nno colon\r\n\r\n"));
+        assert!(is_bad_request("GET / HTTP/1.1\r\n: empty\r\n\r\n"));
+        assert!(is_bad_request("GET / HTTP/1.1\r\nA: b\r\n folded\r\n\r\n"));
+        assert!(is_bad_request("GET / HTTP/1.1\r\nHost : x\r\n\r\n"));
+    }
+
+    #[test]
+    fn header_values() {
+        let request =
+            read("GET / HTTP/1.1\r\nContent-Type:  text/plain \r\n\r\n")
+                .unwrap();
+        assert_eq!(request.header("content-type"), Some("text/plain"));
+    }
+
+    #[test]
+    fn body() {
+        let request = read(
+            "POST /weight HTTP/1.1\r\nContent-Length: 6\r\n\
+             Content-Type: application/x-www-form-urlencoded\r\n\r\nw=80.5",
+        )
+        .unwrap();
+        assert_eq!(request.params(), [("w".into(), "80.5".into())]);
+        assert!(is_bad_request(
+            "POST / HTTP/1.1\r\nContent-Length: -1\r\n\r\n"
+        ));
+        assert!(matches!(
+            read("POST / HTTP/1.1\r\nContent-Length: 2048\r\n\r\n"),
+            Err(Error::BodyTooLarge(2048))
+        ));
+    }
+
+    #[test]
+    fn invalid_utf8() {
+        assert!(matches!(
+            Request::read(&b"GET /\xff HTTP/1.1\r\n\r\n"[..], 1024),
+            Err(Error::BadRequest(_))
+        ));
+    }
+
+    #[test]
+    fn content_length() {
+        let length = |response: Response| {
+            let bytes = response.as_bytes();
+            let head = String::from_utf8_lossy(&bytes);
+            head.lines()
+                .find_map(|l| l.strip_prefix("Content-Length: "))
+                .map(str::to_owned)
+        };
+        let ok = Response::ok;
+        assert_eq!(length(ok().body("héllo".into())).as_deref(), Some("6"));
+        let bytes = Body::Bytes(vec![0; 3]);
+        assert_eq!(length(ok().body(bytes)).as_deref(), Some("3"));
+        assert_eq!(length(Response::redirect("/")).as_deref(), Some("0"));
+        let stream = Body::Stream(Box::new(|_| {}));
+        assert_eq!(length(ok().body(stream)), None);
+        let upgrade =
+            Response::switching_protocols("websocket", Box::new(|_| {}));
+        assert_eq!(length(upgrade), None);
+    }
+
+    #[test]
+    fn redirect() {
+        let target = format!("/p/{}/history", "rex");
+        let bytes = Response::redirect(target).as_bytes();
+        let head = String::from_utf8(bytes).unwrap();
+        assert!(head.starts_with("HTTP/1.1 303 See Other\r\n"));
+        assert!(head.contains("\r\nLocation: /p/rex/history\r\n"));
+        assert!(head.ends_with("\r\n\r\n"));
+    }
+
+    #[test]
+    fn truncated() {
+        // a client hanging up partway through the head
+        assert!(is_bad_request("GET"));
+        assert!(read("GET / HTTP/1.1\r\nHost: x").is_ok());
+    }
+
+    #[test]
+    fn header_bytes() {
+        // a field of `n` bytes, counting its name, colon and value
+        let field = |n: usize| format!("X: {}", "a".repeat(n - 3));
+        let head = |fields: &[String], end: &str| {
+            let mut head = "GET / HTTP/1.1".to_owned();
+            for field in fields {
+                head += &format!("{end}{field}");
+            }
+            format!("{head}{end}{end}")
+        };
+        let too_large = |request: &str| {
+            matches!(read(request), Err(Error::HeaderTooLarge(_)))
+        };
+        for end in ["\r\n", "\n"] {
+            let at_limit = head(&[field(MAX_HEADER_BYTES)], end);
+            assert!(read(&at_limit).is_ok());
+            let over = head(&[field(MAX_HEADER_BYTES + 1)], end);
+            assert!(too_large(&over));
+
+            let half = MAX_HEADER_BYTES / 2;
+            let at_limit = head(&[field(half), field(half)], end);
+            assert!(read(&at_limit).is_ok());
+            let over = head(&[field(half), field(half + 1)], end);
+            assert!(too_large(&over));
+            let after = head(&[field(half), field(half), field(4)], end);
+            assert!(too_large(&after));
+        }
+    }
+
+    #[test]
+    fn percent_round_trip() {
+        let url = "http://localhost:9999/withings/callback?a=b c&d=é";
+        let encoded = percent_encode(url);
+        assert_eq!(
+            encoded,
+            "http%3A%2F%2Flocalhost%3A9999%2Fwithings%2Fcallback%3Fa%3Db%20c\
+             %26d%3D%C3%A9"
+        );
+        assert_eq!(percent_decode(&encoded), url);
+    }
+}
@@ -0,0 +1,651 @@
+//! Reading the weight and sleep exports of other apps, for bringing over
+//! history from them.
+
+use std::{
+    collections::BTreeMap,
+    io::{Cursor, Read},
+    str::FromStr,
+};
+
+use time::{Date, Month, OffsetDateTime, PrimitiveDateTime, Time, UtcOffset};
+use zip::ZipArchive;
+
+use crate::{
+    binary, crypto,
+    json::{self, Json},
+    store::{format_date, parse_date, parse_weight, valid_tag, valid_weight},
+    timezone::Timezone,
+    units::Unit,
+    Entry, Error, Result,
+};
+
+/// The first bytes of a zip archive.
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+
+/// The apps whose exports can be imported.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    /// The `Measurement-Summary` CSV from MyFitnessPal's data export
+    MyFitnessPal,
+
+    /// The backup file exported by the Libra Android app
+    Libra,
+
+    /// The `weight.csv` file from Withings Health Mate's data export
+    Withings,
+
+    /// The CSV file exported by the Renpho app
+    Renpho,
+
+    /// The account export archive from Zepp, formerly Mi Fit, or the `BODY`
+    /// CSV file inside it
+    Zepp,
+
+    /// The data file of another weight-watcher server
+    WeightWatcher,
+
+    /// An array of objects with a `date`, a `weight`, and optionally `tags`,
+    /// like the entries in live update messages
+    Json,
+}
+
+impl Format {
+    /// The name of the app the format comes from.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::MyFitnessPal => "MyFitnessPal",
+            Self::Libra => "Libra",
+            Self::Withings => "Withings Health Mate",
+            Self::Renpho => "Renpho",
+            Self::Zepp => "Zepp",
+            Self::WeightWatcher => "a weight-watcher data file",
+            Self::Json => "JSON",
+        }
+    }
+
+    /// Guess the format of `contents` from its header or first lines,
+    /// returning `None` if it doesn't look like any of them.
+    pub fn detect(contents: &[u8]) -> Option<Self> {
+        if crypto::is_encrypted(contents) || binary::is_binary(contents) {
+            return Some(Self::WeightWatcher);
+        }
+        if contents.starts_with(ZIP_MAGIC) {
+            return Some(Self::Zepp);
+        }
+        let text = String::from_utf8_lossy(contents);
+        let text = text.trim_start_matches('\u{feff}');
+        if text.trim_start().starts_with('[') {
+            return Some(Self::Json);
+        }
+        let mut lines = text.lines().filter(|line| !line.trim().is_empty());
+        let first = lines.clone().next()?;
+        let libra_row = |line: &str| {
+            let date = line.split(';').next()?.split('T').next()?;
+            line.contains(';').then(|| parse_date(date))?
+        };
+        if text.lines().any(|line| line.starts_with("#Units:"))
+            || libra_row(first).is_some()
+        {
+            return Some(Self::Libra);
+        }
+        let header = csv_fields(first);
+        if renpho_columns(&header).is_ok() {
+            return Some(Self::Renpho);
+        }
+        if zepp_columns(&header).is_ok() {
+            return Some(Self::Zepp);
+        }
+        if column(&header, "Date").is_ok() {
+            if header.iter().any(|h| h.trim().starts_with("Weight (")) {
+                return Some(Self::Withings);
+            }
+            if column(&header, "Weight").is_ok() {
+                return Some(Self::MyFitnessPal);
+            }
+        }
+        lines
+            .any(|line| Entry::parse(line).is_some())
+            .then_some(Self::WeightWatcher)
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "myfitnesspal" => Ok(Self::MyFitnessPal),
+            "libra" => Ok(Self::Libra),
+            "withings" => Ok(Self::Withings),
+            "renpho" => Ok(Self::Renpho),
+            "zepp" => Ok(Self::Zepp),
+            "weight-watcher" => Ok(Self::WeightWatcher),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("unknown import format `{s}`")),
+        }
+    }
+}
+
+/// Read the entries from `contents`, an export in `format`. Weights are
+/// assumed to be in `unit` unless the file says otherwise, and are converted
+/// to `to`. Exports that record the time of each weigh-in are dated in
+/// `timezone`. The entries are returned in the order they appear in the file.
+pub fn parse(
+    format: Format,
+    contents: &str,
+    unit: Unit,
+    to: Unit,
+    timezone: Timezone,
+) -> Result<Vec<Entry>> {
+    let (mut entries, file_unit) = match format {
+        Format::MyFitnessPal => (myfitnesspal(contents)?, None),
+        Format::Libra => libra(contents)?,
+        Format::Withings => withings(contents)?,
+        Format::Renpho => renpho(contents)?,
+        Format::Zepp => zepp(contents, timezone)?,
+        Format::WeightWatcher => (data_file(contents)?, None),
+        Format::Json => (json(contents)?, None),
+    };
+    let unit = file_unit.unwrap_or(unit);
+    for entry in &mut entries {
+        entry.weight = (unit.convert(entry.weight, to) * 10.0).round() / 10.0;
+    }
+    Ok(entries)
+}
+
+/// The sleep exports that can be imported.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SleepFormat {
+    /// The sleep section of Fitbit's CSV data export
+    Fitbit,
+
+    /// A CSV file with a `Date` column and an `Hours` column
+    Csv,
+}
+
+impl SleepFormat {
+    /// The name of the format, for messages.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Fitbit => "Fitbit",
+            Self::Csv => "CSV",
+        }
+    }
+}
+
+impl FromStr for SleepFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "fitbit" => Ok(Self::Fitbit),
+            "csv" => Ok(Self::Csv),
+            _ => Err(format!("unknown sleep format `{s}`")),
+        }
+    }
+}
+
+/// Read the hours slept each night from `contents`, an export in `format`,
+/// in date order. Each night is dated by the morning it ended on, and
+/// several sleeps ending on the same day, like naps, are added together.
+pub fn sleep(format: SleepFormat, contents: &str) -> Result<Vec<(Date, f64)>> {
+    let contents = contents.trim_start_matches('\u{feff}');
+    let mut nights = BTreeMap::new();
+    let mut add = |date, hours| *nights.entry(date).or_insert(0.0) += hours;
+    match format {
+        // the sleep table is one of several in the export, with its own
+        // header and ending at a blank line
+        SleepFormat::Fitbit => {
+            let mut lines = contents
+                .lines()
+                .enumerate()
+                .skip_while(|(_, line)| !line.contains("Minutes Asleep"));
+            let Some((_, header)) = lines.next() else {
+                return Err(Error::Import("missing sleep table".into()));
+            };
+            let header = csv_fields(header);
+            let date_col = column(&header, "End Time")?;
+            let minutes_col = column(&header, "Minutes Asleep")?;
+            for (i, line) in
+                lines.take_while(|(_, line)| !line.trim().is_empty())
+            {
+                let fields = csv_fields(line);
+                let field =
+                    |col: usize| fields.get(col).map_or("", |f| f.trim());
+                let date = field(date_col);
+                let date = parse_date(date.split(' ').next().unwrap_or(date))
+                    .ok_or_else(|| bad_field(i, "date", date))?;
+                let minutes: f64 =
+                    field(minutes_col).replace(',', "").parse().map_err(
+                        |_| bad_field(i, "duration", field(minutes_col)),
+                    )?;
+                add(date, minutes / 60.0);
+            }
+        }
+        SleepFormat::Csv => {
+            let mut lines = contents.lines().enumerate();
+            let Some((_, header)) = lines.next() else {
+                return Ok(Vec::new());
+            };
+            let header = csv_fields(header);
+            let date_col = column(&header, "Date")?;
+            let hours_col = column(&header, "Hours")?;
+            for (i, line) in lines.filter(|(_, line)| !line.trim().is_empty()) {
+                let fields = csv_fields(line);
+                let field =
+                    |col: usize| fields.get(col).map_or("", |f| f.trim());
+                let date = field(date_col);
+                let date = parse_date(date)
+                    .or_else(|| parse_us_date(date))
+                    .ok_or_else(|| bad_field(i, "date", date))?;
+                let hours = field(hours_col)
+                    .parse()
+                    .map_err(|_| bad_field(i, "duration", field(hours_col)))?;
+                add(date, hours);
+            }
+        }
+    }
+    if let Some((date, _)) =
+        nights.iter().find(|(_, h)| !(0.0..=24.0).contains(*h))
+    {
+        return Err(Error::Import(format!(
+            "more than 24 hours of sleep on {}",
+            format_date(*date)
+        )));
+    }
+    Ok(nights
+        .into_iter()
+        .map(|(date, hours)| (date, (hours * 10.0).round() / 10.0))
+        .collect())
+}
+
+/// Read the period start dates from `contents`, in date order. Period
+/// trackers export in many formats, so any file with a `YYYY-MM-DD` or
+/// `MM/DD/YYYY` date as the first field of each line is accepted, like a CSV
+/// file or a plain list of dates. Lines without one, like headers, are
+/// skipped.
+pub fn period_starts(contents: &str) -> Result<Vec<Date>> {
+    let mut starts: Vec<_> = contents
+        .lines()
+        .filter_map(|line| {
+            let fields = csv_fields(line.trim_start_matches('\u{feff}'));
+            let date = fields.first()?.trim();
+            parse_date(date).or_else(|| parse_us_date(date))
+        })
+        .collect();
+    if starts.is_empty() {
+        return Err(Error::Import("no dates found".into()));
+    }
+    starts.sort();
+    starts.dedup();
+    Ok(starts)
+}
+
+/// MyFitnessPal exports have a `Date` column and a `Weight` column among
+/// other measurements, with no indication of the unit. Rows for days with
+/// only other measurements have an empty weight. Dates are `YYYY-MM-DD` in
+/// current exports and `MM/DD/YYYY` in older ones.
+fn myfitnesspal(contents: &str) -> Result<Vec<Entry>> {
+    let mut lines = contents.lines().enumerate();
+    let Some((_, header)) = lines.next() else {
+        return Ok(Vec::new());
+    };
+    // spreadsheet programs often add a byte order mark
+    let header = csv_fields(header.trim_start_matches('\u{feff}'));
+    let date_col = column(&header, "Date")?;
+    let weight_col = column(&header, "Weight")?;
+    read_rows(lines, csv_fields, date_col, weight_col, |date| {
+        parse_date(date).or_else(|| parse_us_date(date))
+    })
+}
+
+/// Libra backups start with comment lines like `#Units:kg`, followed by rows
+/// like `2017-01-14T06:41:12.000Z;77.1;77.1;;;` with the date, weight, and
+/// trend separated by semicolons. Timestamps are in UTC, and only their date
+/// is used.
+fn libra(contents: &str) -> Result<(Vec<Entry>, Option<Unit>)> {
+    let mut unit = None;
+    for line in contents.lines() {
+        if let Some(u) = line.trim().strip_prefix("#Units:") {
+            unit = Some(u.trim().parse().map_err(Error::Import)?);
+        }
+    }
+    let lines = contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.starts_with('#'));
+    let split = |line: &str| line.split(';').map(str::to_owned).collect();
+    let entries = read_rows(lines, split, 0, 1, |date| {
+        parse_date(date.split('T').next()?)
+    })?;
+    Ok((entries, unit))
+}
+
+/// Data files have lines of the form `YYYY-MM-DD weight [#tag ...]`. Lines
+/// that can't be read are skipped, as when the file is loaded normally.
+fn data_file(contents: &str) -> Result<Vec<Entry>> {
+    let entries: Vec<_> = contents.lines().flat_map(Entry::parse).collect();
+    if entries.is_empty() && !contents.trim().is_empty() {
+        return Err(Error::Import("the file has no readable entries".into()));
+    }
+    Ok(entries)
+}
+
+/// JSON files hold an array of objects like `{"date": "2026-10-15",
+/// "weight": 81.4, "tags": ["travel"]}`. Dates may also be timestamps like
+/// `2026-10-15T07:30:00Z`, of which only the date is used, and other fields
+/// are ignored.
+fn json(contents: &str) -> Result<Vec<Entry>> {
+    let value = json::parse(contents.trim_start_matches('\u{feff}'))
+        .map_err(Error::Import)?;
+    let Json::Array(items) = value else {
+        return Err(Error::Import("expected an array of entries".into()));
+    };
+    let mut entries = Vec::new();
+    for (i, item) in items.iter().enumerate() {
+        let bad = |kind: &str, value: &Json| {
+            Error::Import(format!("invalid {kind} {value} in entry {}", i + 1))
+        };
+        let Json::Object(_) = item else {
+            return Err(bad("entry", item));
+        };
+        let field = |name: &str| item.get(name);
+        let date = match field("date") {
+            Json::String(s) => parse_date(s.split('T').next().unwrap_or(s)),
+            _ => None,
+        }
+        .ok_or_else(|| bad("date", field("date")))?;
+        let weight = match field("weight") {
+            Json::Number(n) => Some(*n),
+            Json::String(s) => parse_weight(s),
+            _ => None,
+        }
+        .filter(|w| valid_weight(*w))
+        .ok_or_else(|| bad("weight", field("weight")))?;
+        let tags = match field("tags") {
+            Json::Null => Vec::new(),
+            Json::Array(tags) => tags
+                .iter()
+                .map(|tag| match tag {
+                    Json::String(s) if valid_tag(s) => Ok(s.clone()),
+                    _ => Err(bad("tag", tag)),
+                })
+                .collect::<Result<_>>()?,
+            tags => return Err(bad("tags", tags)),
+        };
+        entries.push(Entry { date, weight, tags });
+    }
+    Ok(entries)
+}
+
+/// Withings exports have a `Date` column with timestamps like `2019-01-15
+/// 07:23:05` and a `Weight (kg)` or `Weight (lb)` column, among others.
+fn withings(contents: &str) -> Result<(Vec<Entry>, Option<Unit>)> {
+    let mut lines = contents.lines().enumerate();
+    let Some((_, header)) = lines.next() else {
+        return Ok((Vec::new(), None));
+    };
+    let header = csv_fields(header.trim_start_matches('\u{feff}'));
+    let date_col = column(&header, "Date")?;
+    let (weight_col, unit) = header
+        .iter()
+        .enumerate()
+        .find_map(|(i, h)| {
+            let unit = h.trim().strip_prefix("Weight (")?.strip_suffix(')')?;
+            Some((i, unit))
+        })
+        .ok_or_else(|| Error::Import("missing Weight column".into()))?;
+    let unit = unit.parse().map_err(Error::Import)?;
+    let entries = read_rows(lines, csv_fields, date_col, weight_col, |date| {
+        parse_date(date.split(' ').next()?)
+    })?;
+    Ok((entries, Some(unit)))
+}
+
+/// Renpho exports have a `Date` and a `Time` column, or a single `Time of
+/// Measurement` column in older versions, and a `Weight(kg)` or `Weight(lb)`
+/// column among the body composition measurements. Depending on the app
+/// version and region, dates look like `2023-01-15`, `2023/01/15`,
+/// `2023.01.15`, or `01/15/2023`, followed by the local time in the older
+/// column. Values may have their unit appended, like `81.40kg`, and
+/// measurements the scale didn't take are `--`.
+fn renpho(contents: &str) -> Result<(Vec<Entry>, Option<Unit>)> {
+    let mut lines = contents.lines().enumerate();
+    let Some((_, header)) = lines.next() else {
+        return Ok((Vec::new(), None));
+    };
+    let header = csv_fields(header.trim_start_matches('\u{feff}'));
+    let (date_col, weight_col, unit) = renpho_columns(&header)?;
+    let split = |line: &str| {
+        csv_fields(line)
+            .into_iter()
+            .map(|field| {
+                let field = field.trim();
+                let value = field.trim_end_matches(char::is_alphabetic);
+                match value {
+                    "--" => String::new(),
+                    _ => value.trim().to_owned(),
+                }
+            })
+            .collect()
+    };
+    let entries = read_rows(lines, split, date_col, weight_col, |date| {
+        let date = date.split([' ', ',', 'T']).next()?;
+        match date.split(['-', '/', '.']).next()?.len() {
+            4 => parse_date(&date.replace(['/', '.'], "-")),
+            _ => parse_us_date(date),
+        }
+    })?;
+    Ok((entries, Some(unit)))
+}
+
+/// The date and weight columns of a Renpho export's `header`, along with
+/// the unit in the weight column's name.
+fn renpho_columns(header: &[String]) -> Result<(usize, usize, Unit)> {
+    let date_col = column(header, "Time of Measurement")
+        .or_else(|_| column(header, "Date"))?;
+    let (weight_col, unit) = header
+        .iter()
+        .enumerate()
+        .find_map(|(i, h)| {
+            let unit = h.trim().strip_prefix("Weight(")?.strip_suffix(')')?;
+            Some((i, unit))
+        })
+        .ok_or_else(|| Error::Import("missing Weight column".into()))?;
+    let unit = unit.parse().map_err(Error::Import)?;
+    Ok((date_col, weight_col, unit))
+}
+
+/// The text of the weight records in `contents`, a Zepp export archive, or
+/// of `contents` itself if it is already the CSV file taken out of one.
+/// Archives protected with a password can't be read, and the file has to be
+/// extracted from them first.
+pub fn zepp_text(contents: &[u8]) -> Result<String> {
+    if !contents.starts_with(ZIP_MAGIC) {
+        return Ok(String::from_utf8_lossy(contents).into_owned());
+    }
+    let bad = |e: zip::result::ZipError| {
+        Error::Import(format!("couldn't read the archive: {e}"))
+    };
+    let mut archive = ZipArchive::new(Cursor::new(contents)).map_err(bad)?;
+    for i in 0..archive.len() {
+        let file = archive.by_index_raw(i).map_err(bad)?;
+        let path = file.name().map_err(bad)?;
+        let name = path.rsplit('/').next().unwrap_or_default();
+        if !(name.starts_with("BODY_") && name.ends_with(".csv")) {
+            continue;
+        }
+        if file.encrypted() {
+            return Err(Error::Import(
+                "the archive is protected with a password, so extract the \
+                 BODY CSV file from it and import that instead"
+                    .into(),
+            ));
+        }
+        drop(file);
+        let mut text = String::new();
+        archive
+            .by_index(i)
+            .map_err(bad)?
+            .read_to_string(&mut text)?;
+        return Ok(text);
+    }
+    Err(Error::Import("the archive has no BODY CSV file".into()))
+}
+
+/// Zepp exports have a `weight` column in kg and either a `timestamp` column
+/// in seconds since the Unix epoch, in older Mi Fit exports, or a `time`
+/// column like `2023-05-14 06:21:33+0000`, with the UTC offset the weigh-in
+/// was recorded in. Each weigh-in is dated in `timezone`, so one just after
+/// midnight lands on the right day. Rows with only body composition have a
+/// weight of `0`.
+fn zepp(
+    contents: &str,
+    timezone: Timezone,
+) -> Result<(Vec<Entry>, Option<Unit>)> {
+    let mut lines = contents.lines().enumerate();
+    let Some((_, header)) = lines.next() else {
+        return Ok((Vec::new(), None));
+    };
+    let header = csv_fields(header.trim_start_matches('\u{feff}'));
+    let (time_col, weight_col) = zepp_columns(&header)?;
+    let split = |line: &str| {
+        let mut fields = csv_fields(line);
+        if let Some(weight) = fields.get_mut(weight_col) {
+            if weight.trim().parse() == Ok(0.0) {
+                weight.clear();
+            }
+        }
+        fields
+    };
+    let entries = read_rows(lines, split, time_col, weight_col, |time| {
+        Some(timezone.convert(parse_time(time)?).date())
+    })?;
+    Ok((entries, Some(Unit::Kg)))
+}
+
+/// The time and weight columns of a Zepp export's `header`.
+fn zepp_columns(header: &[String]) -> Result<(usize, usize)> {
+    let time_col =
+        column(header, "timestamp").or_else(|_| column(header, "time"))?;
+    Ok((time_col, column(header, "weight")?))
+}
+
+/// Parse a time in seconds or milliseconds since the Unix epoch, or like
+/// `2023-05-14 06:21:33+0000` with an optional UTC offset, taken to be UTC
+/// if missing.
+fn parse_time(s: &str) -> Option<OffsetDateTime> {
+    if let Ok(n) = s.parse::<i64>() {
+        // no weigh-in is from before 1973, so anything this large counts
+        // milliseconds
+        let seconds = if n > 100_000_000_000 { n / 1000 } else { n };
+        return OffsetDateTime::from_unix_timestamp(seconds).ok();
+    }
+    let (date, rest) = s.split_once([' ', 'T'])?;
+    let (time, offset) =
+        rest.split_at(rest.find(['+', '-', 'Z']).unwrap_or(rest.len()));
+    let mut hms = time
+        .split(':')
+        .map(|f| f.split('.').next().and_then(|f| f.parse().ok()));
+    let time = Time::from_hms(
+        hms.next()??,
+        hms.next()??,
+        hms.next().flatten().unwrap_or(0),
+    )
+    .ok()?;
+    let offset = match offset {
+        "" | "Z" => UtcOffset::UTC,
+        offset => {
+            let digits: String =
+                offset.chars().filter(char::is_ascii_digit).collect();
+            let sign = if offset.starts_with('-') { -1 } else { 1 };
+            let hours: i8 = digits.get(..2)?.parse().ok()?;
+            let minutes: i8 = digits.get(2..4).unwrap_or("0").parse().ok()?;
+            UtcOffset::from_hms(sign * hours, sign * minutes, 0).ok()?
+        }
+    };
+    Some(PrimitiveDateTime::new(parse_date(date)?, time).assume_offset(offset))
+}
+
+/// Read an entry from each of `lines`, which are numbered from zero. Each
+/// line is split into fields with `split`, and the date and weight are taken
+/// from the `date_col` and `weight_col` fields. Blank lines and rows without
+/// a weight are skipped.
+fn read_rows<'a>(
+    lines: impl Iterator<Item = (usize, &'a str)>,
+    split: impl Fn(&str) -> Vec<String>,
+    date_col: usize,
+    weight_col: usize,
+    parse_date: impl Fn(&str) -> Option<Date>,
+) -> Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    for (i, line) in lines {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let fields = split(line);
+        let field = |col: usize| fields.get(col).map_or("", |f| f.trim());
+        let weight = field(weight_col);
+        if weight.is_empty() {
+            continue;
+        }
+        let date = field(date_col);
+        let date =
+            parse_date(date).ok_or_else(|| bad_field(i, "date", date))?;
+        let weight = parse_weight(weight)
+            .filter(|w| valid_weight(*w))
+            .ok_or_else(|| bad_field(i, "weight", weight))?;
+        entries.push(Entry {
+            date,
+            weight,
+            tags: Vec::new(),
+        });
+    }
+    Ok(entries)
+}
+
+/// The index of the column called `name` in `header`, ignoring case.
+fn column(header: &[String], name: &str) -> Result<usize> {
+    header
+        .iter()
+        .position(|h| h.trim().eq_ignore_ascii_case(name))
+        .ok_or_else(|| Error::Import(format!("missing {name} column")))
+}
+
+fn bad_field(line: usize, kind: &str, value: &str) -> Error {
+    Error::Import(format!("invalid {kind} `{value}` on line {}", line + 1))
+}
+
+/// Parse a date in the `MM/DD/YYYY` format.
+fn parse_us_date(s: &str) -> Option<Date> {
+    let mut fields = s.split('/');
+    let month: u8 = fields.next()?.parse().ok()?;
+    let day = fields.next()?.parse().ok()?;
+    let year = fields.next()?.parse().ok()?;
+    if fields.next().is_some() {
+        return None;
+    }
+    Date::from_calendar_date(year, Month::try_from(month).ok()?, day).ok()
+}
+
+/// Split a line of CSV into its fields, removing the quotes around quoted
+/// fields.
+fn csv_fields(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
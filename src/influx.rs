@@ -0,0 +1,74 @@
+//! Pushing the entries to InfluxDB, or anything else that accepts its line
+//! protocol like VictoriaMetrics, on a schedule. Every entry is sent each
+//! time, which is harmless since points with the same tags and timestamp
+//! replace each other.
+
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+    sync::Arc,
+    time::Duration,
+};
+
+use tracing::{error, info, instrument};
+
+use crate::{crypto, Error, Result, State};
+
+/// Send every profile's entries to the configured `influx_url` with curl.
+#[instrument(skip_all)]
+pub fn push(state: &State) -> Result<()> {
+    let config = state.config();
+    let Some(url) = config.influx_url.clone() else {
+        return Ok(());
+    };
+    // the token goes through stdin rather than the command line so other
+    // users can't see it in the process list
+    let token = match &config.influx_token_file {
+        Some(path) => {
+            let token = crypto::read_passphrase(path)?
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"");
+            format!("header = \"Authorization: Token {token}\"\n")
+        }
+        None => String::new(),
+    };
+    drop(config);
+    let lines = state.influx_lines();
+
+    let tmp = std::env::temp_dir().join("weight-watcher-influx.tmp");
+    std::fs::write(&tmp, lines)?;
+    let mut child = Command::new("curl")
+        .args(["--silent", "--show-error", "--fail", "--config", "-"])
+        .arg("--data-binary")
+        .arg(format!("@{}", tmp.display()))
+        .arg(&url)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    stdin.write_all(token.as_bytes())?;
+    drop(stdin);
+    let output = child.wait_with_output();
+    let _ = std::fs::remove_file(&tmp);
+    let output = output?;
+    if !output.status.success() {
+        return Err(Error::Influx(format!(
+            "pushing to {url} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    info!("pushed entries to {url}");
+    Ok(())
+}
+
+/// Spawn a thread that pushes the entries in `state` every `interval`,
+/// starting right away.
+pub fn push_periodically(state: Arc<State>, interval: Duration) {
+    std::thread::spawn(move || loop {
+        if let Err(e) = push(&state) {
+            error!("failed to push entries: {e}");
+        }
+        std::thread::sleep(interval);
+    });
+}
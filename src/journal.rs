@@ -0,0 +1,170 @@
+//! Small dated logs kept alongside a profile's weights, like its other
+//! measurements or medication changes. Each journal is a text file with one
+//! record per line, next to the profile's data file.
+
+use std::{
+    fmt::Display,
+    io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, PoisonError, RwLock, RwLockReadGuard,
+    },
+};
+
+use time::Date;
+use tracing::{debug, instrument};
+
+use crate::{crypto::Cipher, store};
+
+/// A line of a journal.
+pub trait Record: Display + Sized {
+    /// Parse a line written by the `Display` implementation.
+    fn parse(line: &str) -> Option<Self>;
+
+    fn date(&self) -> Date;
+
+    /// Whether recording `self` should remove `other`, like an earlier
+    /// measurement of the same thing on the same day.
+    fn replaces(&self, _other: &Self) -> bool {
+        false
+    }
+}
+
+/// The records of one journal along with the file they are persisted to.
+/// Journals are small and rarely written, so the file is rewritten in full
+/// on every change and only created once there is something to put in it.
+pub struct Journal<T> {
+    path: PathBuf,
+
+    /// Held while the file is being written
+    file: Mutex<()>,
+    data: RwLock<Vec<T>>,
+
+    /// Incremented whenever the records change, for caching things derived
+    /// from them
+    version: AtomicU64,
+
+    /// Set if the profile's data is encrypted, in which case this file is
+    /// too
+    cipher: Option<Cipher>,
+}
+
+impl<T: Record + PartialEq> Journal<T> {
+    /// Load the records in the file at `path`, encrypting them with a key
+    /// derived from `passphrase` if given.
+    #[instrument(skip(passphrase), fields(path = %path.display()))]
+    pub fn open(path: &Path, passphrase: Option<Arc<str>>) -> io::Result<Self> {
+        let contents = read(path)?;
+        let cipher =
+            passphrase.map(|p| Cipher::new(p, &contents)).transpose()?;
+        let data = parse(&contents, cipher.as_ref())?;
+        debug!(records = data.len(), "loaded journal");
+        Ok(Self {
+            path: path.to_owned(),
+            file: Mutex::default(),
+            data: RwLock::new(data),
+            version: AtomicU64::new(0),
+            cipher,
+        })
+    }
+
+    /// Every record in date order.
+    pub fn records(&self) -> RwLockReadGuard<'_, Vec<T>> {
+        self.data.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// The version of the records, which changes whenever they do.
+    pub fn version(&self) -> u64 {
+        self.version.load(Ordering::Acquire)
+    }
+
+    /// Reload the records from disk.
+    pub fn update(&self) -> io::Result<()> {
+        let _file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+        let data = parse(&read(&self.path)?, self.cipher.as_ref())?;
+        if *self.records() != data {
+            self.set_data(data);
+        }
+        Ok(())
+    }
+
+    /// Add `record` in place of any records it replaces.
+    pub fn record(&self, record: T) -> io::Result<()> {
+        self.extend([record])
+    }
+
+    /// Add each of `records` in turn, in place of any records they replace,
+    /// writing the file only once.
+    #[instrument(skip_all, fields(path = %self.path.display()))]
+    pub fn extend(
+        &self,
+        records: impl IntoIterator<Item = T>,
+    ) -> io::Result<()> {
+        let _file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut data = parse(&read(&self.path)?, self.cipher.as_ref())?;
+        for record in records {
+            data.retain(|r| !record.replaces(r));
+            data.push(record);
+        }
+        data.sort_by_key(T::date);
+        self.write(data)?;
+        debug!("added records");
+        Ok(())
+    }
+
+    /// Remove the records for which `keep` returns false, returning them.
+    #[instrument(skip_all, fields(path = %self.path.display()))]
+    pub fn remove(
+        &self,
+        mut keep: impl FnMut(&T) -> bool,
+    ) -> io::Result<Vec<T>> {
+        let _file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+        let data: Vec<T> = parse(&read(&self.path)?, self.cipher.as_ref())?;
+        let (data, removed): (Vec<_>, Vec<_>) =
+            data.into_iter().partition(|r| keep(r));
+        if !removed.is_empty() {
+            self.write(data)?;
+            debug!(removed = removed.len(), "removed records");
+        }
+        Ok(removed)
+    }
+
+    /// Replace the file and the in-memory records with `data`. The caller
+    /// holds `file`.
+    fn write(&self, data: Vec<T>) -> io::Result<()> {
+        let contents: String = data.iter().map(|r| format!("{r}\n")).collect();
+        let contents = match &self.cipher {
+            Some(cipher) => cipher.encrypt(contents.as_bytes())?,
+            None => contents.into_bytes(),
+        };
+        store::replace_file(&self.path, &contents)?;
+        self.set_data(data);
+        Ok(())
+    }
+
+    fn set_data(&self, data: Vec<T>) {
+        *self.data.write().unwrap_or_else(PoisonError::into_inner) = data;
+        self.version.fetch_add(1, Ordering::AcqRel);
+    }
+}
+
+/// The contents of the file at `path`, which may not exist yet.
+fn read(path: &Path) -> io::Result<Vec<u8>> {
+    match std::fs::read(path) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        result => result,
+    }
+}
+
+fn parse<T: Record>(
+    contents: &[u8],
+    cipher: Option<&Cipher>,
+) -> io::Result<Vec<T>> {
+    let mut data: Vec<_> = store::text(contents, cipher)?
+        .lines()
+        .flat_map(T::parse)
+        .collect();
+    data.sort_by_key(T::date);
+    Ok(data)
+}
@@ -0,0 +1,191 @@
+//! Reading and writing the small amounts of JSON exchanged with other apps,
+//! like imported files and requests from dashboards.
+
+use std::fmt::{Display, Write};
+
+type Result<T> = std::result::Result<T, String>;
+
+/// A JSON value, with object fields in the order they appear.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+/// Describes the value in an error message, showing strings and numbers and
+/// only the type of anything else.
+impl Display for Json {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(b) => write!(f, "{b}"),
+            Json::Number(n) => write!(f, "{n}"),
+            Json::String(s) => write!(f, "`{s}`"),
+            Json::Array(_) => write!(f, "array"),
+            Json::Object(_) => write!(f, "object"),
+        }
+    }
+}
+
+impl Json {
+    /// The field called `key` if this is an object that has one, or null.
+    pub fn get(&self, key: &str) -> &Json {
+        match self {
+            Json::Object(fields) => fields
+                .iter()
+                .find(|(k, _)| k == key)
+                .map_or(&Json::Null, |(_, v)| v),
+            _ => &Json::Null,
+        }
+    }
+
+    /// The string if this is one.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+/// Parse `s`, which must hold a single JSON value.
+pub fn parse(s: &str) -> Result<Json> {
+    let mut parser = Parser(s);
+    let value = parser.value()?;
+    if !parser.0.trim().is_empty() {
+        return Err(parser.error());
+    }
+    Ok(value)
+}
+
+/// `s` as a JSON string literal, quotes included.
+pub fn string(s: &str) -> String {
+    let mut out = String::from('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if c.is_control() => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// The unread part of a JSON document.
+struct Parser<'a>(&'a str);
+
+impl Parser<'_> {
+    fn error(&self) -> String {
+        let near: String = self.0.trim_start().chars().take(10).collect();
+        format!("invalid JSON near `{near}`")
+    }
+
+    /// Skip whitespace and consume `token` if it comes next.
+    fn eat(&mut self, token: &str) -> bool {
+        self.0 = self.0.trim_start();
+        match self.0.strip_prefix(token) {
+            Some(rest) => {
+                self.0 = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn expect(&mut self, token: &str) -> Result<()> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(self.error())
+        }
+    }
+
+    fn value(&mut self) -> Result<Json> {
+        if self.eat("null") {
+            Ok(Json::Null)
+        } else if self.eat("true") {
+            Ok(Json::Bool(true))
+        } else if self.eat("false") {
+            Ok(Json::Bool(false))
+        } else if self.eat("[") {
+            let mut items = Vec::new();
+            if !self.eat("]") {
+                loop {
+                    items.push(self.value()?);
+                    if self.eat("]") {
+                        break;
+                    }
+                    self.expect(",")?;
+                }
+            }
+            Ok(Json::Array(items))
+        } else if self.eat("{") {
+            let mut fields = Vec::new();
+            if !self.eat("}") {
+                loop {
+                    let key = self.string()?;
+                    self.expect(":")?;
+                    fields.push((key, self.value()?));
+                    if self.eat("}") {
+                        break;
+                    }
+                    self.expect(",")?;
+                }
+            }
+            Ok(Json::Object(fields))
+        } else if self.0.starts_with('"') {
+            self.string().map(Json::String)
+        } else {
+            let end = self
+                .0
+                .find(|c: char| {
+                    !matches!(c, '0'..='9' | '-' | '+' | '.' | 'e' | 'E')
+                })
+                .unwrap_or(self.0.len());
+            let number = self.0[..end].parse().map_err(|_| self.error())?;
+            self.0 = &self.0[end..];
+            Ok(Json::Number(number))
+        }
+    }
+
+    fn string(&mut self) -> Result<String> {
+        self.expect("\"")?;
+        let mut s = String::new();
+        let mut chars = self.0.char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.0 = &self.0[i + 1..];
+                    return Ok(s);
+                }
+                '\\' => match chars.next().map(|(_, c)| c) {
+                    Some('n') => s.push('\n'),
+                    Some('t') => s.push('\t'),
+                    Some('r') => s.push('\r'),
+                    Some('b') => s.push('\u{8}'),
+                    Some('f') => s.push('\u{c}'),
+                    Some('u') => {
+                        let hex: String =
+                            chars.by_ref().take(4).map(|(_, c)| c).collect();
+                        let c = u32::from_str_radix(&hex, 16)
+                            .ok()
+                            .and_then(char::from_u32)
+                            .unwrap_or(char::REPLACEMENT_CHARACTER);
+                        s.push(c);
+                    }
+                    Some(c @ ('"' | '\\' | '/')) => s.push(c),
+                    _ => break,
+                },
+                c => s.push(c),
+            }
+        }
+        Err(self.error())
+    }
+}
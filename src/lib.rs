@@ -0,0 +1,67 @@
+//! Simple web app to record body weights over time.
+//!
+//! The binary is a thin wrapper around this library, which is split into an
+//! HTTP layer ([`http`]), storage of the weight entries ([`store`]), summary
+//! statistics ([`stats`]), graphing with gnuplot ([`plot`]), and the request
+//! handlers tying them together ([`web`]). Other tools can use the same
+//! entries and trend math through [`WeightStore`] and [`stats`].
+
+#[cfg(feature = "tokio")]
+pub mod async_server;
+pub mod audit;
+pub mod backup;
+pub mod binary;
+pub mod cidr;
+pub mod cli;
+pub mod config;
+pub mod crypto;
+pub mod cycle;
+pub mod date_format;
+pub mod error;
+pub mod events;
+pub mod exercise;
+pub mod export;
+pub mod git;
+pub mod grafana;
+pub mod growth;
+pub mod http;
+pub mod import;
+pub mod influx;
+pub mod journal;
+pub mod json;
+pub mod logging;
+pub mod mdns;
+pub mod medication;
+pub mod merge;
+pub mod metrics;
+pub mod notes;
+pub mod paths;
+pub mod pdf;
+pub mod photos;
+pub mod plot;
+pub mod profile;
+pub mod qr;
+pub mod render;
+#[cfg(unix)]
+pub mod signal;
+pub mod sse;
+pub mod stats;
+pub mod status;
+pub mod store;
+#[cfg(unix)]
+pub mod systemd;
+pub mod templates;
+pub mod timezone;
+pub mod tokens;
+pub mod trash;
+pub mod units;
+pub mod watch;
+pub mod web;
+pub mod websocket;
+pub mod withings;
+
+pub use config::Config;
+pub use error::{Error, Result};
+pub use http::{Request, Response};
+pub use store::{Entry, Store, WeightStore};
+pub use web::State;
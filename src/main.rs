@@ -1,301 +1,374 @@
-use std::{
-    fmt::Display,
-    fs::File,
-    io::{BufRead, BufReader, Read, Seek, Write},
-    net::{TcpListener, TcpStream},
-    path::{Path, PathBuf},
-    process::{Command, Stdio},
+use std::{io::Write, net::TcpListener, sync::Arc, time::Duration};
+
+use clap::{error::ErrorKind, CommandFactory, Parser};
+use time::UtcOffset;
+use tracing::{error, info, warn};
+use weight_watcher::{
+    audit::Source,
+    backup,
+    cli::{Cli, Command, LogTokenCommand, TokenCommand},
+    crypto, export, logging, paths,
+    timezone::Timezone,
+    tokens::{self, Tokens},
+    web, Config, State, Store,
 };
 
-use time::OffsetDateTime;
-
-enum ContentType {
-    Html,
-    Png,
-}
-
-impl Display for ContentType {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            ContentType::Html => write!(f, "text/html"),
-            ContentType::Png => write!(f, "image/png"),
+fn main() -> weight_watcher::Result<()> {
+    let mut cli = Cli::parse();
+
+    // these only describe the command line, so they need nothing else
+    match cli.command {
+        Some(Command::Completions { shell }) => {
+            let mut command = Cli::command();
+            let name = command.get_name().to_owned();
+            // generate panics if it can't write, like to a closed pipe
+            let mut script = Vec::new();
+            clap_complete::generate(shell, &mut command, name, &mut script);
+            std::io::stdout().write_all(&script)?;
+            return Ok(());
+        }
+        Some(Command::Man { ref dir }) => {
+            match dir {
+                Some(dir) => clap_mangen::generate_to(Cli::command(), dir)?,
+                None => clap_mangen::Man::new(Cli::command())
+                    .render(&mut std::io::stdout())?,
+            }
+            return Ok(());
         }
+        _ => {}
     }
-}
-
-enum Body {
-    String(String),
-    Bytes(Vec<u8>),
-}
 
-impl From<&str> for Body {
-    fn from(value: &str) -> Self {
-        Self::String(value.into())
+    let config_dir = paths::config_dir()?;
+    if !config_dir.exists() {
+        std::fs::create_dir_all(&config_dir)?;
     }
-}
 
-impl From<String> for Body {
-    fn from(value: String) -> Self {
-        Self::String(value)
+    let config_file = cli.config.clone().unwrap_or(config_dir.join("config"));
+    let config = Config::load(&config_file);
+    let log_level = cli.log_level().unwrap_or(&config.log_level);
+    logging::init(log_level, config.log_format);
+    for warning in &config.warnings {
+        warn!("{warning}");
     }
-}
-
-struct Response {
-    status: usize,
-    location: Option<&'static str>,
-    content_type: ContentType,
-    body: Body,
-}
 
-impl Response {
-    fn ok() -> Self {
-        Self {
-            status: 200,
-            body: Body::String(String::new()),
-            content_type: ContentType::Html,
-            location: None,
+    // this has to happen before any other threads are spawned
+    let local = match UtcOffset::current_local_offset() {
+        Ok(offset) => Timezone::Fixed(offset),
+        Err(e) => {
+            if config.timezone.is_none() {
+                warn!(
+                    "{e}, using UTC instead. Set `timezone` in the config \
+                     file to choose a different timezone"
+                );
+            }
+            Timezone::Fixed(UtcOffset::UTC)
         }
+    };
+    let command = cli.command.take().unwrap_or(Command::Serve);
+    // tokens don't need the data, which may be locked by the server
+    if let Command::Token { action } = command {
+        let timezone = config.timezone.unwrap_or(local);
+        return token_command(&Tokens::new(&config_file), action, timezone);
     }
-
-    fn redirect(to: &'static str) -> Self {
-        Self {
-            status: 303,
-            location: Some(to),
-            body: Body::String(String::new()),
-            content_type: ContentType::Html,
-        }
+    if let Command::LogToken { action } = command {
+        let timezone = config.timezone.unwrap_or(local);
+        let tokens = Tokens::quick_log(&config_file);
+        return log_token_command(&tokens, action, &config, timezone);
     }
 
-    fn err() -> Self {
-        Self {
-            status: 404,
-            body: Body::String(String::new()),
-            content_type: ContentType::Html,
-            location: None,
+    let data_file = match cli.data_file.clone() {
+        Some(data_file) => data_file,
+        None => paths::data_file()?,
+    };
+    let store = match &config.encryption_key_file {
+        Some(key_file) => {
+            let passphrase =
+                crypto::read_passphrase(key_file).inspect_err(|e| {
+                    error!(
+                        "failed to read encryption key file {}: {e}",
+                        key_file.display()
+                    )
+                })?;
+            Store::open_encrypted(&data_file, &passphrase)?
         }
+        None => Store::open(&data_file)?,
+    };
+    store.set_format(config.data_format)?;
+
+    let git_sync = (config.git && config.git_remote.is_some())
+        .then_some(config.git_sync_interval);
+    let backup = config
+        .backup_url
+        .is_some()
+        .then_some(config.backup_interval);
+    let influx = config
+        .influx_url
+        .is_some()
+        .then_some(config.influx_interval);
+    let withings = config
+        .withings_client_id
+        .is_some()
+        .then_some(config.withings_interval);
+    let mdns = config
+        .mdns_name
+        .clone()
+        .map(|name| (name, config.title.clone()));
+    let mut state = State::new(store, config_file, config, local)?;
+    if cli.read_only {
+        state.force_read_only();
     }
+    let state = Arc::new(state);
 
-    fn body(mut self, body: Body) -> Self {
-        self.body = body;
-        self
-    }
-
-    fn content_type(mut self, content_type: ContentType) -> Self {
-        self.content_type = content_type;
-        self
-    }
-
-    fn reason(&self) -> &'static str {
-        match self.status {
-            200 => "OK",
-            303 => "See Other",
-            404 => "Not Found",
-            _ => "",
+    match command {
+        Command::Serve => {
+            run(state, &cli, git_sync, backup, influx, withings, mdns)
         }
-    }
-
-    fn as_bytes(&self) -> Vec<u8> {
-        let mut header = self.to_string().into_bytes();
-
-        match &self.body {
-            Body::String(s) => header.extend(s.as_bytes()),
-            Body::Bytes(bytes) => header.extend(bytes),
+        Command::Token { .. }
+        | Command::LogToken { .. }
+        | Command::Completions { .. }
+        | Command::Man { .. } => {
+            unreachable!("handled before loading data")
+        }
+        Command::Export {
+            file,
+            format,
+            profile,
+        } => {
+            let Some(format) =
+                format.or_else(|| export::Format::from_path(&file))
+            else {
+                Cli::command()
+                    .error(
+                        ErrorKind::ValueValidation,
+                        "can't tell the export format from the file name, \
+                         use a .dat, .csv, .json, or .txt extension or pass \
+                         --format",
+                    )
+                    .exit()
+            };
+            let summary =
+                state.export_file(&file, format, profile.as_deref())?;
+            println!("{summary}");
+            Ok(())
+        }
+        Command::Restore { file, merge } => {
+            let mode = if merge {
+                backup::RestoreMode::Merge
+            } else {
+                backup::RestoreMode::Replace
+            };
+            let summary =
+                state.restore(&std::fs::read(file)?, mode, Source::Cli)?;
+            println!("{summary}");
+            Ok(())
+        }
+        Command::Merge {
+            file,
+            conflicts,
+            profile,
+        } => {
+            let summary = state.merge_file(
+                &std::fs::read(file)?,
+                profile.as_deref(),
+                conflicts,
+                Source::Cli,
+            )?;
+            println!("{summary}");
+            Ok(())
+        }
+        Command::Check { fix } => {
+            let problems = state.check();
+            if problems.is_empty() {
+                println!("No problems found in the data files.");
+                return Ok(());
+            }
+            for problem in &problems {
+                println!("{problem}");
+            }
+            if fix {
+                println!("{}", state.repair(Source::Cli)?);
+                let remaining = state.check();
+                if remaining.is_empty() {
+                    return Ok(());
+                }
+                println!("These still need to be fixed by hand:");
+                for problem in &remaining {
+                    println!("{problem}");
+                }
+            }
+            std::process::exit(1)
+        }
+        Command::Graph {
+            output,
+            days,
+            tag,
+            profile,
+        } => state.graph_to(&output, days, tag.as_deref(), profile.as_deref()),
+        Command::Import {
+            file,
+            format,
+            unit,
+            conflicts,
+            profile,
+        } => {
+            let summary = state.import_file(
+                &std::fs::read(file)?,
+                format,
+                unit,
+                profile.as_deref(),
+                conflicts,
+            )?;
+            println!("{summary}");
+            Ok(())
         }
-
-        header
     }
 }
 
-impl Display for Response {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "HTTP/1.1 {} {}\r\n", self.status, self.reason())?;
-        if let Some(location) = self.location {
-            write!(f, "Location: {}", location)?;
-        } else {
-            write!(f, "Content-Type: {}\r\n", self.content_type)?;
+/// Create, list, or revoke API tokens as requested by `action`.
+fn token_command(
+    tokens: &Tokens,
+    action: TokenCommand,
+    timezone: Timezone,
+) -> weight_watcher::Result<()> {
+    match action {
+        TokenCommand::Create { label } => {
+            let Some(label) = tokens::valid_label(&label) else {
+                eprintln!("labels must be between 1 and 100 characters");
+                std::process::exit(1);
+            };
+            let (api_token, token) =
+                tokens.create(&label, None, timezone.today())?;
+            eprintln!("created token {} for {label}", api_token.id);
+            println!("{token}");
+        }
+        TokenCommand::List => {
+            for token in tokens.list()? {
+                println!(
+                    "{} {} {}",
+                    token.id,
+                    weight_watcher::store::format_date(token.created),
+                    token.label
+                );
+            }
+        }
+        TokenCommand::Revoke { id } => {
+            if !tokens.revoke(&id)? {
+                eprintln!("no token has the ID {id}");
+                std::process::exit(1);
+            }
+            eprintln!("revoked token {id}");
         }
-        write!(f, "\r\n")?;
-
-        Ok(())
     }
+    Ok(())
 }
 
-fn dispatch(mut stream: TcpStream, state: &mut State) {
-    let buf_reader = BufReader::new(&mut stream);
-    let request: Vec<_> = buf_reader
-        .lines()
-        .map(Result::unwrap)
-        .take_while(|line| !line.is_empty())
-        .collect();
-    assert!(!request.is_empty());
-    let fields: Vec<_> = request[0].split_ascii_whitespace().collect();
-    assert!(fields.len() == 3);
-    let url = fields[1];
-    let parts: Vec<_> = url.split('?').collect();
-    assert!(matches!(parts.len(), 1 | 2));
-    let response = match parts[0] {
-        "/" => index(state),
-        "/weight" if parts.len() == 2 => weight(parts[1], state),
-        "/favicon.ico" => Response::ok()
-            .content_type(ContentType::Png)
-            .body(Body::Bytes(include_bytes!("../logo.png").to_vec())),
-        f @ "/tmp/weight-watcher.png" => Response::ok()
-            .content_type(ContentType::Png)
-            .body(Body::Bytes(std::fs::read(f).unwrap())),
-        _ => {
-            Response::err().body(include_str!("../templates/error.html").into())
+/// Create, list, or revoke quick-log tokens as requested by `action`.
+fn log_token_command(
+    tokens: &Tokens,
+    action: LogTokenCommand,
+    config: &Config,
+    timezone: Timezone,
+) -> weight_watcher::Result<()> {
+    match action {
+        LogTokenCommand::Create { label, profile } => {
+            let Some(label) = tokens::valid_label(&label) else {
+                eprintln!("labels must be between 1 and 100 characters");
+                std::process::exit(1);
+            };
+            if let Some(name) = &profile {
+                if !config.profile_names().contains(name) {
+                    eprintln!("there is no profile called {name}");
+                    std::process::exit(1);
+                }
+            }
+            let (log_token, token) =
+                tokens.create(&label, profile.as_deref(), timezone.today())?;
+            eprintln!("created token {} for {label}", log_token.id);
+            println!("/log/{token}");
+        }
+        LogTokenCommand::List => {
+            for token in tokens.list()? {
+                println!(
+                    "{} {} {} {}",
+                    token.id,
+                    weight_watcher::store::format_date(token.created),
+                    token.profile.as_deref().unwrap_or("-"),
+                    token.label
+                );
+            }
+        }
+        LogTokenCommand::Revoke { id } => {
+            if !tokens.revoke(&id)? {
+                eprintln!("no token has the ID {id}");
+                std::process::exit(1);
+            }
+            eprintln!("revoked token {id}");
         }
-    };
-    stream.write_all(&response.as_bytes()).unwrap();
-}
-
-fn index(state: &mut State) -> Response {
-    state.update();
-    let tmpl = include_str!("../templates/index.html")
-        .replace("{{table}}", &state.html_table());
-    state.graph();
-    Response::ok().body(tmpl.into())
-}
-
-fn weight(query: &str, state: &mut State) -> Response {
-    let params: Vec<&str> = query.split('=').collect();
-    if params.len() != 2 {
-        return Response::err();
     }
-    let Ok(weight) = params[1].parse::<f64>() else {
-        return Response::err();
-    };
-    let now = OffsetDateTime::now_local().unwrap();
-    let date = format_date(&now);
-    writeln!(state.outfile, "{date} {weight:.1}",).unwrap();
-    state.data.push((date, weight));
-    Response::redirect("/")
-}
-
-fn format_date(date: &OffsetDateTime) -> String {
-    format!(
-        "{}-{:02}-{:02}",
-        date.year(),
-        date.month() as u8,
-        date.day()
-    )
-}
-
-struct State {
-    data: Vec<(String, f64)>,
-    config_file: PathBuf,
-    outfile: File,
+    Ok(())
 }
 
-impl State {
-    fn update(&mut self) {
-        self.data = load_current(&mut self.outfile);
+/// Start the background tasks and serve requests until the process is
+/// killed.
+fn run(
+    state: Arc<State>,
+    cli: &Cli,
+    git_sync: Option<Duration>,
+    backup: Option<Duration>,
+    influx: Option<Duration>,
+    withings: Option<Duration>,
+    mdns: Option<(String, String)>,
+) -> weight_watcher::Result<()> {
+    weight_watcher::watch::update_periodically(Arc::clone(&state));
+    weight_watcher::render::render_in_background(Arc::clone(&state));
+    if let Some(interval) = git_sync {
+        weight_watcher::git::sync_periodically(Arc::clone(&state), interval);
     }
-
-    fn html_table(&self) -> String {
-        use std::fmt::Write;
-        let mut table = String::new();
-        for (date, weight) in self.data.iter().rev().take(7) {
-            writeln!(table, "<tr><td>{date}</td><td>{weight:.1}</td></tr>")
-                .unwrap();
-        }
-        table
+    if let Some(interval) = backup {
+        backup::upload_periodically(Arc::clone(&state), interval);
     }
-
-    fn minmax(&self) -> (Option<f64>, Option<f64>) {
-        let mut weights: Vec<_> = self.data.iter().map(|p| p.1).collect();
-        weights.sort_by(f64::total_cmp);
-        let min = weights.first().cloned();
-        let max = weights.last().cloned();
-        (min, max)
+    if let Some(interval) = influx {
+        weight_watcher::influx::push_periodically(Arc::clone(&state), interval);
+    }
+    if let Some(interval) = withings {
+        weight_watcher::withings::sync_periodically(
+            Arc::clone(&state),
+            interval,
+        );
     }
 
-    fn graph(&self) {
-        let name = self.config_file.to_str().unwrap();
-        let now = OffsetDateTime::now_local().unwrap();
-        let start_date = now - 28 * time::Duration::DAY;
-        let date_start = format_date(&start_date);
-        let date_end = format_date(&(now + time::Duration::DAY));
+    #[cfg(unix)]
+    weight_watcher::signal::reload_on_sighup(Arc::clone(&state))?;
+    #[cfg(unix)]
+    weight_watcher::signal::toggle_maintenance_on_sigusr1(Arc::clone(&state))?;
 
-        let mut gp_script = include_str!("plot.gp")
-            .replace("{{name}}", name)
-            .replace("{{date_start}}", &date_start)
-            .replace("{{date_end}}", &date_end);
-        const WEIGHT_PAD: f64 = 5.0;
-        if let (Some(weight_start), Some(weight_end)) = self.minmax() {
-            let weight_start = weight_start - WEIGHT_PAD;
-            let weight_end = weight_end + WEIGHT_PAD;
-            let weight_range =
-                format!("set yrange [{}:{}]", weight_start, weight_end);
-            gp_script = gp_script.replace("{{yrange}}", &weight_range);
-        } else {
-            gp_script = gp_script.replace("{{yrange}}", "set yrange [0:1000]");
-        }
+    let _mdns = mdns.and_then(|(name, title)| {
+        weight_watcher::mdns::advertise(&name, &title, cli.port)
+    });
 
-        let mut child = Command::new("gnuplot")
-            .stdin(Stdio::piped())
-            .spawn()
-            .unwrap();
-        let mut stdin = child.stdin.take().unwrap();
-        std::thread::spawn(move || {
-            stdin.write_all(gp_script.as_bytes()).unwrap();
-        });
-        let output = child.wait().unwrap();
-        if output.code() != Some(0) {
-            eprintln!("error running gnuplot");
-        }
+    #[cfg(feature = "tokio")]
+    if cli.use_async {
+        return tokio::runtime::Runtime::new()?
+            .block_on(weight_watcher::async_server::serve(state, cli.port));
     }
+    serve(state, cli.port)
 }
 
-fn load_current(config: &mut File) -> Vec<(String, f64)> {
-    config.rewind().unwrap();
-    let mut contents = String::new();
-    config.read_to_string(&mut contents).unwrap();
-    contents
-        .lines()
-        .flat_map(|line| {
-            let sp: Vec<_> = line.split_ascii_whitespace().collect();
-            if sp.len() != 2 {
-                return None;
+fn serve(state: Arc<State>, port: u16) -> weight_watcher::Result<()> {
+    let listener = TcpListener::bind(("0.0.0.0", port))?;
+    let addr = listener.local_addr()?;
+    info!("listening on {addr}");
+    #[cfg(unix)]
+    weight_watcher::systemd::ready(addr.port());
+
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let state = Arc::clone(&state);
+                std::thread::spawn(move || web::dispatch(stream, &state));
             }
-            let date = sp[0].to_owned();
-            let Ok(weight) = sp[1].parse::<f64>() else {
-                return None;
-            };
-            Some((date, weight))
-        })
-        .collect()
-}
-
-fn main() -> std::io::Result<()> {
-    let home = std::env::var("HOME").unwrap();
-    let home = Path::new(&home);
-    let config_dir = home.join(".config").join("weight-watcher");
-    if !config_dir.exists() {
-        std::fs::create_dir_all(&config_dir)
-            .expect("failed to create config dir");
-    }
-
-    let config_file = config_dir.join("weights.dat");
-    let mut config = File::options()
-        .create(true)
-        .read(true)
-        .append(true)
-        .open(&config_file)
-        .expect("failed to open weights file");
-
-    let cur = load_current(&mut config);
-
-    let mut state = State {
-        data: cur,
-        outfile: config,
-        config_file,
-    };
-
-    let listener = TcpListener::bind("0.0.0.0:9999")?;
-
-    for stream in listener.incoming().map(Result::unwrap) {
-        dispatch(stream, &mut state);
+            Err(e) => error!("failed to accept connection: {e}"),
+        }
     }
     Ok(())
 }
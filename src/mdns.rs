@@ -0,0 +1,36 @@
+//! Advertising the server on the local network with multicast DNS, so that
+//! other devices can find it as `<name>.local` and in service browsers
+//! instead of needing its IP address.
+
+use mdns_sd::{ServiceDaemon, ServiceInfo};
+use tracing::{error, info};
+
+/// Advertise an HTTP service called `title` on `port` at `<name>.local`. The
+/// advertisement lasts as long as the returned daemon is kept around. Errors
+/// are only logged since the server works fine without it.
+pub fn advertise(name: &str, title: &str, port: u16) -> Option<ServiceDaemon> {
+    let result = ServiceDaemon::new().and_then(|daemon| {
+        let host = format!("{name}.local.");
+        let service = ServiceInfo::new(
+            "_http._tcp.local.",
+            title,
+            &host,
+            "",
+            port,
+            &[("path", "/")][..],
+        )?
+        .enable_addr_auto();
+        daemon.register(service)?;
+        Ok(daemon)
+    });
+    match result {
+        Ok(daemon) => {
+            info!("advertising on the local network as {name}.local:{port}");
+            Some(daemon)
+        }
+        Err(e) => {
+            error!("failed to advertise with mDNS: {e}");
+            None
+        }
+    }
+}
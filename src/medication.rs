@@ -0,0 +1,89 @@
+//! A log of when medications or supplements were started and stopped, which
+//! often explains changes in the trend of a profile's weight. Each profile
+//! keeps them in a `.medications` file next to its data file, with lines of
+//! the form `YYYY-MM-DD start|stop name`.
+
+use std::{fmt::Display, str::FromStr};
+
+use time::Date;
+
+use crate::{
+    journal::{Journal, Record},
+    store::{format_date, parse_date},
+};
+
+/// The medication changes of one profile.
+pub type Medications = Journal<MedicationChange>;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Change {
+    Start,
+    Stop,
+}
+
+impl FromStr for Change {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "start" => Ok(Self::Start),
+            "stop" => Ok(Self::Stop),
+            _ => Err(format!("unknown change `{s}`, expected start or stop")),
+        }
+    }
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Change::Start => write!(f, "start"),
+            Change::Stop => write!(f, "stop"),
+        }
+    }
+}
+
+/// Starting or stopping the medication called `name`, which can include a
+/// dose like `metformin 500mg`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MedicationChange {
+    pub date: Date,
+    pub change: Change,
+    pub name: String,
+}
+
+impl Record for MedicationChange {
+    fn parse(line: &str) -> Option<Self> {
+        let (date, rest) = line.trim().split_once(' ')?;
+        let (change, name) = rest.trim_start().split_once(' ')?;
+        Some(Self {
+            date: parse_date(date)?,
+            change: change.parse().ok()?,
+            name: valid_name(name)?,
+        })
+    }
+
+    fn date(&self) -> Date {
+        self.date
+    }
+}
+
+/// Formats the change as a line of a medications file.
+impl Display for MedicationChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {}",
+            format_date(self.date),
+            self.change,
+            self.name
+        )
+    }
+}
+
+/// `name` with surrounding whitespace trimmed and inner whitespace
+/// collapsed, or `None` if it is empty or too long to be a medication.
+pub fn valid_name(name: &str) -> Option<String> {
+    const MAX_LEN: usize = 100;
+    let name = name.split_whitespace().collect::<Vec<_>>().join(" ");
+    (!name.is_empty() && name.len() <= MAX_LEN).then_some(name)
+}
@@ -0,0 +1,142 @@
+//! Combining the entries of two data files, like one kept on a laptop and one
+//! on a server, with a choice of what to do when both have a different entry
+//! on the same date.
+
+use std::{fmt::Display, str::FromStr};
+
+use crate::Entry;
+
+/// What to do with a date that has different entries in each file.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Policy {
+    /// Keep the existing entries and skip the other file's
+    #[default]
+    Keep,
+
+    /// Replace the existing entries with the other file's
+    Replace,
+
+    /// Keep the entries from both files
+    Both,
+}
+
+impl FromStr for Policy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "keep" => Ok(Self::Keep),
+            "replace" => Ok(Self::Replace),
+            "both" => Ok(Self::Both),
+            _ => Err(format!(
+                "unknown conflict policy `{s}`, expected keep, replace, or both"
+            )),
+        }
+    }
+}
+
+/// What a merge did.
+#[derive(Debug, Default, PartialEq)]
+pub struct Report {
+    /// The entries that were added
+    pub added: Vec<Entry>,
+
+    /// The existing entries that were replaced
+    pub removed: Vec<Entry>,
+
+    /// How many dates had different entries in each file
+    pub conflicts: usize,
+
+    /// The policy the conflicts were resolved with
+    pub policy: Policy,
+
+    /// How many of the other file's entries were already there
+    pub unchanged: usize,
+}
+
+impl Report {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Describes the merge in a sentence for the user.
+impl Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let entries = |n: usize| match n {
+            1 => "1 entry".to_owned(),
+            n => format!("{n} entries"),
+        };
+        write!(f, "Added {}", entries(self.added.len()))?;
+        if self.conflicts > 0 {
+            let dates = match self.conflicts {
+                1 => "1 conflicting date".to_owned(),
+                n => format!("{n} conflicting dates"),
+            };
+            match self.policy {
+                Policy::Keep => {
+                    write!(f, ", keeping the existing entries on {dates}")?
+                }
+                Policy::Replace => write!(
+                    f,
+                    ", replacing {} on {dates}",
+                    entries(self.removed.len())
+                )?,
+                Policy::Both => {
+                    write!(f, ", keeping both files' entries on {dates}")?
+                }
+            }
+        }
+        match self.unchanged {
+            0 => {}
+            1 => write!(f, ". 1 entry was already there")?,
+            n => write!(f, ". {n} entries were already there")?,
+        }
+        write!(f, ".")
+    }
+}
+
+/// Merge `theirs` into `ours`, resolving dates with different entries in
+/// each by `policy`, and return the entries afterwards in date order along
+/// with what changed.
+pub fn merge(
+    mut ours: Vec<Entry>,
+    mut theirs: Vec<Entry>,
+    policy: Policy,
+) -> (Vec<Entry>, Report) {
+    let mut report = Report {
+        policy,
+        ..Report::default()
+    };
+    theirs.sort_by_key(|e| e.date);
+    for group in theirs.chunk_by(|a, b| a.date == b.date) {
+        let date = group[0].date;
+        let (existing, new): (Vec<_>, Vec<_>) =
+            group.iter().partition(|e| ours.contains(e));
+        report.unchanged += existing.len();
+        if new.is_empty() {
+            continue;
+        }
+        let new = new.into_iter().cloned();
+        if !ours.iter().any(|e| e.date == date) {
+            report.added.extend(new);
+            continue;
+        }
+        report.conflicts += 1;
+        match policy {
+            Policy::Keep => {}
+            Policy::Replace => {
+                let (removed, kept): (Vec<_>, Vec<_>) = ours
+                    .into_iter()
+                    .partition(|e| e.date == date && !group.contains(e));
+                ours = kept;
+                report.removed.extend(removed);
+                report.added.extend(new);
+            }
+            Policy::Both => report.added.extend(new),
+        }
+    }
+    ours.extend(report.added.iter().cloned());
+    ours.sort_by_key(|e| e.date);
+    (ours, report)
+}
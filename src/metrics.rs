@@ -0,0 +1,103 @@
+//! Dated measurements other than body weight, like height or calorie intake.
+//! Each profile keeps them in a `.metrics` file next to its data file, with
+//! lines of the form `YYYY-MM-DD metric value`.
+
+use std::fmt::Display;
+
+use time::Date;
+
+use crate::{
+    journal::{Journal, Record},
+    store::{self, format_date, parse_date},
+};
+
+/// A single measurement of `metric`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Measurement {
+    pub date: Date,
+    pub metric: String,
+    pub value: f64,
+}
+
+impl Measurement {
+    /// Parse a line of a metrics file.
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut sp = line.split_ascii_whitespace();
+        let date = parse_date(sp.next()?)?;
+        let metric = sp.next().filter(|m| store::valid_tag(m))?.to_owned();
+        let value = sp.next()?.parse().ok()?;
+        if sp.next().is_some() {
+            return None;
+        }
+        Some(Self {
+            date,
+            metric,
+            value,
+        })
+    }
+}
+
+/// Formats the measurement as a line of a metrics file.
+impl Display for Measurement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {:.1}",
+            format_date(self.date),
+            self.metric,
+            self.value
+        )
+    }
+}
+
+/// The tape measurements on the measurements page, by metric and label.
+/// Like heights, these are in cm.
+pub const BODY: [(&str, &str); 4] = [
+    ("waist", "Waist"),
+    ("hips", "Hips"),
+    ("chest", "Chest"),
+    ("arms", "Arms"),
+];
+
+/// The metric calorie intake is recorded as, in kcal per day.
+pub const CALORIES: &str = "calories";
+
+/// The metric the fat mass measured by a smart scale is recorded as, in the
+/// profile's unit.
+pub const FAT_MASS: &str = "fat_mass";
+
+/// The metric the optional 1 to 5 rating of mood and energy given with a
+/// weight is recorded as, dated by the weight's entry.
+pub const MOOD: &str = "mood";
+
+/// The metric nightly sleep is recorded as, in hours, dated by the morning
+/// the night ended on.
+pub const SLEEP: &str = "sleep";
+
+/// The measurements of one profile.
+pub type Metrics = Journal<Measurement>;
+
+impl Record for Measurement {
+    fn parse(line: &str) -> Option<Self> {
+        Measurement::parse(line)
+    }
+
+    fn date(&self) -> Date {
+        self.date
+    }
+
+    fn replaces(&self, other: &Self) -> bool {
+        self.date == other.date && self.metric == other.metric
+    }
+}
+
+impl Metrics {
+    /// The dates and values of `metric` in date order.
+    pub fn series(&self, metric: &str) -> Vec<(Date, f64)> {
+        self.records()
+            .iter()
+            .filter(|m| m.metric == metric)
+            .map(|m| (m.date, m.value))
+            .collect()
+    }
+}
@@ -0,0 +1,59 @@
+//! Short notes on days, explaining odd weights like `scale was on carpet`.
+//! Each profile keeps its notes in a `.notes` file next to its data file,
+//! with lines of the form `YYYY-MM-DD text`.
+
+use std::fmt::Display;
+
+use time::Date;
+
+use crate::{
+    journal::{Journal, Record},
+    store::{format_date, parse_date},
+};
+
+/// The longest note, in characters.
+pub const MAX_LEN: usize = 200;
+
+/// The notes of one profile.
+pub type Notes = Journal<Note>;
+
+/// A note on the entries of one day.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Note {
+    pub date: Date,
+    pub text: String,
+}
+
+impl Record for Note {
+    fn parse(line: &str) -> Option<Self> {
+        let (date, text) = line.trim().split_once(' ')?;
+        Some(Self {
+            date: parse_date(date)?,
+            text: valid_text(text)?,
+        })
+    }
+
+    fn date(&self) -> Date {
+        self.date
+    }
+
+    /// Each day has one note, so a new one replaces the old.
+    fn replaces(&self, other: &Self) -> bool {
+        self.date == other.date
+    }
+}
+
+/// Formats the note as a line of a notes file.
+impl Display for Note {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", format_date(self.date), self.text)
+    }
+}
+
+/// `text` with surrounding whitespace trimmed and inner whitespace,
+/// including line breaks, collapsed, or `None` if it is empty or longer than
+/// [`MAX_LEN`].
+pub fn valid_text(text: &str) -> Option<String> {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    (!text.is_empty() && text.chars().count() <= MAX_LEN).then_some(text)
+}
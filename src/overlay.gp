@@ -0,0 +1,24 @@
+$weights << EOD
+{{weights}}EOD
+
+$values << EOD
+{{values}}EOD
+
+set terminal pngcairo
+set output '{{output}}'
+
+set timefmt "%Y-%m-%d"
+set xdata time
+set xrange ["{{date_start}}":"{{date_end}}"]
+set format x "{{date_format}}"
+set ylabel "Weight"
+set y2label "{{y2label}}"
+set ytics nomirror
+set y2tics
+set y2range [0:*]
+set xlabel "Date"
+set style fill solid 0.3 noborder
+set boxwidth 0.8 * 86400 absolute
+unset key
+plot $values u 1:2 axes x1y2 w boxes lc "steelblue", \
+	$weights u 1:2 w linespoints pointtype 7 lc "black"
@@ -0,0 +1,85 @@
+//! Default locations for the files weight-watcher reads and writes, following
+//! the conventions of each platform. On Linux, these respect the XDG base
+//! directory variables `XDG_CONFIG_HOME` and `XDG_DATA_HOME`.
+
+use std::{io, path::PathBuf};
+
+use tracing::info;
+
+use crate::{growth::Chart, plot::Span, Error, Result};
+
+/// The name of the data file within [`data_dir`].
+pub const DATA_FILE: &str = "weights.dat";
+
+/// The directory holding the config file and logo: `~/.config/weight-watcher`
+/// on Linux, `~/Library/Application Support/weight-watcher` on macOS, and
+/// `%APPDATA%\weight-watcher` on Windows.
+pub fn config_dir() -> Result<PathBuf> {
+    dirs::config_dir()
+        .map(|dir| dir.join("weight-watcher"))
+        .ok_or(Error::NoDirectory("config"))
+}
+
+/// The directory holding the data file: `~/.local/share/weight-watcher` on
+/// Linux. This is the same as [`config_dir`] on macOS and Windows.
+pub fn data_dir() -> Result<PathBuf> {
+    dirs::data_dir()
+        .map(|dir| dir.join("weight-watcher"))
+        .ok_or(Error::NoDirectory("data"))
+}
+
+/// Where gnuplot writes the rendered graph of `span` for `profile`, or for
+/// the default profile if `profile` is `None`.
+pub fn graph_file(profile: Option<&str>, span: Span) -> PathBuf {
+    let span = span.name();
+    let name = match profile {
+        Some(profile) => format!("weight-watcher-p-{profile}-{span}.png"),
+        None => format!("weight-watcher-{span}.png"),
+    };
+    std::env::temp_dir().join(name)
+}
+
+/// Where gnuplot writes the growth `chart` for `profile`, or for the
+/// default profile if `profile` is `None`.
+pub fn growth_file(profile: Option<&str>, chart: Chart) -> PathBuf {
+    chart_file(profile, &format!("growth-{}", chart.name()))
+}
+
+/// Where gnuplot writes the chart called `name` for `profile`, or for the
+/// default profile if `profile` is `None`.
+pub fn chart_file(profile: Option<&str>, name: &str) -> PathBuf {
+    let name = match profile {
+        Some(profile) => format!("weight-watcher-p-{profile}-{name}.png"),
+        None => format!("weight-watcher-{name}.png"),
+    };
+    std::env::temp_dir().join(name)
+}
+
+/// The default path to the data file, creating [`data_dir`] if needed.
+///
+/// Older versions kept the data file in the config directory, so it is moved
+/// to the data directory if it's found there and there isn't already a data
+/// file in the new location.
+pub fn data_file() -> Result<PathBuf> {
+    let dir = data_dir()?;
+    std::fs::create_dir_all(&dir)?;
+    let new = dir.join(DATA_FILE);
+    let old = config_dir()?.join(DATA_FILE);
+    if old == new || new.exists() || !old.exists() {
+        return Ok(new);
+    }
+    info!(
+        "moving data file from {} to {}",
+        old.display(),
+        new.display()
+    );
+    if let Err(e) = std::fs::rename(&old, &new) {
+        // rename fails across filesystems, so fall back on copying
+        if e.kind() != io::ErrorKind::CrossesDevices {
+            return Err(e.into());
+        }
+        std::fs::copy(&old, &new)?;
+        std::fs::remove_file(&old)?;
+    }
+    Ok(new)
+}
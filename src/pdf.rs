@@ -0,0 +1,506 @@
+//! PDF reports of the entries in a period, written directly rather than
+//! printed from a browser. The documents only use the standard Helvetica
+//! fonts, which every PDF reader has, so nothing needs to be embedded, and
+//! the graph is drawn with vector lines instead of gnuplot's image.
+
+use std::{collections::HashMap, fmt::Write};
+
+use time::{Date, Weekday};
+
+use crate::{date_format::DateFormat, stats, units, units::Unit, Entry};
+
+/// The size of an A4 page in points.
+const WIDTH: f64 = 595.0;
+const HEIGHT: f64 = 842.0;
+const MARGIN: f64 = 50.0;
+
+/// The height of a line of body text.
+const LINE: f64 = 14.0;
+
+#[derive(Clone, Copy)]
+enum Font {
+    Regular,
+    Bold,
+}
+
+impl Font {
+    fn name(self) -> &'static str {
+        match self {
+            Font::Regular => "F1",
+            Font::Bold => "F2",
+        }
+    }
+}
+
+/// A document being laid out from the top of the first page down, starting
+/// new pages as they fill up.
+struct Document {
+    /// The content stream of each page
+    pages: Vec<String>,
+
+    /// How far up the page the next line goes
+    y: f64,
+}
+
+impl Document {
+    fn new() -> Self {
+        Self {
+            pages: vec![String::new()],
+            y: HEIGHT - MARGIN,
+        }
+    }
+
+    fn content(&mut self) -> &mut String {
+        self.pages.last_mut().expect("there is always a page")
+    }
+
+    /// Start a new page unless there is `height` left on this one.
+    fn reserve(&mut self, height: f64) {
+        if self.y - height < MARGIN {
+            self.pages.push(String::new());
+            self.y = HEIGHT - MARGIN;
+        }
+    }
+
+    fn text(&mut self, x: f64, y: f64, font: Font, size: f64, s: &str) {
+        text(self.content(), x, y, font, size, s);
+    }
+
+    /// Draw a line through `points` in `gray`, from 0 for black to 1 for
+    /// white.
+    fn line(&mut self, points: &[(f64, f64)], width: f64, gray: f64) {
+        let Some(((x, y), rest)) = points.split_first() else {
+            return;
+        };
+        let out = self.content();
+        write!(out, "{gray} G {width} w {x:.2} {y:.2} m").unwrap();
+        for (x, y) in rest {
+            write!(out, " {x:.2} {y:.2} l").unwrap();
+        }
+        writeln!(out, " S").unwrap();
+    }
+
+    /// Fill a square `size` points wide centered on `x` and `y`.
+    fn dot(&mut self, x: f64, y: f64, size: f64, gray: f64) {
+        writeln!(
+            self.content(),
+            "{gray} g {:.2} {:.2} {size} {size} re f",
+            x - size / 2.0,
+            y - size / 2.0
+        )
+        .unwrap();
+    }
+
+    fn heading(&mut self, s: &str, size: f64) {
+        self.reserve(size + 2.0 * LINE);
+        self.y -= size + 6.0;
+        self.text(MARGIN, self.y, Font::Bold, size, s);
+        self.y -= 8.0;
+    }
+
+    fn paragraph(&mut self, s: &str) {
+        self.reserve(LINE);
+        self.y -= LINE;
+        self.text(MARGIN, self.y, Font::Regular, 10.0, s);
+    }
+
+    /// Lines of labels followed by their values.
+    fn pairs(&mut self, pairs: &[(&str, String)]) {
+        for (label, value) in pairs {
+            self.reserve(LINE);
+            self.y -= LINE;
+            self.text(MARGIN, self.y, Font::Bold, 10.0, label);
+            self.text(MARGIN + 120.0, self.y, Font::Regular, 10.0, value);
+        }
+        self.y -= LINE / 2.0;
+    }
+
+    /// A table with the `columns` at the given widths, repeating their
+    /// headings at the top of each page it continues onto.
+    fn table(&mut self, columns: &[(&str, f64)], rows: &[Vec<String>]) {
+        let header = |doc: &mut Self| {
+            doc.y -= LINE;
+            let mut x = MARGIN;
+            for (heading, width) in columns {
+                doc.text(x, doc.y, Font::Bold, 10.0, heading);
+                x += width;
+            }
+            doc.line(&[(MARGIN, doc.y - 4.0), (x, doc.y - 4.0)], 0.5, 0.0);
+            doc.y -= 4.0;
+        };
+        self.reserve(3.0 * LINE);
+        header(self);
+        for row in rows {
+            if self.y - LINE < MARGIN {
+                self.reserve(3.0 * LINE);
+                header(self);
+            }
+            self.y -= LINE;
+            let mut x = MARGIN;
+            for (cell, (_, width)) in row.iter().zip(columns) {
+                self.text(x, self.y, Font::Regular, 10.0, cell);
+                x += width;
+            }
+        }
+        self.y -= LINE / 2.0;
+    }
+
+    /// The finished file, with the pages numbered at the bottom.
+    fn finish(mut self) -> Vec<u8> {
+        let count = self.pages.len();
+        if count > 1 {
+            for (i, page) in self.pages.iter_mut().enumerate() {
+                let number = format!("Page {} of {count}", i + 1);
+                let x = WIDTH - MARGIN - 60.0;
+                text(page, x, MARGIN / 2.0, Font::Regular, 8.0, &number);
+            }
+        }
+        let mut objects = vec![
+            "<< /Type /Catalog /Pages 2 0 R >>".to_owned(),
+            String::new(),
+            "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica \
+             /Encoding /WinAnsiEncoding >>"
+                .to_owned(),
+            "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica-Bold \
+             /Encoding /WinAnsiEncoding >>"
+                .to_owned(),
+        ];
+        let mut kids = Vec::new();
+        for content in &self.pages {
+            let page = objects.len() + 1;
+            kids.push(format!("{page} 0 R"));
+            objects.push(format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {WIDTH} \
+                 {HEIGHT}] /Resources << /Font << /F1 3 0 R /F2 4 0 R >> >> \
+                 /Contents {} 0 R >>",
+                page + 1
+            ));
+            objects.push(format!(
+                "<< /Length {} >>\nstream\n{content}endstream",
+                content.len()
+            ));
+        }
+        objects[1] = format!(
+            "<< /Type /Pages /Kids [{}] /Count {count} >>",
+            kids.join(" ")
+        );
+
+        let mut out = b"%PDF-1.4\n".to_vec();
+        let mut offsets = Vec::new();
+        for (i, object) in objects.iter().enumerate() {
+            offsets.push(out.len());
+            out.extend(format!("{} 0 obj\n{object}\nendobj\n", i + 1).bytes());
+        }
+        let xref = out.len();
+        let mut table =
+            format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+        for offset in offsets {
+            writeln!(table, "{offset:010} 00000 n ").unwrap();
+        }
+        write!(
+            table,
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{xref}\n%%EOF\n",
+            objects.len() + 1
+        )
+        .unwrap();
+        out.extend(table.bytes());
+        out
+    }
+}
+
+/// Add `s` to the `content` stream of a page with its baseline starting at
+/// `x` and `y`.
+fn text(content: &mut String, x: f64, y: f64, font: Font, size: f64, s: &str) {
+    writeln!(
+        content,
+        "BT /{} {size} Tf {x:.2} {y:.2} Td ({}) Tj ET",
+        font.name(),
+        encode(s)
+    )
+    .unwrap();
+}
+
+/// `s` as the inside of a PDF string in the fonts' Windows-1252 encoding,
+/// which matches Latin-1 for the characters it can show. Other characters
+/// are replaced with `?`.
+fn encode(s: &str) -> String {
+    let mut out = String::new();
+    for c in s.chars() {
+        match c {
+            '\\' | '(' | ')' => {
+                out.push('\\');
+                out.push(c);
+            }
+            ' '..='~' => out.push(c),
+            '\u{a0}'..='\u{ff}' => write!(out, "\\{:03o}", c as u32).unwrap(),
+            _ => out.push('?'),
+        }
+    }
+    out
+}
+
+/// What goes into a report on the entries from `from` to `to`.
+pub struct Report<'a> {
+    pub title: &'a str,
+
+    /// The profile the entries belong to, `None` for the default profile
+    pub profile: Option<&'a str>,
+    pub from: Date,
+    pub to: Date,
+
+    /// The day the report is made
+    pub today: Date,
+
+    /// The entries in the period, in date order
+    pub entries: &'a [&'a Entry],
+
+    /// The 30-day rolling average up to each date
+    pub trend: &'a HashMap<Date, f64>,
+    pub unit: Unit,
+
+    /// The unit of the weights, if they should be shown in stones and
+    /// pounds
+    pub stones: Option<Unit>,
+    pub date_format: &'a DateFormat,
+
+    /// The day of the official weigh-in, if only those entries should count
+    /// towards the statistics
+    pub weigh_in_day: Option<Weekday>,
+
+    /// The weight the profile is aiming for, if any
+    pub goal: Option<f64>,
+}
+
+impl Report<'_> {
+    /// The report as a PDF file.
+    pub fn render(&self) -> Vec<u8> {
+        let mut doc = Document::new();
+        let dates = format!(
+            "{} to {}",
+            self.date_format.format(self.from),
+            self.date_format.format(self.to)
+        );
+        doc.heading(&format!("{}: {dates}", self.title), 18.0);
+        match self.profile {
+            Some(name) => doc.paragraph(&format!(
+                "Profile {name}, created {}.",
+                self.date_format.format(self.today)
+            )),
+            None => doc.paragraph(&format!(
+                "Created {}.",
+                self.date_format.format(self.today)
+            )),
+        }
+        if self.entries.is_empty() {
+            doc.paragraph("There are no entries in this period.");
+            return doc.finish();
+        }
+        self.graph(&mut doc);
+
+        let counted = match self.weigh_in_day {
+            Some(day) => stats::official(self.entries.iter().copied(), day),
+            None => self.entries.to_vec(),
+        };
+        if let (Some(first), Some(last)) = (counted.first(), counted.last()) {
+            let (min, max) = stats::minmax(counted.iter().copied())
+                .expect("there are entries");
+            let average = counted.iter().map(|e| e.weight).sum::<f64>()
+                / counted.len() as f64;
+            doc.heading("Summary", 13.0);
+            doc.pairs(&[
+                ("Entries", counted.len().to_string()),
+                ("Average", self.weight(average)),
+                ("Lowest", self.weight(min)),
+                ("Highest", self.weight(max)),
+                ("First", self.dated(first)),
+                ("Last", self.dated(last)),
+                ("Change", self.change(last.weight - first.weight)),
+            ]);
+
+            if let Some(goal) = self.goal {
+                doc.heading("Goal", 13.0);
+                let to_go = last.weight - goal;
+                let start = first.weight - goal;
+                let mut pairs = vec![
+                    ("Goal", self.weight(goal)),
+                    ("Latest", self.dated(last)),
+                ];
+                // past the goal from the side the period started on
+                let reached = to_go == 0.0
+                    || (start != 0.0 && to_go.signum() != start.signum());
+                if reached {
+                    pairs.push(("To go", "Reached".to_owned()));
+                } else {
+                    pairs.push((
+                        "To go",
+                        format!("{:.1} {}", to_go.abs(), self.unit),
+                    ));
+                    if start != 0.0 {
+                        pairs.push((
+                            "Progress",
+                            format!(
+                                "{:.0}% of the way from {}",
+                                (1.0 - to_go / start) * 100.0,
+                                self.weight(first.weight)
+                            ),
+                        ));
+                    }
+                }
+                doc.pairs(&pairs);
+            }
+
+            let months = stats::monthly(counted.iter().copied());
+            if months.len() > 1 {
+                doc.heading("By month", 13.0);
+                let rows: Vec<_> = months
+                    .iter()
+                    .map(|m| {
+                        vec![
+                            format!("{} {}", m.month, m.year),
+                            m.count.to_string(),
+                            self.weight(m.average),
+                            self.weight(m.min),
+                            self.weight(m.max),
+                            self.change(m.change),
+                        ]
+                    })
+                    .collect();
+                doc.table(
+                    &[
+                        ("Month", 100.0),
+                        ("Entries", 55.0),
+                        ("Average", 85.0),
+                        ("Min", 85.0),
+                        ("Max", 85.0),
+                        ("Change", 85.0),
+                    ],
+                    &rows,
+                );
+            }
+        }
+
+        doc.heading("Entries", 13.0);
+        let rows: Vec<_> = self
+            .entries
+            .iter()
+            .map(|e| {
+                let mut tags = e
+                    .tags
+                    .iter()
+                    .map(|t| format!("#{t}"))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                if tags.len() > 40 {
+                    tags.truncate(tags.floor_char_boundary(37));
+                    tags.push_str("...");
+                }
+                vec![
+                    self.date_format.format(e.date),
+                    self.weight(e.weight),
+                    self.weight(self.trend[&e.date]),
+                    tags,
+                ]
+            })
+            .collect();
+        doc.table(
+            &[
+                ("Date", 110.0),
+                ("Weight", 90.0),
+                ("30-day average", 100.0),
+                ("Tags", 195.0),
+            ],
+            &rows,
+        );
+        doc.finish()
+    }
+
+    fn weight(&self, weight: f64) -> String {
+        match self.stones {
+            Some(_) => units::format_weight(weight, self.stones),
+            None => format!("{weight:.1} {}", self.unit),
+        }
+    }
+
+    fn change(&self, change: f64) -> String {
+        format!("{change:+.1} {}", self.unit)
+    }
+
+    fn dated(&self, entry: &Entry) -> String {
+        format!(
+            "{} on {}",
+            self.weight(entry.weight),
+            self.date_format.format(entry.date)
+        )
+    }
+
+    /// Draw the weights and their trend across the page.
+    fn graph(&self, doc: &mut Document) {
+        const HEIGHT: f64 = 220.0;
+        // room for the labels on the axes
+        const LEFT: f64 = 45.0;
+        const BOTTOM: f64 = 18.0;
+        doc.reserve(HEIGHT + LINE);
+        doc.y -= HEIGHT + LINE;
+        let (x0, y0) = (MARGIN + LEFT, doc.y + BOTTOM);
+        let (width, height) = (WIDTH - 2.0 * MARGIN - LEFT, HEIGHT - BOTTOM);
+
+        let values = self
+            .entries
+            .iter()
+            .flat_map(|e| [e.weight, self.trend[&e.date]]);
+        let (min, max) = values
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), v| {
+                (min.min(v), max.max(v))
+            });
+        let pad = ((max - min) * 0.05).max(0.5);
+        let (min, max) = (min - pad, max + pad);
+        let days = ((self.to - self.from).whole_days() + 1) as f64;
+        let x = |date: Date| {
+            x0 + ((date - self.from).whole_days() as f64 + 0.5) / days * width
+        };
+        let y = |weight: f64| y0 + (weight - min) / (max - min) * height;
+
+        let step = [0.1, 0.2, 0.5, 1.0, 2.0, 5.0, 10.0, 20.0, 50.0, 100.0]
+            .into_iter()
+            .find(|step| (max - min) / step <= 8.0)
+            .unwrap_or(200.0);
+        let mut tick = (min / step).ceil() * step;
+        while tick <= max {
+            doc.line(&[(x0, y(tick)), (x0 + width, y(tick))], 0.5, 0.85);
+            let label = match step < 1.0 {
+                true => format!("{tick:.1}"),
+                false => format!("{tick:.0}"),
+            };
+            doc.text(MARGIN, y(tick) - 3.0, Font::Regular, 8.0, &label);
+            tick += step;
+        }
+        let labels = 5.min(days as i64 - 1).max(1);
+        for i in 0..=labels {
+            let date = self.from
+                + time::Duration::days(i * (days as i64 - 1) / labels);
+            let label = self.date_format.format(date);
+            // roughly centered, at about half a point per character
+            let left = x(date) - label.len() as f64 * 2.2;
+            doc.line(&[(x(date), y0), (x(date), y0 + height)], 0.5, 0.85);
+            doc.text(left, y0 - 12.0, Font::Regular, 8.0, &label);
+        }
+        doc.line(&[(x0, y0 + height), (x0, y0), (x0 + width, y0)], 0.75, 0.0);
+
+        let weights: Vec<_> = self
+            .entries
+            .iter()
+            .map(|e| (x(e.date), y(e.weight)))
+            .collect();
+        doc.line(&weights, 0.75, 0.6);
+        for (x, y) in weights {
+            doc.dot(x, y, 2.5, 0.3);
+        }
+        let trend: Vec<_> = self
+            .entries
+            .iter()
+            .map(|e| (x(e.date), y(self.trend[&e.date])))
+            .collect();
+        doc.line(&trend, 1.5, 0.0);
+        doc.y -= LINE / 2.0;
+    }
+}
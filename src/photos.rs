@@ -0,0 +1,188 @@
+//! Progress photos. Each profile keeps its photos in a directory next to its
+//! data file, named by the date they were taken on like `2024-03-01-1.jpg`.
+//! Photos are encrypted like the data files if encryption is enabled.
+
+use std::{io, path::PathBuf, sync::Arc};
+
+use time::Date;
+use tracing::{debug, instrument};
+
+use crate::{
+    crypto::{self, Cipher},
+    http::ContentType,
+    store::{format_date, parse_date},
+};
+
+/// The kinds of images that can be uploaded.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Jpeg,
+    Png,
+    Webp,
+}
+
+impl Format {
+    /// Identify the image format from the first bytes of `data`.
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            Some(Self::Jpeg)
+        } else if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(Self::Png)
+        } else if data.len() >= 12
+            && &data[..4] == b"RIFF"
+            && &data[8..12] == b"WEBP"
+        {
+            Some(Self::Webp)
+        } else {
+            None
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            Format::Jpeg => "jpg",
+            Format::Png => "png",
+            Format::Webp => "webp",
+        }
+    }
+
+    pub fn content_type(&self) -> ContentType {
+        match self {
+            Format::Jpeg => ContentType::Jpeg,
+            Format::Png => ContentType::Png,
+            Format::Webp => ContentType::Webp,
+        }
+    }
+}
+
+/// A stored photo.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Photo {
+    pub date: Date,
+
+    /// The photo's file name, which identifies it in URLs
+    pub name: String,
+    pub format: Format,
+}
+
+impl Photo {
+    /// Parse a file name written by [`Photos::add`]. Anything else, like
+    /// a path with slashes in it, is rejected.
+    pub fn parse(name: &str) -> Option<Self> {
+        let (stem, extension) = name.rsplit_once('.')?;
+        let format = match extension {
+            "jpg" => Format::Jpeg,
+            "png" => Format::Png,
+            "webp" => Format::Webp,
+            _ => return None,
+        };
+        let (date, n) = stem.rsplit_once('-')?;
+        if n.is_empty() || !n.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        Some(Self {
+            date: parse_date(date)?,
+            name: name.to_owned(),
+            format,
+        })
+    }
+}
+
+/// One profile's photos.
+pub struct Photos {
+    dir: PathBuf,
+    cipher: Option<Cipher>,
+}
+
+impl Photos {
+    /// The photos in `dir`, which is created when the first one is added,
+    /// encrypted with a key derived from `passphrase` if given.
+    pub fn open(
+        dir: PathBuf,
+        passphrase: Option<Arc<str>>,
+    ) -> io::Result<Self> {
+        let mut photos = Self { dir, cipher: None };
+        if let Some(passphrase) = passphrase {
+            // reuse the salt of an existing photo so that reading them
+            // doesn't need a key derived for each one
+            let contents = match photos.list()?.first() {
+                Some(photo) => std::fs::read(photos.dir.join(&photo.name))?,
+                None => Vec::new(),
+            };
+            photos.cipher = Some(Cipher::new(passphrase, &contents)?);
+        }
+        Ok(photos)
+    }
+
+    /// Every photo, in date order.
+    pub fn list(&self) -> io::Result<Vec<Photo>> {
+        let entries = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Ok(Vec::new())
+            }
+            Err(e) => return Err(e),
+        };
+        let mut photos = Vec::new();
+        for entry in entries {
+            if let Some(photo) =
+                entry?.file_name().to_str().and_then(Photo::parse)
+            {
+                photos.push(photo);
+            }
+        }
+        photos.sort_by(|a, b| (a.date, &a.name).cmp(&(b.date, &b.name)));
+        Ok(photos)
+    }
+
+    /// Save the image in `data` as a photo taken on `date`.
+    #[instrument(skip(self, data), fields(dir = %self.dir.display()))]
+    pub fn add(
+        &self,
+        date: Date,
+        format: Format,
+        data: &[u8],
+    ) -> io::Result<Photo> {
+        std::fs::create_dir_all(&self.dir)?;
+        let contents = match &self.cipher {
+            Some(cipher) => cipher.encrypt(data)?,
+            None => data.to_vec(),
+        };
+        for n in 1.. {
+            let name =
+                format!("{}-{n}.{}", format_date(date), format.extension());
+            let path = self.dir.join(&name);
+            // create_new so that simultaneous uploads can't clobber each other
+            match std::fs::File::options()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+            {
+                Ok(mut file) => {
+                    io::Write::write_all(&mut file, &contents)?;
+                    debug!(name, "saved photo");
+                    return Ok(Photo { date, name, format });
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+                Err(e) => return Err(e),
+            }
+        }
+        unreachable!("ran out of photo names")
+    }
+
+    /// The image data of `photo`.
+    pub fn read(&self, photo: &Photo) -> io::Result<Vec<u8>> {
+        let contents = std::fs::read(self.dir.join(&photo.name))?;
+        if !crypto::is_encrypted(&contents) {
+            return Ok(contents);
+        }
+        match &self.cipher {
+            Some(cipher) => cipher.decrypt(&contents),
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the photo is encrypted, but no encryption_key_file is \
+                 configured",
+            )),
+        }
+    }
+}
@@ -1,11 +1,16 @@
+$data << EOD
+{{data}}EOD
+
 set terminal pngcairo
-set output "/tmp/weight-watcher.png"
+set output '{{output}}'
 
 set timefmt "%Y-%m-%d"
 set xdata time
 set xrange ["{{date_start}}":"{{date_end}}"]
+set format x "{{date_format}}"
 {{yrange}}
+{{markers}}
 set ylabel "Weight"
 set xlabel "Date"
 unset key
-plot "{{name}}" u 1:2 w linespoints pointtype 7 lc "black"
\ No newline at end of file
+plot $data u 1:2 w linespoints pointtype 7 lc "black"
\ No newline at end of file
@@ -0,0 +1,372 @@
+use std::{
+    fmt::Write as _,
+    io::Write,
+    path::Path,
+    process::{Command, Stdio},
+    str::FromStr,
+};
+
+use time::Date;
+use tracing::{debug, instrument};
+
+use crate::{
+    date_format::DateFormat, growth, store::format_date, units::Unit, Entry,
+    Error, Result,
+};
+
+/// How far back a graph goes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Span {
+    /// The given number of days before today
+    Days(u32),
+
+    /// Everything since the first entry
+    All,
+}
+
+impl Span {
+    /// The span of the graph on the index page.
+    pub const RECENT: Self = Self::Days(28);
+
+    /// The name of the span in URLs and file names, like `90` or `all`.
+    pub fn name(&self) -> String {
+        match self {
+            Span::Days(days) => days.to_string(),
+            Span::All => "all".to_owned(),
+        }
+    }
+}
+
+impl FromStr for Span {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "all" => Ok(Self::All),
+            _ => match s.parse() {
+                Ok(days) if days > 0 => Ok(Self::Days(days)),
+                _ => Err(format!(
+                    "invalid graph span `{s}`, expected a number of days or \
+                     all"
+                )),
+            },
+        }
+    }
+}
+
+/// A mark on a graph at a date, like starting a medication or a workout, or
+/// a stretch of time starting at the date.
+pub struct Marker {
+    pub date: Date,
+    pub kind: MarkerKind,
+
+    /// Any color gnuplot understands, like `dark-green`
+    pub color: &'static str,
+}
+
+pub enum MarkerKind {
+    /// A labeled vertical line across the graph, for rare events
+    Line(String),
+
+    /// A short tick along the bottom of the graph, for frequent ones
+    Tick,
+
+    /// A shaded stretch of the graph up to and including `end`, for periods
+    /// of time
+    Band { end: Date },
+
+    /// A small point at a weight, for entries left out of the line
+    Point(f64),
+
+    /// A small point on a second y-axis from 1 to 5, for ratings like mood
+    /// that are only shown faintly next to the weights
+    Rating(f64),
+}
+
+/// The y-axis of a graph of weights.
+pub struct Axis {
+    /// The minimum and maximum weights, which are padded to set the range
+    pub range: Option<(f64, f64)>,
+
+    /// The unit of the weights, if the axis should be labeled in stones and
+    /// pounds rather than in that unit
+    pub stones: Option<Unit>,
+}
+
+/// Render the `span` of `entries` before `today` to `output` with gnuplot,
+/// with the y-axis set up by `axis` and the x-axis labeled with dates in
+/// `date_format`. Any `markers` in the span are drawn over the weights.
+#[instrument(skip_all, fields(output = %output.display()))]
+pub fn graph<'a>(
+    entries: impl Iterator<Item = &'a Entry>,
+    output: &Path,
+    axis: &Axis,
+    span: Span,
+    today: Date,
+    date_format: &DateFormat,
+    markers: &[Marker],
+) -> Result<()> {
+    let mut data = String::new();
+    let mut first = None;
+    for entry in entries {
+        writeln!(data, "{} {}", format_date(entry.date), entry.weight).unwrap();
+        first = Some(first.map_or(entry.date, |d: Date| d.min(entry.date)));
+    }
+    let start = match span {
+        Span::Days(days) => today - time::Duration::days(days.into()),
+        Span::All => first.unwrap_or(today - 28 * time::Duration::DAY),
+    };
+    let date_start = format_date(start);
+    let date_end = format_date(today + time::Duration::DAY);
+
+    let mut gp_script = include_str!("plot.gp")
+        // the output can be any path from the command line, so quotes are
+        // doubled as gnuplot expects in single-quoted strings
+        .replace("{{output}}", &output.to_string_lossy().replace('\'', "''"))
+        .replace("{{data}}", &data)
+        .replace("{{date_start}}", &date_start)
+        .replace("{{date_end}}", &date_end)
+        .replace("{{date_format}}", &date_format.strftime());
+    const WEIGHT_PAD: f64 = 5.0;
+    if let Some((weight_start, weight_end)) = axis.range {
+        // a smaller pad keeps light pets' graphs from looking flat
+        let pad = WEIGHT_PAD.min(weight_end.abs() / 10.0);
+        let weight_start = weight_start - pad;
+        let weight_end = weight_end + pad;
+        let mut weight_range =
+            format!("set yrange [{}:{}]", weight_start, weight_end);
+        if let Some(unit) = axis.stones {
+            weight_range.push('\n');
+            weight_range.push_str(&stone_tics(weight_start, weight_end, unit));
+        }
+        gp_script = gp_script.replace("{{yrange}}", &weight_range);
+    } else {
+        gp_script = gp_script.replace("{{yrange}}", "set yrange [0:1000]");
+    }
+    // markers go in last so that their labels can't contain placeholders
+    let mut marker_lines = String::new();
+    for marker in markers {
+        let end = match marker.kind {
+            MarkerKind::Band { end } => end,
+            _ => marker.date,
+        };
+        if end < start || marker.date > today {
+            continue;
+        }
+        let date = format_date(marker.date);
+        let color = marker.color;
+        match &marker.kind {
+            MarkerKind::Line(label) => writeln!(
+                marker_lines,
+                "set arrow from first \"{date}\", graph 0 to first \"{date}\", \
+                 graph 1 nohead dt 3 lc \"{color}\"\n\
+                 set label \"{}\" at first \"{date}\", graph 0.98 rotate by \
+                 90 right offset -0.5,0 font \",8\" tc \"{color}\"",
+                label.replace('\\', "\\\\").replace('"', "\\\"")
+            ),
+            MarkerKind::Band { end } => writeln!(
+                marker_lines,
+                "set object rect from first \"{date}\", graph 0 to first \
+                 \"{}\", graph 1 behind fc \"{color}\" fs solid 0.15 \
+                 noborder",
+                format_date(*end + time::Duration::DAY)
+            ),
+            MarkerKind::Tick => writeln!(
+                marker_lines,
+                "set arrow from first \"{date}\", graph 0 to first \"{date}\", \
+                 graph 0.04 nohead lw 3 lc \"{color}\""
+            ),
+            MarkerKind::Point(weight) => writeln!(
+                marker_lines,
+                "set label \"\" at first \"{date}\", first {weight} back \
+                 point pt 7 ps 0.6 lc \"{color}\""
+            ),
+            MarkerKind::Rating(rating) => writeln!(
+                marker_lines,
+                "set label \"\" at first \"{date}\", second {rating} back \
+                 point pt 7 ps 0.5 lc \"{color}\""
+            ),
+        }
+        .unwrap();
+    }
+    if markers
+        .iter()
+        .any(|m| matches!(m.kind, MarkerKind::Rating(_)))
+    {
+        marker_lines.push_str(
+            "set y2range [0.5:5.5]\nset y2tics 1,1,5 tc \"gray\"\n\
+             set y2label \"Mood\" tc \"gray\"\n",
+        );
+    }
+    let gp_script = gp_script.replace("{{markers}}", &marker_lines);
+
+    run(gp_script)
+}
+
+/// A gnuplot command labeling the y-axis between `start` and `end`, which
+/// are in `unit`, with whole or half stones, or every two pounds if the range
+/// is too small for those.
+fn stone_tics(start: f64, end: f64, unit: Unit) -> String {
+    let start = unit.convert(start, Unit::Lb).ceil() as i64;
+    let end = unit.convert(end, Unit::Lb).floor() as i64;
+    let step = match end - start {
+        56.. => 14,
+        14.. => 7,
+        _ => 2,
+    };
+    let tics: Vec<_> = (start..=end)
+        .filter(|lb| lb % step == 0)
+        .map(|lb| {
+            let label = match lb % 14 {
+                0 => format!("{}st", lb / 14),
+                pounds => format!("{}st {pounds}", lb / 14),
+            };
+            format!("\"{label}\" {}", Unit::Lb.convert(lb as f64, unit))
+        })
+        .collect();
+    format!("set ytics ({})", tics.join(", "))
+}
+
+/// Render each of the `series` of dated values with its title to `output`
+/// with gnuplot, from the earliest value to `today`. The x-axis is labeled
+/// with dates in `date_format` and the y-axis with `ylabel`. Empty series
+/// are left out.
+#[instrument(skip_all, fields(output = %output.display()))]
+pub fn series(
+    series: &[(&str, Vec<(Date, f64)>)],
+    output: &Path,
+    today: Date,
+    date_format: &DateFormat,
+    ylabel: &str,
+) -> Result<()> {
+    let mut data = String::new();
+    let mut plots = Vec::new();
+    for (title, values) in series.iter().filter(|(_, v)| !v.is_empty()) {
+        if !plots.is_empty() {
+            data.push_str("\n\n");
+        }
+        for (date, value) in values {
+            writeln!(data, "{} {value}", format_date(*date)).unwrap();
+        }
+        plots.push(format!(
+            "$data index {} u 1:2 w linespoints pointtype 7 title \"{title}\"",
+            plots.len()
+        ));
+    }
+    let start = series
+        .iter()
+        .filter_map(|(_, values)| values.first())
+        .map(|(date, _)| *date)
+        .min()
+        .unwrap_or(today - 28 * time::Duration::DAY);
+    let gp_script = include_str!("series.gp")
+        .replace("{{output}}", &output.to_string_lossy())
+        .replace("{{data}}", &data)
+        .replace("{{date_start}}", &format_date(start))
+        .replace("{{date_end}}", &format_date(today + time::Duration::DAY))
+        .replace("{{date_format}}", &date_format.strftime())
+        .replace("{{ylabel}}", ylabel)
+        .replace("{{plots}}", &plots.join(", \\\n\t"));
+    run(gp_script)
+}
+
+/// Render `entries` with the daily `values` of another series drawn as bars
+/// behind them on a second y-axis labeled `y2label`, from the first value to
+/// `today`. The x-axis is labeled with dates in `date_format`.
+#[instrument(skip_all, fields(output = %output.display()))]
+pub fn overlay<'a>(
+    entries: impl Iterator<Item = &'a Entry>,
+    values: &[(Date, f64)],
+    output: &Path,
+    today: Date,
+    date_format: &DateFormat,
+    y2label: &str,
+) -> Result<()> {
+    let start = values
+        .first()
+        .map_or(today - 28 * time::Duration::DAY, |(date, _)| *date);
+    let mut weights = String::new();
+    for entry in entries.filter(|e| e.date >= start) {
+        writeln!(weights, "{} {}", format_date(entry.date), entry.weight)
+            .unwrap();
+    }
+    let mut data = String::new();
+    for (date, value) in values {
+        writeln!(data, "{} {value}", format_date(*date)).unwrap();
+    }
+    let gp_script = include_str!("overlay.gp")
+        .replace("{{output}}", &output.to_string_lossy())
+        .replace("{{weights}}", &weights)
+        .replace("{{values}}", &data)
+        .replace("{{date_start}}", &format_date(start))
+        .replace("{{date_end}}", &format_date(today + time::Duration::DAY))
+        .replace("{{date_format}}", &date_format.strftime())
+        .replace("{{y2label}}", y2label);
+    run(gp_script)
+}
+
+/// Render a growth chart of a child's `points`, pairs of age in months and
+/// value, to `output` with gnuplot. The percentile `curves` from
+/// [`growth::CURVES`] are drawn behind the points, each as pairs of age and
+/// value, and `ylabel` labels the y-axis.
+#[instrument(skip_all, fields(output = %output.display()))]
+pub fn growth(
+    points: &[(f64, f64)],
+    curves: &[Vec<(f64, f64)>],
+    output: &Path,
+    ylabel: &str,
+) -> Result<()> {
+    let mut data = String::new();
+    for (months, value) in points {
+        writeln!(data, "{months:.2} {value}").unwrap();
+    }
+    let mut curve_data = String::new();
+    let mut labels = String::new();
+    for (i, (curve, (name, _))) in curves.iter().zip(growth::CURVES).enumerate()
+    {
+        if i > 0 {
+            // blocks are separated by two blank lines for `index`
+            curve_data.push_str("\n\n");
+        }
+        for (months, value) in curve {
+            writeln!(curve_data, "{months:.2} {value:.2}").unwrap();
+        }
+        if let Some((months, value)) = curve.last() {
+            writeln!(
+                labels,
+                "set label \"{name}\" at {months:.2},{value:.2} left"
+            )
+            .unwrap();
+        }
+    }
+    let max_age = curves
+        .iter()
+        .filter_map(|c| c.last())
+        .map(|(months, _)| *months)
+        .fold(1.0, f64::max);
+    let gp_script = include_str!("growth.gp")
+        .replace("{{output}}", &output.to_string_lossy())
+        .replace("{{data}}", &data)
+        .replace("{{curves}}", &curve_data)
+        .replace("{{curve_count}}", &(curves.len() as i32 - 1).to_string())
+        .replace("{{labels}}", &labels)
+        .replace("{{max_age}}", &format!("{:.1}", max_age * 1.1))
+        .replace("{{ylabel}}", ylabel);
+    run(gp_script)
+}
+
+/// Run gnuplot with `gp_script` on its standard input.
+fn run(gp_script: String) -> Result<()> {
+    let start = std::time::Instant::now();
+    let mut child = Command::new("gnuplot").stdin(Stdio::piped()).spawn()?;
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let writer =
+        std::thread::spawn(move || stdin.write_all(gp_script.as_bytes()));
+    let output = child.wait()?;
+    let written = writer.join().expect("gnuplot writer thread panicked");
+    if !output.success() {
+        return Err(Error::Plot(output.code()));
+    }
+    debug!(elapsed = ?start.elapsed(), "rendered graph");
+    Ok(written?)
+}
@@ -0,0 +1,541 @@
+//! Profiles keep separate data series for several people on one server. The
+//! default profile is served at `/`, and each named profile listed in the
+//! config file is served at `/p/<name>/`.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use time::Date;
+use tracing::{info, warn};
+
+use crate::{
+    cycle::{self, Cycles, Phase},
+    exercise::Exercise,
+    growth::{self, Chart, Child},
+    journal::Journal,
+    medication::{Change, Medications},
+    metrics::{self, Metrics},
+    notes::Notes,
+    paths,
+    photos::Photos,
+    plot::{self, Axis, Marker, MarkerKind, Span},
+    stats,
+    trash::Trash,
+    units::Unit,
+    Config, Entry, Result, Store,
+};
+
+pub struct Profile {
+    /// `None` for the default profile
+    name: Option<String>,
+    store: Store,
+    metrics: Metrics,
+    medications: Medications,
+    exercise: Exercise,
+    cycles: Cycles,
+    notes: Notes,
+    trash: Trash,
+    photos: Photos,
+
+    /// Held while gnuplot is writing one of this profile's graphs so that
+    /// concurrent page loads don't clobber each other's graphs.
+    graph_locks: Mutex<HashMap<Span, Arc<Mutex<()>>>>,
+
+    /// Held while gnuplot is writing one of this profile's other charts,
+    /// like its growth charts
+    chart_lock: Mutex<()>,
+
+    /// How long the last of this profile's weight graphs took to render
+    last_render: Mutex<Option<Duration>>,
+}
+
+/// The metric heights are recorded as, in cm.
+pub const HEIGHT: &str = "height";
+
+impl Profile {
+    /// Set up the profile with the entries in `store`, loading its other
+    /// journals and its photos from next to the store's data file.
+    pub fn open(name: Option<String>, store: Store) -> std::io::Result<Self> {
+        let path = |extension| store.path().with_extension(extension);
+        let metrics = Journal::open(&path("metrics"), store.passphrase())?;
+        let medications =
+            Journal::open(&path("medications"), store.passphrase())?;
+        let exercise = Journal::open(&path("exercise"), store.passphrase())?;
+        let cycles = Journal::open(&path("cycle"), store.passphrase())?;
+        let notes = Journal::open(&path("notes"), store.passphrase())?;
+        let trash = Journal::open(&path("trash"), store.passphrase())?;
+        let photos = Photos::open(path("photos"), store.passphrase())?;
+        Ok(Self {
+            name,
+            store,
+            metrics,
+            medications,
+            exercise,
+            cycles,
+            notes,
+            trash,
+            photos,
+            graph_locks: Mutex::default(),
+            chart_lock: Mutex::default(),
+            last_render: Mutex::default(),
+        })
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn store(&self) -> &Store {
+        &self.store
+    }
+
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    pub fn medications(&self) -> &Medications {
+        &self.medications
+    }
+
+    pub fn exercise(&self) -> &Exercise {
+        &self.exercise
+    }
+
+    pub fn cycles(&self) -> &Cycles {
+        &self.cycles
+    }
+
+    pub fn notes(&self) -> &Notes {
+        &self.notes
+    }
+
+    pub fn trash(&self) -> &Trash {
+        &self.trash
+    }
+
+    pub fn photos(&self) -> &Photos {
+        &self.photos
+    }
+
+    /// The version of all of the profile's data, which changes whenever
+    /// any of it does.
+    pub fn version(&self) -> u64 {
+        self.store.version()
+            + self.metrics.version()
+            + self.medications.version()
+            + self.exercise.version()
+            + self.cycles.version()
+            + self.notes.version()
+            + self.trash.version()
+    }
+
+    /// Reload all of the profile's data from disk.
+    pub fn update(&self) -> std::io::Result<()> {
+        self.store.update()?;
+        self.metrics.update()?;
+        self.medications.update()?;
+        self.exercise.update()?;
+        self.cycles.update()?;
+        self.notes.update()?;
+        self.trash.update()
+    }
+
+    /// The URL prefix of this profile's pages, without a trailing slash.
+    pub fn base(&self) -> String {
+        match &self.name {
+            Some(name) => format!("/p/{name}"),
+            None => String::new(),
+        }
+    }
+
+    /// Where this profile's graph of `span` is rendered.
+    pub fn graph_file(&self, span: Span) -> PathBuf {
+        paths::graph_file(self.name(), span)
+    }
+
+    /// Where this profile's growth `chart` is rendered.
+    pub fn growth_file(&self, chart: Chart) -> PathBuf {
+        paths::growth_file(self.name(), chart)
+    }
+
+    /// Render `child`'s growth `chart` up to `today` from this profile's
+    /// weights or heights, with weights in `unit`.
+    pub fn growth_graph(
+        &self,
+        chart: Chart,
+        child: &Child,
+        today: Date,
+        unit: Unit,
+    ) -> Result<()> {
+        let points: Vec<_> = match chart {
+            Chart::Weight => self
+                .store
+                .entries()
+                .iter()
+                .filter(|e| e.date >= child.birth)
+                .map(|e| (child.months(e.date), e.weight))
+                .collect(),
+            Chart::Height => self
+                .metrics
+                .series(HEIGHT)
+                .into_iter()
+                .filter(|(date, _)| *date >= child.birth)
+                .map(|(date, height)| (child.months(date), height))
+                .collect(),
+        };
+        // show a few months ahead of the child's age
+        let months = child.months(today) + 3.0;
+        let mut curves = growth::curves(chart, child.sex, months);
+        if chart == Chart::Weight {
+            for (_, weight) in curves.iter_mut().flatten() {
+                *weight = Unit::Kg.convert(*weight, unit);
+            }
+        }
+        let ylabel = match chart {
+            Chart::Weight => format!("Weight ({unit})"),
+            Chart::Height => "Height (cm)".to_owned(),
+        };
+        let _guard = self
+            .chart_lock
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        plot::growth(&points, &curves, &self.growth_file(chart), &ylabel)
+    }
+
+    /// Where this profile's chart of body measurements is rendered.
+    pub fn measurements_file(&self) -> PathBuf {
+        paths::chart_file(self.name(), "measurements")
+    }
+
+    /// Render the chart of every body measurement up to `today`.
+    pub fn measurements_graph(
+        &self,
+        today: Date,
+        config: &Config,
+    ) -> Result<()> {
+        let series: Vec<_> = metrics::BODY
+            .iter()
+            .map(|(metric, label)| (*label, self.metrics.series(metric)))
+            .collect();
+        let _guard = self
+            .chart_lock
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        plot::series(
+            &series,
+            &self.measurements_file(),
+            today,
+            &config.date_format,
+            "Measurement (cm)",
+        )
+    }
+
+    /// Where this profile's chart of sleep and weight is rendered.
+    pub fn sleep_file(&self) -> PathBuf {
+        paths::chart_file(self.name(), "sleep")
+    }
+
+    /// Render the chart of nightly sleep behind the weights, up to `today`.
+    pub fn sleep_graph(&self, today: Date, config: &Config) -> Result<()> {
+        let sleep = self.metrics.series(metrics::SLEEP);
+        let entries = self.store.entries();
+        let _guard = self
+            .chart_lock
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        plot::overlay(
+            entries.iter(),
+            &sleep,
+            &self.sleep_file(),
+            today,
+            &config.date_format,
+            "Sleep (hours)",
+        )
+    }
+
+    /// Delete the graphs rendered so far, returning how many there were.
+    /// They are rendered again the next time they are needed.
+    pub fn clear_graphs(&self) -> usize {
+        let locks = self
+            .graph_locks
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let mut removed = 0;
+        for (span, lock) in locks.iter() {
+            let _guard = lock.lock().unwrap_or_else(PoisonError::into_inner);
+            match std::fs::remove_file(self.graph_file(*span)) {
+                Ok(()) => removed += 1,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => warn!("failed to remove {} graph: {e}", span.name()),
+            }
+        }
+        removed
+    }
+
+    /// Render the graph of the `span` before `today` to its
+    /// [`graph_file`](Self::graph_file), as for [`graph_to`](Self::graph_to).
+    pub fn graph(
+        &self,
+        span: Span,
+        today: Date,
+        tag: Option<&str>,
+        config: &Config,
+    ) -> Result<()> {
+        let lock = Arc::clone(
+            self.graph_locks
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .entry(span)
+                .or_default(),
+        );
+        let _guard = lock.lock().unwrap_or_else(PoisonError::into_inner);
+        self.graph_to(&self.graph_file(span), span, today, tag, config)
+    }
+
+    /// Render the graph of the entries from `first` to `last` for a
+    /// report, returning the PNG. Unlike the other graphs, the y-axis only
+    /// fits the entries in those dates.
+    pub fn report_graph(
+        &self,
+        first: Date,
+        last: Date,
+        config: &Config,
+    ) -> Result<Vec<u8>> {
+        let output = paths::chart_file(self.name(), "report");
+        let span = Span::Days((last - first).whole_days() as u32);
+        let _guard = self
+            .chart_lock
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        self.render(&output, span, last, None, Some(first..=last), config)?;
+        Ok(std::fs::read(output)?)
+    }
+
+    /// Render the graph of the `span` before `today` to `output`, showing
+    /// only entries tagged `tag` if given. Entries with any of the
+    /// `exclude_tags` in `config` are plotted but don't count towards the
+    /// y-axis range. If `config` sets a `weigh_in_day`, the line only joins
+    /// the official weigh-ins and the other entries are drawn as faint
+    /// points.
+    pub fn graph_to(
+        &self,
+        output: &Path,
+        span: Span,
+        today: Date,
+        tag: Option<&str>,
+        config: &Config,
+    ) -> Result<()> {
+        self.render(output, span, today, tag, None, config)
+    }
+
+    /// Render a graph as for [`graph_to`](Self::graph_to), fitting the
+    /// y-axis to the entries in `fit` if given rather than to every entry.
+    fn render(
+        &self,
+        output: &Path,
+        span: Span,
+        today: Date,
+        tag: Option<&str>,
+        fit: Option<RangeInclusive<Date>>,
+        config: &Config,
+    ) -> Result<()> {
+        let exclude = &config.exclude_tags;
+        let entries = self.store.entries();
+        let shown: Vec<_> = entries
+            .iter()
+            .filter(|e| tag.is_none_or(|t| e.has_tag(t)))
+            .collect();
+        let range = stats::minmax(
+            shown
+                .iter()
+                .copied()
+                .filter(|e| !exclude.iter().any(|t| e.has_tag(t)))
+                .filter(|e| fit.as_ref().is_none_or(|f| f.contains(&e.date))),
+        );
+        let starts: Vec<_> =
+            self.cycles.records().iter().map(|s| s.date).collect();
+        let mut markers: Vec<_> = cycle::phases(&starts)
+            .into_iter()
+            .map(|(phase, start, end)| Marker {
+                date: start,
+                kind: MarkerKind::Band { end },
+                color: match phase {
+                    Phase::Period => "red",
+                    Phase::Premenstrual => "orange",
+                },
+            })
+            .collect();
+        markers.extend(self.exercise.records().iter().map(|w| Marker {
+            date: w.date,
+            kind: MarkerKind::Tick,
+            color: "dark-cyan",
+        }));
+        let plotted = match config.weigh_in_day {
+            Some(day) => {
+                let official = stats::official(shown.iter().copied(), day);
+                let is_official = |entry: &Entry| {
+                    official
+                        .binary_search_by_key(&entry.date, |e| e.date)
+                        .is_ok_and(|i| std::ptr::eq(official[i], entry))
+                };
+                markers.extend(shown.iter().filter(|e| !is_official(e)).map(
+                    |e| Marker {
+                        date: e.date,
+                        kind: MarkerKind::Point(e.weight),
+                        color: "gray",
+                    },
+                ));
+                official
+            }
+            None => shown,
+        };
+        markers.extend(self.metrics.series(metrics::MOOD).into_iter().map(
+            |(date, mood)| Marker {
+                date,
+                kind: MarkerKind::Rating(mood),
+                color: "light-blue",
+            },
+        ));
+        markers.extend(self.medications.records().iter().map(|m| Marker {
+            date: m.date,
+            kind: MarkerKind::Line(match m.change {
+                Change::Start => format!("+{}", m.name),
+                Change::Stop => format!("-{}", m.name),
+            }),
+            color: match m.change {
+                Change::Start => "dark-green",
+                Change::Stop => "dark-red",
+            },
+        }));
+        let axis = Axis {
+            range,
+            stones: config.stones_for(self.name()),
+        };
+        let started = Instant::now();
+        plot::graph(
+            plotted.into_iter(),
+            output,
+            &axis,
+            span,
+            today,
+            &config.date_format,
+            &markers,
+        )?;
+        *self
+            .last_render
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = Some(started.elapsed());
+        Ok(())
+    }
+
+    /// How long the last of this profile's weight graphs took to render, if
+    /// one has been rendered since the server started.
+    pub fn last_render(&self) -> Option<Duration> {
+        *self
+            .last_render
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Whether `name` can be used as a profile name. Names appear in URLs and
+/// file names, so they are limited to ASCII letters, digits, `-`, and `_`.
+pub fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Split the profile name off of a request path like `/p/anna/history`,
+/// returning the name and the rest of the path. Paths without a profile
+/// prefix belong to the default profile.
+pub fn split_path(path: &str) -> (Option<&str>, &str) {
+    let Some(rest) = path.strip_prefix("/p/") else {
+        return (None, path);
+    };
+    match rest.find('/') {
+        Some(i) => (Some(&rest[..i]), &rest[i..]),
+        None => (Some(rest), "/"),
+    }
+}
+
+/// The default profile and the named profiles.
+pub struct Profiles {
+    default: Arc<Profile>,
+    named: BTreeMap<String, Arc<Profile>>,
+
+    /// Directory holding the data files of the named profiles
+    dir: PathBuf,
+}
+
+impl Profiles {
+    /// Create the profiles with `default` as the default profile. The data
+    /// files for named profiles are kept in a `profiles` directory next to
+    /// the default profile's data file.
+    pub fn new(default: Store) -> std::io::Result<Self> {
+        let dir = default
+            .path()
+            .parent()
+            .unwrap_or(Path::new(""))
+            .join("profiles");
+        Ok(Self {
+            default: Arc::new(Profile::open(None, default)?),
+            named: BTreeMap::new(),
+            dir,
+        })
+    }
+
+    /// Open the named profiles in `names`, keeping any that are already open
+    /// and dropping those that aren't in `names`.
+    pub fn set_names(&mut self, names: &[String]) -> Result<()> {
+        if !names.is_empty() {
+            std::fs::create_dir_all(&self.dir)?;
+        }
+        let mut named = BTreeMap::new();
+        for name in names {
+            let profile = match self.named.remove(name) {
+                Some(profile) => profile,
+                None => {
+                    info!("opening profile {name}");
+                    let path = self.dir.join(format!("{name}.dat"));
+                    let store = self.default.store.open_sibling(&path)?;
+                    Arc::new(Profile::open(Some(name.clone()), store)?)
+                }
+            };
+            named.insert(name.clone(), profile);
+        }
+        self.named = named;
+        Ok(())
+    }
+
+    /// The profile called `name`, or the default profile for `None`.
+    pub fn get(&self, name: Option<&str>) -> Option<Arc<Profile>> {
+        match name {
+            Some(name) => self.named.get(name).cloned(),
+            None => Some(Arc::clone(&self.default)),
+        }
+    }
+
+    pub fn default_profile(&self) -> &Profile {
+        &self.default
+    }
+
+    /// All of the profiles, starting with the default.
+    pub fn iter(&self) -> impl Iterator<Item = &Arc<Profile>> {
+        std::iter::once(&self.default).chain(self.named.values())
+    }
+
+    /// Reload every profile's data from disk.
+    pub fn update(&self) -> std::io::Result<()> {
+        for profile in self.iter() {
+            profile.update()?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,28 @@
+//! QR codes for pointing phones at the server.
+
+use std::fmt::Write;
+
+use qrcodegen::{QrCode, QrCodeEcc};
+
+/// Render `text` as a QR code in an SVG image, or `None` if it is too long to
+/// fit in one.
+pub fn svg(text: &str) -> Option<String> {
+    let qr = QrCode::encode_text(text, QrCodeEcc::Medium).ok()?;
+    // scanners need a quiet zone of four modules around the code
+    const BORDER: i32 = 4;
+    let size = qr.size() + 2 * BORDER;
+    let mut path = String::new();
+    for y in 0..qr.size() {
+        for x in 0..qr.size() {
+            if qr.get_module(x, y) {
+                write!(path, "M{},{}h1v1h-1z", x + BORDER, y + BORDER).unwrap();
+            }
+        }
+    }
+    Some(format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {size} \
+         {size}\" shape-rendering=\"crispEdges\">\
+         <rect width=\"100%\" height=\"100%\" fill=\"white\"/>\
+         <path d=\"{path}\" fill=\"black\"/></svg>"
+    ))
+}
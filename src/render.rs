@@ -0,0 +1,168 @@
+//! Rendering the graph on each profile's index page in the background. Pages
+//! are served straight away with the last graph rendered, and a new one is
+//! drawn whenever the data changes, after which open pages are told to
+//! refresh.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex, PoisonError,
+    },
+};
+
+use time::Date;
+use tracing::debug;
+
+use crate::{events::Event, plot::Span, profile::Profile, State};
+
+/// What a profile's recent graph was rendered from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GraphKey {
+    pub version: u64,
+    pub today: Date,
+    pub tag: Option<String>,
+}
+
+/// How a profile's recent graph compares to the one a page needs.
+#[derive(Debug, PartialEq)]
+pub enum Status {
+    /// The graph is up to date
+    Current,
+
+    /// The graph shows the right entries, but they have changed since
+    Outdated,
+
+    /// There is no graph, or it shows entries with a different tag
+    Missing,
+}
+
+/// A profile whose recent graph should be rendered, showing only entries
+/// tagged `tag` if given.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Job {
+    pub profile: Option<String>,
+    pub tag: Option<String>,
+}
+
+/// Keeps track of the rendered graphs and queues new ones for the thread
+/// started by [`render_in_background`].
+pub struct Renderer {
+    jobs: Sender<Job>,
+
+    /// Taken by the background thread once it starts. Until then, graphs are
+    /// rendered by whoever needs them.
+    queue: Mutex<Option<Receiver<Job>>>,
+
+    /// What each profile's recent graph was last rendered from
+    rendered: Mutex<HashMap<Option<String>, GraphKey>>,
+}
+
+impl Default for Renderer {
+    fn default() -> Self {
+        let (jobs, queue) = mpsc::channel();
+        Self {
+            jobs,
+            queue: Mutex::new(Some(queue)),
+            rendered: Mutex::default(),
+        }
+    }
+}
+
+impl Renderer {
+    /// How `profile`'s recent graph compares to one rendered from `key`.
+    pub fn status(&self, profile: &Profile, key: &GraphKey) -> Status {
+        let rendered =
+            self.rendered.lock().unwrap_or_else(PoisonError::into_inner);
+        if !profile.graph_file(Span::RECENT).exists() {
+            return Status::Missing;
+        }
+        match rendered.get(&profile.name().map(str::to_owned)) {
+            Some(rendered) if rendered == key => Status::Current,
+            Some(rendered) if rendered.tag == key.tag => Status::Outdated,
+            _ => Status::Missing,
+        }
+    }
+
+    /// Note that `profile`'s recent graph was rendered from `key`.
+    pub fn record(&self, profile: Option<&str>, key: GraphKey) {
+        self.rendered
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(profile.map(str::to_owned), key);
+    }
+
+    /// Queue `job` for the background thread, returning false if it isn't
+    /// running and the graph has to be rendered right away instead.
+    pub fn queue(&self, job: Job) -> bool {
+        let running = self
+            .queue
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .is_none();
+        running && self.jobs.send(job).is_ok()
+    }
+
+    /// The tag `profile`'s recent graph was last rendered with, or `None`
+    /// if it hasn't been rendered.
+    fn last_tag(&self, profile: Option<&str>) -> Option<Option<String>> {
+        self.rendered
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&profile.map(str::to_owned))
+            .map(|key| key.tag.clone())
+    }
+}
+
+/// Spawn a thread that renders the graphs queued in `state`'s renderer, and
+/// another that queues a new graph whenever a profile's data changes.
+pub fn render_in_background(state: Arc<State>) {
+    let Some(queue) = state
+        .renderer()
+        .queue
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .take()
+    else {
+        return;
+    };
+
+    let events = state.events().subscribe();
+    let watcher = Arc::clone(&state);
+    std::thread::spawn(move || {
+        for message in events {
+            if let Event::Graphed = message.event {
+                continue;
+            }
+            let renderer = watcher.renderer();
+            // graphs nobody has looked at yet can wait until they are
+            let Some(tag) = renderer.last_tag(message.profile.as_deref())
+            else {
+                continue;
+            };
+            let job = Job {
+                profile: message.profile.clone(),
+                tag,
+            };
+            if renderer.jobs.send(job).is_err() {
+                break;
+            }
+        }
+    });
+
+    std::thread::spawn(move || {
+        while let Ok(job) = queue.recv() {
+            // a burst of changes only needs one graph at the end
+            let mut jobs = vec![job];
+            for job in queue.try_iter() {
+                if !jobs.contains(&job) {
+                    jobs.push(job);
+                }
+            }
+            for job in jobs {
+                debug!(?job, "rendering graph");
+                state.render_graph(&job);
+            }
+        }
+    });
+}
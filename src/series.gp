@@ -0,0 +1,14 @@
+$data << EOD
+{{data}}EOD
+
+set terminal pngcairo
+set output '{{output}}'
+
+set timefmt "%Y-%m-%d"
+set xdata time
+set xrange ["{{date_start}}":"{{date_end}}"]
+set format x "{{date_format}}"
+set ylabel "{{ylabel}}"
+set xlabel "Date"
+set key outside right top
+plot {{plots}}
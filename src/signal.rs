@@ -0,0 +1,39 @@
+//! Unix signal handling.
+
+use std::sync::Arc;
+
+use signal_hook::{
+    consts::{SIGHUP, SIGUSR1},
+    iterator::Signals,
+};
+use tracing::{error, info};
+
+use crate::State;
+
+/// Spawn a thread that reloads the config and data files in `state` whenever
+/// the process receives SIGHUP.
+pub fn reload_on_sighup(state: Arc<State>) -> std::io::Result<()> {
+    let mut signals = Signals::new([SIGHUP])?;
+    std::thread::spawn(move || {
+        for _ in signals.forever() {
+            info!("received SIGHUP, reloading config and data");
+            if let Err(e) = state.reload() {
+                error!("failed to reload: {e}");
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Spawn a thread that switches maintenance mode on or off in `state`
+/// whenever the process receives SIGUSR1.
+pub fn toggle_maintenance_on_sigusr1(state: Arc<State>) -> std::io::Result<()> {
+    let mut signals = Signals::new([SIGUSR1])?;
+    std::thread::spawn(move || {
+        for _ in signals.forever() {
+            let on = state.toggle_maintenance();
+            info!(on, "received SIGUSR1, switched maintenance mode");
+        }
+    });
+    Ok(())
+}
@@ -0,0 +1,47 @@
+//! Server-sent events telling open pages to refresh. Unlike the WebSocket
+//! in [`crate::websocket`], this only says that something changed, which is
+//! all a page needs to reload itself, and browsers reconnect on their own
+//! with `EventSource`.
+
+use std::{
+    io::Write,
+    net::TcpStream,
+    sync::{
+        mpsc::{Receiver, RecvTimeoutError},
+        Arc,
+    },
+    time::Duration,
+};
+
+use tracing::debug;
+
+use crate::events::{self, Message};
+
+/// How often to send a comment on an idle stream, to notice when the client
+/// has gone away and to keep proxies from timing it out.
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Send a `changed` event over `stream` whenever `profile` changes, until
+/// the client disconnects.
+pub fn stream(
+    mut stream: TcpStream,
+    events: Receiver<Arc<Message>>,
+    profile: Option<String>,
+) {
+    debug!("event stream opened");
+    // ask browsers to wait a few seconds before reconnecting, in case the
+    // server is restarting
+    let mut next: &[u8] = b"retry: 5000\n\n";
+    while stream.write_all(next).is_ok() {
+        next = match events::next_for(
+            &events,
+            profile.as_deref(),
+            KEEPALIVE_INTERVAL,
+        ) {
+            Ok(_) => b"event: changed\ndata: \n\n",
+            Err(RecvTimeoutError::Timeout) => b": keepalive\n\n",
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+    }
+    debug!("event stream closed");
+}
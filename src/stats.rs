@@ -0,0 +1,155 @@
+pub mod tags;
+pub mod weekly;
+
+use std::collections::HashMap;
+
+use time::{Date, Duration, Weekday};
+
+use crate::Entry;
+
+/// The smallest and largest weights in `entries`, or `None` if `entries` is
+/// empty.
+pub fn minmax<'a>(
+    entries: impl IntoIterator<Item = &'a Entry>,
+) -> Option<(f64, f64)> {
+    let mut weights: Vec<_> = entries.into_iter().map(|e| e.weight).collect();
+    weights.sort_by(f64::total_cmp);
+    Some((*weights.first()?, *weights.last()?))
+}
+
+/// The `p`th percentiles of the weights in `entries` for each `p` in
+/// `percentiles`, interpolating between the closest weights, or `None` if
+/// `entries` is empty. Unlike [`minmax`], these are hardly affected by a few
+/// outliers.
+pub fn percentiles<'a, const N: usize>(
+    entries: impl IntoIterator<Item = &'a Entry>,
+    percentiles: [f64; N],
+) -> Option<[f64; N]> {
+    let mut weights: Vec<_> = entries.into_iter().map(|e| e.weight).collect();
+    if weights.is_empty() {
+        return None;
+    }
+    weights.sort_by(f64::total_cmp);
+    Some(percentiles.map(|p| {
+        let rank = p.clamp(0.0, 100.0) / 100.0 * (weights.len() - 1) as f64;
+        let (lower, upper) = (rank.floor() as usize, rank.ceil() as usize);
+        let fraction = rank - lower as f64;
+        weights[lower] + (weights[upper] - weights[lower]) * fraction
+    }))
+}
+
+/// Statistics for the entries in one month.
+pub struct Month {
+    pub year: i32,
+    pub month: time::Month,
+    pub count: usize,
+    pub average: f64,
+    pub min: f64,
+    pub max: f64,
+
+    /// The last weight in the month minus the first
+    pub change: f64,
+}
+
+/// Summarize `entries` by month, in date order.
+pub fn monthly<'a>(entries: impl IntoIterator<Item = &'a Entry>) -> Vec<Month> {
+    let mut entries: Vec<_> = entries.into_iter().collect();
+    entries.sort_by_key(|e| e.date);
+    entries
+        .chunk_by(|a, b| {
+            (a.date.year(), a.date.month()) == (b.date.year(), b.date.month())
+        })
+        .map(|chunk| {
+            let weights: Vec<_> = chunk.iter().map(|e| e.weight).collect();
+            let (min, max) =
+                minmax(chunk.iter().copied()).expect("chunks are never empty");
+            Month {
+                year: chunk[0].date.year(),
+                month: chunk[0].date.month(),
+                count: weights.len(),
+                average: weights.iter().sum::<f64>() / weights.len() as f64,
+                min,
+                max,
+                change: weights[weights.len() - 1] - weights[0],
+            }
+        })
+        .collect()
+}
+
+/// The official weigh-ins among `entries`, which are in date order: the
+/// first entry on each `day` of the week.
+pub fn official<'a>(
+    entries: impl IntoIterator<Item = &'a Entry>,
+    day: Weekday,
+) -> Vec<&'a Entry> {
+    let mut official: Vec<&Entry> = Vec::new();
+    for entry in entries.into_iter().filter(|e| e.date.weekday() == day) {
+        if official.last().is_none_or(|last| last.date != entry.date) {
+            official.push(entry);
+        }
+    }
+    official
+}
+
+/// The Monday starting the week containing `date`.
+pub fn week_start(date: Date) -> Date {
+    date - Duration::days(date.weekday().number_days_from_monday().into())
+}
+
+/// The average weight over the `days` days up to and including each date in
+/// `entries`.
+pub fn rolling_average<'a>(
+    entries: impl IntoIterator<Item = &'a Entry>,
+    days: i64,
+) -> HashMap<Date, f64> {
+    let mut entries: Vec<_> = entries.into_iter().collect();
+    entries.sort_by_key(|e| e.date);
+    let mut averages = HashMap::new();
+    let mut start = 0;
+    let mut sum = 0.0;
+    for (end, entry) in entries.iter().enumerate() {
+        sum += entry.weight;
+        while entries[start].date <= entry.date - Duration::days(days) {
+            sum -= entries[start].weight;
+            start += 1;
+        }
+        // later entries on the same date overwrite this with the average
+        // including them
+        averages.insert(entry.date, sum / (end + 1 - start) as f64);
+    }
+    averages
+}
+
+/// The last weight recorded before each date in `entries`, for the change
+/// since then. The first date has none.
+pub fn previous_weights<'a>(
+    entries: impl IntoIterator<Item = &'a Entry>,
+) -> HashMap<Date, f64> {
+    let mut entries: Vec<_> = entries.into_iter().collect();
+    // stable, so entries on the same day stay in the order they were added
+    entries.sort_by_key(|e| e.date);
+    let days: Vec<_> = entries.chunk_by(|a, b| a.date == b.date).collect();
+    days.windows(2)
+        .map(|pair| (pair[1][0].date, pair[0][pair[0].len() - 1].weight))
+        .collect()
+}
+
+/// Which way a change in weight counts as progress, for coloring changes.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Direction {
+    #[default]
+    Loss,
+    Gain,
+}
+
+impl std::str::FromStr for Direction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "loss" => Ok(Self::Loss),
+            "gain" => Ok(Self::Gain),
+            _ => Err(format!("unknown direction `{s}`, expected loss or gain")),
+        }
+    }
+}
@@ -0,0 +1,59 @@
+//! How weight changes around tagged periods, like trips or illnesses. A
+//! period is a run of entries with a tag, and its effect is the change from
+//! the last entry before the run to the first one after it.
+
+use std::collections::BTreeMap;
+
+use crate::Entry;
+
+/// The average effect of the periods tagged `tag`.
+pub struct TagEffect {
+    pub tag: String,
+
+    /// How many complete periods there were
+    pub periods: usize,
+
+    /// The average change in weight across the periods
+    pub average_change: f64,
+}
+
+/// The effect of every tag in `entries`, sorted by tag. Periods at the very
+/// start of the data or still ongoing at the end have nothing to compare
+/// against, so they don't count.
+pub fn effects<'a>(
+    entries: impl IntoIterator<Item = &'a Entry>,
+) -> Vec<TagEffect> {
+    let mut entries: Vec<_> = entries.into_iter().collect();
+    entries.sort_by_key(|e| e.date);
+    let mut changes: BTreeMap<&str, Vec<f64>> = BTreeMap::new();
+    for tag in entries.iter().flat_map(|e| &e.tags) {
+        if changes.contains_key(tag.as_str()) {
+            continue;
+        }
+        let tag_changes = changes.entry(tag).or_default();
+        let mut before = None;
+        let mut in_period = false;
+        for entry in &entries {
+            if entry.has_tag(tag) {
+                in_period = true;
+                continue;
+            }
+            if in_period {
+                if let Some(before) = before {
+                    tag_changes.push(entry.weight - before);
+                }
+                in_period = false;
+            }
+            before = Some(entry.weight);
+        }
+    }
+    changes
+        .into_iter()
+        .filter(|(_, changes)| !changes.is_empty())
+        .map(|(tag, changes)| TagEffect {
+            tag: tag.to_owned(),
+            periods: changes.len(),
+            average_change: changes.iter().sum::<f64>() / changes.len() as f64,
+        })
+        .collect()
+}
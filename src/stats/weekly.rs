@@ -0,0 +1,111 @@
+//! How a daily series like calorie intake or sleep relates to weight. Both
+//! are averaged over weeks starting on Mondays, and each week's average is
+//! compared with the change from that week's average weight to the next
+//! week's, since what happens during a week shows up on the scale over the
+//! days after.
+
+use std::collections::BTreeMap;
+
+use time::{Date, Duration};
+
+use super::week_start;
+use crate::Entry;
+
+/// A daily series and weight change over one week.
+pub struct Week {
+    /// The Monday the week starts on
+    pub start: Date,
+
+    /// How many days of the week have a value
+    pub days: usize,
+
+    /// The average of the days with a value
+    pub average: f64,
+
+    /// The change in average weight from this week to the next, if both
+    /// have weights
+    pub change: Option<f64>,
+}
+
+/// How weekly weight change follows the weekly averages.
+pub struct Fit {
+    /// The correlation between the averages and the changes, from -1 to 1
+    pub correlation: f64,
+
+    /// The daily value at which weight is estimated to stay the same, if
+    /// weight rises with the value, like with calories eaten
+    pub steady: Option<f64>,
+}
+
+/// The fewest weeks with both an average and a weight change to fit.
+pub const MIN_WEEKS: usize = 3;
+
+/// Summarize the `daily` values by week alongside the weight change in
+/// `entries`, in date order. Weeks without any values are left out.
+pub fn weekly<'a>(
+    daily: &[(Date, f64)],
+    entries: impl IntoIterator<Item = &'a Entry>,
+) -> Vec<Week> {
+    let mut weights: BTreeMap<Date, (f64, usize)> = BTreeMap::new();
+    for entry in entries {
+        let (sum, count) = weights.entry(week_start(entry.date)).or_default();
+        *sum += entry.weight;
+        *count += 1;
+    }
+    let average =
+        |start| weights.get(&start).map(|(sum, count)| sum / *count as f64);
+
+    let mut days: BTreeMap<Date, BTreeMap<Date, f64>> = BTreeMap::new();
+    for (date, value) in daily {
+        // a later value for the same day replaces an earlier one
+        days.entry(week_start(*date))
+            .or_default()
+            .insert(*date, *value);
+    }
+    days.into_iter()
+        .map(|(start, days)| {
+            let next = start + Duration::weeks(1);
+            Week {
+                start,
+                days: days.len(),
+                average: days.values().sum::<f64>() / days.len() as f64,
+                change: average(next)
+                    .zip(average(start))
+                    .map(|(next, this)| next - this),
+            }
+        })
+        .collect()
+}
+
+/// Fit a line through the weekly changes against the averages, or `None` if
+/// fewer than [`MIN_WEEKS`] weeks have both or the averages never vary.
+pub fn fit(weeks: &[Week]) -> Option<Fit> {
+    let points: Vec<_> = weeks
+        .iter()
+        .filter_map(|w| Some((w.average, w.change?)))
+        .collect();
+    if points.len() < MIN_WEEKS {
+        return None;
+    }
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|p| p.0).sum::<f64>() / n;
+    let mean_y = points.iter().map(|p| p.1).sum::<f64>() / n;
+    let (mut sxx, mut syy, mut sxy) = (0.0, 0.0, 0.0);
+    for (x, y) in &points {
+        sxx += (x - mean_x).powi(2);
+        syy += (y - mean_y).powi(2);
+        sxy += (x - mean_x) * (y - mean_y);
+    }
+    if sxx == 0.0 {
+        return None;
+    }
+    let slope = sxy / sxx;
+    Some(Fit {
+        correlation: if syy == 0.0 {
+            0.0
+        } else {
+            sxy / (sxx * syy).sqrt()
+        },
+        steady: (slope > 0.0).then(|| mean_x - mean_y / slope),
+    })
+}
@@ -0,0 +1,66 @@
+//! Counts of the requests the server has handled since it started, for the
+//! `/status` page.
+
+use std::{
+    collections::BTreeMap,
+    sync::{Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+/// What the server has done since it started.
+pub struct Activity {
+    started: Instant,
+
+    /// The requests handled for each route
+    requests: Mutex<BTreeMap<String, u64>>,
+}
+
+impl Default for Activity {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            requests: Mutex::default(),
+        }
+    }
+}
+
+impl Activity {
+    /// Count a request for `route`.
+    pub fn record(&self, route: &str) {
+        let mut requests =
+            self.requests.lock().unwrap_or_else(PoisonError::into_inner);
+        match requests.get_mut(route) {
+            Some(count) => *count += 1,
+            None => {
+                requests.insert(route.to_owned(), 1);
+            }
+        }
+    }
+
+    /// How long the server has been running.
+    pub fn uptime(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// The requests handled for each route, by route.
+    pub fn requests(&self) -> BTreeMap<String, u64> {
+        self.requests
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
+
+/// Format `duration` in its two largest units, like `3d 4h` or `5m 12s`.
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    let (days, hours) = (seconds / 86400, seconds / 3600 % 24);
+    let (minutes, seconds) = (seconds / 60 % 60, seconds % 60);
+    if days > 0 {
+        format!("{days}d {hours}h")
+    } else if hours > 0 {
+        format!("{hours}h {minutes}m")
+    } else {
+        format!("{minutes}m {seconds}s")
+    }
+}
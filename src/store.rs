@@ -0,0 +1,932 @@
+use std::{
+    borrow::Cow,
+    collections::{hash_map, HashMap, HashSet},
+    fmt::Display,
+    fs::File,
+    io::{Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, PoisonError, RwLock, RwLockReadGuard,
+    },
+    time::SystemTime,
+};
+
+use time::{Date, Month};
+use tracing::{debug, info, instrument};
+
+use crate::{
+    binary,
+    crypto::{self, Cipher},
+    merge::{self, Policy, Report},
+    stats,
+};
+
+/// A single weight measurement.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub date: Date,
+    pub weight: f64,
+
+    /// Labels like `sick` or `travel` for filtering entries
+    pub tags: Vec<String>,
+}
+
+impl Entry {
+    /// Parse a line of the data file, which has the form
+    /// `YYYY-MM-DD weight [#tag ...]`.
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut sp = line.split_ascii_whitespace();
+        let date = parse_date(sp.next()?)?;
+        let weight = sp.next()?.parse().ok().filter(|w| valid_weight(*w))?;
+        let tags = sp
+            .map(|field| {
+                field
+                    .strip_prefix('#')
+                    .filter(|tag| valid_tag(tag))
+                    .map(str::to_owned)
+            })
+            .collect::<Option<_>>()?;
+        Some(Self { date, weight, tags })
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+}
+
+/// Formats the entry as a line of the data file.
+impl Display for Entry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {:.1}", format_date(self.date), self.weight)?;
+        for tag in &self.tags {
+            write!(f, " #{tag}")?;
+        }
+        Ok(())
+    }
+}
+
+/// How far, as a fraction, a weight can be from the previous entry's within
+/// [`JUMP_DAYS`] before [`Store::check`] reports it.
+const JUMP: f64 = 0.2;
+
+/// How many days apart two entries can be for [`JUMP`] to apply. Weight can
+/// change a lot over longer gaps, especially for children.
+const JUMP_DAYS: i64 = 30;
+
+/// The largest weight an entry can have. Pets weighed in grams can reach the
+/// tens of thousands, but anything this large is a typo.
+pub const WEIGHT_LIMIT: f64 = 1_000_000.0;
+
+/// Whether `weight` can be recorded in an entry: a finite, positive number
+/// below [`WEIGHT_LIMIT`]. Anything else would break the statistics and the
+/// graph's axes.
+pub fn valid_weight(weight: f64) -> bool {
+    weight.is_finite() && weight > 0.0 && weight < WEIGHT_LIMIT
+}
+
+/// Round `weight` to the one decimal place the data file keeps, so that an
+/// entry is the same before and after it is written.
+pub fn round_weight(weight: f64) -> f64 {
+    (weight * 10.0).round() / 10.0
+}
+
+/// Parse a weight typed by a person or exported by another app, which may
+/// use `,` as the decimal separator like `81,4`. Weights are always written
+/// with `.`, so the data file only ever has one. A `,` alongside a `.`, as in
+/// `1,234.5`, is rejected rather than read as a thousands separator.
+pub fn parse_weight(s: &str) -> Option<f64> {
+    let s = s.trim();
+    if s.contains('.') {
+        s.parse().ok()
+    } else {
+        s.replacen(',', ".", 1).parse().ok()
+    }
+}
+
+/// Whether `tag` can be attached to an entry. Tags appear in the data file and
+/// in URLs, so they are limited to lowercase ASCII letters, digits, `-`, and
+/// `_`.
+pub fn valid_tag(tag: &str) -> bool {
+    !tag.is_empty()
+        && tag.chars().all(|c| {
+            c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_'
+        })
+}
+
+/// Split user input like `sick, Travel` into tags, returning `None` if any of
+/// them is invalid. Tags are lowercased and duplicates are dropped.
+pub fn parse_tags(s: &str) -> Option<Vec<String>> {
+    let mut tags: Vec<String> = Vec::new();
+    for tag in s.split(|c: char| c == ',' || c.is_whitespace()) {
+        let tag = tag.trim_start_matches('#').to_lowercase();
+        if tag.is_empty() || tags.contains(&tag) {
+            continue;
+        }
+        if !valid_tag(&tag) {
+            return None;
+        }
+        tags.push(tag);
+    }
+    Some(tags)
+}
+
+/// Format `date` as `YYYY-MM-DD`, the format used in the data file and
+/// expected by the gnuplot script.
+pub fn format_date(date: Date) -> String {
+    format!(
+        "{}-{:02}-{:02}",
+        date.year(),
+        date.month() as u8,
+        date.day()
+    )
+}
+
+/// Parse a date in the `YYYY-MM-DD` format written by [`format_date`].
+pub fn parse_date(s: &str) -> Option<Date> {
+    let mut fields = s.split('-');
+    let year = fields.next()?.parse().ok()?;
+    let month: u8 = fields.next()?.parse().ok()?;
+    let day = fields.next()?.parse().ok()?;
+    if fields.next().is_some() {
+        return None;
+    }
+    Date::from_calendar_date(year, Month::try_from(month).ok()?, day).ok()
+}
+
+/// How the entries are laid out in the data file.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum DataFormat {
+    /// One entry per line, which can be edited by hand
+    #[default]
+    Text,
+
+    /// The compact format in [`crate::binary`], for long histories
+    Binary,
+}
+
+impl std::str::FromStr for DataFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "binary" => Ok(Self::Binary),
+            _ => Err(format!("unknown data format `{s}`")),
+        }
+    }
+}
+
+/// How many entries [`Store::iter_batched`] copies at a time.
+const BATCH_SIZE: usize = 1024;
+
+/// The weight entries along with the file they are persisted to.
+///
+/// A `Store` can be shared between threads. Any number of readers can look at
+/// the entries at once, while writers take turns with the file and then
+/// briefly lock the entries to update them.
+pub struct Store {
+    path: PathBuf,
+    file: Mutex<File>,
+    data: RwLock<Vec<Entry>>,
+
+    /// Incremented whenever the entries change, for caching things derived
+    /// from them.
+    version: AtomicU64,
+
+    /// What the data file looked like when it was last read or written, so
+    /// that it's only read again when it changes. Only changed while `file`
+    /// is locked.
+    stamp: Mutex<Option<Stamp>>,
+
+    /// The format of the data file. Text files are appended to, while
+    /// binary ones are rewritten in full on every change. Only changed while
+    /// `file` is locked.
+    format: Mutex<DataFormat>,
+
+    /// Set if the data file is encrypted. Encrypted files can't be appended
+    /// to, so they are rewritten in full on every change.
+    cipher: Option<Cipher>,
+}
+
+impl Store {
+    /// Open the data file at `path`, creating it if it doesn't exist, and
+    /// load its entries.
+    pub fn open(path: &Path) -> std::io::Result<Self> {
+        Self::open_with(path, None)
+    }
+
+    /// Like [`Store::open`], but the data file is encrypted with a key
+    /// derived from `passphrase`. An existing plaintext file is encrypted
+    /// when it is opened.
+    pub fn open_encrypted(
+        path: &Path,
+        passphrase: &str,
+    ) -> std::io::Result<Self> {
+        Self::open_with(path, Some(passphrase.into()))
+    }
+
+    /// Open the data file at `path` with the same encryption and format as
+    /// this store.
+    pub fn open_sibling(&self, path: &Path) -> std::io::Result<Self> {
+        let store = Self::open_with(path, self.passphrase())?;
+        store.set_format(self.format())?;
+        Ok(store)
+    }
+
+    /// The format of the data file.
+    pub fn format(&self) -> DataFormat {
+        *self.format.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Convert the data file to `format` if it isn't already.
+    #[instrument(skip(self), fields(path = %self.path.display()))]
+    pub fn set_format(&self, format: DataFormat) -> std::io::Result<()> {
+        let mut file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+        self.refresh(&mut file)?;
+        let mut current =
+            self.format.lock().unwrap_or_else(PoisonError::into_inner);
+        if *current == format {
+            return Ok(());
+        }
+        *current = format;
+        drop(current);
+        let data = self.entries().clone();
+        if !data.is_empty() {
+            info!("converting data file");
+        }
+        self.rewrite(&mut file, data)
+    }
+
+    /// The passphrase the data file is encrypted with, if it is.
+    pub fn passphrase(&self) -> Option<Arc<str>> {
+        self.cipher.as_ref().map(|c| Arc::clone(c.passphrase()))
+    }
+
+    #[instrument(skip_all, fields(path = %path.display()))]
+    fn open_with(
+        path: &Path,
+        passphrase: Option<Arc<str>>,
+    ) -> std::io::Result<Self> {
+        let mut file = File::options()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(path)?;
+        let contents = read(&mut file)?;
+        let cipher =
+            passphrase.map(|p| Cipher::new(p, &contents)).transpose()?;
+        let data = parse(&contents, cipher.as_ref())?;
+        debug!(entries = data.len(), "loaded data file");
+        let stamp = Stamp::of(&file).ok();
+        let format = data_format(&contents, cipher.as_ref())?;
+        let store = Self {
+            path: path.to_owned(),
+            file: Mutex::new(file),
+            data: RwLock::new(Vec::new()),
+            version: AtomicU64::new(0),
+            stamp: Mutex::new(stamp),
+            format: Mutex::new(format),
+            cipher,
+        };
+        if store.cipher.is_some() && !crypto::is_encrypted(&contents) {
+            if !contents.is_empty() {
+                info!("encrypting data file");
+            }
+            let mut file =
+                store.file.lock().unwrap_or_else(PoisonError::into_inner);
+            store.rewrite(&mut file, data)?;
+        } else {
+            store.set_data(data);
+        }
+        Ok(store)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Read access to the entries. Writes to the store wait until the
+    /// returned guard is dropped.
+    pub fn entries(&self) -> RwLockReadGuard<'_, Vec<Entry>> {
+        self.data.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Copies of the entries, taken a batch at a time so that going through
+    /// them slowly, like while sending them to a client, doesn't keep the
+    /// entries locked the whole time. Entries changed partway through may be
+    /// skipped or seen twice.
+    pub fn iter_batched(&self) -> impl Iterator<Item = Entry> + '_ {
+        let mut next = 0;
+        std::iter::from_fn(move || {
+            let batch: Vec<_> = self
+                .entries()
+                .get(next..)?
+                .iter()
+                .take(BATCH_SIZE)
+                .cloned()
+                .collect();
+            next += batch.len();
+            (!batch.is_empty()).then_some(batch)
+        })
+        .flatten()
+    }
+
+    /// The version of the entries, which changes whenever they do.
+    pub fn version(&self) -> u64 {
+        self.version.load(Ordering::Acquire)
+    }
+
+    /// Pick up any changes made to the data file by something else, like a
+    /// text editor or `git pull`. Reading the entries never touches the
+    /// disk, so this is left to [`crate::watch`] and to writes.
+    #[instrument(skip(self), fields(path = %self.path.display()))]
+    pub fn update(&self) -> std::io::Result<()> {
+        let mut file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+        self.refresh(&mut file)
+    }
+
+    /// Bring the in-memory entries up to date with the locked data `file`.
+    /// Nothing is read if the file is unchanged, and only the new lines are
+    /// read if it was appended to. Otherwise the file is reopened in case it
+    /// was replaced and read in full.
+    fn refresh(&self, file: &mut File) -> std::io::Result<()> {
+        let mut stamp =
+            self.stamp.lock().unwrap_or_else(PoisonError::into_inner);
+        let current = std::fs::metadata(&self.path).ok().map(Stamp::new);
+        if current.is_some() && current == *stamp {
+            return Ok(());
+        }
+        if let (Some(old), Some(current)) = (*stamp, current) {
+            if self.appends() && current.extends(&old) {
+                file.seek(SeekFrom::Start(old.len))?;
+                let mut contents = Vec::new();
+                file.read_to_end(&mut contents)?;
+                // leave a line that's still being written for next time
+                let end = contents.iter().rposition(|&b| b == b'\n');
+                let contents = &contents[..end.map_or(0, |i| i + 1)];
+                let new = parse(contents, None)?;
+                debug!(entries = new.len(), "read appended entries");
+                if !new.is_empty() {
+                    self.data
+                        .write()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .extend(new);
+                    self.version.fetch_add(1, Ordering::AcqRel);
+                }
+                *stamp = Some(Stamp {
+                    len: old.len + contents.len() as u64,
+                    ..current
+                });
+                return Ok(());
+            }
+        }
+        *file = File::options()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&self.path)?;
+        let contents = read(file)?;
+        let data = parse(&contents, self.cipher.as_ref())?;
+        debug!(entries = data.len(), "reloaded data file");
+        // a file replaced with one in the other format is written back in
+        // that format rather than appended to in the wrong one
+        if !contents.is_empty() {
+            *self.format.lock().unwrap_or_else(PoisonError::into_inner) =
+                data_format(&contents, self.cipher.as_ref())?;
+        }
+        if *self.entries() != data {
+            self.set_data(data);
+        }
+        *stamp = Stamp::of(file).ok();
+        Ok(())
+    }
+
+    /// Apply `change` to the entries and save them, returning what `change`
+    /// returns. Every change to the entries goes through here, so the
+    /// in-memory entries and the data file always agree. Entries added to
+    /// the end of a plaintext file are appended, and anything else rewrites
+    /// the file.
+    fn write<T>(
+        &self,
+        change: impl FnOnce(&mut Vec<Entry>) -> T,
+    ) -> std::io::Result<T> {
+        let mut file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+        self.refresh(&mut file)?;
+        let mut data = self.entries().clone();
+        let result = change(&mut data);
+        let appended = {
+            let old = self.entries();
+            if data == *old {
+                return Ok(result);
+            }
+            (self.appends() && data.starts_with(&old)).then(|| old.len())
+        };
+        match appended {
+            Some(start) => {
+                let lines: String =
+                    data[start..].iter().map(|e| format!("{e}\n")).collect();
+                file.write_all(lines.as_bytes())?;
+                file.sync_data()?;
+                // if something else appended to the file since it was last
+                // read, leave the stamp alone so that the next refresh reads
+                // it in full
+                let mut stamp =
+                    self.stamp.lock().unwrap_or_else(PoisonError::into_inner);
+                if let (Some(old), Ok(new)) = (*stamp, Stamp::of(&file)) {
+                    if new.inode == old.inode
+                        && new.len == old.len + lines.len() as u64
+                    {
+                        *stamp = Some(new);
+                    }
+                }
+                drop(stamp);
+                self.set_data(data);
+            }
+            None => self.rewrite(&mut file, data)?,
+        }
+        Ok(result)
+    }
+
+    /// Append `entry` to the entries.
+    #[instrument(skip(self), fields(path = %self.path.display()))]
+    pub fn append(&self, entry: Entry) -> std::io::Result<()> {
+        self.write(|data| data.push(entry))?;
+        debug!("appended entry");
+        Ok(())
+    }
+
+    /// Merge the entries in `new` into the existing ones, resolving dates
+    /// that have different entries in each by `policy`, and return what
+    /// changed. Since the new entries can be older than the existing ones,
+    /// the entries are kept in date order.
+    #[instrument(skip(self, new), fields(path = %self.path.display()))]
+    pub fn merge(
+        &self,
+        new: Vec<Entry>,
+        policy: Policy,
+    ) -> std::io::Result<Report> {
+        let report = self.write(|data| {
+            let (merged, report) = merge::merge(data.clone(), new, policy);
+            if !report.is_empty() {
+                *data = merged;
+            }
+            report
+        })?;
+        debug!(
+            added = report.added.len(),
+            removed = report.removed.len(),
+            "merged entries"
+        );
+        Ok(report)
+    }
+
+    /// Replace every entry with `data`, such as ones restored from a backup,
+    /// returning the entries that were there before.
+    #[instrument(skip_all, fields(path = %self.path.display()))]
+    pub fn reset(&self, mut data: Vec<Entry>) -> std::io::Result<Vec<Entry>> {
+        data.sort_by_key(|e| e.date);
+        let len = data.len();
+        let old = self.write(|old| std::mem::replace(old, data))?;
+        debug!(len, "reset entries");
+        Ok(old)
+    }
+
+    /// Record `entry` in place of any entries already on the same date, or
+    /// append it if there aren't any, returning the entries it replaced.
+    #[instrument(skip(self), fields(path = %self.path.display()))]
+    pub fn replace(&self, entry: Entry) -> std::io::Result<Vec<Entry>> {
+        let old = self.write(|data| {
+            let Some(i) = data.iter().position(|e| e.date == entry.date) else {
+                data.push(entry);
+                return Vec::new();
+            };
+            let (old, mut kept): (Vec<_>, Vec<_>) = std::mem::take(data)
+                .into_iter()
+                .partition(|e| e.date == entry.date);
+            kept.insert(i, entry);
+            *data = kept;
+            old
+        })?;
+        debug!("replaced entry");
+        Ok(old)
+    }
+
+    /// Put `new` in place of the first entry equal to `old`, which should be
+    /// on the same date to keep the entries in order, returning whether there
+    /// was one.
+    #[instrument(skip(self), fields(path = %self.path.display()))]
+    pub fn swap(&self, old: &Entry, new: Entry) -> std::io::Result<bool> {
+        let swapped = self.write(|data| {
+            let i = data.iter().position(|e| e == old)?;
+            data[i] = new;
+            Some(())
+        })?;
+        if swapped.is_some() {
+            debug!("swapped entry");
+        }
+        Ok(swapped.is_some())
+    }
+
+    /// Add `entry` in date order, even if its date already has an entry,
+    /// such as one taken back out of the trash.
+    #[instrument(skip(self), fields(path = %self.path.display()))]
+    pub fn insert(&self, entry: Entry) -> std::io::Result<()> {
+        self.write(|data| {
+            let i = data.partition_point(|e| e.date <= entry.date);
+            data.insert(i, entry);
+        })?;
+        debug!("inserted entry");
+        Ok(())
+    }
+
+    /// Remove the first entry equal to `entry`, returning whether there was
+    /// one.
+    #[instrument(skip(self), fields(path = %self.path.display()))]
+    pub fn remove(&self, entry: &Entry) -> std::io::Result<bool> {
+        let removed = self.write(|data| {
+            let i = data.iter().position(|e| e == entry)?;
+            Some(data.remove(i))
+        })?;
+        if removed.is_some() {
+            debug!("removed entry");
+        }
+        Ok(removed.is_some())
+    }
+
+    /// Rewrite the data file in date order without any lines that can't be
+    /// read or that repeat an earlier entry exactly, returning how many
+    /// unreadable lines were dropped and the repeated entries that were
+    /// removed.
+    #[instrument(skip_all, fields(path = %self.path.display()))]
+    pub fn compact(&self) -> std::io::Result<(usize, Vec<Entry>)> {
+        let mut file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+        let text = text(&read(&mut file)?, self.cipher.as_ref())?;
+        let lines = text.lines().filter(|l| !l.trim().is_empty()).count();
+        let mut data = Vec::new();
+        let mut repeated = Vec::new();
+        let mut seen = HashSet::new();
+        let mut read = 0;
+        for entry in text.lines().flat_map(Entry::parse) {
+            read += 1;
+            if seen.insert(entry.to_string()) {
+                data.push(entry);
+            } else {
+                repeated.push(entry);
+            }
+        }
+        let dropped = lines - read;
+        data.sort_by_key(|e| e.date);
+        self.rewrite(&mut file, data)?;
+        debug!(dropped, repeated = repeated.len(), "compacted data file");
+        Ok((dropped, repeated))
+    }
+
+    /// Look for problems in the data file, returning a description of each
+    /// one. Lines that can't be read are otherwise skipped silently. Besides
+    /// those, entries out of date order, entries that repeat an earlier one
+    /// exactly, and weights that are far from the previous entry's are
+    /// reported, the last because they are usually typos or a different
+    /// unit.
+    pub fn check(&self) -> std::io::Result<Vec<String>> {
+        let mut file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+        let text = text(&read(&mut file)?, self.cipher.as_ref())?;
+        let mut problems = Vec::new();
+        let mut last = None;
+        let mut seen = HashMap::new();
+        let mut previous: Option<Entry> = None;
+        for (i, line) in text.lines().enumerate() {
+            let n = i + 1;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let Some(entry) = Entry::parse(line) else {
+                let weight = line.split_ascii_whitespace().nth(1);
+                match weight.and_then(|w| w.parse::<f64>().ok()) {
+                    Some(weight) if !valid_weight(weight) => {
+                        problems.push(format!(
+                            "line {n}: weight {weight} isn't a positive \
+                             number below {WEIGHT_LIMIT}"
+                        ))
+                    }
+                    _ => problems
+                        .push(format!("line {n}: unreadable entry `{line}`")),
+                }
+                continue;
+            };
+            if last.is_some_and(|last| entry.date < last) {
+                problems.push(format!(
+                    "line {n}: {} is out of date order",
+                    format_date(entry.date)
+                ));
+            }
+            last = last.max(Some(entry.date));
+            match seen.entry(entry.to_string()) {
+                hash_map::Entry::Occupied(first) => {
+                    problems.push(format!(
+                        "line {n}: repeats the entry on line {}",
+                        first.get()
+                    ));
+                    continue;
+                }
+                hash_map::Entry::Vacant(slot) => {
+                    slot.insert(n);
+                }
+            }
+            if let Some(prev) = previous.as_ref().filter(|prev| {
+                (entry.date - prev.date).whole_days().abs() <= JUMP_DAYS
+                    && (entry.weight - prev.weight).abs() > JUMP * prev.weight
+            }) {
+                problems.push(format!(
+                    "line {n}: weight {:.1} is more than {}% away from {:.1} \
+                     on {}",
+                    entry.weight,
+                    JUMP * 100.0,
+                    prev.weight,
+                    format_date(prev.date)
+                ));
+            }
+            previous = Some(entry);
+        }
+        Ok(problems)
+    }
+
+    /// Whether new entries can be appended to the data file, rather than
+    /// having to rewrite it.
+    fn appends(&self) -> bool {
+        self.cipher.is_none() && self.format() == DataFormat::Text
+    }
+
+    /// Replace the contents of the data file and the in-memory entries with
+    /// `data`. `file` is the locked handle to the data file, which is
+    /// reopened afterwards.
+    fn rewrite(
+        &self,
+        file: &mut File,
+        data: Vec<Entry>,
+    ) -> std::io::Result<()> {
+        let contents = match self.format() {
+            DataFormat::Text => data
+                .iter()
+                .map(|e| format!("{e}\n"))
+                .collect::<String>()
+                .into(),
+            DataFormat::Binary => binary::encode(&data)?,
+        };
+        let contents = match &self.cipher {
+            Some(cipher) => cipher.encrypt(&contents)?,
+            None => contents,
+        };
+        replace_file(&self.path, &contents)?;
+        *file = File::options().read(true).append(true).open(&self.path)?;
+        *self.stamp.lock().unwrap_or_else(PoisonError::into_inner) =
+            Stamp::of(file).ok();
+        self.set_data(data);
+        Ok(())
+    }
+
+    /// Replace the in-memory entries with `data`.
+    fn set_data(&self, data: Vec<Entry>) {
+        *self.data.write().unwrap_or_else(PoisonError::into_inner) = data;
+        self.version.fetch_add(1, Ordering::AcqRel);
+    }
+}
+
+/// Somewhere one person's weight entries are kept, for tools built on this
+/// library. [`Store`] keeps them in a data file in the server's format,
+/// while a `Vec<Entry>` keeps them in memory, like entries read with
+/// [`parse_data`] or fetched from elsewhere. Either way, the latest weight
+/// and the trend come from the same [`stats`] the server shows.
+pub trait WeightStore {
+    /// Copies of every entry, in the order they were recorded.
+    fn all_entries(&self) -> Vec<Entry>;
+
+    /// Record `entry` after the existing entries.
+    fn add(&mut self, entry: Entry) -> std::io::Result<()>;
+
+    /// Remove the first entry equal to `entry`, returning whether there was
+    /// one.
+    fn remove(&mut self, entry: &Entry) -> std::io::Result<bool>;
+
+    /// The entry with the latest date, or the last one recorded if several
+    /// share it.
+    fn latest(&self) -> Option<Entry> {
+        self.all_entries().into_iter().max_by_key(|e| e.date)
+    }
+
+    /// The average weight over the `days` days up to the latest entry, as
+    /// in the trend on the server's pages and graphs.
+    fn trend(&self, days: i64) -> Option<f64> {
+        let entries = self.all_entries();
+        let latest = entries.iter().map(|e| e.date).max()?;
+        stats::rolling_average(&entries, days).get(&latest).copied()
+    }
+}
+
+impl WeightStore for Store {
+    fn all_entries(&self) -> Vec<Entry> {
+        self.entries().clone()
+    }
+
+    fn add(&mut self, entry: Entry) -> std::io::Result<()> {
+        self.append(entry)
+    }
+
+    fn remove(&mut self, entry: &Entry) -> std::io::Result<bool> {
+        Store::remove(self, entry)
+    }
+}
+
+impl WeightStore for Vec<Entry> {
+    fn all_entries(&self) -> Vec<Entry> {
+        self.clone()
+    }
+
+    fn add(&mut self, entry: Entry) -> std::io::Result<()> {
+        self.push(entry);
+        Ok(())
+    }
+
+    fn remove(&mut self, entry: &Entry) -> std::io::Result<bool> {
+        let Some(i) = self.iter().position(|e| e == entry) else {
+            return Ok(false);
+        };
+        Vec::remove(self, i);
+        Ok(true)
+    }
+}
+
+/// The entries in the `contents` of an unencrypted data file in either
+/// format, for reading a copy of one without opening it as a [`Store`].
+pub fn parse_data(contents: &[u8]) -> std::io::Result<Vec<Entry>> {
+    parse(contents, None)
+}
+
+/// The size, modification time, and inode of a data file, which change
+/// whenever it is written to or replaced.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Stamp {
+    len: u64,
+    modified: Option<SystemTime>,
+    inode: u64,
+}
+
+impl Stamp {
+    fn new(metadata: std::fs::Metadata) -> Self {
+        #[cfg(unix)]
+        let inode = std::os::unix::fs::MetadataExt::ino(&metadata);
+        #[cfg(not(unix))]
+        let inode = 0;
+        Self {
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+            inode,
+        }
+    }
+
+    fn of(file: &File) -> std::io::Result<Self> {
+        file.metadata().map(Self::new)
+    }
+
+    /// Whether this is the same file as `old` with more appended to it.
+    fn extends(&self, old: &Self) -> bool {
+        self.inode == old.inode && self.len > old.len
+    }
+}
+
+/// Replace the file at `path` with `contents`. They are written to a
+/// temporary file first so that a failure partway through doesn't lose the
+/// existing contents, and synced to disk before and after the temporary file
+/// is renamed so that neither version is lost in a power cut.
+pub fn replace_file(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut tmp = path.to_owned().into_os_string();
+    tmp.push(".tmp");
+    let mut file = File::create(&tmp)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    std::fs::rename(&tmp, path)?;
+    // the rename itself is only durable once the directory is synced, which
+    // only works on Unix
+    #[cfg(unix)]
+    if let Some(dir) = path.parent() {
+        let dir = if dir.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            dir
+        };
+        File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
+
+fn read(file: &mut File) -> std::io::Result<Vec<u8>> {
+    file.rewind()?;
+    let mut contents = Vec::new();
+    file.read_to_end(&mut contents)?;
+    Ok(contents)
+}
+
+/// Parse the entries from the `contents` of a data file, decrypting them
+/// with `cipher` if the file is encrypted. A plaintext file is read as is
+/// even with a cipher, and is encrypted the next time it is written.
+fn parse(
+    contents: &[u8],
+    cipher: Option<&Cipher>,
+) -> std::io::Result<Vec<Entry>> {
+    let contents = decrypt(contents, cipher)?;
+    if binary::is_binary(&contents) {
+        return binary::decode(&contents);
+    }
+    Ok(String::from_utf8_lossy(&contents)
+        .lines()
+        .flat_map(Entry::parse)
+        .collect())
+}
+
+/// The format of a data file with `contents`.
+fn data_format(
+    contents: &[u8],
+    cipher: Option<&Cipher>,
+) -> std::io::Result<DataFormat> {
+    Ok(match binary::is_binary(&decrypt(contents, cipher)?) {
+        true => DataFormat::Binary,
+        false => DataFormat::Text,
+    })
+}
+
+/// The text of a data file with `contents`, decrypted with `cipher` if the
+/// file is encrypted. A binary data file is converted to the text format.
+pub(crate) fn text(
+    contents: &[u8],
+    cipher: Option<&Cipher>,
+) -> std::io::Result<String> {
+    let contents = decrypt(contents, cipher)?;
+    if binary::is_binary(&contents) {
+        return Ok(binary::decode(&contents)?
+            .iter()
+            .map(|e| format!("{e}\n"))
+            .collect());
+    }
+    Ok(String::from_utf8_lossy(&contents).into_owned())
+}
+
+/// The `contents` of a file, decrypted with `cipher` if it is encrypted.
+fn decrypt<'a>(
+    contents: &'a [u8],
+    cipher: Option<&Cipher>,
+) -> std::io::Result<Cow<'a, [u8]>> {
+    if !crypto::is_encrypted(contents) {
+        return Ok(Cow::Borrowed(contents));
+    }
+    let Some(cipher) = cipher else {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "the data file is encrypted, but no encryption_key_file is \
+             configured",
+        ));
+    };
+    Ok(Cow::Owned(cipher.decrypt(contents)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decimal_point() {
+        assert_eq!(parse_weight("80.5"), Some(80.5));
+        assert_eq!(parse_weight(" 80.5 "), Some(80.5));
+        assert_eq!(parse_weight("80"), Some(80.0));
+    }
+
+    #[test]
+    fn decimal_comma() {
+        assert_eq!(parse_weight("80,5"), Some(80.5));
+        assert_eq!(parse_weight("81,4\n"), Some(81.4));
+    }
+
+    #[test]
+    fn thousands_separator() {
+        assert_eq!(parse_weight("1,234.5"), None);
+        assert_eq!(parse_weight("1.234,5"), None);
+        assert_eq!(parse_weight("1,234,5"), None);
+    }
+
+    #[test]
+    fn bad_weight() {
+        assert_eq!(parse_weight(""), None);
+        assert_eq!(parse_weight("heavy"), None);
+        assert_eq!(parse_weight("80 kg"), None);
+        assert_eq!(parse_weight("80.5.1"), None);
+    }
+
+    #[test]
+    fn invalid_weight() {
+        // these parse, and are turned away by `valid_weight`
+        assert!(!valid_weight(parse_weight("nan").unwrap()));
+        assert!(!valid_weight(parse_weight("-80,5").unwrap()));
+        assert!(!valid_weight(0.0));
+        assert!(!valid_weight(f64::NAN));
+        assert!(!valid_weight(WEIGHT_LIMIT));
+    }
+}
@@ -0,0 +1,97 @@
+//! Notifications for running under systemd as a `Type=notify` service. The
+//! service manager passes a socket in `NOTIFY_SOCKET` for reporting when the
+//! server is ready, and `WATCHDOG_USEC` if it expects to hear that the server
+//! is still working at least that often.
+
+use std::{
+    io::{self, Read, Write},
+    net::TcpStream,
+    os::unix::net::{SocketAddr, UnixDatagram},
+    time::Duration,
+};
+
+use tracing::{debug, error, warn};
+
+/// Send `state`, like `READY=1`, to the service manager. Does nothing when
+/// not running under systemd.
+pub fn notify(state: &str) -> io::Result<()> {
+    let Some(path) = std::env::var_os("NOTIFY_SOCKET") else {
+        return Ok(());
+    };
+    let addr = match path.as_encoded_bytes().strip_prefix(b"@") {
+        #[cfg(target_os = "linux")]
+        Some(name) => {
+            use std::os::linux::net::SocketAddrExt;
+            SocketAddr::from_abstract_name(name)?
+        }
+        #[cfg(not(target_os = "linux"))]
+        Some(_) => {
+            return Err(io::Error::other(
+                "abstract notification sockets are only supported on Linux",
+            ))
+        }
+        None => SocketAddr::from_pathname(&path)?,
+    };
+    UnixDatagram::unbound()?.send_to_addr(state.as_bytes(), &addr)?;
+    debug!("notified systemd: {state}");
+    Ok(())
+}
+
+/// Tell the service manager that the server is listening on `port`, and
+/// start sending watchdog notifications if it asked for them.
+pub fn ready(port: u16) {
+    if let Err(e) = notify("READY=1") {
+        error!("failed to notify systemd: {e}");
+    }
+    if let Some(interval) = watchdog_interval() {
+        watchdog(port, interval);
+    }
+}
+
+/// How often the service manager expects a watchdog notification, if at
+/// all.
+fn watchdog_interval() -> Option<Duration> {
+    let usec: u64 = std::env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+    // the variables are meant for the main process, not its children
+    if let Ok(pid) = std::env::var("WATCHDOG_PID") {
+        if pid.parse() != Ok(std::process::id()) {
+            return None;
+        }
+    }
+    (usec > 0).then(|| Duration::from_micros(usec))
+}
+
+/// Spawn a thread that requests a page from the server on `port` twice per
+/// `interval` and notifies the watchdog whenever it gets an answer, so that
+/// systemd restarts the server if it stops responding.
+fn watchdog(port: u16, interval: Duration) {
+    let period = interval / 2;
+    std::thread::spawn(move || loop {
+        std::thread::sleep(period);
+        match check(port, period) {
+            Ok(()) => {
+                if let Err(e) = notify("WATCHDOG=1") {
+                    error!("failed to notify systemd watchdog: {e}");
+                }
+            }
+            Err(e) => warn!("server failed its health check: {e}"),
+        }
+    });
+}
+
+/// Request `/health` from the server on `port`, failing if it doesn't
+/// respond within `timeout`. Any status counts, including the 403 for a
+/// server whose `allow` setting leaves out the loopback address.
+fn check(port: u16, timeout: Duration) -> io::Result<()> {
+    let mut stream =
+        TcpStream::connect_timeout(&([127, 0, 0, 1], port).into(), timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+    stream.write_all(b"GET /health HTTP/1.1\r\nHost: localhost\r\n\r\n")?;
+    let mut status = [0; 12];
+    stream.read_exact(&mut status)?;
+    if !status.starts_with(b"HTTP/1.1 ") {
+        return Err(io::Error::other("invalid response"));
+    }
+    Ok(())
+}
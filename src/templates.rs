@@ -0,0 +1,122 @@
+//! The HTML templates for each page. These are embedded in the binary, but a
+//! file with the same name in the `templates` directory next to the config
+//! file takes precedence, so the pages can be customized without rebuilding.
+
+use std::{collections::HashMap, path::Path};
+
+use tracing::{debug, warn};
+
+/// The embedded templates, by file name.
+const EMBEDDED: &[(&str, &str)] = &[
+    ("admin.html", include_str!("../templates/admin.html")),
+    ("audit.html", include_str!("../templates/audit.html")),
+    ("calories.html", include_str!("../templates/calories.html")),
+    ("chart.html", include_str!("../templates/chart.html")),
+    ("confirm.html", include_str!("../templates/confirm.html")),
+    ("cycle.html", include_str!("../templates/cycle.html")),
+    ("error.html", include_str!("../templates/error.html")),
+    ("exercise.html", include_str!("../templates/exercise.html")),
+    ("graphs.html", include_str!("../templates/graphs.html")),
+    ("growth.html", include_str!("../templates/growth.html")),
+    ("history.html", include_str!("../templates/history.html")),
+    ("import.html", include_str!("../templates/import.html")),
+    ("index.html", include_str!("../templates/index.html")),
+    (
+        "maintenance.html",
+        include_str!("../templates/maintenance.html"),
+    ),
+    (
+        "measurements.html",
+        include_str!("../templates/measurements.html"),
+    ),
+    (
+        "medications.html",
+        include_str!("../templates/medications.html"),
+    ),
+    ("monthly.html", include_str!("../templates/monthly.html")),
+    ("photos.html", include_str!("../templates/photos.html")),
+    ("qr.html", include_str!("../templates/qr.html")),
+    ("report.html", include_str!("../templates/report.html")),
+    ("restore.html", include_str!("../templates/restore.html")),
+    ("sleep.html", include_str!("../templates/sleep.html")),
+    ("status.html", include_str!("../templates/status.html")),
+    ("tags.html", include_str!("../templates/tags.html")),
+    ("tokens.html", include_str!("../templates/tokens.html")),
+    ("trash.html", include_str!("../templates/trash.html")),
+    ("versions.html", include_str!("../templates/versions.html")),
+    ("withings.html", include_str!("../templates/withings.html")),
+];
+
+#[derive(Default)]
+pub struct Templates {
+    overrides: HashMap<&'static str, String>,
+}
+
+impl Templates {
+    /// Load any overrides for the embedded templates from `dir`.
+    pub fn load(dir: &Path) -> Self {
+        let mut overrides = HashMap::new();
+        for (name, _) in EMBEDDED {
+            let path = dir.join(name);
+            match std::fs::read_to_string(&path) {
+                Ok(tmpl) => {
+                    debug!("using template override {}", path.display());
+                    overrides.insert(*name, tmpl);
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => {
+                    warn!("failed to read template {}: {e}", path.display())
+                }
+            }
+        }
+        Self { overrides }
+    }
+
+    /// The template called `name`, which must be one of the embedded
+    /// templates.
+    pub fn get(&self, name: &str) -> &str {
+        if let Some(tmpl) = self.overrides.get(name) {
+            return tmpl;
+        }
+        EMBEDDED
+            .iter()
+            .find_map(|(n, tmpl)| (*n == name).then_some(*tmpl))
+            .unwrap_or_else(|| panic!("no template named {name}"))
+    }
+}
+
+/// How tightly the tables on each page are laid out.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Density {
+    Compact,
+
+    /// The browser's own spacing
+    #[default]
+    Normal,
+
+    Comfortable,
+}
+
+impl Density {
+    /// The CSS padding of table cells at this density.
+    pub fn cell_padding(self) -> &'static str {
+        match self {
+            Self::Compact => "0 0.25em",
+            Self::Normal => "1px",
+            Self::Comfortable => "0.4em 0.8em",
+        }
+    }
+}
+
+impl std::str::FromStr for Density {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "compact" => Ok(Self::Compact),
+            "normal" => Ok(Self::Normal),
+            "comfortable" => Ok(Self::Comfortable),
+            _ => Err(format!("unknown density `{s}`")),
+        }
+    }
+}
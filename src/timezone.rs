@@ -0,0 +1,86 @@
+use std::str::FromStr;
+
+use time::{Date, OffsetDateTime, PrimitiveDateTime, Time, UtcOffset};
+use time_tz::{timezones, OffsetDateTimeExt, PrimitiveDateTimeExt, Tz};
+
+/// The timezone used to decide which day it is when stamping entries and
+/// drawing the graph.
+#[derive(Clone, Copy)]
+pub enum Timezone {
+    /// A constant offset from UTC
+    Fixed(UtcOffset),
+
+    /// A zone from the IANA database, like `Europe/Berlin`, which follows
+    /// daylight saving time changes
+    Named(&'static Tz),
+}
+
+impl Timezone {
+    /// The current time in this timezone.
+    pub fn now(&self) -> OffsetDateTime {
+        self.convert(OffsetDateTime::now_utc())
+    }
+
+    /// The same instant as `time`, in this timezone.
+    pub fn convert(&self, time: OffsetDateTime) -> OffsetDateTime {
+        match self {
+            Self::Fixed(offset) => time.to_offset(*offset),
+            Self::Named(tz) => time.to_timezone(*tz),
+        }
+    }
+
+    /// The current date in this timezone.
+    pub fn today(&self) -> Date {
+        self.now().date()
+    }
+
+    /// The instant when it is `time` on `date` in this timezone. Times skipped
+    /// by a daylight saving change are taken as being in the earlier offset.
+    pub fn at(&self, date: Date, time: Time) -> OffsetDateTime {
+        let local = PrimitiveDateTime::new(date, time);
+        match self {
+            Self::Fixed(offset) => local.assume_offset(*offset),
+            Self::Named(tz) => local
+                .assume_timezone(*tz)
+                .take_first()
+                .unwrap_or_else(|| local.assume_timezone_utc(*tz)),
+        }
+    }
+}
+
+impl FromStr for Timezone {
+    type Err = String;
+
+    /// Parse `UTC`, a fixed offset like `+02:00` or `-0530`, or an IANA
+    /// timezone name.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("utc") || s == "Z" {
+            return Ok(Self::Fixed(UtcOffset::UTC));
+        }
+        if s.starts_with(['+', '-']) {
+            return parse_offset(s).map(Self::Fixed).ok_or_else(|| {
+                format!("invalid UTC offset `{s}`, expected like +02:00")
+            });
+        }
+        timezones::get_by_name(s)
+            .map(Self::Named)
+            .ok_or_else(|| format!("unknown timezone `{s}`"))
+    }
+}
+
+/// Parse an offset like `+02`, `+02:00`, or `-0530`.
+fn parse_offset(s: &str) -> Option<UtcOffset> {
+    let sign = if s.starts_with('-') { -1 } else { 1 };
+    let digits = s[1..].replace(':', "");
+    if !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let (hours, minutes) = match digits.len() {
+        2 => (&digits[..], "0"),
+        4 => (&digits[..2], &digits[2..]),
+        _ => return None,
+    };
+    let hours: i8 = hours.parse().ok()?;
+    let minutes: i8 = minutes.parse().ok()?;
+    UtcOffset::from_hms(sign * hours, sign * minutes, 0).ok()
+}
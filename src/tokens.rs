@@ -0,0 +1,228 @@
+//! API tokens, so that scripts and shortcuts can use the administrative pages
+//! without the admin password, each with its own credential that can be
+//! revoked on its own. Tokens look like `ww_<id>_<secret>` and are sent in an
+//! `Authorization: Bearer` header. Only a hash of each token is kept, in a
+//! `tokens` file next to the config file with lines of the form
+//! `id hash YYYY-MM-DD label`.
+//!
+//! Quick-log tokens are narrower: each one can only record weights for one
+//! profile, but it goes in the URL itself, like `/log/wl_<id>_<secret>?w=81.4`,
+//! for devices that can't send headers. They are kept in a `log-tokens` file
+//! with the profile (`-` for the default) after the creation date.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::{Mutex, PoisonError},
+};
+
+use blake2::{Blake2s256, Digest};
+use chacha20poly1305::aead::Generate;
+use time::Date;
+use tracing::{debug, instrument};
+
+use crate::store::{format_date, parse_date};
+
+const PREFIX: &str = "ww_";
+const LOG_PREFIX: &str = "wl_";
+
+/// A token without its secret.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ApiToken {
+    /// Identifies the token for revoking it
+    pub id: String,
+
+    /// The hex-encoded hash of the whole token
+    hash: String,
+
+    pub created: Date,
+
+    /// The profile a quick-log token records weights for, `None` for the
+    /// default profile and for API tokens
+    pub profile: Option<String>,
+
+    /// What the token is for, like `phone shortcut`
+    pub label: String,
+}
+
+impl ApiToken {
+    /// Parse a line of a tokens file, which has a profile field if
+    /// `profiles` is set.
+    fn parse(line: &str, profiles: bool) -> Option<Self> {
+        let mut sp = line.trim().splitn(if profiles { 5 } else { 4 }, ' ');
+        let id = sp.next()?.to_owned();
+        let hash = sp.next()?.to_owned();
+        let created = parse_date(sp.next()?)?;
+        let profile = match profiles {
+            true => match sp.next()? {
+                "-" => None,
+                name => Some(name.to_owned()),
+            },
+            false => None,
+        };
+        let label = sp.next().unwrap_or_default().to_owned();
+        Some(Self {
+            id,
+            hash,
+            created,
+            profile,
+            label,
+        })
+    }
+}
+
+/// The tokens file. It is read again on every use so that tokens created or
+/// revoked from the command line take effect while the server is running.
+pub struct Tokens {
+    path: PathBuf,
+
+    /// What every token starts with, which tells the kinds apart
+    prefix: &'static str,
+
+    /// Whether each token is for one profile, as quick-log tokens are
+    profiles: bool,
+
+    /// Held while the file is being rewritten
+    file: Mutex<()>,
+}
+
+impl Tokens {
+    /// The tokens in the `tokens` file next to `config_file`, which is
+    /// created along with the first token.
+    pub fn new(config_file: &Path) -> Self {
+        Self {
+            path: config_file.parent().unwrap_or(Path::new("")).join("tokens"),
+            prefix: PREFIX,
+            profiles: false,
+            file: Mutex::default(),
+        }
+    }
+
+    /// The quick-log tokens in the `log-tokens` file next to `config_file`.
+    pub fn quick_log(config_file: &Path) -> Self {
+        Self {
+            path: config_file
+                .parent()
+                .unwrap_or(Path::new(""))
+                .join("log-tokens"),
+            prefix: LOG_PREFIX,
+            profiles: true,
+            file: Mutex::default(),
+        }
+    }
+
+    /// Every token, oldest first.
+    pub fn list(&self) -> io::Result<Vec<ApiToken>> {
+        match std::fs::read_to_string(&self.path) {
+            Ok(contents) => Ok(contents
+                .lines()
+                .filter_map(|line| ApiToken::parse(line, self.profiles))
+                .collect()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Create a token labeled `label`, which should be checked with
+    /// [`valid_label`], on `today`, for recording weights for `profile` if
+    /// these are quick-log tokens. Returns it along with the token itself,
+    /// which can't be recovered later.
+    #[instrument(skip(self))]
+    pub fn create(
+        &self,
+        label: &str,
+        profile: Option<&str>,
+        today: Date,
+    ) -> io::Result<(ApiToken, String)> {
+        let _file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut tokens = self.list()?;
+        let id = hex(&<[u8; 4]>::generate());
+        let secret = hex(&<[u8; 32]>::generate());
+        let token = format!("{}{id}_{secret}", self.prefix);
+        let api_token = ApiToken {
+            id,
+            hash: hash(&token),
+            created: today,
+            profile: profile.filter(|_| self.profiles).map(str::to_owned),
+            label: label.to_owned(),
+        };
+        tokens.push(api_token.clone());
+        self.write(&tokens)?;
+        debug!(id = api_token.id, "created API token");
+        Ok((api_token, token))
+    }
+
+    /// Revoke the token with `id`, returning whether there was one.
+    #[instrument(skip(self))]
+    pub fn revoke(&self, id: &str) -> io::Result<bool> {
+        let _file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut tokens = self.list()?;
+        let before = tokens.len();
+        tokens.retain(|t| t.id != id);
+        if tokens.len() == before {
+            return Ok(false);
+        }
+        self.write(&tokens)?;
+        debug!("revoked API token");
+        Ok(true)
+    }
+
+    /// The token matching `token`, if it is a valid one.
+    pub fn verify(&self, token: &str) -> Option<ApiToken> {
+        let (id, _) = token.strip_prefix(self.prefix)?.split_once('_')?;
+        let hash = hash(token);
+        self.list().ok()?.into_iter().find(|t| {
+            t.id == id && constant_time_eq(t.hash.as_bytes(), hash.as_bytes())
+        })
+    }
+
+    fn write(&self, tokens: &[ApiToken]) -> io::Result<()> {
+        let contents: String = tokens
+            .iter()
+            .map(|t| {
+                let profile = match self.profiles {
+                    true => format!(" {}", t.profile.as_deref().unwrap_or("-")),
+                    false => String::new(),
+                };
+                format!(
+                    "{} {} {}{profile} {}\n",
+                    t.id,
+                    t.hash,
+                    format_date(t.created),
+                    t.label
+                )
+            })
+            .collect();
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+        let mut options = std::fs::File::options();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        io::Write::write_all(&mut options.open(&tmp)?, contents.as_bytes())?;
+        std::fs::rename(&tmp, &self.path)
+    }
+}
+
+/// `label` with surrounding whitespace trimmed and inner whitespace
+/// collapsed, or `None` if it is empty or too long.
+pub fn valid_label(label: &str) -> Option<String> {
+    const MAX_LEN: usize = 100;
+    let label = label.split_whitespace().collect::<Vec<_>>().join(" ");
+    (!label.is_empty() && label.len() <= MAX_LEN).then_some(label)
+}
+
+fn hash(token: &str) -> String {
+    hex(&Blake2s256::digest(token.as_bytes()))
+}
+
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Compare `a` and `b` in time that depends only on their lengths, so that
+/// timing doesn't reveal how much of a secret was right.
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len()
+        && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
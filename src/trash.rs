@@ -0,0 +1,45 @@
+//! Entries that were deleted, which are kept for a while so that a mistaken
+//! delete can be undone. Each profile keeps them in a `.trash` file next to
+//! its data file, with lines of the form `YYYY-MM-DD entry` giving the date
+//! of the delete followed by the entry as it appeared in the data file.
+
+use std::fmt::Display;
+
+use time::Date;
+
+use crate::{
+    journal::{Journal, Record},
+    store::{format_date, parse_date},
+    Entry,
+};
+
+/// The deleted entries of one profile.
+pub type Trash = Journal<Deleted>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Deleted {
+    /// When the entry was deleted
+    pub date: Date,
+    pub entry: Entry,
+}
+
+impl Record for Deleted {
+    fn parse(line: &str) -> Option<Self> {
+        let (date, entry) = line.trim().split_once(' ')?;
+        Some(Self {
+            date: parse_date(date)?,
+            entry: Entry::parse(entry)?,
+        })
+    }
+
+    fn date(&self) -> Date {
+        self.date
+    }
+}
+
+/// Formats the deleted entry as a line of a trash file.
+impl Display for Deleted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", format_date(self.date), self.entry)
+    }
+}
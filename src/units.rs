@@ -0,0 +1,141 @@
+use std::{fmt::Display, str::FromStr};
+
+const LB_PER_KG: f64 = 2.204_622_621_8;
+const G_PER_KG: f64 = 1000.0;
+const LB_PER_ST: f64 = 14.0;
+
+/// A unit of body weight.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Unit {
+    #[default]
+    Kg,
+    Lb,
+
+    /// Grams, for small pets
+    G,
+}
+
+impl Unit {
+    /// Convert `weight` from this unit to `to`.
+    pub fn convert(self, weight: f64, to: Unit) -> f64 {
+        let kg = match self {
+            Unit::Kg => weight,
+            Unit::Lb => weight / LB_PER_KG,
+            Unit::G => weight / G_PER_KG,
+        };
+        match to {
+            Unit::Kg => kg,
+            Unit::Lb => kg * LB_PER_KG,
+            Unit::G => kg * G_PER_KG,
+        }
+    }
+}
+
+/// `weight` as shown in tables: in stones and pounds if `stones` gives the
+/// unit it is in, or else to one decimal place.
+pub fn format_weight(weight: f64, stones: Option<Unit>) -> String {
+    match stones {
+        Some(unit) => format_stones(weight, unit),
+        None => format!("{weight:.1}"),
+    }
+}
+
+/// `weight` in `unit` as stones and pounds to the nearest tenth of a pound,
+/// like `12st 10.4`.
+pub fn format_stones(weight: f64, unit: Unit) -> String {
+    let tenths = (unit.convert(weight, Unit::Lb) * 10.0).round() as i64;
+    let per_stone = LB_PER_ST as i64 * 10;
+    let (stones, tenths) = (tenths / per_stone, tenths % per_stone);
+    format!("{stones}st {}.{}", tenths / 10, tenths % 10)
+}
+
+/// Parse stones and pounds like `12st 10`, `12 stone 10.5 lb`, or `12st`
+/// into a weight in `unit`, rounded to the one decimal place the data file
+/// keeps. Both `.` and `,` are accepted as the decimal separator of the
+/// pounds.
+pub fn parse_stones(s: &str, unit: Unit) -> Option<f64> {
+    let s = s.trim().to_ascii_lowercase();
+    let (stones, pounds) = s.split_once("st")?;
+    let stones: u32 = stones.trim().parse().ok()?;
+    let pounds = pounds
+        .strip_prefix("ones")
+        .or_else(|| pounds.strip_prefix("one"))
+        .unwrap_or(pounds)
+        .trim();
+    let pounds = pounds
+        .strip_suffix("lbs")
+        .or_else(|| pounds.strip_suffix("lb"))
+        .unwrap_or(pounds)
+        .trim();
+    let pounds = match pounds {
+        "" => 0.0,
+        pounds => crate::store::parse_weight(pounds)
+            .filter(|lb| (0.0..LB_PER_ST).contains(lb))?,
+    };
+    let lb = f64::from(stones) * LB_PER_ST + pounds;
+    Some(crate::store::round_weight(Unit::Lb.convert(lb, unit)))
+}
+
+impl FromStr for Unit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "kg" | "kgs" => Ok(Self::Kg),
+            "lb" | "lbs" => Ok(Self::Lb),
+            "g" => Ok(Self::G),
+            _ => Err(format!("unknown unit `{s}`, expected kg, lb, or g")),
+        }
+    }
+}
+
+impl Display for Unit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Unit::Kg => write!(f, "kg"),
+            Unit::Lb => write!(f, "lb"),
+            Unit::G => write!(f, "g"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn whole_stones() {
+        assert_eq!(parse_stones("12st", Unit::Lb), Some(168.0));
+        assert_eq!(parse_stones("12 stone", Unit::Lb), Some(168.0));
+        assert_eq!(format_stones(168.0, Unit::Lb), "12st 0.0");
+    }
+
+    #[test]
+    fn stones_and_pounds() {
+        assert_eq!(parse_stones("12st 10", Unit::Lb), Some(178.0));
+        assert_eq!(parse_stones("12 stone 10,5 lb", Unit::Lb), Some(178.5));
+        assert_eq!(parse_stones("12ST 10.5LBS", Unit::Lb), Some(178.5));
+        assert_eq!(format_stones(178.5, Unit::Lb), "12st 10.5");
+    }
+
+    #[test]
+    fn rounded_like_the_data_file() {
+        // 178 lb is 80.74 kg, which is saved as 80.7
+        let kg = parse_stones("12st 10", Unit::Kg).unwrap();
+        assert_eq!(kg, 80.7);
+        assert_eq!(format_stones(kg, Unit::Kg), "12st 9.9");
+    }
+
+    #[test]
+    fn too_many_pounds() {
+        assert_eq!(parse_stones("12st 14", Unit::Lb), None);
+        assert_eq!(parse_stones("12st 20lb", Unit::Lb), None);
+    }
+
+    #[test]
+    fn bad_stones() {
+        for s in ["", "12", "st 10", "twelve st", "12st x", "-1st", "12st -1"] {
+            assert_eq!(parse_stones(s, Unit::Lb), None, "{s}");
+        }
+    }
+}
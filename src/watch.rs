@@ -0,0 +1,24 @@
+//! Noticing changes made to the data files while the server is running, like
+//! an entry added in a text editor or by another copy of weight-watcher.
+//! Pages are served from the entries in memory without looking at the disk,
+//! so the files are instead checked in the background.
+
+use std::{sync::Arc, time::Duration};
+
+use tracing::error;
+
+use crate::State;
+
+/// How often the data files are checked for changes.
+pub const INTERVAL: Duration = Duration::from_secs(2);
+
+/// Spawn a thread that picks up changes to the data files in `state` every
+/// [`INTERVAL`].
+pub fn update_periodically(state: Arc<State>) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(INTERVAL);
+        if let Err(e) = state.update_profiles() {
+            error!("failed to check the data files for changes: {e}");
+        }
+    });
+}
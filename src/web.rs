@@ -0,0 +1,4975 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    io::{self, Write},
+    net::{IpAddr, TcpStream},
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, PoisonError, RwLock, RwLockReadGuard,
+    },
+};
+
+use time::Date;
+use tracing::{debug, error, info, info_span, warn};
+
+use crate::{
+    audit::{self, Action, Audit, Source},
+    backup,
+    config::{self, Config},
+    crypto,
+    cycle::{self, PeriodStart},
+    date_format::DateFormat,
+    events::{Event, Events},
+    exercise::{self, Workout},
+    export,
+    git::Repo,
+    grafana,
+    growth::{self, Chart, Child},
+    http::{base64_encode, Body, ContentType, Request, Response},
+    import::{self, Format, SleepFormat},
+    journal::Record,
+    json,
+    medication::{self, Change, MedicationChange},
+    merge::{Policy, Report},
+    metrics::{self, Measurement},
+    notes::{self, Note},
+    pdf,
+    photos::{self, Photo},
+    plot::Span,
+    profile::{self, Profile, Profiles},
+    qr,
+    render::{GraphKey, Job, Renderer, Status},
+    sse,
+    stats::{self, weekly, Direction},
+    status::{self, Activity},
+    store::{self, Entry, Store},
+    templates::Templates,
+    timezone::Timezone,
+    tokens::{self, ApiToken, Tokens},
+    trash::Deleted,
+    units::{self, Unit},
+    websocket,
+    withings::{self, WeighIn, Withings},
+    Error, Result,
+};
+
+/// Everything the request handlers need to serve the app. The handlers only
+/// need a shared reference, so a single `State` can be shared between
+/// threads.
+pub struct State {
+    profiles: RwLock<Profiles>,
+
+    /// Where `config` was loaded from. The logo is loaded from the same
+    /// directory.
+    config_file: PathBuf,
+    config: RwLock<Config>,
+    logo: RwLock<Vec<u8>>,
+    templates: RwLock<Templates>,
+
+    /// The repository the data files are committed to, if enabled. This is
+    /// only set up at startup.
+    repo: Option<Repo>,
+
+    /// The last index page rendered for each profile, so that reloading an
+    /// unchanged page doesn't redo the work. Only one page is kept per
+    /// profile since the graph file belongs to the last one rendered.
+    index_cache: Mutex<HashMap<Option<String>, (IndexKey, String)>>,
+
+    /// The table of recent entries last rendered for each profile, along
+    /// with the note about how many are shown. This changes less often than
+    /// the rest of the index page, which also depends on the date and on the
+    /// profile's other data.
+    table_cache: Mutex<HashMap<Option<String>, (TableKey, String, String)>>,
+
+    /// Changes to the data, for pages that update live.
+    events: Events,
+
+    /// The index pages' graphs, which are rendered in the background
+    renderer: Renderer,
+
+    /// The API tokens accepted in place of the admin password
+    tokens: Tokens,
+
+    /// The tokens in `/log/<token>` URLs for recording weights
+    log_tokens: Tokens,
+
+    /// The Withings account weigh-ins are fetched from
+    withings: Withings,
+
+    /// Every change made to the entries
+    audit: Audit,
+
+    /// The requests handled since the server started
+    activity: Activity,
+
+    /// Whether the command line asked for read-only mode, which holds even
+    /// if the config is reloaded without it
+    read_only: bool,
+
+    /// Whether most pages are answered with the maintenance page, while the
+    /// data is restored or moved
+    maintenance: AtomicBool,
+
+    /// The timezone to use when the config doesn't set one. This has to be
+    /// determined up front because the `time` crate refuses to look up the
+    /// local offset once the process has multiple threads.
+    local: Timezone,
+}
+
+/// What an index page depends on besides the config and templates, which
+/// clear the cache when they are reloaded.
+#[derive(PartialEq)]
+struct IndexKey {
+    version: u64,
+    today: Date,
+    tag: Option<String>,
+    rows: usize,
+}
+
+/// What the table on an index page depends on besides the config.
+#[derive(PartialEq)]
+struct TableKey {
+    version: u64,
+    tag: Option<String>,
+    rows: usize,
+}
+
+impl State {
+    /// Set up the state with `store` as the default profile and open the
+    /// named profiles listed in `config`.
+    pub fn new(
+        store: Store,
+        config_file: PathBuf,
+        config: Config,
+        local: Timezone,
+    ) -> Result<Self> {
+        let logo = config::load_logo(&config_file);
+        let templates = Templates::load(&config::templates_dir(&config_file));
+        let repo = if config.git {
+            let dir = store.path().parent().unwrap_or(Path::new(""));
+            Some(Repo::open(dir, config.git_remote.clone())?)
+        } else {
+            None
+        };
+        let audit = Audit::open(
+            &store.path().with_extension("audit"),
+            store.passphrase(),
+        )?;
+        let mut profiles = Profiles::new(store)?;
+        profiles.set_names(&config.profile_names())?;
+        let tokens = Tokens::new(&config_file);
+        let log_tokens = Tokens::quick_log(&config_file);
+        let withings = Withings::new(&config_file);
+        Ok(Self {
+            profiles: RwLock::new(profiles),
+            config_file,
+            config: RwLock::new(config),
+            logo: RwLock::new(logo),
+            templates: RwLock::new(templates),
+            repo,
+            index_cache: Mutex::default(),
+            table_cache: Mutex::default(),
+            events: Events::default(),
+            renderer: Renderer::default(),
+            tokens,
+            log_tokens,
+            withings,
+            audit,
+            activity: Activity::default(),
+            read_only: false,
+            maintenance: AtomicBool::new(false),
+            local,
+        })
+    }
+
+    /// Re-read the config file, logo, templates, and data files, for picking
+    /// up changes made while the server is running. Logging settings are only
+    /// applied at startup.
+    pub fn reload(&self) -> Result<()> {
+        let config = Config::load(&self.config_file);
+        for warning in &config.warnings {
+            warn!("{warning}");
+        }
+        {
+            let mut profiles = self
+                .profiles
+                .write()
+                .unwrap_or_else(PoisonError::into_inner);
+            profiles.set_names(&config.profile_names())?;
+        }
+        self.update_profiles()?;
+        *self.config.write().unwrap_or_else(PoisonError::into_inner) = config;
+        *self.logo.write().unwrap_or_else(PoisonError::into_inner) =
+            config::load_logo(&self.config_file);
+        *self
+            .templates
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) =
+            Templates::load(&config::templates_dir(&self.config_file));
+        self.index_cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+        self.table_cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+        Ok(())
+    }
+
+    /// Pull and push the data repository, then reload the data files to pick
+    /// up any changes pulled in.
+    pub fn sync(&self) -> Result<()> {
+        let Some(repo) = &self.repo else {
+            return Ok(());
+        };
+        repo.sync()?;
+        self.update_profiles()?;
+        Ok(())
+    }
+
+    /// Pick up changes made to every profile's data files by something
+    /// else, notifying subscribers to [`State::events`] about the profiles
+    /// that changed.
+    pub fn update_profiles(&self) -> Result<()> {
+        let profiles = self.profiles();
+        for profile in profiles.iter() {
+            let version = profile.version();
+            profile.update()?;
+            if profile.version() != version {
+                self.events.publish(profile.name(), Event::Changed);
+            }
+        }
+        Ok(())
+    }
+
+    /// Refuse changes to the data over the web whatever the config says.
+    pub fn force_read_only(&mut self) {
+        self.read_only = true;
+    }
+
+    /// Whether changes to the data over the web are refused.
+    pub fn read_only(&self) -> bool {
+        self.read_only || self.config().read_only
+    }
+
+    /// Whether the server is in maintenance mode.
+    pub fn maintenance(&self) -> bool {
+        self.maintenance.load(Ordering::Relaxed)
+    }
+
+    /// Switch maintenance mode on or off.
+    pub fn set_maintenance(&self, on: bool) {
+        self.maintenance.store(on, Ordering::Relaxed);
+    }
+
+    /// Switch maintenance mode to the opposite of what it was, returning
+    /// whether it is now on.
+    pub fn toggle_maintenance(&self) -> bool {
+        !self.maintenance.fetch_xor(true, Ordering::Relaxed)
+    }
+
+    pub fn events(&self) -> &Events {
+        &self.events
+    }
+
+    pub fn renderer(&self) -> &Renderer {
+        &self.renderer
+    }
+
+    pub fn withings(&self) -> &Withings {
+        &self.withings
+    }
+
+    /// Render the recent graph for `job` unless it is already up to date,
+    /// and tell open pages about the new graph.
+    pub fn render_graph(&self, job: &Job) {
+        let Some(profile) = self.profiles().get(job.profile.as_deref()) else {
+            return;
+        };
+        let today = self.today();
+        let key = GraphKey {
+            version: profile.version(),
+            today,
+            tag: job.tag.clone(),
+        };
+        if self.renderer.status(&profile, &key) == Status::Current {
+            return;
+        }
+        let config = self.config();
+        match profile.graph(Span::RECENT, today, job.tag.as_deref(), &config) {
+            Ok(()) => {
+                self.renderer.record(profile.name(), key);
+                self.events.publish(profile.name(), Event::Graphed);
+            }
+            Err(e) => error!("failed to generate graph: {e}"),
+        }
+    }
+
+    /// Render the graph of the `span` before today to `output` for the
+    /// profile called `name`, showing only entries tagged `tag` if given.
+    pub fn graph_to(
+        &self,
+        output: &Path,
+        span: Span,
+        tag: Option<&str>,
+        name: Option<&str>,
+    ) -> Result<()> {
+        let profile = self.profiles().get(name).ok_or_else(|| {
+            Error::Io(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!(
+                    "there is no profile called {}",
+                    name.unwrap_or("default")
+                ),
+            ))
+        })?;
+        let today = self.today();
+        profile.graph_to(output, span, today, tag, &self.config())
+    }
+
+    /// Every profile's entries in InfluxDB line protocol.
+    pub fn influx_lines(&self) -> Vec<u8> {
+        let config = self.config();
+        let timezone = self.timezone();
+        let mut out = Vec::new();
+        for profile in self.profiles().iter() {
+            let entries = profile.store().entries().clone();
+            let unit = config.unit_for(profile.name());
+            export::influx(&mut out, entries, profile.name(), unit, timezone)
+                .expect("writing to a Vec can't fail");
+        }
+        out
+    }
+
+    /// A backup of every profile's data.
+    pub fn snapshot(&self) -> String {
+        backup::snapshot(&self.profiles())
+    }
+
+    /// Restore the backup with `contents`, returning a summary for the user.
+    /// The backup is checked completely before anything changes, and the
+    /// current data is saved to the `snapshots` directory next to the data
+    /// file first in case the restore wasn't wanted.
+    pub fn restore(
+        &self,
+        contents: &[u8],
+        mode: backup::RestoreMode,
+        source: Source,
+    ) -> Result<String> {
+        let config = self.config();
+        let backup = backup::parse(contents, &config)?;
+        let profiles = self.profiles();
+        profiles.update()?;
+        let mut restores = Vec::new();
+        for (name, entries) in backup {
+            let profile = profiles.get(name.as_deref()).ok_or_else(|| {
+                Error::Restore(format!(
+                    "the backup has entries for the profile {}, which isn't \
+                     configured",
+                    name.as_deref().unwrap_or("default")
+                ))
+            })?;
+            restores.push((profile, entries));
+        }
+
+        let path = self.snapshot_path(&profiles, "before-restore", "txt")?;
+        let snapshot = backup::snapshot(&profiles);
+        let snapshot = match &config.encryption_key_file {
+            Some(key_file) => {
+                let passphrase = crypto::read_passphrase(key_file)?;
+                crypto::Cipher::new(passphrase.into(), &[])?
+                    .encrypt(snapshot.as_bytes())?
+            }
+            None => snapshot.into_bytes(),
+        };
+        std::fs::write(&path, snapshot)?;
+        info!("saved the current data to {}", path.display());
+        drop(config);
+
+        let mut restored = 0;
+        for (profile, entries) in &restores {
+            match mode {
+                backup::RestoreMode::Replace => {
+                    restored += entries.len();
+                    let old = profile.store().reset(entries.clone())?;
+                    let removed = old
+                        .iter()
+                        .filter(|e| !entries.contains(e))
+                        .map(|e| (Action::Delete, Some(e.clone()), None));
+                    let added = entries
+                        .iter()
+                        .filter(|e| !old.contains(e))
+                        .map(|e| (Action::Import, None, Some(e.clone())));
+                    self.audit(source, profile, removed.chain(added));
+                }
+                backup::RestoreMode::Merge => {
+                    let report =
+                        profile.store().merge(entries.clone(), Policy::Keep)?;
+                    restored += report.added.len();
+                    self.audit(
+                        source,
+                        profile,
+                        report
+                            .added
+                            .into_iter()
+                            .map(|e| (Action::Import, None, Some(e))),
+                    );
+                }
+            }
+        }
+        drop(profiles);
+        for (profile, _) in &restores {
+            self.events.publish(profile.name(), Event::Changed);
+        }
+        info!(restored, ?mode, "restored backup");
+        self.commit(&format!("Restore {restored} entries from a backup"));
+        let verb = match mode {
+            backup::RestoreMode::Replace => "Restored",
+            backup::RestoreMode::Merge => "Merged",
+        };
+        let profiles = match restores.len() {
+            1 => "1 profile".to_owned(),
+            n => format!("{n} profiles"),
+        };
+        Ok(format!(
+            "{verb} {restored} entries into {profiles}. The previous data \
+             was saved to {}.",
+            path.display()
+        ))
+    }
+
+    /// Merge the data file with `contents` into the entries of the profile
+    /// called `name`, or the default profile for `None`, resolving dates
+    /// that have different entries in each by `policy`. Returns a summary
+    /// for the user.
+    pub fn merge_file(
+        &self,
+        contents: &[u8],
+        name: Option<&str>,
+        policy: Policy,
+        source: Source,
+    ) -> Result<String> {
+        let profile = self.profiles().get(name).ok_or_else(|| {
+            Error::Import(format!(
+                "there is no profile called {}",
+                name.unwrap_or("default")
+            ))
+        })?;
+        let contents = data_file_text(&profile, contents)?;
+        let entries = import::parse(
+            Format::WeightWatcher,
+            &contents,
+            Unit::Kg,
+            Unit::Kg,
+            self.timezone(),
+        )?;
+        let report =
+            self.merge(&profile, entries, policy, source, "another data file")?;
+        Ok(report.to_string())
+    }
+
+    /// Import `contents`, an export in `format` or in whichever format it
+    /// looks like if not given, into the entries of the profile called
+    /// `name`, resolving conflicts by `policy`. Weights are assumed to be in
+    /// `unit` if the file doesn't say, or the profile's unit if not given.
+    pub fn import_file(
+        &self,
+        contents: &[u8],
+        format: Option<Format>,
+        unit: Option<Unit>,
+        name: Option<&str>,
+        policy: Policy,
+    ) -> Result<String> {
+        let profile = self.profiles().get(name).ok_or_else(|| {
+            Error::Import(format!(
+                "there is no profile called {}",
+                name.unwrap_or("default")
+            ))
+        })?;
+        let format =
+            format.or_else(|| Format::detect(contents)).ok_or_else(|| {
+                Error::Import("couldn't tell what format the file is in".into())
+            })?;
+        let contents = match format {
+            Format::WeightWatcher => data_file_text(&profile, contents)?,
+            Format::Zepp => import::zepp_text(contents)?,
+            _ => String::from_utf8_lossy(contents).into_owned(),
+        };
+        let to = self.config().unit_for(profile.name());
+        let entries = import::parse(
+            format,
+            &contents,
+            unit.unwrap_or(to),
+            to,
+            self.timezone(),
+        )?;
+        let read = format!(
+            "Read {} from {}.",
+            match entries.len() {
+                1 => "1 entry".to_owned(),
+                n => format!("{n} entries"),
+            },
+            format.name()
+        );
+        let report =
+            self.merge(&profile, entries, policy, Source::Cli, format.name())?;
+        Ok(format!("{read} {report}"))
+    }
+
+    /// Add `weigh_ins` from Withings to the entries of the profile called
+    /// `name`, along with their fat mass, and return a summary for the user.
+    /// Days that already have a different entry keep it.
+    pub fn add_weigh_ins(
+        &self,
+        name: Option<&str>,
+        weigh_ins: &[WeighIn],
+    ) -> Result<String> {
+        let profile = self.profiles().get(name).ok_or_else(|| {
+            Error::Import(format!(
+                "there is no profile called {}",
+                name.unwrap_or("default")
+            ))
+        })?;
+        let to = self.config().unit_for(profile.name());
+        let timezone = self.timezone();
+        // the scale measures to more places than the data file keeps, so
+        // weigh-ins fetched twice still match
+        let round = |kg: f64| (Unit::Kg.convert(kg, to) * 10.0).round() / 10.0;
+        let mut entries = Vec::new();
+        let mut fat = Vec::new();
+        for weigh_in in weigh_ins {
+            let date = timezone.convert(weigh_in.time).date();
+            entries.push(Entry {
+                date,
+                weight: round(weigh_in.weight),
+                tags: Vec::new(),
+            });
+            if let Some(kg) = weigh_in.fat_mass {
+                fat.push(Measurement {
+                    date,
+                    metric: metrics::FAT_MASS.to_owned(),
+                    value: round(kg),
+                });
+            }
+        }
+        if !fat.is_empty() {
+            profile.metrics().extend(fat)?;
+            self.events.publish(profile.name(), Event::Changed);
+        }
+        let report = self.merge(
+            &profile,
+            entries,
+            Policy::Keep,
+            Source::Import,
+            "Withings",
+        )?;
+        Ok(report.to_string())
+    }
+
+    /// Write the entries of the profile called `name` to `path` in
+    /// `format`, or every profile's for a backup, and return a summary for
+    /// the user. The file is replaced all at once, so it is never left half
+    /// written.
+    pub fn export_file(
+        &self,
+        path: &Path,
+        format: export::Format,
+        name: Option<&str>,
+    ) -> Result<String> {
+        let profiles = self.profiles();
+        let profile = profiles.get(name).ok_or_else(|| {
+            Error::Io(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!(
+                    "there is no profile called {}",
+                    name.unwrap_or("default")
+                ),
+            ))
+        })?;
+        let mut out = Vec::new();
+        let count = match format {
+            export::Format::Backup => {
+                backup::write_snapshot(
+                    &mut out,
+                    profiles.iter().map(|p| &**p),
+                )?;
+                profiles.iter().map(|p| p.store().entries().len()).sum()
+            }
+            format => {
+                let entries = profile.store().entries().clone();
+                let count = entries.len();
+                match format {
+                    export::Format::Csv => export::csv(&mut out, entries)?,
+                    export::Format::Json => export::json(&mut out, entries)?,
+                    _ => export::data_file(&mut out, entries)?,
+                }
+                count
+            }
+        };
+        store::replace_file(path, &out)?;
+        Ok(format!(
+            "Wrote {} to {}.",
+            match count {
+                1 => "1 entry".to_owned(),
+                n => format!("{n} entries"),
+            },
+            path.display()
+        ))
+    }
+
+    /// Merge `entries` from `from`, like an app's name, into `profile`'s
+    /// entries, resolving conflicts by `policy`.
+    fn merge(
+        &self,
+        profile: &Profile,
+        entries: Vec<Entry>,
+        policy: Policy,
+        source: Source,
+        from: &str,
+    ) -> Result<Report> {
+        let total = entries.len();
+        let report = profile.store().merge(entries, policy)?;
+        let removed = report
+            .removed
+            .iter()
+            .map(|e| (Action::Delete, Some(e.clone()), None));
+        let added = report
+            .added
+            .iter()
+            .map(|e| (Action::Import, None, Some(e.clone())));
+        self.audit(source, profile, removed.chain(added));
+        info!(
+            added = report.added.len(),
+            removed = report.removed.len(),
+            total,
+            "merged entries"
+        );
+        if !report.is_empty() {
+            self.events.publish(profile.name(), Event::Changed);
+            self.commit(&format!(
+                "Import {} entries{} from {from}",
+                report.added.len(),
+                for_profile(profile),
+            ));
+        }
+        Ok(report)
+    }
+
+    /// A new file in the `snapshots` directory next to the data file, named
+    /// `name` followed by the current time and `extension`. The directory is
+    /// created if needed.
+    fn snapshot_path(
+        &self,
+        profiles: &Profiles,
+        name: &str,
+        extension: &str,
+    ) -> Result<PathBuf> {
+        let now = self.timezone().now();
+        let dir = profiles
+            .default_profile()
+            .store()
+            .path()
+            .parent()
+            .unwrap_or(Path::new(""))
+            .join("snapshots");
+        std::fs::create_dir_all(&dir)?;
+        let stem = format!(
+            "{name}-{}-{:02}{:02}{:02}",
+            store::format_date(now.date()),
+            now.hour(),
+            now.minute(),
+            now.second()
+        );
+        // never overwrite an earlier snapshot, which may be the only copy of
+        // the data from before a mistaken change
+        let mut path = dir.join(format!("{stem}.{extension}"));
+        for i in 1.. {
+            if !path.exists() {
+                break;
+            }
+            path = dir.join(format!("{stem}-{i}.{extension}"));
+        }
+        Ok(path)
+    }
+
+    /// Rewrite every profile's data file in date order, dropping lines that
+    /// can't be read and entries that repeat an earlier one exactly, and
+    /// return a summary for the user.
+    pub fn compact(&self, source: Source) -> Result<String> {
+        let profiles = self.profiles();
+        let mut dropped = 0;
+        let mut removed = 0;
+        for profile in profiles.iter() {
+            let (unreadable, repeated) = profile.store().compact()?;
+            dropped += unreadable;
+            removed += repeated.len();
+            self.audit(
+                source,
+                profile,
+                repeated
+                    .into_iter()
+                    .map(|e| (Action::Delete, Some(e), None)),
+            );
+            self.events.publish(profile.name(), Event::Changed);
+        }
+        drop(profiles);
+        info!(dropped, removed, "compacted data files");
+        self.commit("Compact the data files");
+        let mut summary = "Compacted the data files".to_owned();
+        let mut dropping = Vec::new();
+        match dropped {
+            0 => {}
+            1 => dropping.push("1 unreadable line".to_owned()),
+            n => dropping.push(format!("{n} unreadable lines")),
+        }
+        match removed {
+            0 => {}
+            1 => dropping.push("1 repeated entry".to_owned()),
+            n => dropping.push(format!("{n} repeated entries")),
+        }
+        if !dropping.is_empty() {
+            summary.push_str(", dropping ");
+            summary.push_str(&dropping.join(" and "));
+        }
+        summary.push('.');
+        Ok(summary)
+    }
+
+    /// Copy each profile's data file as it is, unreadable lines and all, to
+    /// the `snapshots` directory and then [`compact`](Self::compact) them,
+    /// returning a summary for the user.
+    pub fn repair(&self, source: Source) -> Result<String> {
+        let profiles = self.profiles();
+        for profile in profiles.iter() {
+            let name = format!(
+                "before-repair-{}",
+                profile.name().unwrap_or("default")
+            );
+            let path = self.snapshot_path(&profiles, &name, "dat")?;
+            std::fs::copy(profile.store().path(), &path)?;
+            info!("saved the current data to {}", path.display());
+        }
+        drop(profiles);
+        let summary = self.compact(source)?;
+        Ok(format!(
+            "Copied the data files to the snapshots directory. {summary}"
+        ))
+    }
+
+    /// Problems found in any profile's data file, each starting with the
+    /// profile's name.
+    pub fn check(&self) -> Vec<String> {
+        let profiles = self.profiles();
+        let mut problems = Vec::new();
+        for profile in profiles.iter() {
+            let name = profile.name().unwrap_or("default");
+            match profile.store().check() {
+                Ok(found) => problems
+                    .extend(found.into_iter().map(|p| format!("{name}: {p}"))),
+                Err(e) => problems.push(format!("{name}: {e}")),
+            }
+        }
+        problems
+    }
+
+    /// Delete every rendered graph and cached page so that they are
+    /// rendered from scratch, returning how many graphs were deleted.
+    pub fn flush_graphs(&self) -> usize {
+        self.index_cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+        self.table_cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+        self.profiles().iter().map(|p| p.clear_graphs()).sum()
+    }
+
+    /// Commit the data files with `message` if git is enabled. The change
+    /// itself has already been saved, so failures are only logged.
+    fn commit(&self, message: &str) {
+        if let Some(repo) = &self.repo {
+            if let Err(e) = repo.commit(message) {
+                error!("failed to commit data: {e}");
+            }
+        }
+    }
+
+    /// Record `changes` to `profile`'s entries from `source` in the audit
+    /// log, as actions with the old and new entries. The changes themselves
+    /// have already been saved, so failures are only logged.
+    fn audit<I>(&self, source: Source, profile: &Profile, changes: I)
+    where
+        I: IntoIterator<Item = (Action, Option<Entry>, Option<Entry>)>,
+    {
+        let time = time::OffsetDateTime::now_utc();
+        let changes: Vec<_> = changes
+            .into_iter()
+            .map(|(action, old, new)| audit::Change {
+                time,
+                source,
+                action,
+                profile: profile.name().map(str::to_owned),
+                old,
+                new,
+            })
+            .collect();
+        if let Err(e) = self.audit.record(&changes) {
+            error!("failed to record changes in the audit log: {e}");
+        }
+    }
+
+    fn profiles(&self) -> RwLockReadGuard<'_, Profiles> {
+        self.profiles.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub(crate) fn config(&self) -> RwLockReadGuard<'_, Config> {
+        self.config.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// The contents of the template file called `name`.
+    fn template(&self, name: &str) -> String {
+        let templates = self
+            .templates
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        templates.get(name).to_owned()
+    }
+
+    /// The template called `name` with the title, accent color, font size,
+    /// and density from `config` filled in. These are escaped like anything
+    /// else from outside the server that ends up in a page.
+    fn page(&self, name: &str, config: &Config) -> String {
+        self.template(name)
+            .replace("{{title}}", &escape_html(&config.title))
+            .replace("{{accent}}", &escape_html(&config.accent))
+            .replace("{{font_size}}", &escape_html(&config.font_size))
+            .replace("{{cell_padding}}", config.density.cell_padding())
+    }
+
+    /// An error page saying `message`, which is plain text and often quotes
+    /// the request, so it is escaped.
+    fn error_page(&self, message: &str) -> String {
+        self.template("error.html")
+            .replace("{{message}}", &escape_html(message))
+    }
+
+    /// The configured timezone, falling back on the local one.
+    fn timezone(&self) -> Timezone {
+        self.config().timezone.unwrap_or(self.local)
+    }
+
+    /// The current date in the configured timezone.
+    fn today(&self) -> Date {
+        self.timezone().today()
+    }
+
+    /// Links to every profile's page, or nothing if there is only the
+    /// default profile.
+    fn profile_nav(&self, current: &Profile) -> String {
+        let profiles = self.profiles();
+        let mut links = Vec::new();
+        for profile in profiles.iter() {
+            let name = profile.name().unwrap_or("default");
+            if profile.name() == current.name() {
+                links.push(format!("<strong>{name}</strong>"));
+            } else {
+                links.push(format!(
+                    "<a href=\"{}/\">{name}</a>",
+                    profile.base()
+                ));
+            }
+        }
+        if links.len() < 2 {
+            return String::new();
+        }
+        format!("<nav>{}</nav>", links.join(" | "))
+    }
+}
+
+/// Read a single request from `stream`, handle it, and write the response
+/// back.
+pub fn dispatch(mut stream: TcpStream, state: &State) {
+    let peer = stream.peer_addr().ok();
+    let _span = info_span!(
+        "connection",
+        peer = %peer.map(|a| a.to_string()).unwrap_or_default()
+    )
+    .entered();
+    let max_body = state.config().max_body_size;
+    // refused clients are answered before anything they send is read
+    let mut response = match forbidden(peer.map(|a| a.ip()), state) {
+        Some(response) => response,
+        None => match Request::read(&mut stream, max_body) {
+            Ok(request) => handle(&request, state),
+            Err(e) => match read_error(e, state) {
+                Some(response) => response,
+                None => return,
+            },
+        },
+    };
+    let bytes = response.as_bytes();
+    let rest = response.take_stream();
+    if let Err(e) = stream.write_all(&bytes) {
+        error!("failed to write response: {e}");
+        return;
+    }
+    if let Some(rest) = rest {
+        rest(stream);
+    }
+}
+
+/// Log an error from reading a request and return the response to send back,
+/// if the connection is still usable.
+pub(crate) fn read_error(e: Error, state: &State) -> Option<Response> {
+    match e {
+        Error::BadRequest(_) => {
+            warn!("{e}");
+            Some(
+                Response::bad_request()
+                    .body(state.error_page("Bad request.").into()),
+            )
+        }
+        Error::HeaderTooLarge(_) => {
+            warn!("{e}");
+            Some(Response::header_fields_too_large().body(
+                state.error_page("Request header fields too large.").into(),
+            ))
+        }
+        Error::BodyTooLarge(_) => {
+            warn!("{e}");
+            let max = state.config().max_body_size;
+            Some(
+                Response::payload_too_large().body(
+                    state
+                        .error_page(&format!(
+                            "Request body too large. The limit is {max} bytes."
+                        ))
+                        .into(),
+                ),
+            )
+        }
+        e => {
+            error!("failed to read request: {e}");
+            None
+        }
+    }
+}
+
+/// A 403 response for a client connecting from `peer` if the `allow`
+/// setting leaves it out, or `None` if it may go on.
+pub fn forbidden(peer: Option<IpAddr>, state: &State) -> Option<Response> {
+    if state.config().allows(peer) {
+        return None;
+    }
+    warn!("refused a client outside the allowed networks");
+    Some(
+        Response::forbidden().body(
+            state
+                .error_page(
+                    "This server doesn't accept requests from your address.",
+                )
+                .into(),
+        ),
+    )
+}
+
+/// Route `request` to the appropriate handler. Errors from the handlers are
+/// logged and turned into 500 responses, and so are panics, which only end
+/// the request they happened in.
+pub fn handle(request: &Request, state: &State) -> Response {
+    // quick-log tokens are the whole credential, so keep them out of the logs
+    let path = match request.path.starts_with("/log/") {
+        true => "/log/<token>",
+        false => &request.path,
+    };
+    let _span = info_span!("request", method = %request.method, path).entered();
+    let routed =
+        panic::catch_unwind(AssertUnwindSafe(|| route(request, state)));
+    let response = match routed {
+        Ok(Ok(response)) => Some(response),
+        Ok(Err(e)) => {
+            error!("error handling {path}: {e}");
+            None
+        }
+        // the panic itself was logged by the panic hook
+        Err(_) => {
+            error!("handler for {path} panicked");
+            None
+        }
+    };
+    let response = response.unwrap_or_else(|| {
+        Response::internal_error().body(
+            state
+                .error_page("Something went wrong on the server.")
+                .into(),
+        )
+    });
+    info!(status = response.status(), "handled request");
+    // the watchdog's health checks would swamp the counts of real requests
+    if path != "/health" {
+        // responses that may not have come from a route, like the 503 for any
+        // path in maintenance mode, share a count so that made-up paths can't
+        // add one each
+        state.activity.record(match response.status() {
+            401 | 403 | 404 | 503 => "(other)",
+            _ => route_name(path),
+        });
+    }
+    response
+}
+
+/// The route `path` belongs to, without the profile name or anything else
+/// that varies between requests for the same page, for counting requests.
+fn route_name(path: &str) -> &str {
+    let (_, path) = profile::split_path(path);
+    if path.starts_with("/report/") {
+        "/report/<month>"
+    } else if path.starts_with("/photos/") {
+        "/photos/<photo>"
+    } else {
+        path
+    }
+}
+
+/// The pages still served in maintenance mode, for switching it off again
+/// and for the restores it is meant for.
+const MAINTENANCE_PAGES: [&str; 5] =
+    ["/admin", "/backup", "/restore", "/status", "/status.json"];
+
+fn route(request: &Request, state: &State) -> Result<Response> {
+    // answered even in maintenance mode, since the server is still working
+    if request.path == "/health" {
+        return Ok(Response::ok()
+            .content_type(ContentType::Text)
+            .body("ok\n".into()));
+    }
+    if state.maintenance()
+        && !MAINTENANCE_PAGES.contains(&request.path.as_str())
+    {
+        let config = state.config();
+        return Ok(Response::service_unavailable()
+            .body(state.page("maintenance.html", &config).into()));
+    }
+    if state.read_only() && changes_data(request) {
+        return Ok(Response::forbidden().body(
+            state
+                .error_page(
+                    "This server is read-only, so nothing can be changed.",
+                )
+                .into(),
+        ));
+    }
+    // pages for the whole server rather than one profile
+    if request.path == "/backup" {
+        return admin(request, state, backup_download);
+    }
+    if request.path == "/restore" {
+        return admin(request, state, restore);
+    }
+    if request.path == "/admin" {
+        return admin(request, state, maintenance);
+    }
+    if request.path == "/tokens" {
+        return admin(request, state, tokens_page);
+    }
+    if request.path == "/audit" {
+        return admin(request, state, audit_page);
+    }
+    if request.path == "/withings" {
+        return admin(request, state, withings_page);
+    }
+    if request.path == "/status" {
+        return admin(request, state, |_, state| Ok(status_page(state)));
+    }
+    if request.path == "/status.json" {
+        return admin(request, state, |_, state| Ok(status_json(state)));
+    }
+    if request.path == "/withings/callback" {
+        return admin(request, state, withings_callback);
+    }
+    if let Some(token) = request.path.strip_prefix("/log/") {
+        return quick_log(request, state, token);
+    }
+    let (name, path) = profile::split_path(&request.path);
+    let Some(profile) = state.profiles().get(name) else {
+        return Ok(
+            Response::err().body(state.error_page("No such profile.").into())
+        );
+    };
+    match path {
+        "/" => index(request, state, &profile),
+        "/weight" => weight(request, state, &profile),
+        "/history" => history(request, state, &profile),
+        "/delete" => delete(request, state, &profile),
+        "/trash" => trash(request, state, &profile),
+        "/versions" => versions(request, state, &profile),
+        "/monthly" => monthly(state, &profile),
+        "/report.pdf" => Ok(report_pdf(request, state, &profile)),
+        path if path.starts_with("/report/") => {
+            report(state, &profile, &path["/report/".len()..])
+        }
+        "/tags" => tags(state, &profile),
+        "/import" => import(request, state, &profile),
+        "/export/apple-health.csv" => {
+            let unit = state.config().unit_for(profile.name());
+            let timezone = state.timezone();
+            Ok(Response::ok().content_type(ContentType::Csv).streamed(
+                request,
+                move |out| {
+                    let entries = profile.store().iter_batched();
+                    export::apple_health(out, entries, unit, timezone)
+                },
+            ))
+        }
+        "/api/latest" => Ok(api_latest(state, &profile)),
+        "/series.json" => Ok(series(state, &profile)),
+        "/export/influx.txt" => {
+            let unit = state.config().unit_for(profile.name());
+            let timezone = state.timezone();
+            Ok(Response::ok().content_type(ContentType::Text).streamed(
+                request,
+                move |out| {
+                    let entries = profile.store().iter_batched();
+                    export::influx(out, entries, profile.name(), unit, timezone)
+                },
+            ))
+        }
+        "/favicon.ico" => Ok(Response::ok()
+            .content_type(ContentType::Png)
+            .body(Body::Bytes(
+                state
+                    .logo
+                    .read()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .clone(),
+            ))),
+        "/graph.png" => graph_image(request, state, &profile),
+        "/grafana"
+        | "/grafana/"
+        | "/grafana/search"
+        | "/grafana/query"
+        | "/grafana/annotations" => grafana(request, state, &profile, path),
+        "/graphs" => graphs(state, &profile),
+        "/chart" => Ok(chart(state, &profile)),
+        "/chart.js" => Ok(Response::ok()
+            .content_type(ContentType::JavaScript)
+            .body(CHART_JS.into())),
+        "/growth" => growth_page(request, state, &profile),
+        "/calories" => calories(request, state, &profile),
+        "/exercise" => exercise(request, state, &profile),
+        "/sleep" => sleep(request, state, &profile),
+        "/cycle" => cycle_page(request, state, &profile),
+        "/sleep.png" => chart_image(request, state, &profile.sleep_file()),
+        "/measurements" => measurements(request, state, &profile),
+        "/measurements.png" => {
+            chart_image(request, state, &profile.measurements_file())
+        }
+        "/medications" => medications(request, state, &profile),
+        "/photos" => admin(request, state, |request, state| {
+            photos(request, state, &profile)
+        }),
+        path if path.starts_with("/photos/") => {
+            admin(request, state, |request, state| {
+                photo_image(request, state, &profile, &path["/photos/".len()..])
+            })
+        }
+        "/growth.png" => growth_image(request, state, &profile),
+        "/qr" => qr_page(request, state, &profile),
+        "/events" => {
+            let events = state.events.subscribe();
+            let name = profile.name().map(str::to_owned);
+            Ok(Response::ok()
+                .content_type(ContentType::EventStream)
+                .header("Cache-Control", "no-cache".into())
+                .body(Body::Stream(Box::new(move |stream| {
+                    sse::stream(stream, events, name)
+                }))))
+        }
+        "/ws" => {
+            let Some(key) = websocket::accept_key(request) else {
+                return Ok(Response::bad_request().body(
+                    state.error_page("Expected a WebSocket handshake.").into(),
+                ));
+            };
+            let events = state.events.subscribe();
+            let timezone = state.timezone();
+            let profile = Arc::clone(&profile);
+            Ok(Response::switching_protocols(
+                "websocket",
+                Box::new(move |stream| {
+                    websocket::session(stream, events, profile, timezone)
+                }),
+            )
+            .header("Sec-WebSocket-Accept", key))
+        }
+        _ => Ok(
+            Response::err().body(state.error_page("Page not found.").into())
+        ),
+    }
+}
+
+/// Whether `request` could change the data, for refusing it in read-only
+/// mode. Forms that save something are posted, except that weights and
+/// deletions are also accepted as plain links, and so are quick-log links
+/// and the Withings callback. Grafana's queries are posted but only read.
+fn changes_data(request: &Request) -> bool {
+    let path = request.path.as_str();
+    if path.starts_with("/log/") || path == "/withings/callback" {
+        return true;
+    }
+    match profile::split_path(path).1 {
+        "/weight" | "/delete" => true,
+        path if path.starts_with("/grafana") => false,
+        _ => !matches!(request.method.as_str(), "GET" | "HEAD"),
+    }
+}
+
+/// The most rows the index page can be asked to list. The history page is
+/// better suited to longer lists.
+const MAX_ROWS: usize = 1000;
+
+/// The `n` query parameter of `request` giving how many rows to list, if
+/// any. Invalid counts are reported as a message for the error page.
+fn rows_param(request: &Request) -> std::result::Result<Option<usize>, String> {
+    match request.params().into_iter().find(|(k, _)| k == "n") {
+        None => Ok(None),
+        Some((_, n)) if n.is_empty() => Ok(None),
+        Some((_, n)) => match n.parse() {
+            Ok(n) if (1..=MAX_ROWS).contains(&n) => Ok(Some(n)),
+            _ => Err(format!(
+                "The number of entries must be between 1 and {MAX_ROWS}."
+            )),
+        },
+    }
+}
+
+/// The `tag` query parameter of `request`, if any. Invalid tags are reported
+/// as a message for the error page.
+fn tag_param(request: &Request) -> std::result::Result<Option<String>, String> {
+    match request.params().into_iter().find(|(k, _)| k == "tag") {
+        None => Ok(None),
+        Some((_, tag)) if tag.is_empty() => Ok(None),
+        Some((_, tag)) if store::valid_tag(&tag) => Ok(Some(tag)),
+        Some(_) => Err("Tags may only contain lowercase letters, digits, - \
+                        and _."
+            .into()),
+    }
+}
+
+fn index(
+    request: &Request,
+    state: &State,
+    profile: &Profile,
+) -> Result<Response> {
+    let tag = match tag_param(request) {
+        Ok(tag) => tag,
+        Err(e) => {
+            return Ok(Response::bad_request().body(state.error_page(&e).into()))
+        }
+    };
+    let rows = match rows_param(request) {
+        Ok(rows) => rows,
+        Err(e) => {
+            return Ok(Response::bad_request().body(state.error_page(&e).into()))
+        }
+    };
+    let today = state.today();
+    let rows = rows.unwrap_or_else(|| state.config().recent_entries);
+    let key = IndexKey {
+        version: profile.version(),
+        today,
+        tag: tag.clone(),
+        rows,
+    };
+    let graph = GraphKey {
+        version: key.version,
+        today,
+        tag: tag.clone(),
+    };
+    let name = profile.name().map(str::to_owned);
+    if let Some((cached, page)) = state
+        .index_cache
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get(&name)
+    {
+        if *cached == key
+            && state.renderer.status(profile, &graph) == Status::Current
+        {
+            debug!("serving cached index");
+            return Ok(Response::ok().body(page.clone().into()));
+        }
+    }
+
+    let (table, since) = index_table(state, profile, tag.as_deref(), rows);
+    let config = state.config();
+    let base = profile.base();
+    // a month only has a few official weigh-ins, so they are taken from
+    // longer ago
+    let days = if config.weigh_in_day.is_some() {
+        90
+    } else {
+        30
+    };
+    let start = today - time::Duration::days(days);
+    let entries = profile.store().entries();
+    let recent = entries.iter().filter(|e| {
+        e.date > start && tag.as_deref().is_none_or(|t| e.has_tag(t))
+    });
+    let percentiles = match config.weigh_in_day {
+        Some(day) => {
+            stats::percentiles(stats::official(recent, day), [10.0, 50.0, 90.0])
+        }
+        None => stats::percentiles(recent, [10.0, 50.0, 90.0]),
+    };
+    let percentiles = match (config.child(profile.name()), percentiles) {
+        // adult statistics aren't meaningful for a growing child
+        (Some(child), _) => growth_summary(
+            child,
+            profile.store().entries().last(),
+            &base,
+            config.unit_for(profile.name()),
+        ),
+        (None, Some([p10, p50, p90])) => format!(
+            "<p>Last {days} days{}: 10th percentile {p10:.1}, median {p50:.1}, \
+             90th percentile {p90:.1}</p>",
+            match config.weigh_in_day {
+                Some(day) => format!(" of official {day} weigh-ins"),
+                None => String::new(),
+            }
+        ),
+        (None, None) => String::new(),
+    };
+    let percentiles = percentiles + &exercise_summary(profile, today);
+    let filter = match &tag {
+        Some(tag) => format!(
+            "<p>Showing entries tagged <strong>{tag}</strong>. \
+             <a href=\"{base}/\">Show all</a></p>"
+        ),
+        None => String::new(),
+    };
+    // the form starts out with the latest weight, since the next one is
+    // usually close to it
+    let (last_weight, previous) = match entries.iter().max_by_key(|e| e.date) {
+        Some(last) => {
+            let weight = units::format_weight(
+                last.weight,
+                config.stones_for(name.as_deref()),
+            );
+            let previous = format!(
+                "Last entry: {weight} on {}",
+                config.date_format.format(last.date)
+            );
+            (weight, previous)
+        }
+        None => Default::default(),
+    };
+    let tmpl = state
+        .page("index.html", &config)
+        .replace("{{profiles}}", &state.profile_nav(profile))
+        .replace("{{base}}", &base)
+        .replace("{{last_weight}}", &last_weight)
+        .replace("{{previous}}", &previous)
+        .replace(
+            "{{hide_form}}",
+            if state.read_only() { " hidden" } else { "" },
+        )
+        .replace("{{filter}}", &filter)
+        .replace("{{table}}", &table)
+        .replace("{{since}}", &since)
+        .replace("{{percentiles}}", &percentiles);
+    let job = Job {
+        profile: name.clone(),
+        tag: tag.clone(),
+    };
+    let graphed = match state.renderer.status(profile, &graph) {
+        Status::Current => true,
+        // the last graph is shown until the new one is ready, and the page
+        // is rendered again then
+        Status::Outdated if state.renderer.queue(job) => false,
+        // a missing graph shouldn't keep the rest of the page from loading,
+        // but the page is rendered again next time to retry
+        _ => {
+            match profile.graph(Span::RECENT, today, tag.as_deref(), &config) {
+                Ok(()) => {
+                    state.renderer.record(profile.name(), graph);
+                    true
+                }
+                Err(e) => {
+                    error!("failed to generate graph: {e}");
+                    false
+                }
+            }
+        }
+    };
+    let tmpl =
+        tmpl.replace("{{graph}}", &graph_src(profile, Span::RECENT, &config));
+    if graphed {
+        state
+            .index_cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(name, (key, tmpl.clone()));
+    }
+    Ok(Response::ok().body(tmpl.into()))
+}
+
+/// The table of the last `rows` entries for the index page, optionally only
+/// those tagged `tag`, along with a note linking to the rest if there are
+/// more. The table is only rendered again once the entries change.
+fn index_table(
+    state: &State,
+    profile: &Profile,
+    tag: Option<&str>,
+    rows: usize,
+) -> (String, String) {
+    let key = TableKey {
+        version: profile.store().version(),
+        tag: tag.map(str::to_owned),
+        rows,
+    };
+    let name = profile.name().map(str::to_owned);
+    if let Some((cached, table, since)) = state
+        .table_cache
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get(&name)
+    {
+        if *cached == key {
+            debug!("using cached table");
+            return (table.clone(), since.clone());
+        }
+    }
+
+    let config = state.config();
+    let base = profile.base();
+    let entries = profile.store().entries();
+    let shown: Vec<_> = entries
+        .iter()
+        .rev()
+        .filter(|e| tag.is_none_or(|t| e.has_tag(t)))
+        .collect();
+    let since = match shown.get(rows.saturating_sub(1)) {
+        Some(oldest) if shown.len() > rows => {
+            let history = match tag {
+                Some(tag) => format!("{base}/history?tag={tag}"),
+                None => format!("{base}/history"),
+            };
+            format!(
+                "<p>The last {rows} of {} entries, since {}. \
+                 <a href=\"{history}\">Show all</a></p>",
+                shown.len(),
+                config.date_format.format(oldest.date)
+            )
+        }
+        _ => String::new(),
+    };
+    let previous = stats::previous_weights(entries.iter());
+    let changes = (&previous, config.good_change_for(profile.name()));
+    let table = html_rows(
+        &base,
+        &config.date_format,
+        config.stones_for(profile.name()),
+        shown.into_iter().take(rows),
+        Columns {
+            changes: Some(changes),
+            ..Columns::default()
+        },
+        None,
+    );
+    state
+        .table_cache
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .insert(name, (key, table.clone(), since.clone()));
+    (table, since)
+}
+
+/// The `src` of an image showing the graph of `span`, which is either
+/// embedded or linked depending on `config`.
+fn graph_src(profile: &Profile, span: Span, config: &Config) -> String {
+    let base = profile.base();
+    let url = match span {
+        Span::RECENT => format!("{base}/graph.png"),
+        span => format!("{base}/graph.png?span={}", span.name()),
+    };
+    image_src(url, &profile.graph_file(span), config)
+}
+
+/// The `src` of an image at `url`, or of the PNG `file` embedded as a
+/// `data:` URI if `config` says to inline graphs.
+fn image_src(url: String, file: &Path, config: &Config) -> String {
+    if !config.inline_graph {
+        return url;
+    }
+    match std::fs::read(file) {
+        Ok(png) => format!("data:image/png;base64,{}", base64_encode(&png)),
+        Err(e) => {
+            error!("failed to read graph: {e}");
+            String::new()
+        }
+    }
+}
+
+/// The chart rendered to `file`, or the 404 page if it hasn't been rendered
+/// yet.
+fn chart_image(
+    request: &Request,
+    state: &State,
+    file: &Path,
+) -> Result<Response> {
+    match std::fs::read(file) {
+        Ok(bytes) => Ok(Response::ok()
+            .content_type(ContentType::Png)
+            .body(Body::Bytes(bytes))
+            .ranged(request)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            Ok(Response::err().body(state.error_page("No such chart.").into()))
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn graph_image(
+    request: &Request,
+    state: &State,
+    profile: &Profile,
+) -> Result<Response> {
+    let span = match request.params().into_iter().find(|(k, _)| k == "span") {
+        Some((_, span)) => match span.parse::<Span>() {
+            Ok(span) => span,
+            Err(e) => {
+                return Ok(
+                    Response::bad_request().body(state.error_page(&e).into())
+                )
+            }
+        },
+        None => Span::RECENT,
+    };
+    Ok(Response::ok()
+        .content_type(ContentType::Png)
+        .body(Body::Bytes(std::fs::read(profile.graph_file(span))?))
+        .ranged(request))
+}
+
+/// The latest entry as JSON for dashboards like Home Assistant's REST
+/// sensor, with its `weight`, `date`, and `tags`, the profile's `unit`, the
+/// 30-day average up to it as the `trend`, and the change since the last
+/// entry at least a week before it. Fields that can't be known yet are null.
+fn api_latest(state: &State, profile: &Profile) -> Response {
+    let unit = state.config().unit_for(profile.name());
+    let entries = profile.store().entries();
+    let latest = entries.iter().max_by_key(|e| e.date);
+    let trend =
+        latest.map(|l| stats::rolling_average(entries.iter(), 30)[&l.date]);
+    let change = latest.and_then(|l| {
+        let week_ago = l.date - time::Duration::weeks(1);
+        let before = entries.iter().filter(|e| e.date <= week_ago);
+        before.max_by_key(|e| e.date).map(|e| l.weight - e.weight)
+    });
+    let number = |n: Option<f64>| match n {
+        Some(n) => format!("{n:.1}"),
+        None => "null".to_owned(),
+    };
+    let tags: Vec<_> = latest
+        .iter()
+        .flat_map(|l| &l.tags)
+        .map(|t| json::string(t))
+        .collect();
+    let body = format!(
+        "{{\"weight\":{},\"unit\":{},\"date\":{},\"tags\":[{}],\
+         \"trend\":{},\"change_7_days\":{},\"entries\":{}}}",
+        number(latest.map(|l| l.weight)),
+        json::string(&unit.to_string()),
+        latest.map_or("null".to_owned(), |l| {
+            json::string(&store::format_date(l.date))
+        }),
+        tags.join(","),
+        number(trend),
+        number(change),
+        entries.len()
+    );
+    Response::ok()
+        .content_type(ContentType::Json)
+        .body(body.into())
+}
+
+/// Every entry of `profile` by date, along with the 30-day rolling average,
+/// tags, and note of each, as parallel lists for the interactive chart.
+fn series(state: &State, profile: &Profile) -> Response {
+    let unit = state.config().unit_for(profile.name());
+    let mut entries = profile.store().entries().clone();
+    entries.sort_by_key(|e| e.date);
+    let trend = stats::rolling_average(entries.iter(), 30);
+    let notes: HashMap<_, _> = profile
+        .notes()
+        .records()
+        .iter()
+        .map(|n| (n.date, n.text.clone()))
+        .collect();
+    let list = |f: &dyn Fn(&Entry) -> String| {
+        entries.iter().map(f).collect::<Vec<_>>().join(",")
+    };
+    let body = format!(
+        "{{\"unit\":{},\"dates\":[{}],\"weight\":[{}],\"trend\":[{}],\
+         \"tags\":[{}],\"notes\":[{}]}}",
+        json::string(&unit.to_string()),
+        list(&|e| json::string(&store::format_date(e.date))),
+        list(&|e| format!("{:.1}", e.weight)),
+        list(&|e| format!("{:.1}", trend[&e.date])),
+        list(&|e| {
+            let tags: Vec<_> = e.tags.iter().map(|t| json::string(t)).collect();
+            format!("[{}]", tags.join(","))
+        }),
+        list(&|e| notes
+            .get(&e.date)
+            .map_or("null".to_owned(), |n| json::string(n))),
+    );
+    Response::ok()
+        .content_type(ContentType::Json)
+        .body(body.into())
+}
+
+/// The script drawing the interactive chart from `/series.json`.
+const CHART_JS: &str = include_str!("../assets/chart.js");
+
+fn chart(state: &State, profile: &Profile) -> Response {
+    let config = state.config();
+    let tmpl = state
+        .page("chart.html", &config)
+        .replace("{{profiles}}", &state.profile_nav(profile))
+        .replace("{{base}}", &profile.base());
+    Response::ok().body(tmpl.into())
+}
+
+/// The endpoints of Grafana's Simple JSON datasource, whose URL is this
+/// profile's `/grafana`. Grafana checks the datasource by requesting the URL
+/// itself.
+fn grafana(
+    request: &Request,
+    state: &State,
+    profile: &Profile,
+    path: &str,
+) -> Result<Response> {
+    let endpoint = path.trim_start_matches("/grafana");
+    if endpoint.is_empty() || endpoint == "/" {
+        return Ok(Response::ok()
+            .content_type(ContentType::Text)
+            .body("weight-watcher\n".into()));
+    }
+    if endpoint == "/search" {
+        return Ok(Response::ok()
+            .content_type(ContentType::Json)
+            .body(grafana::search().into()));
+    }
+    let body = String::from_utf8_lossy(&request.body);
+    let timezone = state.timezone();
+    let entries = profile.store().entries();
+    let result = json::parse(&body).and_then(|body| match endpoint {
+        "/query" => grafana::query(&body, &entries, timezone),
+        _ => grafana::annotations(
+            &body,
+            &entries,
+            &profile.medications().records(),
+            timezone,
+        ),
+    });
+    drop(entries);
+    Ok(match result {
+        Ok(json) => Response::ok()
+            .content_type(ContentType::Json)
+            .body(json.into()),
+        Err(msg) => Response::bad_request()
+            .content_type(ContentType::Text)
+            .body(format!("{msg}\n").into()),
+    })
+}
+
+/// A sentence about where `latest` puts `child` on the growth charts, with
+/// a link to them.
+fn growth_summary(
+    child: &Child,
+    latest: Option<&Entry>,
+    base: &str,
+    unit: Unit,
+) -> String {
+    let link = format!("<a href=\"{base}/growth\">growth charts</a>");
+    let Some(entry) = latest else {
+        return format!("<p>See the {link}.</p>");
+    };
+    let months = child.months(entry.date);
+    let kg = unit.convert(entry.weight, Unit::Kg);
+    match Chart::Weight.percentile(child.sex, months, kg) {
+        Some(p) => format!(
+            "<p>At {}, {:.1} {unit} is around the {} percentile for {}. See \
+             the {link}.</p>",
+            growth::format_age(months),
+            entry.weight,
+            growth::ordinal(p),
+            child.sex
+        ),
+        None => format!("<p>See the {link}.</p>"),
+    }
+}
+
+/// A child's weights and heights plotted against the growth standards, and
+/// the form for recording heights.
+fn growth_page(
+    request: &Request,
+    state: &State,
+    profile: &Profile,
+) -> Result<Response> {
+    use std::fmt::Write;
+    let today = state.today();
+    let config = state.config();
+    let unit = config.unit_for(profile.name());
+    let Some(child) = config.child(profile.name()).copied() else {
+        return Ok(Response::err().body(
+            state
+                .error_page(
+                    "This profile isn't tracking a child. Add it to the \
+                     children setting to see growth charts.",
+                )
+                .into(),
+        ));
+    };
+    if request.method == "POST" {
+        drop(config);
+        return record_height(request, state, profile, today);
+    }
+    std::thread::scope(|s| {
+        for chart in [Chart::Weight, Chart::Height] {
+            s.spawn(move || {
+                if let Err(e) = profile.growth_graph(chart, &child, today, unit)
+                {
+                    error!("failed to generate {} chart: {e}", chart.name());
+                }
+            });
+        }
+    });
+
+    // the last weight of each day alongside that day's height
+    let mut days: BTreeMap<Date, (Option<f64>, Option<f64>)> = BTreeMap::new();
+    for entry in profile.store().entries().iter() {
+        days.entry(entry.date).or_default().0 = Some(entry.weight);
+    }
+    for (date, height) in profile.metrics().series(profile::HEIGHT) {
+        days.entry(date).or_default().1 = Some(height);
+    }
+    let mut table = String::new();
+    for (date, (weight, height)) in days.iter().rev() {
+        if *date < child.birth {
+            continue;
+        }
+        let months = child.months(*date);
+        let cells = |chart: Chart, value: Option<f64>, to_table: f64| {
+            let Some(value) = value else {
+                return "<td></td><td></td>".to_owned();
+            };
+            let percentile = chart
+                .percentile(child.sex, months, value * to_table)
+                .map(growth::ordinal)
+                .unwrap_or_default();
+            format!("<td>{value:.1}</td><td>{percentile}</td>")
+        };
+        writeln!(
+            table,
+            "<tr><td>{}</td><td>{}</td>{}{}</tr>",
+            config.date_format.format(*date),
+            growth::format_age(months),
+            cells(Chart::Weight, *weight, unit.convert(1.0, Unit::Kg)),
+            cells(Chart::Height, *height, 1.0),
+        )
+        .unwrap();
+    }
+
+    let base = profile.base();
+    let src = |chart: Chart| {
+        image_src(
+            format!("{base}/growth.png?chart={}", chart.name()),
+            &profile.growth_file(chart),
+            &config,
+        )
+    };
+    let tmpl = state
+        .page("growth.html", &config)
+        .replace("{{profiles}}", &state.profile_nav(profile))
+        .replace("{{base}}", &base)
+        .replace("{{unit}}", &unit.to_string())
+        .replace("{{sex}}", &child.sex.to_string())
+        .replace("{{age}}", &growth::format_age(child.months(today)))
+        .replace("{{weight_chart}}", &src(Chart::Weight))
+        .replace("{{height_chart}}", &src(Chart::Height))
+        .replace("{{table}}", &table);
+    Ok(Response::ok().body(tmpl.into()))
+}
+
+const MIN_HEIGHT: f64 = 30.0;
+const MAX_HEIGHT: f64 = 200.0;
+
+/// Record the height submitted to the growth page on `date`.
+fn record_height(
+    request: &Request,
+    state: &State,
+    profile: &Profile,
+    date: Date,
+) -> Result<Response> {
+    let params = request.params();
+    let height = params
+        .iter()
+        .find(|(k, _)| k == "h")
+        .and_then(|(_, h)| h.parse::<f64>().ok())
+        .filter(|h| (MIN_HEIGHT..=MAX_HEIGHT).contains(h));
+    let Some(height) = height else {
+        let msg = format!(
+            "Height must be a number of cm between {MIN_HEIGHT} and \
+             {MAX_HEIGHT}."
+        );
+        return Ok(Response::bad_request().body(state.error_page(&msg).into()));
+    };
+    profile.metrics().record(Measurement {
+        date,
+        metric: profile::HEIGHT.to_owned(),
+        value: height,
+    })?;
+    state.events.publish(profile.name(), Event::Changed);
+    state.commit(&format!(
+        "Record height {height:.1} cm{} on {}",
+        for_profile(profile),
+        store::format_date(date)
+    ));
+    Ok(Response::redirect(format!("{}/growth", profile.base())))
+}
+
+fn growth_image(
+    request: &Request,
+    state: &State,
+    profile: &Profile,
+) -> Result<Response> {
+    let chart = match request.params().into_iter().find(|(k, _)| k == "chart") {
+        Some((_, chart)) if chart == "height" => Chart::Height,
+        Some((_, chart)) if chart == "weight" => Chart::Weight,
+        None => Chart::Weight,
+        Some(_) => {
+            return Ok(Response::bad_request().body(
+                state
+                    .error_page("The chart must be weight or height.")
+                    .into(),
+            ))
+        }
+    };
+    Ok(Response::ok()
+        .content_type(ContentType::Png)
+        .body(Body::Bytes(std::fs::read(profile.growth_file(chart))?))
+        .ranged(request))
+}
+
+/// The spans of the graphs on the dashboard.
+const DASHBOARD: [(Span, &str); 3] = [
+    (Span::Days(30), "Last 30 days"),
+    (Span::Days(90), "Last 90 days"),
+    (Span::All, "All time"),
+];
+
+/// Graphs of several spans at once, rendered concurrently.
+fn graphs(state: &State, profile: &Profile) -> Result<Response> {
+    use std::fmt::Write;
+    let today = state.today();
+    let config = state.config();
+    std::thread::scope(|s| {
+        for (span, _) in DASHBOARD {
+            let config = &config;
+            s.spawn(move || {
+                if let Err(e) = profile.graph(span, today, None, config) {
+                    error!("failed to generate {} graph: {e}", span.name());
+                }
+            });
+        }
+    });
+    let mut images = String::new();
+    for (span, label) in DASHBOARD {
+        writeln!(
+            images,
+            "<h3>{label}</h3>\n<img src=\"{}\" \
+             style=\"width:100%;max-width:640px\">",
+            graph_src(profile, span, &config)
+        )
+        .unwrap();
+    }
+    let tmpl = state
+        .page("graphs.html", &config)
+        .replace("{{profiles}}", &state.profile_nav(profile))
+        .replace("{{base}}", &profile.base())
+        .replace("{{graphs}}", &images);
+    Ok(Response::ok().body(tmpl.into()))
+}
+
+/// The range of plausible adult weights in kg, converted to the profile's
+/// unit before checking a weight.
+const MIN_WEIGHT: f64 = 30.0;
+const MAX_WEIGHT: f64 = 250.0;
+
+fn weight(
+    request: &Request,
+    state: &State,
+    profile: &Profile,
+) -> Result<Response> {
+    let params = request.params();
+    let Some((_, w)) = params.iter().find(|(k, _)| k == "w") else {
+        return Ok(Response::bad_request().body(
+            state
+                .error_page("Missing weight in form submission.")
+                .into(),
+        ));
+    };
+    let weight = match read_weight(state, profile, w) {
+        Ok(weight) => weight,
+        Err(msg) => {
+            return Ok(
+                Response::bad_request().body(state.error_page(&msg).into())
+            )
+        }
+    };
+    let Some(tags) = read_tags(&params) else {
+        return Ok(
+            Response::bad_request().body(state.error_page(TAGS_MESSAGE).into())
+        );
+    };
+    let Ok(mood) = read_mood(&params) else {
+        return Ok(
+            Response::bad_request().body(state.error_page(MOOD_MESSAGE).into())
+        );
+    };
+    let note = match params.iter().find(|(k, _)| k == "note") {
+        Some((_, note)) if !note.trim().is_empty() => {
+            match notes::valid_text(note) {
+                Some(note) => Some(note),
+                None => {
+                    let msg = format!(
+                        "Notes can be at most {} characters.",
+                        notes::MAX_LEN
+                    );
+                    return Ok(Response::bad_request()
+                        .body(state.error_page(&msg).into()));
+                }
+            }
+        }
+        _ => None,
+    };
+    let date = state.today();
+    let confirmed = params.iter().any(|(k, v)| k == "confirm" && v == "yes");
+    if !confirmed {
+        let page = confirm_page(
+            state,
+            profile,
+            date,
+            weight,
+            &tags,
+            mood,
+            note.as_deref(),
+        );
+        if let Some(page) = page {
+            return Ok(Response::ok().body(page.into()));
+        }
+    }
+    let entry = Entry { date, weight, tags };
+    // the mood and note go first so that the weight's commit includes them
+    let save = || {
+        if let Some(mood) = mood {
+            profile.metrics().record(Measurement {
+                date,
+                metric: metrics::MOOD.to_owned(),
+                value: mood.into(),
+            })?;
+        }
+        if let Some(text) = note {
+            profile.notes().record(Note { date, text })?;
+        }
+        record(state, profile, entry, source(request, state))
+    };
+    // say why rather than just that something went wrong, since the weight
+    // has to be entered again
+    if let Err(e) = save() {
+        let msg = format!("Your weight couldn't be saved: {e}.");
+        return Ok(
+            Response::internal_error().body(state.error_page(&msg).into())
+        );
+    }
+    // relative to the weight URL, so this goes back to the profile's page
+    Ok(Response::redirect(format!("{}/", profile.base())))
+}
+
+/// Record the weight in the `w` parameter for the profile of the quick-log
+/// `token`, and any `tags`. There is no confirmation page, and the answer is
+/// plain text for whatever shortcut or script sent the request.
+fn quick_log(
+    request: &Request,
+    state: &State,
+    token: &str,
+) -> Result<Response> {
+    let text = |response: Response, msg: String| {
+        response
+            .content_type(ContentType::Text)
+            .body(format!("{msg}\n").into())
+    };
+    let Some(log_token) = state.log_tokens.verify(token) else {
+        warn!("rejected quick-log token");
+        return Ok(text(
+            Response::forbidden(),
+            "This link has been revoked or is wrong.".to_owned(),
+        ));
+    };
+    let Some(profile) = state.profiles().get(log_token.profile.as_deref())
+    else {
+        return Ok(text(
+            Response::err(),
+            "This link's profile no longer exists.".to_owned(),
+        ));
+    };
+    let params = request.params();
+    let Some((_, w)) = params.iter().find(|(k, _)| k == "w") else {
+        return Ok(text(Response::bad_request(), "Missing weight.".to_owned()));
+    };
+    let weight = match read_weight(state, &profile, w) {
+        Ok(weight) => weight,
+        Err(msg) => return Ok(text(Response::bad_request(), msg)),
+    };
+    let Some(tags) = read_tags(&params) else {
+        return Ok(text(Response::bad_request(), TAGS_MESSAGE.to_owned()));
+    };
+    debug!(
+        token = log_token.id,
+        "recording weight with quick-log token"
+    );
+    let entry = Entry {
+        date: state.today(),
+        weight,
+        tags,
+    };
+    if let Err(e) = record(state, &profile, entry, Source::Api) {
+        return Ok(text(
+            Response::internal_error(),
+            format!("The weight couldn't be saved: {e}."),
+        ));
+    }
+    let unit = state.config().unit_for(profile.name());
+    Ok(text(
+        Response::ok(),
+        format!("Recorded {weight:.1} {unit}."),
+    ))
+}
+
+/// Read the weight `w` entered for `profile`, or say what is wrong with it.
+fn read_weight(
+    state: &State,
+    profile: &Profile,
+    w: &str,
+) -> std::result::Result<f64, String> {
+    // adults' weights are checked against a plausible range to catch
+    // typos, while children and pets can weigh almost anything
+    let (adult, stones, unit) = {
+        let config = state.config();
+        let name = profile.name();
+        let adult = !config.is_pet(name) && config.child(name).is_none();
+        (adult, config.stones_for(name), config.unit_for(name))
+    };
+    let bound = |kg: f64| Unit::Kg.convert(kg, unit).round();
+    let (min, max) = (bound(MIN_WEIGHT), bound(MAX_WEIGHT));
+    // plain numbers are still taken to be in the recorded unit, which is
+    // also how the confirmation page sends the weight back
+    let parsed = stones
+        .and_then(|unit| units::parse_stones(w, unit))
+        .or_else(|| store::parse_weight(w))
+        .map(store::round_weight);
+    match parsed {
+        Some(w) if adult && (min..=max).contains(&w) => Ok(w),
+        Some(w) if !adult && store::valid_weight(w) => Ok(w),
+        _ => Err(if stones.is_some() {
+            format!(
+                "Weight must be stones and pounds like 12st 10, or a \
+                 number of {unit} between {min} and {max}."
+            )
+        } else if adult {
+            format!(
+                "Weight must be a number of {unit} between {min} and \
+                 {max}."
+            )
+        } else {
+            format!(
+                "Weight must be a positive number below {}.",
+                store::WEIGHT_LIMIT
+            )
+        }),
+    }
+}
+
+const TAGS_MESSAGE: &str = "Tags may only contain letters, digits, - and _, \
+                            separated by spaces or commas.";
+
+/// The tags in the `tags` parameter, if any, or `None` if they are invalid.
+fn read_tags(params: &[(String, String)]) -> Option<Vec<String>> {
+    params
+        .iter()
+        .find(|(k, _)| k == "tags")
+        .map_or(Some(Vec::new()), |(_, tags)| store::parse_tags(tags))
+}
+
+const MOOD_MESSAGE: &str = "The mood must be a whole number from 1 to 5.";
+
+/// The 1 to 5 rating in the optional `mood` parameter, or `Err` if it is
+/// invalid.
+fn read_mood(
+    params: &[(String, String)],
+) -> std::result::Result<Option<u8>, ()> {
+    match params.iter().find(|(k, _)| k == "mood") {
+        Some((_, mood)) if !mood.is_empty() => match mood.parse() {
+            Ok(mood @ 1..=5) => Ok(Some(mood)),
+            _ => Err(()),
+        },
+        _ => Ok(None),
+    }
+}
+
+/// Save a new `entry` for `profile`, replacing the day's entry instead if
+/// the `overwrite_same_day` setting is on.
+fn record(
+    state: &State,
+    profile: &Profile,
+    entry: Entry,
+    source: Source,
+) -> io::Result<()> {
+    let saved = if state.config().overwrite_same_day {
+        profile.store().replace(entry.clone())
+    } else {
+        profile.store().append(entry.clone()).map(|()| Vec::new())
+    };
+    let old = saved.inspect_err(|e| error!("failed to save {entry}: {e}"))?;
+    state.audit(source, profile, replaced(old, &entry));
+    let message = format!(
+        "Record {:.1}{} on {}",
+        entry.weight,
+        for_profile(profile),
+        store::format_date(entry.date)
+    );
+    state.events.publish(profile.name(), Event::Added(entry));
+    state.commit(&message);
+    Ok(())
+}
+
+/// A page asking whether to save `weight` anyway, if it differs from the
+/// latest entry up to `date` by more than the `confirm_change` setting.
+fn confirm_page(
+    state: &State,
+    profile: &Profile,
+    date: Date,
+    weight: f64,
+    tags: &[String],
+    mood: Option<u8>,
+    note: Option<&str>,
+) -> Option<String> {
+    let config = state.config();
+    let threshold = config.confirm_change?;
+    let entries = profile.store().entries();
+    let previous = entries
+        .iter()
+        .filter(|e| e.date <= date)
+        .max_by_key(|e| e.date)?;
+    let change = weight - previous.weight;
+    if change.abs() <= threshold {
+        return None;
+    }
+    let since = match (date - previous.date).whole_days() {
+        0 => "earlier today".to_owned(),
+        1 => "yesterday".to_owned(),
+        _ => config.date_format.format(previous.date),
+    };
+    let stones = config.stones_for(profile.name());
+    let message = format!(
+        "That's {change:+.1} {} since {since}, when you recorded {}. Save {} \
+         anyway?",
+        config.unit_for(profile.name()),
+        units::format_weight(previous.weight, stones),
+        units::format_weight(weight, stones),
+    );
+    Some(
+        state
+            .page("confirm.html", &config)
+            .replace("{{base}}", &profile.base())
+            .replace("{{message}}", &message)
+            .replace("{{weight}}", &weight.to_string())
+            .replace("{{tags}}", &tags.join(" "))
+            .replace(
+                "{{mood}}",
+                &mood.map(|m| m.to_string()).unwrap_or_default(),
+            )
+            .replace("{{note}}", &escape_html(note.unwrap_or_default())),
+    )
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum SortKey {
+    Date,
+    Weight,
+}
+
+/// How many days on either side of the date jumped to on the history page
+/// are shown with it.
+const JUMP_CONTEXT_DAYS: i64 = 3;
+
+/// Sorting and filtering options for the history table, taken from the query
+/// string of a `/history` request.
+struct HistoryQuery {
+    sort: SortKey,
+    descending: bool,
+    year: Option<i32>,
+    month: Option<u8>,
+    above: Option<f64>,
+    below: Option<f64>,
+    tag: Option<String>,
+
+    /// A date to jump to, showing the week around it
+    date: Option<Date>,
+}
+
+impl HistoryQuery {
+    fn parse(
+        params: Vec<(String, String)>,
+    ) -> std::result::Result<Self, String> {
+        fn num<T: std::str::FromStr>(
+            key: &str,
+            value: &str,
+        ) -> std::result::Result<T, String> {
+            value.parse().map_err(|_| {
+                format!("Invalid value for {key}: must be a number.")
+            })
+        }
+        let mut ret = Self {
+            sort: SortKey::Date,
+            descending: true,
+            year: None,
+            month: None,
+            above: None,
+            below: None,
+            tag: None,
+            date: None,
+        };
+        for (key, value) in params {
+            if value.is_empty() {
+                continue;
+            }
+            match key.as_str() {
+                "sort" => {
+                    ret.sort = match value.as_str() {
+                        "date" => SortKey::Date,
+                        "weight" => SortKey::Weight,
+                        _ => {
+                            return Err(
+                                "Sort must be either date or weight.".into()
+                            )
+                        }
+                    }
+                }
+                "order" => {
+                    ret.descending = match value.as_str() {
+                        "asc" => false,
+                        "desc" => true,
+                        _ => return Err("Order must be asc or desc.".into()),
+                    }
+                }
+                "year" => ret.year = Some(num(&key, &value)?),
+                "month" => {
+                    let month = num(&key, &value)?;
+                    if !(1..=12).contains(&month) {
+                        return Err("Month must be between 1 and 12.".into());
+                    }
+                    ret.month = Some(month);
+                }
+                "above" => ret.above = Some(num(&key, &value)?),
+                "below" => ret.below = Some(num(&key, &value)?),
+                "tag" => {
+                    if !store::valid_tag(&value) {
+                        return Err("Tags may only contain lowercase \
+                                    letters, digits, - and _."
+                            .into());
+                    }
+                    ret.tag = Some(value);
+                }
+                "date" => {
+                    ret.date =
+                        Some(store::parse_date(&value).ok_or(
+                            "Dates must look like 2024-03-01.".to_owned(),
+                        )?)
+                }
+                _ => {}
+            }
+        }
+        Ok(ret)
+    }
+
+    fn matches(&self, entry: &Entry) -> bool {
+        self.year.is_none_or(|y| entry.date.year() == y)
+            && self.month.is_none_or(|m| entry.date.month() as u8 == m)
+            && self.above.is_none_or(|a| entry.weight > a)
+            && self.below.is_none_or(|b| entry.weight < b)
+            && self.tag.as_deref().is_none_or(|t| entry.has_tag(t))
+            && self.date.is_none_or(|d| {
+                (entry.date - d).whole_days().abs() <= JUMP_CONTEXT_DAYS
+            })
+    }
+}
+
+fn history(
+    request: &Request,
+    state: &State,
+    profile: &Profile,
+) -> Result<Response> {
+    let query = match HistoryQuery::parse(request.params()) {
+        Ok(q) => q,
+        Err(e) => {
+            return Ok(Response::bad_request().body(state.error_page(&e).into()))
+        }
+    };
+    let entries = profile.store().entries();
+    let mut rows: Vec<_> = entries
+        .iter()
+        .filter(|entry| query.matches(entry))
+        .collect();
+    match query.sort {
+        SortKey::Date => rows.sort_by_key(|e| e.date),
+        SortKey::Weight => rows.sort_by(|a, b| a.weight.total_cmp(&b.weight)),
+    }
+    if query.descending {
+        rows.reverse();
+    }
+    let edited: HashSet<_> = state
+        .audit
+        .changes()?
+        .into_iter()
+        .filter(|c| {
+            c.action == Action::Edit && c.profile.as_deref() == profile.name()
+        })
+        .filter_map(|c| Some(c.new?.date))
+        .collect();
+    let config = state.config();
+    let averages = stats::rolling_average(entries.iter(), 30);
+    let previous = stats::previous_weights(entries.iter());
+    let notice = match query.date {
+        Some(date) if !rows.iter().any(|e| e.date == date) => format!(
+            "<p>There is no entry on {}{}.</p>",
+            config.date_format.format(date),
+            if rows.is_empty() {
+                " or in the week around it"
+            } else {
+                ", so the entries around it are shown"
+            }
+        ),
+        _ => String::new(),
+    };
+    let table = html_rows(
+        &profile.base(),
+        &config.date_format,
+        config.stones_for(profile.name()),
+        rows.into_iter(),
+        Columns {
+            changes: Some((&previous, config.good_change_for(profile.name()))),
+            averages: Some(&averages),
+            edited: Some(&edited),
+            read_only: state.read_only(),
+        },
+        query.date,
+    );
+
+    let show = |v: Option<String>| v.unwrap_or_default();
+    let sort = if query.sort == SortKey::Date {
+        "date"
+    } else {
+        "weight"
+    };
+    let order = if query.descending { "desc" } else { "asc" };
+    let tmpl = state
+        .page("history.html", &config)
+        .replace("{{profiles}}", &state.profile_nav(profile))
+        .replace("{{base}}", &profile.base())
+        .replace(
+            "{{sort_options}}",
+            &options(&[("date", "Date"), ("weight", "Weight")], sort),
+        )
+        .replace(
+            "{{order_options}}",
+            &options(&[("desc", "Descending"), ("asc", "Ascending")], order),
+        )
+        .replace("{{year}}", &show(query.year.map(|y| y.to_string())))
+        .replace("{{month}}", &show(query.month.map(|m| m.to_string())))
+        .replace("{{above}}", &show(query.above.map(|a| a.to_string())))
+        .replace("{{below}}", &show(query.below.map(|b| b.to_string())))
+        .replace("{{tag}}", &show(query.tag))
+        .replace("{{date}}", &show(query.date.map(store::format_date)))
+        .replace("{{notice}}", &notice)
+        .replace("{{table}}", &table);
+    Ok(Response::ok().body(tmpl.into()))
+}
+
+/// Move the entry in the form to the trash.
+fn delete(
+    request: &Request,
+    state: &State,
+    profile: &Profile,
+) -> Result<Response> {
+    let params = request.params();
+    let entry = params
+        .iter()
+        .find(|(k, _)| k == "entry")
+        .and_then(|(_, entry)| Entry::parse(entry));
+    let Some(entry) = entry else {
+        return Ok(Response::bad_request().body(
+            state
+                .error_page("Missing or invalid entry in form submission.")
+                .into(),
+        ));
+    };
+    // the form sends the entry as it is shown, so it is matched the same
+    // way in case the data file keeps more decimal places
+    let shown = entry.to_string();
+    let stored = profile
+        .store()
+        .entries()
+        .iter()
+        .find(|e| e.to_string() == shown)
+        .cloned();
+    let Some(entry) = stored else {
+        return Ok(Response::err().body(
+            state
+                .error_page("That entry has already been deleted.")
+                .into(),
+        ));
+    };
+    let today = state.today();
+    // the entry goes into the trash first so that it can't be lost if
+    // removing it fails partway
+    profile.trash().record(Deleted {
+        date: today,
+        entry: entry.clone(),
+    })?;
+    profile.store().remove(&entry)?;
+    empty_trash(state, profile, today)?;
+    state.audit(
+        source(request, state),
+        profile,
+        [(Action::Delete, Some(entry.clone()), None)],
+    );
+    state.events.publish(profile.name(), Event::Changed);
+    state.commit(&format!(
+        "Delete {:.1}{} on {}",
+        entry.weight,
+        for_profile(profile),
+        store::format_date(entry.date)
+    ));
+    Ok(Response::redirect(back(request, profile, "history")))
+}
+
+/// Permanently remove the entries that have been in `profile`'s trash for
+/// longer than the `trash_days` setting.
+fn empty_trash(state: &State, profile: &Profile, today: Date) -> Result<()> {
+    let days = state.config().trash_days;
+    let expired = profile
+        .trash()
+        .remove(|d| (today - d.date).whole_days() < i64::from(days))?;
+    if !expired.is_empty() {
+        info!(removed = expired.len(), "emptied trash");
+    }
+    Ok(())
+}
+
+/// The deleted entries, newest first, with buttons for restoring them.
+fn trash(
+    request: &Request,
+    state: &State,
+    profile: &Profile,
+) -> Result<Response> {
+    use std::fmt::Write;
+    if request.method == "POST" {
+        return restore_deleted(request, state, profile);
+    }
+    let today = state.today();
+    empty_trash(state, profile, today)?;
+    let config = state.config();
+    let base = profile.base();
+    let mut table = String::new();
+    for deleted in profile.trash().records().iter().rev() {
+        let Entry { date, weight, tags } = &deleted.entry;
+        writeln!(
+            table,
+            "<tr><td>{date}</td><td>{weight:.1}</td><td>{tags}</td>\
+             <td>{deleted_on}</td><td><form action=\"{base}/trash\" \
+             method=\"post\"><input type=\"hidden\" name=\"deleted\" \
+             value=\"{deleted}\" /><button>Restore</button></form></td></tr>",
+            date = config.date_format.format(*date),
+            tags = tags
+                .iter()
+                .map(|t| format!("#{t}"))
+                .collect::<Vec<_>>()
+                .join(" "),
+            deleted_on = config.date_format.format(deleted.date),
+        )
+        .unwrap();
+    }
+    let days = match config.trash_days {
+        1 => "1 day".to_owned(),
+        n => format!("{n} days"),
+    };
+    let tmpl = state
+        .page("trash.html", &config)
+        .replace("{{profiles}}", &state.profile_nav(profile))
+        .replace("{{base}}", &base)
+        .replace("{{days}}", &days)
+        .replace("{{table}}", &table);
+    Ok(Response::ok().body(tmpl.into()))
+}
+
+/// Put the deleted entry in the form back into the data file.
+fn restore_deleted(
+    request: &Request,
+    state: &State,
+    profile: &Profile,
+) -> Result<Response> {
+    let params = request.params();
+    let deleted = params
+        .iter()
+        .find(|(k, _)| k == "deleted")
+        .and_then(|(_, deleted)| Deleted::parse(deleted));
+    let Some(deleted) = deleted else {
+        return Ok(Response::bad_request().body(
+            state
+                .error_page("Missing or invalid entry in form submission.")
+                .into(),
+        ));
+    };
+    // only one copy comes back if the same entry was deleted twice
+    let mut found = false;
+    let removed = profile.trash().remove(|d| {
+        let matches = !found && *d == deleted;
+        found |= matches;
+        !matches
+    })?;
+    let Some(Deleted { entry, .. }) = removed.into_iter().next() else {
+        return Ok(Response::err().body(
+            state
+                .error_page("That entry is no longer in the trash.")
+                .into(),
+        ));
+    };
+    profile.store().insert(entry.clone())?;
+    state.audit(
+        source(request, state),
+        profile,
+        [(Action::Restore, None, Some(entry.clone()))],
+    );
+    state.events.publish(profile.name(), Event::Changed);
+    state.commit(&format!(
+        "Restore {:.1}{} on {}",
+        entry.weight,
+        for_profile(profile),
+        store::format_date(entry.date)
+    ));
+    Ok(Response::redirect(format!("{}/trash", profile.base())))
+}
+
+/// The audit log changes for recording `new` in place of the `old` entries
+/// on its date.
+fn replaced(
+    old: Vec<Entry>,
+    new: &Entry,
+) -> Vec<(Action, Option<Entry>, Option<Entry>)> {
+    let mut old = old.into_iter();
+    let Some(first) = old.next() else {
+        return vec![(Action::Create, None, Some(new.clone()))];
+    };
+    std::iter::once((Action::Edit, Some(first), Some(new.clone())))
+        .chain(old.map(|e| (Action::Delete, Some(e), None)))
+        .collect()
+}
+
+/// Every value the `date` query parameter's entries have had according to
+/// the audit log, newest first, with buttons for reverting to each one.
+fn versions(
+    request: &Request,
+    state: &State,
+    profile: &Profile,
+) -> Result<Response> {
+    use std::fmt::Write;
+    let params = request.params();
+    let field = |name: &str| {
+        params
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+            .unwrap_or_default()
+    };
+    let Some(date) = store::parse_date(field("date")) else {
+        return Ok(Response::bad_request()
+            .body(state.error_page("Missing or invalid date.").into()));
+    };
+    let changes: Vec<_> = state
+        .audit
+        .changes()?
+        .into_iter()
+        .filter(|c| c.profile.as_deref() == profile.name())
+        .collect();
+    if request.method == "POST" {
+        // only values the entry has had can be reverted to, so that this
+        // isn't a way around the checks on entering a weight
+        let entry = Entry::parse(field("entry")).filter(|e| {
+            e.date == date
+                && changes.iter().any(|c| {
+                    c.old.as_ref() == Some(e) || c.new.as_ref() == Some(e)
+                })
+        });
+        let Some(entry) = entry else {
+            return Ok(Response::bad_request().body(
+                state
+                    .error_page("Missing or invalid entry in form submission.")
+                    .into(),
+            ));
+        };
+        // the entry the value replaces, or none to add it back on a date
+        // with no entries left
+        let change = match field("current") {
+            "" if profile.store().entries().iter().all(|e| e.date != date) => {
+                profile.store().insert(entry.clone())?;
+                (Action::Create, None, Some(entry.clone()))
+            }
+            current => {
+                let current = Entry::parse(current).filter(|e| e.date == date);
+                let swapped = match &current {
+                    Some(current) => {
+                        profile.store().swap(current, entry.clone())?
+                    }
+                    None => false,
+                };
+                if !swapped {
+                    return Ok(Response::err()
+                        .body(state.error_page("No such entry.").into()));
+                }
+                (Action::Edit, current, Some(entry.clone()))
+            }
+        };
+        state.audit(source(request, state), profile, [change]);
+        state.events.publish(profile.name(), Event::Changed);
+        state.commit(&format!(
+            "Revert to {:.1}{} on {}",
+            entry.weight,
+            for_profile(profile),
+            store::format_date(date)
+        ));
+        return Ok(Response::redirect(format!(
+            "{}/versions?date={}",
+            profile.base(),
+            store::format_date(date)
+        )));
+    }
+
+    // each value along with when it was recorded, if that is in the log
+    let mut versions: Vec<(Option<time::OffsetDateTime>, Entry)> = Vec::new();
+    for change in &changes {
+        if let Some(old) = change.old.clone().filter(|e| e.date == date) {
+            if !versions.iter().any(|(_, e)| *e == old) {
+                versions.push((None, old));
+            }
+        }
+        if let Some(new) = change.new.clone().filter(|e| e.date == date) {
+            versions.push((Some(change.time), new));
+        }
+    }
+    let current = profile.store().entries();
+    let timezone = state.timezone();
+    let config = state.config();
+    let base = profile.base();
+    let mut table = String::new();
+    for (time, entry) in versions.iter().rev() {
+        let recorded = match time {
+            Some(time) => {
+                let time = timezone.convert(*time);
+                format!(
+                    "{} {:02}:{:02}",
+                    config.date_format.format(time.date()),
+                    time.hour(),
+                    time.minute()
+                )
+            }
+            None => String::new(),
+        };
+        let action = if current.contains(entry) {
+            "Current".to_owned()
+        } else if let Some(replaces) = successor(&changes, entry, &current) {
+            format!(
+                "<form action=\"{base}/versions\" method=\"post\">\
+                 <input type=\"hidden\" name=\"date\" value=\"{}\" />\
+                 <input type=\"hidden\" name=\"entry\" value=\"{entry}\" />\
+                 <input type=\"hidden\" name=\"current\" value=\"{}\" />\
+                 <button>Revert</button></form>",
+                store::format_date(date),
+                replaces.map(Entry::to_string).unwrap_or_default()
+            )
+        } else {
+            String::new()
+        };
+        let tags: Vec<_> = entry.tags.iter().map(|t| format!("#{t}")).collect();
+        writeln!(
+            table,
+            "<tr><td>{:.1}</td><td>{}</td><td>{recorded}</td><td>{action}</td>\
+             </tr>",
+            entry.weight,
+            tags.join(" ")
+        )
+        .unwrap();
+    }
+    let tmpl = state
+        .page("versions.html", &config)
+        .replace("{{profiles}}", &state.profile_nav(profile))
+        .replace("{{base}}", &base)
+        .replace("{{date}}", &config.date_format.format(date))
+        .replace("{{table}}", &table);
+    Ok(Response::ok().body(tmpl.into()))
+}
+
+/// The entry among `current` that `entry` was changed into by the edits in
+/// `changes`, for reverting it. The single entry on its date stands in when
+/// that can't be followed, and `Some(None)` means there are none to replace.
+fn successor<'a>(
+    changes: &[audit::Change],
+    entry: &Entry,
+    current: &'a [Entry],
+) -> Option<Option<&'a Entry>> {
+    let mut value = entry.clone();
+    for change in changes {
+        if change.old.as_ref() != Some(&value) {
+            continue;
+        }
+        match &change.new {
+            Some(new) => value = new.clone(),
+            None => break,
+        }
+    }
+    if let Some(e) = current.iter().find(|e| **e == value) {
+        return Some(Some(e));
+    }
+    let mut same_day = current.iter().filter(|e| e.date == entry.date);
+    match (same_day.next(), same_day.next()) {
+        (None, _) => Some(None),
+        (Some(e), None) => Some(Some(e)),
+        _ => None,
+    }
+}
+
+/// A table of each month's statistics, newest first.
+fn monthly(state: &State, profile: &Profile) -> Result<Response> {
+    use std::fmt::Write;
+    let entries = profile.store().entries();
+    let config = state.config();
+    let months = match config.weigh_in_day {
+        Some(day) => stats::monthly(stats::official(entries.iter(), day)),
+        None => stats::monthly(entries.iter()),
+    };
+    let base = profile.base();
+    let good = config.good_change_for(profile.name());
+    let mut table = String::new();
+    for month in months.iter().rev() {
+        writeln!(
+            table,
+            "<tr><td><a href=\"{base}/history?year={year}&amp;month={num}\">\
+             {name} {year}</a></td><td>{count}</td><td>{average:.1}</td>\
+             <td>{min:.1}</td><td>{max:.1}</td>{change}<td>\
+             <a href=\"{base}/report/{year}-{num:02}\">Report</a></td></tr>",
+            year = month.year,
+            num = month.month as u8,
+            name = month.month,
+            count = month.count,
+            average = month.average,
+            min = month.min,
+            max = month.max,
+            change = change_cell(month.change, good),
+        )
+        .unwrap();
+    }
+    let tmpl = state
+        .page("monthly.html", &config)
+        .replace("{{profiles}}", &state.profile_nav(profile))
+        .replace("{{base}}", &base)
+        .replace("{{table}}", &table);
+    Ok(Response::ok().body(tmpl.into()))
+}
+
+/// The first and last dates of a month like `2024-05`.
+fn month_dates(month: &str) -> Option<(Date, Date)> {
+    let (year, month) = month.split_once('-')?;
+    if year.len() != 4 || month.len() != 2 {
+        return None;
+    }
+    let year = year.parse().ok()?;
+    let month = time::Month::try_from(month.parse::<u8>().ok()?).ok()?;
+    let first = Date::from_calendar_date(year, month, 1).ok()?;
+    let last = first
+        .replace_day(time::util::days_in_year_month(year, month))
+        .ok()?;
+    Some((first, last))
+}
+
+/// A page for printing or saving as a PDF with the graph, statistics, and
+/// entries of one `month`, like `2024-05`.
+fn report(state: &State, profile: &Profile, month: &str) -> Result<Response> {
+    use std::fmt::Write;
+    let Some((first, last)) = month_dates(month) else {
+        return Ok(Response::bad_request()
+            .body(state.error_page("Expected a month like 2024-05.").into()));
+    };
+    let config = state.config();
+    let unit = config.unit_for(profile.name());
+    let stones = config.stones_for(profile.name());
+    let weight = |w: f64| match stones {
+        Some(_) => units::format_weight(w, stones),
+        None => format!("{w:.1} {unit}"),
+    };
+    let base = profile.base();
+    let name = format!("{} {}", first.month(), first.year());
+    let entries = profile.store().entries();
+    let months = match config.weigh_in_day {
+        Some(day) => stats::monthly(stats::official(entries.iter(), day)),
+        None => stats::monthly(entries.iter()),
+    };
+    let index = months
+        .iter()
+        .position(|m| (m.year, m.month) == (first.year(), first.month()));
+    let mut shown: Vec<_> = entries
+        .iter()
+        .filter(|e| (first..=last).contains(&e.date))
+        .collect();
+    shown.sort_by_key(|e| e.date);
+
+    let mut summary = String::new();
+    let mut graph = String::new();
+    match index {
+        Some(i) => {
+            let month = &months[i];
+            let mut rows = String::new();
+            let mut row = |label: &str, value: String| {
+                writeln!(rows, "<tr><th>{label}</th><td>{value}</td></tr>")
+                    .unwrap();
+            };
+            row("Entries", month.count.to_string());
+            row("Average", weight(month.average));
+            row("Lowest", weight(month.min));
+            row("Highest", weight(month.max));
+            row("Change", format!("{:+.1} {unit}", month.change));
+            if let Some(previous) = i.checked_sub(1).map(|i| &months[i]) {
+                row(
+                    &format!(
+                        "Average compared to {} {}",
+                        previous.month, previous.year
+                    ),
+                    format!("{:+.1} {unit}", month.average - previous.average),
+                );
+            }
+            summary = format!("<table class=\"summary\">\n{rows}</table>");
+            match profile.report_graph(first, last, &config) {
+                Ok(png) => {
+                    graph = format!(
+                        "<img src=\"data:image/png;base64,{}\" \
+                         alt=\"Graph of {name}\">",
+                        base64_encode(&png)
+                    );
+                }
+                Err(e) => error!("failed to generate report graph: {e}"),
+            }
+        }
+        None => writeln!(summary, "<p>No entries in {name}.</p>").unwrap(),
+    }
+    let averages = stats::rolling_average(entries.iter(), 30);
+    let previous = stats::previous_weights(entries.iter());
+    let table = html_rows(
+        &base,
+        &config.date_format,
+        stones,
+        shown.into_iter(),
+        Columns {
+            changes: Some((&previous, config.good_change_for(profile.name()))),
+            averages: Some(&averages),
+            ..Columns::default()
+        },
+        None,
+    );
+    let created = match profile.name() {
+        Some(name) => format!(
+            "Profile {}, created {}.",
+            escape_html(name),
+            config.date_format.format(state.today())
+        ),
+        None => {
+            format!("Created {}.", config.date_format.format(state.today()))
+        }
+    };
+    let previous = first - time::Duration::DAY;
+    let next = last + time::Duration::DAY;
+    let tmpl = state
+        .page("report.html", &config)
+        .replace("{{profiles}}", &state.profile_nav(profile))
+        .replace("{{base}}", &base)
+        .replace(
+            "{{previous}}",
+            &format!("{}-{:02}", previous.year(), previous.month() as u8),
+        )
+        .replace(
+            "{{next}}",
+            &format!("{}-{:02}", next.year(), next.month() as u8),
+        )
+        .replace("{{month}}", &name)
+        .replace("{{from}}", &store::format_date(first))
+        .replace("{{to}}", &store::format_date(last))
+        .replace("{{created}}", &created)
+        .replace("{{graph}}", &graph)
+        .replace("{{summary}}", &summary)
+        .replace("{{table}}", &table);
+    Ok(Response::ok().body(tmpl.into()))
+}
+
+/// A PDF report on the entries between the `from` and `to` dates in the
+/// query, which default to the 30 days up to today.
+fn report_pdf(request: &Request, state: &State, profile: &Profile) -> Response {
+    let params = request.params();
+    let date = |key: &str| match params.iter().find(|(k, _)| k == key) {
+        Some((_, v)) if !v.is_empty() => {
+            store::parse_date(v).map(Some).ok_or(())
+        }
+        _ => Ok(None),
+    };
+    let today = state.today();
+    let (from, to) = match (date("from"), date("to")) {
+        (Ok(from), Ok(to)) => {
+            let to = to.unwrap_or(today);
+            (from.unwrap_or(to - time::Duration::days(29)), to)
+        }
+        _ => {
+            return Response::bad_request().body(
+                state.error_page("Expected dates like 2024-05-01.").into(),
+            )
+        }
+    };
+    if from > to {
+        return Response::bad_request().body(
+            state
+                .error_page("The report has to start before it ends.")
+                .into(),
+        );
+    }
+    let config = state.config();
+    let entries = profile.store().entries();
+    let mut shown: Vec<_> = entries
+        .iter()
+        .filter(|e| (from..=to).contains(&e.date))
+        .collect();
+    shown.sort_by_key(|e| e.date);
+    let trend = stats::rolling_average(entries.iter(), 30);
+    let pdf = pdf::Report {
+        title: &config.title,
+        profile: profile.name(),
+        from,
+        to,
+        today,
+        entries: &shown,
+        trend: &trend,
+        unit: config.unit_for(profile.name()),
+        stones: config.stones_for(profile.name()),
+        date_format: &config.date_format,
+        weigh_in_day: config.weigh_in_day,
+        goal: config.goal_for(profile.name()),
+    }
+    .render();
+    let filename = format!(
+        "weight-report-{}-to-{}.pdf",
+        store::format_date(from),
+        store::format_date(to)
+    );
+    Response::ok()
+        .content_type(ContentType::Pdf)
+        .header(
+            "Content-Disposition",
+            format!("attachment; filename=\"{filename}\""),
+        )
+        .body(Body::Bytes(pdf))
+}
+
+/// A table of how weight changed around each tag's periods.
+fn tags(state: &State, profile: &Profile) -> Result<Response> {
+    use std::fmt::Write;
+    let effects = stats::tags::effects(profile.store().entries().iter());
+    let base = profile.base();
+    let config = state.config();
+    let mut table = String::new();
+    for effect in effects {
+        writeln!(
+            table,
+            "<tr><td><a href=\"{base}/history?tag={tag}\">#{tag}</a></td>\
+             <td>{periods}</td><td>{change:+.1} {unit}</td></tr>",
+            tag = effect.tag,
+            periods = effect.periods,
+            change = effect.average_change,
+            unit = config.unit_for(profile.name()),
+        )
+        .unwrap();
+    }
+    let tmpl = state
+        .page("tags.html", &config)
+        .replace("{{profiles}}", &state.profile_nav(profile))
+        .replace("{{base}}", &base)
+        .replace("{{table}}", &table);
+    Ok(Response::ok().body(tmpl.into()))
+}
+
+/// The table and chart of tape measurements, and the form for adding to
+/// them.
+fn measurements(
+    request: &Request,
+    state: &State,
+    profile: &Profile,
+) -> Result<Response> {
+    use std::fmt::Write;
+    let today = state.today();
+    if request.method == "POST" {
+        return record_measurements(request, state, profile, today);
+    }
+    let config = state.config();
+    let mut days: BTreeMap<Date, [Option<f64>; metrics::BODY.len()]> =
+        BTreeMap::new();
+    for m in profile.metrics().records().iter() {
+        if let Some(i) = metrics::BODY.iter().position(|(k, _)| *k == m.metric)
+        {
+            days.entry(m.date).or_default()[i] = Some(m.value);
+        }
+    }
+    let mut table = String::new();
+    for (date, values) in days.iter().rev() {
+        write!(table, "<tr><td>{}</td>", config.date_format.format(*date))
+            .unwrap();
+        for value in values {
+            match value {
+                Some(value) => write!(table, "<td>{value:.1}</td>").unwrap(),
+                None => table.push_str("<td></td>"),
+            }
+        }
+        table.push_str("</tr>\n");
+    }
+    let chart = if days.is_empty() {
+        String::new()
+    } else {
+        match profile.measurements_graph(today, &config) {
+            Ok(()) => format!(
+                "<img src=\"{}\" style=\"width:100%;max-width:640px\">",
+                image_src(
+                    format!("{}/measurements.png", profile.base()),
+                    &profile.measurements_file(),
+                    &config,
+                )
+            ),
+            Err(e) => {
+                error!("failed to generate measurements chart: {e}");
+                String::new()
+            }
+        }
+    };
+    let mut inputs = String::new();
+    let mut headers = String::new();
+    for (metric, label) in metrics::BODY {
+        writeln!(
+            inputs,
+            "<label for=\"{metric}\">{label}:</label>\n\
+             <input type=\"number\" step=\"0.1\" id=\"{metric}\" \
+             name=\"{metric}\" />"
+        )
+        .unwrap();
+        write!(headers, "<th>{label}</th>").unwrap();
+    }
+    let tmpl = state
+        .page("measurements.html", &config)
+        .replace("{{profiles}}", &state.profile_nav(profile))
+        .replace("{{base}}", &profile.base())
+        .replace("{{today}}", &store::format_date(today))
+        .replace("{{inputs}}", &inputs)
+        .replace("{{headers}}", &headers)
+        .replace("{{chart}}", &chart)
+        .replace("{{table}}", &table);
+    Ok(Response::ok().body(tmpl.into()))
+}
+
+const MIN_MEASUREMENT: f64 = 5.0;
+const MAX_MEASUREMENT: f64 = 300.0;
+
+/// Record the measurements submitted to the measurements page, on `today`
+/// unless the form gives another date. Fields left empty are skipped.
+fn record_measurements(
+    request: &Request,
+    state: &State,
+    profile: &Profile,
+    today: Date,
+) -> Result<Response> {
+    let params = request.params();
+    let field = |name: &str| {
+        params
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.trim())
+            .unwrap_or_default()
+    };
+    let date = match field("date") {
+        "" => Some(today),
+        date => store::parse_date(date),
+    };
+    let Some(date) = date else {
+        return Ok(Response::bad_request().body(
+            state.error_page("Dates must look like 2024-03-01.").into(),
+        ));
+    };
+    let mut new = Vec::new();
+    for (metric, label) in metrics::BODY {
+        let value = match field(metric) {
+            "" => continue,
+            value => value.parse::<f64>().ok(),
+        };
+        match value {
+            Some(value)
+                if (MIN_MEASUREMENT..=MAX_MEASUREMENT).contains(&value) =>
+            {
+                new.push(Measurement {
+                    date,
+                    metric: metric.to_owned(),
+                    value,
+                })
+            }
+            _ => {
+                let msg = format!(
+                    "{label} must be a number of cm between {MIN_MEASUREMENT} \
+                     and {MAX_MEASUREMENT}."
+                );
+                return Ok(
+                    Response::bad_request().body(state.error_page(&msg).into())
+                );
+            }
+        }
+    }
+    if new.is_empty() {
+        return Ok(Response::bad_request()
+            .body(state.error_page("No measurements were entered.").into()));
+    }
+    let count = match new.len() {
+        1 => "1 measurement".to_owned(),
+        n => format!("{n} measurements"),
+    };
+    for measurement in new {
+        profile.metrics().record(measurement)?;
+    }
+    state.events.publish(profile.name(), Event::Changed);
+    state.commit(&format!(
+        "Record {count}{} on {}",
+        for_profile(profile),
+        store::format_date(date)
+    ));
+    Ok(Response::redirect(format!(
+        "{}/measurements",
+        profile.base()
+    )))
+}
+
+/// Weekly calorie intake next to weekly weight change, and the form for
+/// logging a day's intake.
+fn calories(
+    request: &Request,
+    state: &State,
+    profile: &Profile,
+) -> Result<Response> {
+    use std::fmt::Write;
+    let today = state.today();
+    if request.method == "POST" {
+        return record_calories(request, state, profile, today);
+    }
+    let config = state.config();
+    let unit = config.unit_for(profile.name());
+    let intake = profile.metrics().series(metrics::CALORIES);
+    let weeks = weekly::weekly(&intake, profile.store().entries().iter());
+    let mut table = String::new();
+    for week in weeks.iter().rev() {
+        write!(
+            table,
+            "<tr><td>{}</td><td>{}</td><td>{:.0} kcal</td>",
+            config.date_format.format(week.start),
+            week.days,
+            week.average
+        )
+        .unwrap();
+        match week.change {
+            Some(change) => {
+                writeln!(table, "<td>{change:+.1} {unit}</td></tr>").unwrap()
+            }
+            None => table.push_str("<td></td></tr>\n"),
+        }
+    }
+    let summary = match weekly::fit(&weeks) {
+        None => "Log calories and weights for a few weeks to see how they \
+                 relate."
+            .to_owned(),
+        Some(fit) => {
+            let mut summary = format!(
+                "The correlation between weekly intake and weight change is \
+                 {:.2}.",
+                fit.correlation
+            );
+            if let Some(steady) = fit.steady {
+                write!(
+                    summary,
+                    " Weight is estimated to hold steady at about {:.0} kcal \
+                     a day.",
+                    steady
+                )
+                .unwrap();
+            }
+            summary
+        }
+    };
+    let tmpl = state
+        .page("calories.html", &config)
+        .replace("{{profiles}}", &state.profile_nav(profile))
+        .replace("{{base}}", &profile.base())
+        .replace("{{today}}", &store::format_date(today))
+        .replace("{{summary}}", &summary)
+        .replace("{{table}}", &table);
+    Ok(Response::ok().body(tmpl.into()))
+}
+
+const MAX_CALORIES: f64 = 20_000.0;
+
+/// Record the intake submitted to the calories page, on `today` unless the
+/// form gives another date.
+fn record_calories(
+    request: &Request,
+    state: &State,
+    profile: &Profile,
+    today: Date,
+) -> Result<Response> {
+    let params = request.params();
+    let field = |name: &str| {
+        params
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.trim())
+            .unwrap_or_default()
+    };
+    let date = match field("date") {
+        "" => Some(today),
+        date => store::parse_date(date),
+    };
+    let Some(date) = date else {
+        return Ok(Response::bad_request().body(
+            state.error_page("Dates must look like 2024-03-01.").into(),
+        ));
+    };
+    let value = match field("kcal").parse::<f64>() {
+        Ok(value) if (0.0..=MAX_CALORIES).contains(&value) => value.round(),
+        _ => {
+            let msg = format!(
+                "Calories must be a number of kcal between 0 and \
+                 {MAX_CALORIES}."
+            );
+            return Ok(
+                Response::bad_request().body(state.error_page(&msg).into())
+            );
+        }
+    };
+    profile.metrics().record(Measurement {
+        date,
+        metric: metrics::CALORIES.to_owned(),
+        value,
+    })?;
+    state.events.publish(profile.name(), Event::Changed);
+    state.commit(&format!(
+        "Record {value} kcal{} on {}",
+        for_profile(profile),
+        store::format_date(date)
+    ));
+    Ok(Response::redirect(format!("{}/calories", profile.base())))
+}
+
+/// Nightly sleep by week next to weekly weight change and drawn behind the
+/// weights, and the form for importing it.
+fn sleep(
+    request: &Request,
+    state: &State,
+    profile: &Profile,
+) -> Result<Response> {
+    use std::fmt::Write;
+    let mut response = Response::ok();
+    let mut message = String::new();
+    if request.method == "POST" {
+        match import_sleep(request, state, profile) {
+            Ok(msg) => message = msg,
+            Err(Error::Import(msg)) => {
+                response = Response::bad_request();
+                message = format!("Import failed: {}.", escape_html(&msg));
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    let today = state.today();
+    let config = state.config();
+    let unit = config.unit_for(profile.name());
+    let sleep = profile.metrics().series(metrics::SLEEP);
+    let weeks = weekly::weekly(&sleep, profile.store().entries().iter());
+    let mut table = String::new();
+    for week in weeks.iter().rev() {
+        write!(
+            table,
+            "<tr><td>{}</td><td>{}</td><td>{:.1} h</td>",
+            config.date_format.format(week.start),
+            week.days,
+            week.average
+        )
+        .unwrap();
+        match week.change {
+            Some(change) => {
+                writeln!(table, "<td>{change:+.1} {unit}</td></tr>").unwrap()
+            }
+            None => table.push_str("<td></td></tr>\n"),
+        }
+    }
+    let summary = match weekly::fit(&weeks) {
+        None => "Import a few weeks of sleep to see how it relates to weight."
+            .to_owned(),
+        Some(fit) => format!(
+            "The correlation between weekly average sleep and weight change \
+             is {:.2}.",
+            fit.correlation
+        ),
+    };
+    let chart = if sleep.is_empty() {
+        String::new()
+    } else {
+        match profile.sleep_graph(today, &config) {
+            Ok(()) => format!(
+                "<img src=\"{}\" style=\"width:100%;max-width:640px\">",
+                image_src(
+                    format!("{}/sleep.png", profile.base()),
+                    &profile.sleep_file(),
+                    &config,
+                )
+            ),
+            Err(e) => {
+                error!("failed to generate sleep chart: {e}");
+                String::new()
+            }
+        }
+    };
+    let tmpl = state
+        .page("sleep.html", &config)
+        .replace("{{profiles}}", &state.profile_nav(profile))
+        .replace("{{base}}", &profile.base())
+        .replace("{{message}}", &message)
+        .replace(
+            "{{format_options}}",
+            &options(&[("fitbit", "Fitbit"), ("csv", "CSV (Date, Hours)")], ""),
+        )
+        .replace("{{summary}}", &summary)
+        .replace("{{chart}}", &chart)
+        .replace("{{table}}", &table);
+    Ok(response.body(tmpl.into()))
+}
+
+/// Import the sleep export uploaded to the sleep page, returning a summary
+/// for the user.
+fn import_sleep(
+    request: &Request,
+    state: &State,
+    profile: &Profile,
+) -> Result<String> {
+    let parts = request
+        .multipart()
+        .ok_or_else(|| Error::Import("expected a file upload".into()))?;
+    let format: SleepFormat = parts
+        .iter()
+        .find(|p| p.name == "format")
+        .map(|p| p.text().parse())
+        .unwrap_or(Ok(SleepFormat::Csv))
+        .map_err(Error::Import)?;
+    let file = parts
+        .iter()
+        .find(|p| p.name == "file" && !p.data.is_empty())
+        .ok_or_else(|| Error::Import("no file was selected".into()))?;
+    let nights = import::sleep(format, &file.text())?;
+    let count = nights.len();
+    profile
+        .metrics()
+        .extend(nights.into_iter().map(|(date, hours)| Measurement {
+            date,
+            metric: metrics::SLEEP.to_owned(),
+            value: hours,
+        }))?;
+    info!(count, "imported sleep");
+    if count > 0 {
+        state.events.publish(profile.name(), Event::Changed);
+        state.commit(&format!(
+            "Import {count} nights of sleep{} from {}",
+            for_profile(profile),
+            format.name()
+        ));
+    }
+    Ok(match count {
+        1 => "Imported 1 night.".to_owned(),
+        n => format!("Imported {n} nights."),
+    })
+}
+
+/// The period start dates with the cycle lengths between them, and the
+/// forms for adding more.
+fn cycle_page(
+    request: &Request,
+    state: &State,
+    profile: &Profile,
+) -> Result<Response> {
+    use std::fmt::Write;
+    let today = state.today();
+    let mut response = Response::ok();
+    let mut message = String::new();
+    if request.method == "POST" && request.multipart().is_none() {
+        let params = request.params();
+        let date = match params.iter().find(|(k, _)| k == "date") {
+            Some((_, date)) if !date.is_empty() => store::parse_date(date),
+            _ => Some(today),
+        };
+        let Some(date) = date else {
+            return Ok(Response::bad_request().body(
+                state.error_page("Dates must look like 2024-03-01.").into(),
+            ));
+        };
+        record_periods(state, profile, vec![date])?;
+        return Ok(Response::redirect(format!("{}/cycle", profile.base())));
+    }
+    if request.method == "POST" {
+        match import_periods(request, state, profile) {
+            Ok(msg) => message = msg,
+            Err(Error::Import(msg)) => {
+                response = Response::bad_request();
+                message = format!("Import failed: {}.", escape_html(&msg));
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    let config = state.config();
+    let starts: Vec<_> =
+        profile.cycles().records().iter().map(|s| s.date).collect();
+    let mut table = String::new();
+    for (i, start) in starts.iter().enumerate().rev() {
+        let length = match starts.get(i + 1) {
+            Some(next) => format!("{} days", (*next - *start).whole_days()),
+            None => String::new(),
+        };
+        writeln!(
+            table,
+            "<tr><td>{}</td><td>{length}</td></tr>",
+            config.date_format.format(*start)
+        )
+        .unwrap();
+    }
+    let prediction = match starts.last() {
+        Some(last) => {
+            let length = cycle::average_length(&starts);
+            format!(
+                "Cycles average {length} days, so the next period is expected \
+                 around {}.",
+                config
+                    .date_format
+                    .format(*last + time::Duration::days(length))
+            )
+        }
+        None => String::new(),
+    };
+    let tmpl = state
+        .page("cycle.html", &config)
+        .replace("{{profiles}}", &state.profile_nav(profile))
+        .replace("{{base}}", &profile.base())
+        .replace("{{message}}", &message)
+        .replace("{{today}}", &store::format_date(today))
+        .replace("{{prediction}}", &prediction)
+        .replace("{{table}}", &table);
+    Ok(response.body(tmpl.into()))
+}
+
+/// Import the file of period start dates uploaded to the cycle page,
+/// returning a summary for the user.
+fn import_periods(
+    request: &Request,
+    state: &State,
+    profile: &Profile,
+) -> Result<String> {
+    let parts = request
+        .multipart()
+        .ok_or_else(|| Error::Import("expected a file upload".into()))?;
+    let file = parts
+        .iter()
+        .find(|p| p.name == "file" && !p.data.is_empty())
+        .ok_or_else(|| Error::Import("no file was selected".into()))?;
+    let starts = import::period_starts(&file.text())?;
+    let count = starts.len();
+    record_periods(state, profile, starts)?;
+    Ok(match count {
+        1 => "Imported 1 period start.".to_owned(),
+        n => format!("Imported {n} period starts."),
+    })
+}
+
+fn record_periods(
+    state: &State,
+    profile: &Profile,
+    starts: Vec<Date>,
+) -> Result<()> {
+    let message = match starts.len() {
+        1 => format!("Record a period start{}", for_profile(profile)),
+        n => format!("Import {n} period starts{}", for_profile(profile)),
+    };
+    profile
+        .cycles()
+        .extend(starts.into_iter().map(|date| PeriodStart { date }))?;
+    state.events.publish(profile.name(), Event::Changed);
+    state.commit(&message);
+    Ok(())
+}
+
+/// The number and length of this week's workouts, for the index page.
+fn exercise_summary(profile: &Profile, today: Date) -> String {
+    let start = stats::week_start(today);
+    let workouts = profile.exercise().records();
+    let week: Vec<_> = workouts.iter().filter(|w| w.date >= start).collect();
+    if week.is_empty() {
+        return String::new();
+    }
+    let minutes = week.iter().map(|w| w.minutes).sum();
+    format!(
+        "<p>Exercise this week: {} {}, {}</p>",
+        week.len(),
+        if week.len() == 1 {
+            "workout"
+        } else {
+            "workouts"
+        },
+        exercise::format_minutes(minutes)
+    )
+}
+
+/// The exercise log with weekly totals, and the form for adding to it.
+fn exercise(
+    request: &Request,
+    state: &State,
+    profile: &Profile,
+) -> Result<Response> {
+    use std::fmt::Write;
+    let today = state.today();
+    if request.method == "POST" {
+        return record_workout(request, state, profile, today);
+    }
+    let config = state.config();
+    let workouts = profile.exercise().records();
+    let mut weeks: BTreeMap<Date, (usize, u32)> = BTreeMap::new();
+    let mut activities = BTreeMap::new();
+    let mut table = String::new();
+    for workout in workouts.iter().rev() {
+        let week = weeks.entry(stats::week_start(workout.date)).or_default();
+        week.0 += 1;
+        week.1 += workout.minutes;
+        *activities.entry(workout.activity.as_str()).or_insert(0) += 1;
+        writeln!(
+            table,
+            "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+            config.date_format.format(workout.date),
+            escape_html(&workout.activity),
+            exercise::format_minutes(workout.minutes)
+        )
+        .unwrap();
+    }
+    let mut weeks_table = String::new();
+    for (start, (count, minutes)) in weeks.iter().rev() {
+        writeln!(
+            weeks_table,
+            "<tr><td>{}</td><td>{count}</td><td>{}</td></tr>",
+            config.date_format.format(*start),
+            exercise::format_minutes(*minutes)
+        )
+        .unwrap();
+    }
+    // suggest the activities logged most often
+    let mut activities: Vec<_> = activities.into_iter().collect();
+    activities.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    let activities: String = activities
+        .iter()
+        .take(20)
+        .map(|(activity, _)| {
+            format!("<option value=\"{}\">", escape_html(activity))
+        })
+        .collect();
+    let tmpl = state
+        .page("exercise.html", &config)
+        .replace("{{profiles}}", &state.profile_nav(profile))
+        .replace("{{base}}", &profile.base())
+        .replace("{{today}}", &store::format_date(today))
+        .replace("{{activities}}", &activities)
+        .replace("{{weeks}}", &weeks_table)
+        .replace("{{table}}", &table);
+    Ok(Response::ok().body(tmpl.into()))
+}
+
+/// The longest workout accepted, a full day.
+const MAX_MINUTES: u32 = 24 * 60;
+
+/// Record the workout submitted to the exercise page, on `today` unless the
+/// form gives another date.
+fn record_workout(
+    request: &Request,
+    state: &State,
+    profile: &Profile,
+    today: Date,
+) -> Result<Response> {
+    let params = request.params();
+    let field = |name: &str| {
+        params
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.trim())
+            .unwrap_or_default()
+    };
+    let date = match field("date") {
+        "" => Some(today),
+        date => store::parse_date(date),
+    };
+    let Some(date) = date else {
+        return Ok(Response::bad_request().body(
+            state.error_page("Dates must look like 2024-03-01.").into(),
+        ));
+    };
+    let Some(activity) = exercise::valid_activity(field("activity")) else {
+        return Ok(Response::bad_request().body(
+            state
+                .error_page("The activity must be between 1 and 50 characters.")
+                .into(),
+        ));
+    };
+    let minutes = match field("minutes").parse::<u32>() {
+        Ok(minutes) if (1..=MAX_MINUTES).contains(&minutes) => minutes,
+        _ => {
+            let msg = format!(
+                "The duration must be a whole number of minutes between 1 \
+                 and {MAX_MINUTES}."
+            );
+            return Ok(
+                Response::bad_request().body(state.error_page(&msg).into())
+            );
+        }
+    };
+    let message = format!(
+        "Record {minutes} minutes of {activity}{} on {}",
+        for_profile(profile),
+        store::format_date(date)
+    );
+    profile.exercise().record(Workout {
+        date,
+        minutes,
+        activity,
+    })?;
+    state.events.publish(profile.name(), Event::Changed);
+    state.commit(&message);
+    Ok(Response::redirect(format!("{}/exercise", profile.base())))
+}
+
+/// The log of medication changes, and the form for adding to it.
+fn medications(
+    request: &Request,
+    state: &State,
+    profile: &Profile,
+) -> Result<Response> {
+    use std::fmt::Write;
+    let today = state.today();
+    if request.method == "POST" {
+        return record_medication(request, state, profile, today);
+    }
+    let config = state.config();
+    let mut table = String::new();
+    for change in profile.medications().records().iter().rev() {
+        writeln!(
+            table,
+            "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+            config.date_format.format(change.date),
+            match change.change {
+                Change::Start => "Started",
+                Change::Stop => "Stopped",
+            },
+            escape_html(&change.name)
+        )
+        .unwrap();
+    }
+    let tmpl = state
+        .page("medications.html", &config)
+        .replace("{{profiles}}", &state.profile_nav(profile))
+        .replace("{{base}}", &profile.base())
+        .replace("{{today}}", &store::format_date(today))
+        .replace("{{table}}", &table);
+    Ok(Response::ok().body(tmpl.into()))
+}
+
+/// Record the medication change submitted to the medications page, on
+/// `today` unless the form gives another date.
+fn record_medication(
+    request: &Request,
+    state: &State,
+    profile: &Profile,
+    today: Date,
+) -> Result<Response> {
+    let params = request.params();
+    let field = |name: &str| {
+        params
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+            .unwrap_or_default()
+    };
+    let date = match field("date") {
+        "" => Some(today),
+        date => store::parse_date(date),
+    };
+    let Some(date) = date else {
+        return Ok(Response::bad_request().body(
+            state.error_page("Dates must look like 2024-03-01.").into(),
+        ));
+    };
+    let change = match field("change").parse::<Change>() {
+        Ok(change) => change,
+        Err(e) => {
+            return Ok(Response::bad_request().body(state.error_page(&e).into()))
+        }
+    };
+    let Some(name) = medication::valid_name(field("name")) else {
+        return Ok(Response::bad_request().body(
+            state
+                .error_page(
+                    "The medication's name must be between 1 and 100 \
+                     characters.",
+                )
+                .into(),
+        ));
+    };
+    let message = format!(
+        "Record {change} of {name}{} on {}",
+        for_profile(profile),
+        store::format_date(date)
+    );
+    profile
+        .medications()
+        .record(MedicationChange { date, change, name })?;
+    state.events.publish(profile.name(), Event::Changed);
+    state.commit(&message);
+    Ok(Response::redirect(format!(
+        "{}/medications",
+        profile.base()
+    )))
+}
+
+/// The gallery of progress photos, and the form for uploading more.
+fn photos(
+    request: &Request,
+    state: &State,
+    profile: &Profile,
+) -> Result<Response> {
+    use std::fmt::Write;
+    let today = state.today();
+    if request.method == "POST" {
+        return upload_photo(request, state, profile, today);
+    }
+    let config = state.config();
+    let unit = config.unit_for(profile.name());
+    let entries = profile.store().entries();
+    let base = profile.base();
+    let mut gallery = String::new();
+    for photo in profile.photos().list()?.iter().rev() {
+        let mut caption = config.date_format.format(photo.date);
+        if let Some(entry) = entries.iter().find(|e| e.date == photo.date) {
+            write!(caption, " · {:.1} {unit}", entry.weight).unwrap();
+        }
+        writeln!(
+            gallery,
+            "<figure><a href=\"{base}/photos/{name}\">\
+             <img src=\"{base}/photos/{name}\" loading=\"lazy\" \
+             alt=\"{caption}\"></a><figcaption>{caption}</figcaption>\
+             </figure>",
+            name = photo.name,
+        )
+        .unwrap();
+    }
+    if gallery.is_empty() {
+        gallery = "<p>No photos yet.</p>".to_owned();
+    }
+    let tmpl = state
+        .page("photos.html", &config)
+        .replace("{{profiles}}", &state.profile_nav(profile))
+        .replace("{{base}}", &base)
+        .replace("{{today}}", &store::format_date(today))
+        .replace("{{gallery}}", &gallery);
+    Ok(Response::ok().body(tmpl.into()))
+}
+
+/// Save the photo uploaded to the photos page, as taken on `today` unless
+/// the form gives another date.
+fn upload_photo(
+    request: &Request,
+    state: &State,
+    profile: &Profile,
+    today: Date,
+) -> Result<Response> {
+    let bad_request = |message: &str| {
+        Ok(Response::bad_request().body(state.error_page(message).into()))
+    };
+    let Some(parts) = request.multipart() else {
+        return bad_request("Expected a file upload.");
+    };
+    let date = match parts.iter().find(|p| p.name == "date") {
+        Some(part) if !part.data.is_empty() => store::parse_date(&part.text()),
+        _ => Some(today),
+    };
+    let Some(date) = date else {
+        return bad_request("Dates must look like 2024-03-01.");
+    };
+    let Some(file) = parts
+        .iter()
+        .find(|p| p.name == "file" && !p.data.is_empty())
+    else {
+        return bad_request("No photo was selected.");
+    };
+    let Some(format) = photos::Format::detect(&file.data) else {
+        return bad_request("Photos must be JPEG, PNG, or WebP images.");
+    };
+    let photo = profile.photos().add(date, format, &file.data)?;
+    state.commit(&format!("Add photo {}{}", photo.name, for_profile(profile)));
+    Ok(Response::redirect(format!("{}/photos", profile.base())))
+}
+
+/// Serve the photo called `name`.
+fn photo_image(
+    request: &Request,
+    state: &State,
+    profile: &Profile,
+    name: &str,
+) -> Result<Response> {
+    // only names of photos are accepted, so paths can't escape the directory
+    let photo = Photo::parse(name).filter(|photo| {
+        profile
+            .photos()
+            .list()
+            .is_ok_and(|photos| photos.contains(photo))
+    });
+    let Some(photo) = photo else {
+        return Ok(
+            Response::err().body(state.error_page("No such photo.").into())
+        );
+    };
+    Ok(Response::ok()
+        .content_type(photo.format.content_type())
+        .header("Cache-Control", "private, max-age=86400".into())
+        .body(Body::Bytes(profile.photos().read(&photo)?))
+        .ranged(request))
+}
+
+/// Render `<option>` tags for `choices`, marking `selected` as selected.
+fn options(choices: &[(&str, &str)], selected: &str) -> String {
+    choices
+        .iter()
+        .map(|(value, label)| {
+            let sel = if *value == selected { " selected" } else { "" };
+            format!("<option value=\"{value}\"{sel}>{label}</option>")
+        })
+        .collect()
+}
+
+fn import(
+    request: &Request,
+    state: &State,
+    profile: &Profile,
+) -> Result<Response> {
+    let mut response = Response::ok();
+    let mut message = String::new();
+    if request.method == "POST" {
+        match import_upload(request, state, profile) {
+            Ok(msg) => message = msg,
+            Err(Error::Import(msg)) => {
+                response = Response::bad_request();
+                message = format!("Import failed: {}.", escape_html(&msg));
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    let config = state.config();
+    let unit = config.unit_for(profile.name()).to_string();
+    let tmpl = state
+        .page("import.html", &config)
+        .replace("{{profiles}}", &state.profile_nav(profile))
+        .replace("{{base}}", &profile.base())
+        .replace("{{message}}", &message)
+        .replace(
+            "{{format_options}}",
+            &options(
+                &[
+                    ("myfitnesspal", "MyFitnessPal"),
+                    ("libra", "Libra"),
+                    ("withings", "Withings Health Mate"),
+                    ("renpho", "Renpho"),
+                    ("zepp", "Zepp (Mi Fit)"),
+                    ("weight-watcher", "weight-watcher data file"),
+                    ("json", "JSON"),
+                ],
+                "",
+            ),
+        )
+        .replace(
+            "{{conflict_options}}",
+            &options(
+                &[
+                    ("keep", "Keep the existing entry"),
+                    ("replace", "Replace it with the imported one"),
+                    ("both", "Keep both"),
+                ],
+                "keep",
+            ),
+        )
+        .replace(
+            "{{unit_options}}",
+            &options(&[("kg", "kg"), ("lb", "lb"), ("g", "g")], &unit),
+        );
+    Ok(response.body(tmpl.into()))
+}
+
+/// Import the file uploaded to the import form, returning a summary for the
+/// user.
+fn import_upload(
+    request: &Request,
+    state: &State,
+    profile: &Profile,
+) -> Result<String> {
+    let parts = request
+        .multipart()
+        .ok_or_else(|| Error::Import("expected a file upload".into()))?;
+    let field = |name: &str| {
+        parts
+            .iter()
+            .find(|p| p.name == name)
+            .map(|p| p.text().into_owned())
+            .unwrap_or_default()
+    };
+    let format: Format = field("format").parse().map_err(Error::Import)?;
+    let unit: Unit = field("unit").parse().map_err(Error::Import)?;
+    let policy: Policy = match field("conflicts").as_str() {
+        "" => Policy::default(),
+        policy => policy.parse().map_err(Error::Import)?,
+    };
+    let file = parts
+        .iter()
+        .find(|p| p.name == "file" && !p.data.is_empty())
+        .ok_or_else(|| Error::Import("no file was selected".into()))?;
+    let contents = match format {
+        Format::WeightWatcher => data_file_text(profile, &file.data)?,
+        Format::Zepp => import::zepp_text(&file.data)?,
+        _ => file.text().into_owned(),
+    };
+    let to = state.config().unit_for(profile.name());
+    let entries = import::parse(format, &contents, unit, to, state.timezone())?;
+    let report =
+        state.merge(profile, entries, policy, Source::Import, format.name());
+    Ok(report?.to_string())
+}
+
+/// The text of `contents`, a data file that may be encrypted with the same
+/// passphrase as `profile`'s.
+fn data_file_text(profile: &Profile, contents: &[u8]) -> Result<String> {
+    let cipher = match profile.store().passphrase() {
+        Some(passphrase) if crypto::is_encrypted(contents) => {
+            Some(crypto::Cipher::new(passphrase, contents)?)
+        }
+        _ => None,
+    };
+    store::text(contents, cipher.as_ref())
+        .map_err(|e| Error::Import(e.to_string()))
+}
+
+/// The API token `request` was sent with, if it has a valid one.
+fn api_token(request: &Request, state: &State) -> Option<ApiToken> {
+    let token = request.header("authorization")?.strip_prefix("Bearer ")?;
+    state.tokens.verify(token.trim())
+}
+
+/// Where a change made by `request` comes from, for the audit log.
+fn source(request: &Request, state: &State) -> Source {
+    match api_token(request, state) {
+        Some(_) => Source::Api,
+        None => Source::Web,
+    }
+}
+
+/// Run `handler` if the request has the admin password or an API token.
+/// Administrative pages are disabled for the password if none is configured.
+fn admin(
+    request: &Request,
+    state: &State,
+    handler: impl FnOnce(&Request, &State) -> Result<Response>,
+) -> Result<Response> {
+    if let Some(token) = api_token(request, state) {
+        debug!(token = token.id, "authorized with API token");
+        return handler(request, state);
+    }
+    let authorized = match &state.config().admin_password {
+        None => {
+            return Ok(Response::forbidden().body(
+                state
+                    .error_page(
+                        "This page is disabled because no \
+                         admin_password_file is configured.",
+                    )
+                    .into(),
+            ))
+        }
+        Some(password) => request.basic_auth().is_some_and(|(_, given)| {
+            tokens::constant_time_eq(given.as_bytes(), password.as_bytes())
+        }),
+    };
+    if !authorized {
+        warn!("rejected admin credentials");
+        return Ok(Response::unauthorized("weight-watcher admin")
+            .body(state.error_page("The admin password is required.").into()));
+    }
+    handler(request, state)
+}
+
+/// The lists of API tokens and quick-log tokens with buttons for revoking
+/// them, and the forms for creating more. Tokens can't be used to manage
+/// tokens.
+fn tokens_page(request: &Request, state: &State) -> Result<Response> {
+    use std::fmt::Write;
+    if api_token(request, state).is_some() {
+        return Ok(Response::forbidden().body(
+            state
+                .error_page(
+                    "API tokens can only be managed with the admin password.",
+                )
+                .into(),
+        ));
+    }
+    let today = state.today();
+    let mut response = Response::ok();
+    let mut message = String::new();
+    if request.method == "POST" {
+        let params = request.params();
+        let field = |name: &str| {
+            params
+                .iter()
+                .find(|(k, _)| k == name)
+                .map(|(_, v)| v.as_str())
+                .unwrap_or_default()
+        };
+        // the quick-log forms say so, and otherwise share the actions
+        let quick = field("kind") == "log";
+        let tokens = match quick {
+            true => &state.log_tokens,
+            false => &state.tokens,
+        };
+        let profile = Some(field("profile")).filter(|p| quick && !p.is_empty());
+        match (field("action"), tokens::valid_label(field("label"))) {
+            ("create", _)
+                if profile.is_some_and(|p| {
+                    state.profiles().get(Some(p)).is_none()
+                }) =>
+            {
+                response = Response::bad_request();
+                message = "There is no such profile.".to_owned();
+            }
+            ("create", Some(label)) => {
+                let (api_token, token) =
+                    tokens.create(&label, profile, today)?;
+                info!(id = api_token.id, quick, "created token");
+                let token = match quick {
+                    true => format!("/log/{token}?w="),
+                    false => token,
+                };
+                message = format!(
+                    "Created a token for {}. Copy it now, since it won't be \
+                     shown again: <code>{token}</code>",
+                    escape_html(&api_token.label)
+                );
+            }
+            ("create", None) => {
+                response = Response::bad_request();
+                message =
+                    "Labels must be between 1 and 100 characters.".to_owned();
+            }
+            ("revoke", _) => {
+                let id = field("id");
+                if tokens.revoke(id)? {
+                    info!(id, quick, "revoked token");
+                    message = "Revoked the token.".to_owned();
+                } else {
+                    response = Response::bad_request();
+                    message = "There is no such token.".to_owned();
+                }
+            }
+            _ => {
+                response = Response::bad_request();
+                message = "Unknown action.".to_owned();
+            }
+        }
+    }
+    let config = state.config();
+    let mut table = String::new();
+    for token in state.tokens.list()? {
+        writeln!(
+            table,
+            "<tr><td>{label}</td><td><code>{id}</code></td><td>{created}</td>\
+             <td><form action=\"/tokens\" method=\"post\">\
+             <input type=\"hidden\" name=\"id\" value=\"{id}\" />\
+             <button name=\"action\" value=\"revoke\">Revoke</button>\
+             </form></td></tr>",
+            label = escape_html(&token.label),
+            id = escape_html(&token.id),
+            created = config.date_format.format(token.created),
+        )
+        .unwrap();
+    }
+    let mut log_table = String::new();
+    for token in state.log_tokens.list()? {
+        writeln!(
+            log_table,
+            "<tr><td>{label}</td><td>{profile}</td><td><code>{id}</code></td>\
+             <td>{created}</td>\
+             <td><form action=\"/tokens\" method=\"post\">\
+             <input type=\"hidden\" name=\"kind\" value=\"log\" />\
+             <input type=\"hidden\" name=\"id\" value=\"{id}\" />\
+             <button name=\"action\" value=\"revoke\">Revoke</button>\
+             </form></td></tr>",
+            label = escape_html(&token.label),
+            profile =
+                escape_html(token.profile.as_deref().unwrap_or("default")),
+            id = escape_html(&token.id),
+            created = config.date_format.format(token.created),
+        )
+        .unwrap();
+    }
+    let mut profiles = String::from("<option value=\"\">default</option>");
+    for name in config.profile_names() {
+        write!(
+            profiles,
+            "<option value=\"{name}\">{name}</option>",
+            name = escape_html(&name)
+        )
+        .unwrap();
+    }
+    let tmpl = state
+        .page("tokens.html", &config)
+        .replace("{{message}}", &message)
+        .replace("{{table}}", &table)
+        .replace("{{profiles}}", &profiles)
+        .replace("{{log_table}}", &log_table);
+    Ok(response.body(tmpl.into()))
+}
+
+/// Whether a Withings account is connected, with buttons for connecting one,
+/// fetching new weigh-ins right away, and disconnecting.
+fn withings_page(request: &Request, state: &State) -> Result<Response> {
+    if request.method != "POST" {
+        return Ok(withings_status(state, Response::ok(), String::new()));
+    }
+    let params = request.params();
+    let action = params
+        .iter()
+        .find(|(k, _)| k == "action")
+        .map(|(_, v)| v.as_str());
+    let (response, message) = match action {
+        Some("connect") => {
+            let url = state.withings.authorize_url(&state.config());
+            match url {
+                Ok(url) => return Ok(Response::redirect(url)),
+                Err(e) => (Response::err(), e.to_string()),
+            }
+        }
+        Some("sync") => match withings::sync(state) {
+            Ok(summary) => (Response::ok(), summary),
+            Err(e) => {
+                error!("failed to sync Withings weigh-ins: {e}");
+                (Response::internal_error(), e.to_string())
+            }
+        },
+        Some("disconnect") => {
+            let message = match state.withings.disconnect()? {
+                true => "Disconnected the Withings account.",
+                false => "No Withings account is connected.",
+            };
+            (Response::ok(), message.to_owned())
+        }
+        _ => (Response::bad_request(), "Unknown action.".to_owned()),
+    };
+    Ok(withings_status(state, response, escape_html(&message)))
+}
+
+/// Where Withings sends the browser back to after the user allows or
+/// refuses access, with a `code` to finish connecting with. The first sync
+/// runs straight away.
+fn withings_callback(request: &Request, state: &State) -> Result<Response> {
+    let params = request.params();
+    let field = |name: &str| {
+        params
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    };
+    let Some(code) = field("code") else {
+        let message = "Withings didn't grant access.".to_owned();
+        return Ok(withings_status(state, Response::bad_request(), message));
+    };
+    let connected = state.withings.connect(
+        &state.config(),
+        code,
+        field("state").unwrap_or_default(),
+    );
+    if let Err(e) = connected {
+        error!("failed to connect Withings account: {e}");
+        let message = escape_html(&e.to_string());
+        return Ok(withings_status(state, Response::err(), message));
+    }
+    let message = match withings::sync(state) {
+        Ok(summary) => format!("Connected. {summary}"),
+        Err(e) => format!("Connected, but the first sync failed: {e}"),
+    };
+    Ok(withings_status(
+        state,
+        Response::ok(),
+        escape_html(&message),
+    ))
+}
+
+/// The Withings page with `message`, which should already be escaped.
+fn withings_status(
+    state: &State,
+    response: Response,
+    message: String,
+) -> Response {
+    let config = state.config();
+    let status = if config.withings_client_id.is_none() {
+        "Set <code>withings_client_id</code>, \
+         <code>withings_client_secret_file</code>, and \
+         <code>withings_redirect_url</code> to connect an account."
+            .to_owned()
+    } else if !state.withings.is_connected() {
+        "No account is connected.".to_owned()
+    } else {
+        match state.withings.last_update() {
+            Some(time) => format!(
+                "Connected. Weigh-ins were last fetched on {}.",
+                config
+                    .date_format
+                    .format(state.timezone().convert(time).date())
+            ),
+            None => "Connected, but nothing has been fetched yet.".to_owned(),
+        }
+    };
+    let tmpl = state
+        .page("withings.html", &config)
+        .replace("{{message}}", &message)
+        .replace("{{status}}", &status);
+    response.body(tmpl.into())
+}
+
+/// The most changes listed on the audit page, which could otherwise grow
+/// without bound.
+const AUDIT_ROWS: usize = 1000;
+
+/// The latest changes in the audit log, newest first.
+fn audit_page(_request: &Request, state: &State) -> Result<Response> {
+    use std::fmt::Write;
+    let timezone = state.timezone();
+    let changes = state.audit.changes()?;
+    let config = state.config();
+    let describe = |entry: &Option<Entry>| match entry {
+        Some(entry) => {
+            let mut out = format!(
+                "{} {:.1}",
+                config.date_format.format(entry.date),
+                entry.weight
+            );
+            for tag in &entry.tags {
+                write!(out, " #{tag}").unwrap();
+            }
+            out
+        }
+        None => String::new(),
+    };
+    let mut table = String::new();
+    for change in changes.iter().rev().take(AUDIT_ROWS) {
+        let time = timezone.convert(change.time);
+        writeln!(
+            table,
+            "<tr><td>{} {:02}:{:02}</td><td>{}</td><td>{}</td><td>{}</td>\
+             <td>{}</td><td>{}</td></tr>",
+            config.date_format.format(time.date()),
+            time.hour(),
+            time.minute(),
+            change.source,
+            change.action,
+            escape_html(change.profile.as_deref().unwrap_or("default")),
+            describe(&change.old),
+            describe(&change.new),
+        )
+        .unwrap();
+    }
+    let notice = if changes.len() > AUDIT_ROWS {
+        format!(
+            "Showing the latest {AUDIT_ROWS} of {} changes.",
+            changes.len()
+        )
+    } else {
+        String::new()
+    };
+    let tmpl = state
+        .page("audit.html", &config)
+        .replace("{{notice}}", &notice)
+        .replace("{{table}}", &table);
+    Ok(Response::ok().body(tmpl.into()))
+}
+
+/// Download a snapshot of every profile's data.
+fn backup_download(request: &Request, state: &State) -> Result<Response> {
+    let profiles: Vec<_> = state.profiles().iter().cloned().collect();
+    let now = state.timezone().now();
+    let filename = format!(
+        "weight-watcher-{}-{:02}{:02}{:02}.txt",
+        store::format_date(now.date()),
+        now.hour(),
+        now.minute(),
+        now.second()
+    );
+    Ok(Response::ok()
+        .content_type(ContentType::Text)
+        .header(
+            "Content-Disposition",
+            format!("attachment; filename=\"{filename}\""),
+        )
+        .streamed(request, move |out| {
+            backup::write_snapshot(out, profiles.iter().map(|p| &**p))
+        }))
+}
+
+/// The form for restoring a backup, and restoring the one uploaded to it.
+fn restore(request: &Request, state: &State) -> Result<Response> {
+    let mut response = Response::ok();
+    let mut message = String::new();
+    if request.method == "POST" {
+        match restore_upload(request, state) {
+            Ok(msg) => message = escape_html(&msg),
+            Err(Error::Restore(msg)) => {
+                response = Response::bad_request();
+                message = format!("Restore failed: {}.", escape_html(&msg));
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    let config = state.config();
+    let tmpl = state
+        .page("restore.html", &config)
+        .replace("{{message}}", &message);
+    Ok(response.body(tmpl.into()))
+}
+
+fn restore_upload(request: &Request, state: &State) -> Result<String> {
+    let parts = request
+        .multipart()
+        .ok_or_else(|| Error::Restore("expected a file upload".into()))?;
+    let mode = parts
+        .iter()
+        .find(|p| p.name == "mode")
+        .map(|p| p.text().parse())
+        .unwrap_or(Ok(backup::RestoreMode::default()))
+        .map_err(Error::Restore)?;
+    let file = parts
+        .iter()
+        .find(|p| p.name == "file" && !p.data.is_empty())
+        .ok_or_else(|| Error::Restore("no file was selected".into()))?;
+    state.restore(&file.data, mode, source(request, state))
+}
+
+/// Buttons for maintenance tasks, and running the one that was pressed.
+fn maintenance(request: &Request, state: &State) -> Result<Response> {
+    let mut response = Response::ok();
+    let mut message = String::new();
+    if request.method == "POST" {
+        let params = request.params();
+        let action = params.iter().find(|(k, _)| k == "action");
+        message = match action.map(|(_, v)| v.as_str()) {
+            Some("compact") => state.compact(Source::Web)?,
+            Some("backup") => {
+                if state.config().backup_url.is_none() {
+                    response = Response::bad_request();
+                    "No backup_url is configured.".to_owned()
+                } else {
+                    match backup::upload(state) {
+                        Ok(()) => "Uploaded a backup.".to_owned(),
+                        Err(Error::Backup(msg)) => {
+                            response = Response::internal_error();
+                            format!("Backup failed: {msg}.")
+                        }
+                        Err(e) => return Err(e),
+                    }
+                }
+            }
+            Some("maintenance_on") => {
+                state.set_maintenance(true);
+                info!("switched maintenance mode on");
+                "Maintenance mode is on. Only this page, backups, restores, \
+                 and the status page are served."
+                    .to_owned()
+            }
+            Some("maintenance_off") => {
+                state.set_maintenance(false);
+                info!("switched maintenance mode off");
+                "Maintenance mode is off.".to_owned()
+            }
+            Some("reload") => {
+                state.reload()?;
+                "Reloaded the config, templates, and data files.".to_owned()
+            }
+            Some("flush") => match state.flush_graphs() {
+                1 => "Deleted 1 graph.".to_owned(),
+                n => format!("Deleted {n} graphs."),
+            },
+            Some("check") => {
+                let problems = state.check();
+                if problems.is_empty() {
+                    "No problems found in the data files.".to_owned()
+                } else {
+                    let items: String = problems
+                        .iter()
+                        .map(|p| format!("<li>{}</li>", escape_html(p)))
+                        .collect();
+                    let count = match problems.len() {
+                        1 => "1 problem".to_owned(),
+                        n => format!("{n} problems"),
+                    };
+                    format!("Found {count} in the data files:<ul>{items}</ul>")
+                }
+            }
+            _ => {
+                response = Response::bad_request();
+                "Unknown maintenance task.".to_owned()
+            }
+        };
+    }
+    let config = state.config();
+    let button = match state.maintenance() {
+        true => {
+            "<button name=\"action\" value=\"maintenance_off\">\
+                 End maintenance</button>"
+        }
+        false => {
+            "<button name=\"action\" value=\"maintenance_on\">\
+                  Start maintenance</button>"
+        }
+    };
+    let tmpl = state
+        .page("admin.html", &config)
+        .replace("{{message}}", &message)
+        .replace("{{maintenance}}", button);
+    Ok(response.body(tmpl.into()))
+}
+
+/// The size of `profile`'s data file in bytes, or `None` if it can't be
+/// read.
+fn data_file_size(profile: &Profile) -> Option<u64> {
+    std::fs::metadata(profile.store().path())
+        .ok()
+        .map(|m| m.len())
+}
+
+/// How long the server has been up, how many requests it has handled for
+/// each route, and how big each profile is.
+fn status_page(state: &State) -> Response {
+    use std::fmt::Write;
+    let requests = state.activity.requests();
+    let mut routes = String::new();
+    for (route, count) in &requests {
+        writeln!(
+            routes,
+            "<tr><td>{}</td><td>{count}</td></tr>",
+            escape_html(route)
+        )
+        .unwrap();
+    }
+    let mut profiles = String::new();
+    for profile in state.profiles().iter() {
+        writeln!(
+            profiles,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            profile.name().unwrap_or("default"),
+            profile.store().entries().len(),
+            data_file_size(profile).map_or("-".to_owned(), |b| b.to_string()),
+            profile
+                .last_render()
+                .map_or("-".to_owned(), |d| format!("{} ms", d.as_millis())),
+        )
+        .unwrap();
+    }
+    let config = state.config();
+    let tmpl = state
+        .page("status.html", &config)
+        .replace(
+            "{{uptime}}",
+            &status::format_duration(state.activity.uptime()),
+        )
+        .replace("{{requests}}", &requests.values().sum::<u64>().to_string())
+        .replace("{{routes}}", &routes)
+        .replace("{{profiles}}", &profiles);
+    Response::ok().body(tmpl.into())
+}
+
+/// The figures on the status page as JSON, for monitoring.
+fn status_json(state: &State) -> Response {
+    let requests = state.activity.requests();
+    let routes: Vec<_> = requests
+        .iter()
+        .map(|(route, count)| format!("{}:{count}", json::string(route)))
+        .collect();
+    let number =
+        |n: Option<u128>| n.map_or("null".to_owned(), |n| n.to_string());
+    let profiles: Vec<_> = state
+        .profiles()
+        .iter()
+        .map(|profile| {
+            format!(
+                "{{\"name\":{},\"entries\":{},\"data_file_bytes\":{},\
+                 \"last_graph_render_ms\":{}}}",
+                profile.name().map_or("null".to_owned(), json::string),
+                profile.store().entries().len(),
+                number(data_file_size(profile).map(u128::from)),
+                number(profile.last_render().map(|d| d.as_millis())),
+            )
+        })
+        .collect();
+    let body = format!(
+        "{{\"uptime_seconds\":{},\"requests\":{},\"routes\":{{{}}},\
+         \"profiles\":[{}]}}",
+        state.activity.uptime().as_secs(),
+        requests.values().sum::<u64>(),
+        routes.join(","),
+        profiles.join(",")
+    );
+    Response::ok()
+        .content_type(ContentType::Json)
+        .body(body.into())
+}
+
+/// A QR code linking to `profile`'s index page, for opening it on a phone.
+fn qr_page(
+    request: &Request,
+    state: &State,
+    profile: &Profile,
+) -> Result<Response> {
+    let config = state.config();
+    let Some(host) = request.header("host") else {
+        return Ok(Response::bad_request().body(
+            state
+                .error_page("The request didn't say which host it was for.")
+                .into(),
+        ));
+    };
+    let url = format!(
+        "http://{}{}/",
+        public_host(host, config.mdns_name.as_deref()),
+        profile.base()
+    );
+    let qr = qr::svg(&url).unwrap_or_default();
+    let url = escape_html(&url);
+    let tmpl = state
+        .page("qr.html", &config)
+        .replace("{{profiles}}", &state.profile_nav(profile))
+        .replace("{{base}}", &profile.base())
+        .replace("{{qr}}", &qr)
+        .replace("{{url}}", &url);
+    Ok(Response::ok().body(tmpl.into()))
+}
+
+/// The address other devices can reach the server at, given the `host` the
+/// request was made to. Loopback addresses are only useful on this machine,
+/// so they are replaced with the mDNS name if there is one, or else the
+/// address of the network interface used to reach other machines.
+fn public_host(host: &str, mdns_name: Option<&str>) -> String {
+    let (name, port) = match host.rsplit_once(':') {
+        Some((name, port)) if !port.contains(']') => (name, Some(port)),
+        _ => (host, None),
+    };
+    let loopback = name.eq_ignore_ascii_case("localhost")
+        || name
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .parse::<std::net::IpAddr>()
+            .is_ok_and(|ip| ip.is_loopback());
+    if !loopback {
+        return host.to_owned();
+    }
+    let public = match mdns_name {
+        Some(name) => format!("{name}.local"),
+        // connecting a UDP socket doesn't send anything, but it picks the
+        // interface that would be used
+        None => match std::net::UdpSocket::bind(("0.0.0.0", 0))
+            .and_then(|s| s.connect(("192.0.2.1", 9)).map(|()| s))
+            .and_then(|s| s.local_addr())
+        {
+            Ok(addr) => addr.ip().to_string(),
+            Err(_) => return host.to_owned(),
+        },
+    };
+    match port {
+        Some(port) => format!("{public}:{port}"),
+        None => public,
+    }
+}
+
+/// Where to send the browser after a form on one of `profile`'s pages: back
+/// to the page the form was on, according to the `Referer` header, or to the
+/// profile's `page` if that can't be told. Only pages of the same profile on
+/// this server are returned to.
+fn back(request: &Request, profile: &Profile, page: &str) -> String {
+    let referer = request.header("referer").and_then(|referer| {
+        let (_, rest) = referer.split_once("://")?;
+        let (host, path) = rest.split_at(rest.find('/')?);
+        (Some(host) == request.header("host")).then_some(path)
+    });
+    match referer {
+        Some(path)
+            if path != request.path
+                && !path.contains(char::is_control)
+                // browsers read both of these as the start of another host
+                && !path.starts_with("//")
+                && !path.contains('\\')
+                && profile::split_path(path).0 == profile.name() =>
+        {
+            path.to_owned()
+        }
+        _ => format!("{}/{page}", profile.base()),
+    }
+}
+
+/// ` for <name>` for a named profile, for describing changes to it.
+fn for_profile(profile: &Profile) -> String {
+    profile
+        .name()
+        .map(|name| format!(" for {name}"))
+        .unwrap_or_default()
+}
+
+/// A table cell showing `change` with an arrow, colored green if it is
+/// progress in the `good` direction, red if it is the other way, and gray if
+/// it rounds to nothing.
+fn change_cell(change: f64, good: Direction) -> String {
+    let tenths = (change * 10.0).round();
+    if tenths == 0.0 {
+        return "<td style=\"color:gray\">&rarr; 0.0</td>".to_owned();
+    }
+    let (arrow, gained) = if tenths > 0.0 {
+        ("&#9650;", true)
+    } else {
+        ("&#9660;", false)
+    };
+    let color = match (good, gained) {
+        (Direction::Gain, true) | (Direction::Loss, false) => "green",
+        _ => "red",
+    };
+    format!("<td style=\"color:{color}\">{arrow} {change:+.1}</td>")
+}
+
+/// Escape the characters in `s` that are special in HTML.
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// The optional columns of a table of entries from [`html_rows`].
+#[derive(Default)]
+struct Columns<'a> {
+    /// The change from the weight before each row's date, colored as
+    /// progress in the given direction
+    changes: Option<(&'a HashMap<Date, f64>, Direction)>,
+
+    /// The average for each row's date
+    averages: Option<&'a HashMap<Date, f64>>,
+
+    /// Adds a column with a delete button for each row, along with a link to
+    /// the earlier versions of the rows on these dates
+    edited: Option<&'a HashSet<Date>>,
+
+    /// Leaves the delete buttons out of the `edited` column
+    read_only: bool,
+}
+
+/// Render `rows` as table rows with dates in `date_format` and weights in
+/// stones and pounds if `stones` gives their unit, with each tag linking to
+/// the history of entries with that tag under `base`, and any other
+/// `columns`. Rows on the `highlight` date are given the `selected` class.
+fn html_rows<'a>(
+    base: &str,
+    date_format: &DateFormat,
+    stones: Option<Unit>,
+    rows: impl Iterator<Item = &'a Entry>,
+    columns: Columns,
+    highlight: Option<Date>,
+) -> String {
+    use std::fmt::Write;
+    let Columns {
+        changes,
+        averages,
+        edited,
+        read_only,
+    } = columns;
+    let mut table = String::new();
+    for entry in rows {
+        let Entry { date, weight, tags } = entry;
+        let change = match changes {
+            Some((previous, good)) => match previous.get(date) {
+                Some(previous) => change_cell(weight - previous, good),
+                None => "<td></td>".to_owned(),
+            },
+            None => String::new(),
+        };
+        let average = match averages.and_then(|a| a.get(date)) {
+            Some(average) => {
+                format!("<td>{}</td>", units::format_weight(*average, stones))
+            }
+            None if averages.is_some() => "<td></td>".to_owned(),
+            None => String::new(),
+        };
+        let class = if highlight == Some(*date) {
+            " class=\"selected\""
+        } else {
+            ""
+        };
+        let date = date_format.format(*date);
+        let tags: Vec<_> = tags
+            .iter()
+            .map(|t| format!("<a href=\"{base}/history?tag={t}\">#{t}</a>"))
+            .collect();
+        let actions = match edited {
+            Some(edited) => {
+                let versions = if edited.contains(&entry.date) {
+                    format!(
+                        " <a href=\"{base}/versions?date={}\">Versions</a>",
+                        store::format_date(entry.date)
+                    )
+                } else {
+                    String::new()
+                };
+                if read_only {
+                    format!("<td>{versions}</td>")
+                } else {
+                    format!(
+                        "<td><form action=\"{base}/delete\" method=\"post\" \
+                         onsubmit=\"return confirm('Move this entry to the \
+                         trash?')\"><input type=\"hidden\" name=\"entry\" \
+                         value=\"{entry}\" /><button>Delete</button></form>\
+                         {versions}</td>"
+                    )
+                }
+            }
+            None => String::new(),
+        };
+        writeln!(
+            table,
+            "<tr{class}><td>{date}</td><td>{}</td>{change}{average}\
+             <td>{}</td>{actions}</tr>",
+            units::format_weight(*weight, stones),
+            tags.join(" ")
+        )
+        .unwrap();
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use time::UtcOffset;
+
+    use super::*;
+
+    /// A server whose default profile has the entries in `data`, kept in a
+    /// fresh directory called `name` under the temporary directory.
+    fn state(name: &str, data: &str) -> State {
+        state_with(name, data, Config::default())
+    }
+
+    fn state_with(name: &str, data: &str, config: Config) -> State {
+        let dir = std::env::temp_dir()
+            .join(format!("weight-watcher-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("weights.dat");
+        std::fs::write(&path, data).unwrap();
+        let store = Store::open(&path).unwrap();
+        let local = Timezone::Fixed(UtcOffset::UTC);
+        State::new(store, dir.join("config"), config, local).unwrap()
+    }
+
+    fn post(state: &State, path: &str, body: &str) -> Response {
+        let request = format!(
+            "POST {path} HTTP/1.1\r\n\
+             Content-Type: application/x-www-form-urlencoded\r\n\
+             Content-Length: {}\r\n\r\n{body}",
+            body.len()
+        );
+        handle(&Request::read(request.as_bytes(), 1024).unwrap(), state)
+    }
+
+    fn get(state: &State, path: &str) -> Response {
+        let request = format!("GET {path} HTTP/1.1\r\n\r\n");
+        handle(&Request::read(request.as_bytes(), 1024).unwrap(), state)
+    }
+
+    fn entries(state: &State) -> Vec<Entry> {
+        state
+            .profiles()
+            .get(None)
+            .unwrap()
+            .store()
+            .entries()
+            .clone()
+    }
+
+    #[test]
+    fn health_is_not_counted() {
+        let state = state("health", "");
+        assert_eq!(get(&state, "/health").status(), 200);
+        assert!(state.activity.requests().is_empty());
+    }
+
+    #[test]
+    fn made_up_paths_share_a_count() {
+        let state = state("made-up", "");
+        state.set_maintenance(true);
+        for path in ["/a", "/b", "/p/nobody/history", "/history"] {
+            assert_eq!(get(&state, path).status(), 503);
+        }
+        state.set_maintenance(false);
+        assert_eq!(get(&state, "/c").status(), 404);
+        assert_eq!(get(&state, "/history").status(), 200);
+        let requests = state.activity.requests();
+        let counts: Vec<_> =
+            requests.iter().map(|(k, v)| (k.as_str(), *v)).collect();
+        assert_eq!(counts, [("(other)", 5), ("/history", 1)]);
+    }
+
+    #[test]
+    fn revert_one_of_several() {
+        let state =
+            state("revert", "2024-05-01 80.0\n2024-05-01 81.0 #travel\n");
+        let audit =
+            "2024-05-01T07:00:00Z\tweb\tcreate\t-\t-\t2024-05-01 80.5\n\
+             2024-05-01T07:05:00Z\tweb\tedit\t-\t2024-05-01 80.5\t\
+             2024-05-01 80.0\n\
+             2024-05-01T19:00:00Z\tweb\tcreate\t-\t-\t\
+             2024-05-01 81.0 #travel\n";
+        let path = state
+            .profiles()
+            .get(None)
+            .unwrap()
+            .store()
+            .path()
+            .to_owned();
+        std::fs::write(path.with_extension("audit"), audit).unwrap();
+
+        let page = get(&state, "/versions?date=2024-05-01").as_bytes();
+        let page = String::from_utf8_lossy(&page);
+        assert!(page.contains(
+            "name=\"entry\" value=\"2024-05-01 80.5\" />\
+             <input type=\"hidden\" name=\"current\" \
+             value=\"2024-05-01 80.0\" />"
+        ));
+
+        // a value the entry never had
+        let body = "date=2024-05-01&entry=2024-05-01+70.0\
+                    &current=2024-05-01+80.0";
+        assert_eq!(post(&state, "/versions", body).status(), 400);
+
+        let body = "date=2024-05-01&entry=2024-05-01+80.5\
+                    &current=2024-05-01+80.0";
+        assert_eq!(post(&state, "/versions", body).status(), 303);
+        let weights: Vec<_> =
+            entries(&state).iter().map(|e| e.weight).collect();
+        assert_eq!(weights, [80.5, 81.0]);
+
+        // the entry it replaced is gone now
+        assert_eq!(post(&state, "/versions", body).status(), 404);
+    }
+
+    #[test]
+    fn back_to_the_same_host() {
+        let state = state("back", "");
+        let profile = state.profiles().get(None).unwrap();
+        let back = |referer: &str| {
+            let request = format!(
+                "POST /weight HTTP/1.1\r\nHost: localhost:9999\r\n\
+                 Referer: {referer}\r\n\r\n"
+            );
+            let request = Request::read(request.as_bytes(), 1024).unwrap();
+            back(&request, &profile, "history")
+        };
+        assert_eq!(back("http://localhost:9999/graphs"), "/graphs");
+        assert_eq!(back("http://example.com/graphs"), "/history");
+        assert_eq!(back("http://localhost:9999//evil.example/"), "/history");
+        assert_eq!(back("http://localhost:9999/\\evil.example/"), "/history");
+        assert_eq!(back("http://localhost:9999/a\\b"), "/history");
+    }
+
+    #[test]
+    fn chart_not_rendered() {
+        let state = state("no-chart", "");
+        let request =
+            Request::read(&b"GET /measurements.png HTTP/1.1\r\n\r\n"[..], 1024)
+                .unwrap();
+        let file = std::env::temp_dir().join("weight-watcher-no-such.png");
+        let response = chart_image(&request, &state, &file).unwrap();
+        assert_eq!(response.status(), 404);
+    }
+
+    #[test]
+    fn weights_are_rounded() {
+        let state = state("rounded", "");
+        assert_eq!(post(&state, "/weight", "w=80.27").status(), 303);
+        assert_eq!(entries(&state)[0].weight, 80.3);
+    }
+
+    #[test]
+    fn bounds_in_pounds() {
+        let config = Config {
+            unit: Unit::Lb,
+            ..Config::default()
+        };
+        let state = state_with("pounds", "", config);
+        assert_eq!(post(&state, "/weight", "w=260").status(), 303);
+        assert_eq!(post(&state, "/weight", "w=50").status(), 400);
+        assert_eq!(entries(&state)[0].weight, 260.0);
+    }
+
+    #[test]
+    fn delete_two_decimals() {
+        let state = state("two-decimals", "2024-05-01 80.27\n");
+        let response = post(&state, "/delete", "entry=2024-05-01+80.3");
+        assert_eq!(response.status(), 303);
+        assert!(entries(&state).is_empty());
+        let response = post(&state, "/delete", "entry=2024-05-01+80.3");
+        assert_eq!(response.status(), 404);
+    }
+}
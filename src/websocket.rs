@@ -0,0 +1,267 @@
+//! WebSocket connections that push changes to a profile's data as they
+//! happen. Each change is sent as a JSON text message like
+//!
+//! ```json
+//! {"type":"added","profile":null,"entry":{"date":"2026-10-15","weight":80.1,"tags":[]},"stats":{...}}
+//! ```
+//!
+//! with a `type` of `stats` for the first message, `added` for a new weight,
+//! and `changed` for anything else, like an import. Every message carries
+//! the profile's current stats.
+
+use std::{
+    fmt::Write as _,
+    io::{self, Read, Write},
+    net::{Shutdown, TcpStream},
+    sync::{
+        mpsc::{Receiver, RecvTimeoutError},
+        Arc, Mutex, PoisonError,
+    },
+    time::Duration,
+};
+
+use tracing::{debug, warn};
+
+use crate::{
+    events::{self, Event, Message},
+    http::{base64_encode, Request},
+    profile::Profile,
+    stats,
+    store::format_date,
+    timezone::Timezone,
+    Entry,
+};
+
+/// Appended to the client's key before hashing it for the handshake.
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// The largest message accepted from a client. Clients have no reason to
+/// send anything but control frames, which are much smaller.
+const MAX_PAYLOAD: u64 = 64 * 1024;
+
+/// How often to ping an idle client, to notice when it has gone away.
+const PING_INTERVAL: Duration = Duration::from_secs(30);
+
+const TEXT: u8 = 0x1;
+const CLOSE: u8 = 0x8;
+const PING: u8 = 0x9;
+const PONG: u8 = 0xA;
+
+/// The `Sec-WebSocket-Accept` value answering `request`'s opening handshake,
+/// or `None` if it isn't a valid WebSocket handshake.
+pub fn accept_key(request: &Request) -> Option<String> {
+    let has_token = |name, token: &str| {
+        request.header(name).is_some_and(|value| {
+            value
+                .split(',')
+                .any(|v| v.trim().eq_ignore_ascii_case(token))
+        })
+    };
+    if request.method != "GET"
+        || !has_token("upgrade", "websocket")
+        || !has_token("connection", "upgrade")
+        || request.header("sec-websocket-version") != Some("13")
+    {
+        return None;
+    }
+    let key = request.header("sec-websocket-key")?.trim();
+    let digest = sha1_smol::Sha1::from(format!("{key}{GUID}")).digest();
+    Some(base64_encode(&digest.bytes()))
+}
+
+/// Send `profile`'s changes from `events` over `stream` until either side
+/// closes the connection.
+pub fn session(
+    stream: TcpStream,
+    events: Receiver<Arc<Message>>,
+    profile: Arc<Profile>,
+    timezone: Timezone,
+) {
+    let writer = match stream.try_clone() {
+        Ok(writer) => Arc::new(Mutex::new(writer)),
+        Err(e) => {
+            warn!("failed to set up WebSocket: {e}");
+            return;
+        }
+    };
+    debug!("WebSocket opened");
+    let reader = {
+        let writer = Arc::clone(&writer);
+        std::thread::spawn(move || read_frames(stream, &writer))
+    };
+    let send = |opcode, payload: &[u8]| {
+        let mut writer = writer.lock().unwrap_or_else(PoisonError::into_inner);
+        write_frame(&mut *writer, opcode, payload)
+    };
+
+    let mut next = Some(message(&profile, "stats", None, timezone));
+    while let Some(text) = next.take() {
+        if send(TEXT, text.as_bytes()).is_err() {
+            break;
+        }
+        next = loop {
+            match events::next_for(&events, profile.name(), PING_INTERVAL) {
+                Ok(m) => match &m.event {
+                    Event::Added(entry) => {
+                        break Some(message(
+                            &profile,
+                            "added",
+                            Some(entry),
+                            timezone,
+                        ))
+                    }
+                    Event::Changed => {
+                        break Some(message(
+                            &profile, "changed", None, timezone,
+                        ))
+                    }
+                    // the stats don't depend on the graphs
+                    Event::Graphed => {}
+                },
+                Err(RecvTimeoutError::Timeout) => {
+                    if send(PING, &[]).is_err() {
+                        break None;
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => break None,
+            }
+        };
+    }
+    let _ = writer
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .shutdown(Shutdown::Both);
+    let _ = reader.join();
+    debug!("WebSocket closed");
+}
+
+/// Answer the client's control frames until it closes the connection, then
+/// shut the connection down so that the sending side stops too.
+fn read_frames(mut stream: TcpStream, writer: &Mutex<TcpStream>) {
+    while let Ok((opcode, payload)) = read_frame(&mut stream) {
+        let mut writer = writer.lock().unwrap_or_else(PoisonError::into_inner);
+        let reply = match opcode {
+            CLOSE => {
+                let _ = write_frame(&mut *writer, CLOSE, &payload);
+                break;
+            }
+            PING => write_frame(&mut *writer, PONG, &payload),
+            _ => Ok(()),
+        };
+        if reply.is_err() {
+            break;
+        }
+    }
+    let _ = stream.shutdown(Shutdown::Both);
+}
+
+/// Read one frame sent by a client, returning its opcode and unmasked
+/// payload.
+fn read_frame(stream: &mut impl Read) -> io::Result<(u8, Vec<u8>)> {
+    let mut head = [0; 2];
+    stream.read_exact(&mut head)?;
+    let opcode = head[0] & 0x0F;
+    if head[1] & 0x80 == 0 {
+        return Err(io::Error::other("client frames must be masked"));
+    }
+    let len = match head[1] & 0x7F {
+        126 => {
+            let mut len = [0; 2];
+            stream.read_exact(&mut len)?;
+            u16::from_be_bytes(len).into()
+        }
+        127 => {
+            let mut len = [0; 8];
+            stream.read_exact(&mut len)?;
+            u64::from_be_bytes(len)
+        }
+        len => len.into(),
+    };
+    if len > MAX_PAYLOAD {
+        return Err(io::Error::other("WebSocket frame too large"));
+    }
+    let mut mask = [0; 4];
+    stream.read_exact(&mut mask)?;
+    let mut payload = vec![0; len as usize];
+    stream.read_exact(&mut payload)?;
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+    Ok((opcode, payload))
+}
+
+/// Write a single unmasked, unfragmented frame.
+fn write_frame(
+    stream: &mut impl Write,
+    opcode: u8,
+    payload: &[u8],
+) -> io::Result<()> {
+    let mut frame = vec![0x80 | opcode];
+    match payload.len() {
+        len @ 0..=125 => frame.push(len as u8),
+        len @ 126..=0xFFFF => {
+            frame.push(126);
+            frame.extend((len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend((len as u64).to_be_bytes());
+        }
+    }
+    frame.extend(payload);
+    stream.write_all(&frame)
+}
+
+/// A JSON message of type `kind` about `profile`, with the `entry` that was
+/// added if any.
+fn message(
+    profile: &Profile,
+    kind: &str,
+    entry: Option<&Entry>,
+    timezone: Timezone,
+) -> String {
+    let mut out = format!("{{\"type\":\"{kind}\",\"profile\":");
+    match profile.name() {
+        Some(name) => write!(out, "\"{name}\"").unwrap(),
+        None => out.push_str("null"),
+    }
+    if let Some(entry) = entry {
+        write!(out, ",\"entry\":{}", entry_json(entry)).unwrap();
+    }
+
+    let entries = profile.store().entries();
+    let month_ago = timezone.today() - time::Duration::days(30);
+    let recent: Vec<_> =
+        entries.iter().filter(|e| e.date > month_ago).collect();
+    let average = (!recent.is_empty()).then(|| {
+        recent.iter().map(|e| e.weight).sum::<f64>() / recent.len() as f64
+    });
+    let range = stats::minmax(recent.iter().copied());
+    let latest = entries.iter().max_by_key(|e| e.date);
+    let number = |n: Option<f64>| match n {
+        Some(n) => format!("{n:.1}"),
+        None => "null".to_owned(),
+    };
+    write!(
+        out,
+        ",\"stats\":{{\"entries\":{},\"latest\":{},\
+         \"average_30_days\":{},\"min_30_days\":{},\"max_30_days\":{}}}}}",
+        entries.len(),
+        latest.map_or("null".to_owned(), entry_json),
+        number(average),
+        number(range.map(|r| r.0)),
+        number(range.map(|r| r.1)),
+    )
+    .unwrap();
+    out
+}
+
+fn entry_json(entry: &Entry) -> String {
+    let tags: Vec<_> = entry.tags.iter().map(|t| format!("\"{t}\"")).collect();
+    format!(
+        "{{\"date\":\"{}\",\"weight\":{:.1},\"tags\":[{}]}}",
+        format_date(entry.date),
+        entry.weight,
+        tags.join(",")
+    )
+}
@@ -0,0 +1,442 @@
+//! Pulling weigh-ins from a Withings account on a schedule, so a connected
+//! scale's entries show up without exporting anything. The account is
+//! connected through Withings' OAuth flow on the `/withings` page, after
+//! which the access and refresh tokens are kept in a `withings` file next to
+//! the config file, on one line along with when the access token expires and
+//! when the weigh-ins were last fetched, both in seconds since the Unix
+//! epoch. Requests are made with curl.
+
+use std::{
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::{Arc, Mutex, PoisonError},
+    time::Duration,
+};
+
+use chacha20poly1305::aead::Generate;
+use time::OffsetDateTime;
+use tracing::{debug, error, info, instrument};
+
+use crate::{
+    crypto,
+    http::percent_encode,
+    json::{self, Json},
+    Config, Error, Result, State,
+};
+
+const AUTHORIZE_URL: &str =
+    "https://account.withings.com/oauth2_user/authorize2";
+const TOKEN_URL: &str = "https://wbsapi.withings.net/v2/oauth2";
+const MEASURE_URL: &str = "https://wbsapi.withings.net/measure";
+
+/// Withings' measure types for weight and fat mass, both in kg.
+const WEIGHT: i64 = 1;
+const FAT_MASS: i64 = 8;
+
+/// The access token is refreshed this many seconds before it expires, so it
+/// doesn't run out partway through a sync.
+const EXPIRY_MARGIN: i64 = 60;
+
+/// One weigh-in from the scale, in kg.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WeighIn {
+    pub time: OffsetDateTime,
+    pub weight: f64,
+    pub fat_mass: Option<f64>,
+}
+
+/// What the `withings` file holds.
+#[derive(Clone, Debug, PartialEq)]
+struct Credentials {
+    access_token: String,
+    refresh_token: String,
+    expires: i64,
+
+    /// The time Withings gave for the last fetch, from which the next one
+    /// continues
+    last_update: i64,
+}
+
+impl Credentials {
+    fn parse(contents: &str) -> Option<Self> {
+        let mut sp = contents.split_ascii_whitespace();
+        Some(Self {
+            access_token: sp.next()?.to_owned(),
+            refresh_token: sp.next()?.to_owned(),
+            expires: sp.next()?.parse().ok()?,
+            last_update: sp.next()?.parse().ok()?,
+        })
+    }
+
+    /// Read the tokens from the `body` of a token response, keeping
+    /// `last_update`.
+    fn from_response(body: &Json, last_update: i64) -> Result<Self> {
+        let missing =
+            || Error::Withings("the token response is missing fields".into());
+        let string = |key: &str| {
+            body.get(key)
+                .as_str()
+                .map(str::to_owned)
+                .ok_or_else(missing)
+        };
+        let Json::Number(expires_in) = body.get("expires_in") else {
+            return Err(missing());
+        };
+        Ok(Self {
+            access_token: string("access_token")?,
+            refresh_token: string("refresh_token")?,
+            expires: now() + *expires_in as i64,
+            last_update,
+        })
+    }
+}
+
+/// The connection to a Withings account.
+pub struct Withings {
+    path: PathBuf,
+
+    /// The `state` sent with the last authorization link, which the
+    /// callback has to send back
+    pending: Mutex<Option<String>>,
+
+    /// Held while the credentials are read and rewritten
+    file: Mutex<()>,
+}
+
+impl Withings {
+    /// The connection kept in the `withings` file next to `config_file`.
+    pub fn new(config_file: &Path) -> Self {
+        Self {
+            path: config_file
+                .parent()
+                .unwrap_or(Path::new(""))
+                .join("withings"),
+            pending: Mutex::default(),
+            file: Mutex::default(),
+        }
+    }
+
+    /// Whether an account has been connected.
+    pub fn is_connected(&self) -> bool {
+        matches!(self.read(), Ok(Some(_)))
+    }
+
+    /// When the weigh-ins were last fetched, if they have been.
+    pub fn last_update(&self) -> Option<OffsetDateTime> {
+        let credentials = self.read().ok()??;
+        (credentials.last_update > 0)
+            .then(|| {
+                OffsetDateTime::from_unix_timestamp(credentials.last_update)
+                    .ok()
+            })
+            .flatten()
+    }
+
+    /// The Withings page that asks the user to let this server read their
+    /// weigh-ins, which sends them back to `withings_redirect_url`.
+    pub fn authorize_url(&self, config: &Config) -> Result<String> {
+        let (client_id, redirect_url) = client(config)?;
+        let state = crate::tokens::hex(&<[u8; 16]>::generate());
+        let url = format!(
+            "{AUTHORIZE_URL}?response_type=code&client_id={}&\
+             scope=user.metrics&redirect_uri={}&state={state}",
+            percent_encode(client_id),
+            percent_encode(redirect_url),
+        );
+        *self.pending.lock().unwrap_or_else(PoisonError::into_inner) =
+            Some(state);
+        Ok(url)
+    }
+
+    /// Finish connecting with the `code` Withings sent back along with
+    /// `state`.
+    #[instrument(skip_all)]
+    pub fn connect(
+        &self,
+        config: &Config,
+        code: &str,
+        state: &str,
+    ) -> Result<()> {
+        let pending = self
+            .pending
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        if pending.is_none_or(|p| {
+            !crate::tokens::constant_time_eq(p.as_bytes(), state.as_bytes())
+        }) {
+            return Err(Error::Withings(
+                "the authorization didn't come from this server's link".into(),
+            ));
+        }
+        let (client_id, redirect_url) = client(config)?;
+        let secret = secret(config)?;
+        let body = call(
+            TOKEN_URL,
+            &[
+                ("action", "requesttoken"),
+                ("grant_type", "authorization_code"),
+                ("client_id", client_id),
+                ("client_secret", &secret),
+                ("code", code),
+                ("redirect_uri", redirect_url),
+            ],
+            None,
+        )?;
+        let credentials = Credentials::from_response(&body, 0)?;
+        let _file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+        self.write(&credentials)?;
+        info!("connected Withings account");
+        Ok(())
+    }
+
+    /// Forget the account, returning whether one was connected.
+    pub fn disconnect(&self) -> io::Result<bool> {
+        let _file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+        match std::fs::remove_file(&self.path) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// The weigh-ins added or changed since the last fetch, along with the
+    /// time to pass to [`Withings::fetched`] once they are saved. Returns
+    /// `None` if no account is connected.
+    #[instrument(skip_all)]
+    pub fn fetch(
+        &self,
+        config: &Config,
+    ) -> Result<Option<(Vec<WeighIn>, i64)>> {
+        let _file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+        let Some(mut credentials) = self.read()? else {
+            return Ok(None);
+        };
+        if credentials.expires - EXPIRY_MARGIN <= now() {
+            let (client_id, _) = client(config)?;
+            let secret = secret(config)?;
+            let body = call(
+                TOKEN_URL,
+                &[
+                    ("action", "requesttoken"),
+                    ("grant_type", "refresh_token"),
+                    ("client_id", client_id),
+                    ("client_secret", &secret),
+                    ("refresh_token", &credentials.refresh_token),
+                ],
+                None,
+            )?;
+            credentials =
+                Credentials::from_response(&body, credentials.last_update)?;
+            self.write(&credentials)?;
+            debug!("refreshed Withings access token");
+        }
+
+        let since = credentials.last_update.to_string();
+        let types = format!("{WEIGHT},{FAT_MASS}");
+        let mut weigh_ins = Vec::new();
+        let mut offset = String::from("0");
+        loop {
+            let body = call(
+                MEASURE_URL,
+                &[
+                    ("action", "getmeas"),
+                    ("meastypes", &types),
+                    ("category", "1"),
+                    ("lastupdate", &since),
+                    ("offset", &offset),
+                ],
+                Some(&credentials.access_token),
+            )?;
+            let Json::Array(groups) = body.get("measuregrps") else {
+                return Err(Error::Withings(
+                    "the measurements response has no measuregrps".into(),
+                ));
+            };
+            weigh_ins.extend(groups.iter().filter_map(weigh_in));
+            match (body.get("more"), body.get("offset")) {
+                (Json::Number(more), Json::Number(next)) if *more != 0.0 => {
+                    offset = (*next as i64).to_string();
+                }
+                _ => {
+                    let update = match body.get("updatetime") {
+                        Json::Number(time) => *time as i64,
+                        _ => now(),
+                    };
+                    return Ok(Some((weigh_ins, update)));
+                }
+            }
+        }
+    }
+
+    /// Note that the weigh-ins up to `update`, from [`Withings::fetch`],
+    /// have been saved.
+    pub fn fetched(&self, update: i64) -> Result<()> {
+        let _file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(mut credentials) = self.read()? {
+            credentials.last_update = update;
+            self.write(&credentials)?;
+        }
+        Ok(())
+    }
+
+    fn read(&self) -> io::Result<Option<Credentials>> {
+        match std::fs::read_to_string(&self.path) {
+            Ok(contents) => Ok(Credentials::parse(&contents)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn write(&self, credentials: &Credentials) -> io::Result<()> {
+        let contents = format!(
+            "{} {} {} {}\n",
+            credentials.access_token,
+            credentials.refresh_token,
+            credentials.expires,
+            credentials.last_update
+        );
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+        let mut options = std::fs::File::options();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        options.open(&tmp)?.write_all(contents.as_bytes())?;
+        std::fs::rename(&tmp, &self.path)
+    }
+}
+
+/// Fetch the new weigh-ins and add them to the `withings_profile`, returning
+/// a summary for the user.
+#[instrument(skip_all)]
+pub fn sync(state: &State) -> Result<String> {
+    let fetched = state.withings().fetch(&state.config())?;
+    let Some((weigh_ins, update)) = fetched else {
+        return Ok("No Withings account is connected.".to_owned());
+    };
+    let profile = state.config().withings_profile.clone();
+    let summary = state.add_weigh_ins(profile.as_deref(), &weigh_ins)?;
+    state.withings().fetched(update)?;
+    info!(weigh_ins = weigh_ins.len(), "synced Withings weigh-ins");
+    Ok(summary)
+}
+
+/// Spawn a thread that syncs the weigh-ins into `state` every `interval`,
+/// starting right away.
+pub fn sync_periodically(state: Arc<State>, interval: Duration) {
+    std::thread::spawn(move || loop {
+        if let Err(e) = sync(&state) {
+            error!("failed to sync Withings weigh-ins: {e}");
+        }
+        std::thread::sleep(interval);
+    });
+}
+
+/// The `withings_client_id` and `withings_redirect_url`, which are needed
+/// for every step of connecting.
+fn client(config: &Config) -> Result<(&str, &str)> {
+    match (&config.withings_client_id, &config.withings_redirect_url) {
+        (Some(id), Some(url)) => Ok((id, url)),
+        _ => Err(Error::Withings(
+            "withings_client_id and withings_redirect_url must be set".into(),
+        )),
+    }
+}
+
+/// The contents of the `withings_client_secret_file`.
+fn secret(config: &Config) -> Result<String> {
+    let path =
+        config.withings_client_secret_file.as_ref().ok_or_else(|| {
+            Error::Withings("withings_client_secret_file must be set".into())
+        })?;
+    Ok(crypto::read_passphrase(path)?)
+}
+
+/// POST `fields` to `url`, with `token` as the bearer token if given, and
+/// return the `body` of the response. Everything goes through curl's stdin
+/// so the secrets don't show up in the process list.
+fn call(
+    url: &str,
+    fields: &[(&str, &str)],
+    token: Option<&str>,
+) -> Result<Json> {
+    let quote = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+    let mut stdin = String::new();
+    for (key, value) in fields {
+        stdin.push_str(&format!(
+            "data-urlencode = \"{}\"\n",
+            quote(&format!("{key}={value}"))
+        ));
+    }
+    if let Some(token) = token {
+        stdin.push_str(&format!(
+            "header = \"Authorization: Bearer {}\"\n",
+            quote(token)
+        ));
+    }
+    let mut child = Command::new("curl")
+        .args(["--silent", "--show-error", "--fail", "--config", "-"])
+        .arg(url)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let mut pipe = child.stdin.take().expect("stdin is piped");
+    pipe.write_all(stdin.as_bytes())?;
+    drop(pipe);
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(Error::Withings(format!(
+            "requesting {url} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    let response = json::parse(&String::from_utf8_lossy(&output.stdout))
+        .map_err(|e| Error::Withings(format!("{url} sent bad JSON: {e}")))?;
+    match response.get("status") {
+        Json::Number(status) if *status == 0.0 => {
+            Ok(response.get("body").clone())
+        }
+        status => Err(Error::Withings(format!(
+            "{url} answered with status {status}{}",
+            match response.get("error").as_str() {
+                Some(error) => format!(": {error}"),
+                None => String::new(),
+            }
+        ))),
+    }
+}
+
+/// The weigh-in in a measure group, if it has a weight.
+fn weigh_in(group: &Json) -> Option<WeighIn> {
+    let Json::Number(date) = group.get("date") else {
+        return None;
+    };
+    let Json::Array(measures) = group.get("measures") else {
+        return None;
+    };
+    // values are integers scaled by a power of ten, like 81400 and -3
+    let value = |kind: i64| {
+        measures.iter().find_map(|m| {
+            match (m.get("type"), m.get("value"), m.get("unit")) {
+                (Json::Number(t), Json::Number(v), Json::Number(u))
+                    if *t as i64 == kind =>
+                {
+                    Some(v * 10f64.powi(*u as i32))
+                }
+                _ => None,
+            }
+        })
+    };
+    Some(WeighIn {
+        time: OffsetDateTime::from_unix_timestamp(*date as i64).ok()?,
+        weight: value(WEIGHT)?,
+        fat_mass: value(FAT_MASS),
+    })
+}
+
+fn now() -> i64 {
+    OffsetDateTime::now_utc().unix_timestamp()
+}